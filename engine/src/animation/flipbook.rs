@@ -0,0 +1,79 @@
+use crate::asset_loader::flipbook::{Flipbook, FlipbookClip, UvRect};
+use crate::scene::World;
+use serde::{Deserialize, Serialize};
+
+/// Component playing a clip of a [`Flipbook`] on an entity, for sprites and other
+/// billboards. Registered as `flipbook` for scene files, e.g.
+/// `"flipbook": (sheet: "fx.explosion", clip: "burst")`.
+///
+/// The engine advances every player each update; whatever draws the entity looks the
+/// sheet up and asks for [`FlipbookPlayer::uv_rect`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlipbookPlayer {
+    /// Asset id of the [`Flipbook`].
+    pub sheet: String,
+    pub clip: String,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    #[serde(default)]
+    pub paused: bool,
+    /// Seconds into the clip.
+    #[serde(default)]
+    pub time: f32,
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+impl FlipbookPlayer {
+    pub fn new(sheet: impl Into<String>, clip: impl Into<String>) -> Self {
+        FlipbookPlayer {
+            sheet: sheet.into(),
+            clip: clip.into(),
+            speed: 1.0,
+            paused: false,
+            time: 0.0,
+        }
+    }
+
+    /// Switches to `clip` of the same sheet, from its first frame.
+    pub fn play(&mut self, clip: impl Into<String>) {
+        self.clip = clip.into();
+        self.time = 0.0;
+        self.paused = false;
+    }
+
+    pub fn advance(&mut self, delta: f32) {
+        if !self.paused {
+            self.time += delta * self.speed;
+        }
+    }
+
+    fn current_clip<'a>(&self, sheet: &'a Flipbook) -> Option<&'a FlipbookClip> {
+        sheet.clip(&self.clip)
+    }
+
+    /// Atlas frame currently shown, or `None` if the sheet has no such clip.
+    pub fn frame(&self, sheet: &Flipbook) -> Option<u32> {
+        self.current_clip(sheet)
+            .map(|clip| clip.frame_at(self.time))
+    }
+
+    pub fn uv_rect(&self, sheet: &Flipbook) -> Option<UvRect> {
+        self.frame(sheet).map(|frame| sheet.frame_uv(frame))
+    }
+
+    /// Whether a clip that doesn't loop has played to its end.
+    pub fn is_finished(&self, sheet: &Flipbook) -> bool {
+        self.current_clip(sheet)
+            .is_some_and(|clip| clip.is_finished(self.time))
+    }
+}
+
+/// Advances every [`FlipbookPlayer`] in `world` by `delta` seconds.
+pub fn advance_flipbooks(world: &mut World, delta: f32) {
+    for (_, player) in world.query_mut::<FlipbookPlayer>() {
+        player.advance(delta);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::asset_loader::gltf_model::{AnimatedProperty, Channel, GltfModel, Interpolation};
+use crate::core::transform::Transform;
+use crate::frame_arena::FrameArena;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::World;
+use anyhow::Result;
+use glam::{Mat4, Quat, Vec4};
+
+mod flipbook;
+
+pub use flipbook::{FlipbookPlayer, advance_flipbooks};
+
+/// Plays one glTF animation at a time and turns it into node poses.
+///
+/// The engine advances the player every frame and applies the resulting transforms to
+/// the scene's mesh instances; game code picks what to play.
+pub struct AnimationPlayer {
+    animation: Option<usize>,
+    time: f32,
+    paused: bool,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        AnimationPlayer {
+            animation: None,
+            time: 0.0,
+            paused: false,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    /// Starts animation `index` of the model from the beginning.
+    pub fn play(&mut self, index: usize) {
+        self.animation = Some(index);
+        self.time = 0.0;
+        self.paused = false;
+    }
+
+    pub fn stop(&mut self) {
+        self.animation = None;
+        self.time = 0.0;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.animation.is_some() && !self.paused
+    }
+
+    pub fn animation(&self) -> Option<usize> {
+        self.animation
+    }
+
+    /// Playback position in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Moves the playhead by `delta` seconds, wrapping or clamping at the animation end.
+    pub fn advance(&mut self, delta: f32, model: &GltfModel) {
+        let Some(animation) = self.animation.and_then(|index| model.animations.get(index)) else {
+            return;
+        };
+        if self.paused {
+            return;
+        }
+        self.time += delta * self.speed;
+        if animation.duration <= 0.0 {
+            self.time = 0.0;
+        } else if self.looping {
+            self.time = self.time.rem_euclid(animation.duration);
+        } else {
+            self.time = self.time.clamp(0.0, animation.duration);
+        }
+    }
+
+    /// Local transform of every node with the current animation applied on top of the
+    /// model's rest pose.
+    pub fn local_transforms(&self, model: &GltfModel) -> Vec<Mat4> {
+        let mut poses = model
+            .nodes
+            .iter()
+            .map(|node| node.transform)
+            .collect::<Vec<_>>();
+        self.pose(model, &mut poses);
+        poses.iter().map(Transform::compute_matrix).collect()
+    }
+
+    /// Like [`AnimationPlayer::local_transforms`], allocated in `arena` for per-frame use.
+    pub fn local_transforms_in<'a>(
+        &self,
+        model: &GltfModel,
+        arena: &'a FrameArena,
+    ) -> &'a mut [Mat4] {
+        let poses = arena.alloc_slice(model.nodes.iter().map(|node| node.transform));
+        self.pose(model, poses);
+        arena.alloc_slice(poses.iter().map(Transform::compute_matrix))
+    }
+
+    /// Applies the current animation to `poses`, one per node of `model`.
+    fn pose(&self, model: &GltfModel, poses: &mut [Transform]) {
+        if let Some(animation) = self.animation.and_then(|index| model.animations.get(index)) {
+            for channel in &animation.channels {
+                let Some(pose) = poses.get_mut(channel.node) else {
+                    continue;
+                };
+                let Some(value) = sample(channel, self.time) else {
+                    continue;
+                };
+                match channel.property {
+                    AnimatedProperty::Translation => pose.translation = value.truncate(),
+                    AnimatedProperty::Rotation => {
+                        pose.rotation = Quat::from_vec4(value).normalize()
+                    }
+                    AnimatedProperty::Scale => pose.scale = value.truncate(),
+                }
+            }
+        }
+    }
+}
+
+/// Evaluates `channel` at `time`, holding the first and last keyframes outside its range.
+fn sample(channel: &Channel, time: f32) -> Option<Vec4> {
+    let times = &channel.times;
+    let key = |i: usize| match channel.interpolation {
+        Interpolation::CubicSpline => channel.values.get(i * 3 + 1).copied(),
+        _ => channel.values.get(i).copied(),
+    };
+    let last = times.len().checked_sub(1)?;
+    if time <= times[0] {
+        return key(0);
+    }
+    if time >= times[last] {
+        return key(last);
+    }
+    let next = times.partition_point(|&t| t <= time);
+    let prev = next - 1;
+    let dt = times[next] - times[prev];
+    let t = if dt > 0.0 {
+        (time - times[prev]) / dt
+    } else {
+        0.0
+    };
+    let rotation = channel.property == AnimatedProperty::Rotation;
+    match channel.interpolation {
+        Interpolation::Step => key(prev),
+        Interpolation::Linear => {
+            let (a, b) = (key(prev)?, key(next)?);
+            Some(if rotation {
+                Vec4::from(Quat::from_vec4(a).slerp(Quat::from_vec4(b), t))
+            } else {
+                a.lerp(b, t)
+            })
+        }
+        Interpolation::CubicSpline => {
+            let value = |i: usize, offset: usize| channel.values.get(i * 3 + offset).copied();
+            let (p0, m0) = (value(prev, 1)?, value(prev, 2)? * dt);
+            let (p1, m1) = (value(next, 1)?, value(next, 0)? * dt);
+            let (t2, t3) = (t * t, t * t * t);
+            let result = (2.0 * t3 - 3.0 * t2 + 1.0) * p0
+                + (t3 - 2.0 * t2 + t) * m0
+                + (-2.0 * t3 + 3.0 * t2) * p1
+                + (t3 - t2) * m1;
+            Some(if rotation { result.normalize() } else { result })
+        }
+    }
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for AnimationPlayer {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        resources.init::<World>()?;
+        resources
+            .get_mut::<World>()
+            .register_component::<FlipbookPlayer>("flipbook");
+        Ok(AnimationPlayer::new())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::engine::Engine;
+use crate::engine::{Engine, EngineConfig};
 use crate::platform::Platform;
 use crate::platform::platform_winit::WinitPlatform;
 
@@ -14,7 +14,11 @@ impl Default for Application {
 
 impl Application {
     pub fn new() -> Application {
-        let engine = Engine::new();
+        Self::with_config(EngineConfig::default())
+    }
+
+    pub fn with_config(config: EngineConfig) -> Application {
+        let engine = Engine::with_config(config);
         let platform = WinitPlatform::new(engine);
         Application {
             platform: Box::new(platform),
@@ -23,5 +27,12 @@ impl Application {
 
     pub fn run(&mut self) {
         self.platform.run();
+        self.shutdown();
+    }
+
+    /// Waits for the GPU and tears the engine down. Runs automatically when the event
+    /// loop exits; calling it again is a no-op.
+    pub fn shutdown(&mut self) {
+        self.platform.shutdown();
     }
 }
@@ -0,0 +1,186 @@
+use anyhow::{Context, Result, anyhow};
+use assets_manager::{BoxedError, FileAsset};
+use std::borrow::Cow;
+use std::io::{Cursor, ErrorKind};
+use std::sync::Arc;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::Time;
+use tracing::warn;
+
+const AUDIO_EXTENSIONS: &[&str] = &["wav", "ogg", "flac"];
+
+/// Sound fully decoded into memory. Use for short effects; long music should be an
+/// [`AudioStream`].
+#[derive(Debug)]
+pub struct AudioClip {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved samples in `[-1, 1]`.
+    pub samples: Vec<f32>,
+}
+
+impl AudioClip {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+}
+
+impl FileAsset for AudioClip {
+    const EXTENSIONS: &'static [&'static str] = AUDIO_EXTENSIONS;
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        let mut decoder = StreamDecoder::new(Arc::from(bytes.into_owned()))?;
+        let mut samples = Vec::new();
+        while let Some(chunk) = decoder.next_chunk()? {
+            samples.extend_from_slice(chunk);
+        }
+        Ok(AudioClip {
+            sample_rate: decoder.sample_rate,
+            channels: decoder.channels,
+            samples,
+        })
+    }
+}
+
+/// Sound kept compressed in memory and decoded chunk by chunk during playback, so a
+/// five-minute track costs megabytes instead of tens of megabytes.
+#[derive(Debug)]
+pub struct AudioStream {
+    bytes: Arc<[u8]>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Length in frames, if the container reports it.
+    pub frames: Option<u64>,
+}
+
+impl AudioStream {
+    /// A new decoder positioned at the start. Each playing instance needs its own.
+    pub fn decoder(&self) -> Result<StreamDecoder> {
+        StreamDecoder::new(self.bytes.clone())
+    }
+}
+
+impl FileAsset for AudioStream {
+    const EXTENSIONS: &'static [&'static str] = AUDIO_EXTENSIONS;
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        let bytes: Arc<[u8]> = Arc::from(bytes.into_owned());
+        // Probe once up front so broken files fail at load time, not mid-playback.
+        let decoder = StreamDecoder::new(bytes.clone())?;
+        Ok(AudioStream {
+            bytes,
+            sample_rate: decoder.sample_rate,
+            channels: decoder.channels,
+            frames: decoder.frames,
+        })
+    }
+}
+
+/// Incremental decoder over an encoded WAV, OGG Vorbis or FLAC file.
+pub struct StreamDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    buffer: Option<SampleBuffer<f32>>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub frames: Option<u64>,
+}
+
+impl StreamDecoder {
+    fn new(bytes: Arc<[u8]>) -> Result<Self> {
+        let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        let probed = symphonia::default::get_probe()
+            .format(
+                &Hint::new(),
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .context("Unsupported audio format")?;
+        let format = probed.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| anyhow!("No audio track"))?;
+        let params = &track.codec_params;
+        let sample_rate = params.sample_rate.context("Unknown sample rate")?;
+        let channels = params.channels.context("Unknown channel layout")?.count() as u16;
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .context("Unsupported audio codec")?;
+        Ok(StreamDecoder {
+            track_id: track.id,
+            frames: params.n_frames,
+            format,
+            decoder,
+            buffer: None,
+            sample_rate,
+            channels,
+        })
+    }
+
+    /// The next block of interleaved samples, or `None` at the end of the stream.
+    pub fn next_chunk(&mut self) -> Result<Option<&[f32]>> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A corrupt packet only drops a few milliseconds; keep going.
+                Err(SymphoniaError::DecodeError(e)) => {
+                    warn!("Skipping undecodable audio packet: {e}");
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            // Buffer capacity counts samples, decoded capacity counts frames.
+            let spec = *decoded.spec();
+            let frames = decoded.capacity();
+            if self
+                .buffer
+                .as_ref()
+                .is_some_and(|buffer| buffer.capacity() < frames * spec.channels.count())
+            {
+                self.buffer = None;
+            }
+            let buffer = self
+                .buffer
+                .get_or_insert_with(|| SampleBuffer::new(frames as u64, spec));
+            buffer.copy_interleaved_ref(decoded);
+            return Ok(Some(buffer.samples()));
+        }
+    }
+
+    /// Jumps to `secs` from the start, e.g. to loop music.
+    pub fn seek(&mut self, secs: f64) -> Result<()> {
+        self.format.seek(
+            SeekMode::Accurate,
+            SeekTo::Time {
+                time: Time::from(secs),
+                track_id: Some(self.track_id),
+            },
+        )?;
+        self.decoder.reset();
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use crate::asset_loader::gltf_model::{
+    Animation, GltfModel, Image, Material, Mesh, Node, Sampler, Scene, Texture,
+};
+use crate::asset_loader::texture_settings::TextureSettings;
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use assets_manager::{BoxedError, FileAsset};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use gltf::image::Format;
+use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+/// Asset id of the manifest written by `elements-import`.
+pub const MANIFEST_ID: &str = "baked.manifest";
+pub const MODEL_EXT: &str = "emdl";
+pub const TEXTURE_EXT: &str = "etex";
+
+/// Bumped whenever a baked layout changes; older files are rejected and need a re-import.
+pub const FORMAT_VERSION: u32 = 8;
+
+const MODEL_MAGIC: [u8; 4] = *b"EMDL";
+const TEXTURE_MAGIC: [u8; 4] = *b"ETEX";
+
+/// Maps source asset ids to the ids of their baked versions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AssetManifest {
+    pub models: BTreeMap<String, String>,
+    pub textures: BTreeMap<String, String>,
+}
+
+impl FileAsset for AssetManifest {
+    const EXTENSION: &'static str = "ron";
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureFormat {
+    /// 8-bit RGBA. Sampled as sRGB or linear according to the texture's settings.
+    Rgba8Srgb,
+    Rgba32Float,
+    /// GPU block-compressed 8-bit RGBA, sampled as sRGB or linear like [`Rgba8Srgb`].
+    ///
+    /// [`Rgba8Srgb`]: TextureFormat::Rgba8Srgb
+    Block(BlockFormat),
+}
+
+/// BCn encodings `elements-import` compresses 8-bit images into when their settings ask
+/// for [`block_compression`](TextureSettings::block_compression).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockFormat {
+    /// Opaque RGB, 8 bytes per 4x4 block.
+    Bc1,
+    /// RGB with smooth alpha, 16 bytes per 4x4 block.
+    Bc3,
+}
+
+impl BlockFormat {
+    /// BC1 for opaque images, BC3 if any pixel of `rgba` is translucent.
+    pub fn for_rgba8(rgba: &[u8]) -> Self {
+        if rgba.chunks_exact(4).all(|pixel| pixel[3] == u8::MAX) {
+            BlockFormat::Bc1
+        } else {
+            BlockFormat::Bc3
+        }
+    }
+
+    /// Bytes of a `width`x`height` image, counting partial blocks at the edges as whole.
+    pub fn size(self, width: u32, height: u32) -> usize {
+        self.codec()
+            .compressed_size(width as usize, height as usize)
+    }
+
+    pub fn compress(self, rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut blocks = vec![0; self.size(width, height)];
+        let params = texpresso::Params {
+            algorithm: texpresso::Algorithm::ClusterFit,
+            ..Default::default()
+        };
+        self.codec()
+            .compress(rgba, width as usize, height as usize, params, &mut blocks);
+        blocks
+    }
+
+    /// Decodes to 8-bit RGBA, for GPUs that can't sample the blocks directly.
+    pub fn decompress(self, blocks: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut rgba = vec![0; (width * height * 4) as usize];
+        self.codec()
+            .decompress(blocks, width as usize, height as usize, &mut rgba);
+        rgba
+    }
+
+    fn codec(self) -> texpresso::Format {
+        match self {
+            BlockFormat::Bc1 => texpresso::Format::Bc1,
+            BlockFormat::Bc3 => texpresso::Format::Bc3,
+        }
+    }
+}
+
+/// Texture with its full mip chain, ready to copy into a GPU image.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BakedTexture {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    /// Every mip level, largest first. Just the base level if mips are generated at
+    /// runtime or disabled.
+    pub mips: Vec<Vec<u8>>,
+    /// Import settings the texture was baked with.
+    pub settings: TextureSettings,
+}
+
+impl BakedTexture {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        encode(TEXTURE_MAGIC, self, self.settings.compression)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        decode(TEXTURE_MAGIC, bytes)
+    }
+}
+
+impl FileAsset for BakedTexture {
+    const EXTENSION: &'static str = TEXTURE_EXT;
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        Ok(BakedTexture::decode(&bytes)?)
+    }
+}
+
+/// Sampler settings stored as their glTF (OpenGL) enum values.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BakedSampler {
+    pub mag_filter: Option<u32>,
+    pub min_filter: Option<u32>,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+}
+
+impl From<&Sampler> for BakedSampler {
+    fn from(sampler: &Sampler) -> Self {
+        BakedSampler {
+            mag_filter: sampler.mag_filter.map(|f| f.as_gl_enum()),
+            min_filter: sampler.min_filter.map(|f| f.as_gl_enum()),
+            wrap_s: sampler.wrap_s.as_gl_enum(),
+            wrap_t: sampler.wrap_t.as_gl_enum(),
+        }
+    }
+}
+
+impl BakedSampler {
+    fn to_sampler(self) -> Result<Sampler> {
+        const MAG: [MagFilter; 2] = [MagFilter::Nearest, MagFilter::Linear];
+        const MIN: [MinFilter; 6] = [
+            MinFilter::Nearest,
+            MinFilter::Linear,
+            MinFilter::NearestMipmapNearest,
+            MinFilter::LinearMipmapNearest,
+            MinFilter::NearestMipmapLinear,
+            MinFilter::LinearMipmapLinear,
+        ];
+        const WRAP: [WrappingMode; 3] = [
+            WrappingMode::ClampToEdge,
+            WrappingMode::MirroredRepeat,
+            WrappingMode::Repeat,
+        ];
+        fn find<T: Copy>(all: &[T], value: u32, gl: fn(&T) -> u32) -> Result<T> {
+            all.iter()
+                .copied()
+                .find(|item| gl(item) == value)
+                .ok_or_else(|| anyhow!("Unknown sampler enum {value}"))
+        }
+        Ok(Sampler {
+            mag_filter: self
+                .mag_filter
+                .map(|v| find(&MAG, v, MagFilter::as_gl_enum))
+                .transpose()?,
+            min_filter: self
+                .min_filter
+                .map(|v| find(&MIN, v, MinFilter::as_gl_enum))
+                .transpose()?,
+            wrap_s: find(&WRAP, self.wrap_s, WrappingMode::as_gl_enum)?,
+            wrap_t: find(&WRAP, self.wrap_t, WrappingMode::as_gl_enum)?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BakedTextureRef {
+    pub image: Option<usize>,
+    pub sampler: BakedSampler,
+}
+
+/// [`GltfModel`] with deduplicated vertices, precomputed tangents and pre-mipped images.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BakedModel {
+    pub scenes: Vec<Scene>,
+    pub nodes: Vec<Node>,
+    pub meshes: Vec<Mesh>,
+    pub images: Vec<BakedTexture>,
+    pub textures: Vec<BakedTextureRef>,
+    pub materials: Vec<Material>,
+    pub animations: Vec<Animation>,
+}
+
+impl BakedModel {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        encode(MODEL_MAGIC, self, true)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        decode(MODEL_MAGIC, bytes)
+    }
+
+    pub fn into_model(self) -> Result<GltfModel> {
+        let images = self
+            .images
+            .into_iter()
+            .map(|texture| {
+                let block_format = match texture.format {
+                    TextureFormat::Rgba8Srgb => None,
+                    TextureFormat::Block(block_format) => Some(block_format),
+                    TextureFormat::Rgba32Float => {
+                        bail!("Model images must be 8-bit, got {:?}", texture.format)
+                    }
+                };
+                let mut levels = texture.mips.into_iter();
+                let pixels = levels.next().context("Baked image has no mip levels")?;
+                Ok(Image {
+                    pixels,
+                    width: texture.width,
+                    height: texture.height,
+                    format: Format::R8G8B8A8,
+                    mips: levels.collect(),
+                    block_format,
+                    settings: texture.settings,
+                    slots: Vec::new(),
+                    has_sidecar: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let textures = self
+            .textures
+            .into_iter()
+            .map(|texture| {
+                Ok(Texture {
+                    image: texture.image,
+                    sampler: texture.sampler.to_sampler()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(GltfModel {
+            scenes: self.scenes,
+            nodes: self.nodes,
+            meshes: self.meshes,
+            images,
+            textures,
+            materials: self.materials,
+            animations: self.animations,
+        })
+    }
+}
+
+/// Baked files are a magic number and [`FORMAT_VERSION`] followed by deflate-compressed
+/// bincode. Uncompressed files use stored deflate blocks, so decoding is the same.
+fn encode<T: Serialize>(magic: [u8; 4], value: &T, compress: bool) -> Result<Vec<u8>> {
+    let mut bytes = magic.to_vec();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    let level = if compress {
+        Compression::default()
+    } else {
+        Compression::none()
+    };
+    let mut encoder = DeflateEncoder::new(bytes, level);
+    encoder.write_all(&bincode::serialize(value)?)?;
+    Ok(encoder.finish()?)
+}
+
+fn decode<T: DeserializeOwned>(magic: [u8; 4], bytes: &[u8]) -> Result<T> {
+    ensure!(
+        bytes.len() >= 8 && bytes[..4] == magic,
+        "Not a baked {} file",
+        String::from_utf8_lossy(&magic)
+    );
+    let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    ensure!(
+        version == FORMAT_VERSION,
+        "Baked file version {version} doesn't match {FORMAT_VERSION}; re-run the importer"
+    );
+    let mut payload = Vec::new();
+    DeflateDecoder::new(&bytes[8..]).read_to_end(&mut payload)?;
+    Ok(bincode::deserialize(&payload)?)
+}
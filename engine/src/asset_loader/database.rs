@@ -0,0 +1,167 @@
+use assets_manager::{BoxedError, FileAsset};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::Hasher;
+use std::str::FromStr;
+
+/// Asset id of the database `elements-import` keeps next to the source assets. Unlike the
+/// baked output, it belongs in version control.
+pub const DATABASE_ID: &str = "asset_db";
+
+/// Prefix of a GUID reference, e.g. `guid:8c3f0e1d2b4a5968`.
+pub const GUID_PREFIX: &str = "guid:";
+
+/// Identifier an asset keeps when its file is renamed or moved. Derived from the asset's
+/// id when it's first imported, then stored in the [`AssetDatabase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct AssetGuid(pub u64);
+
+impl AssetGuid {
+    /// The GUID an asset first imported as `id` gets, unless it's already taken.
+    pub fn from_id(id: &str) -> Self {
+        let mut hasher = StableHasher::new();
+        hasher.write(id.as_bytes());
+        AssetGuid(hasher.finish())
+    }
+
+    /// Parses a `guid:` reference. `None` for plain asset ids.
+    pub fn from_reference(reference: &str) -> Option<Self> {
+        reference.strip_prefix(GUID_PREFIX)?.parse().ok()
+    }
+
+    /// The `guid:` reference to use in scene files.
+    pub fn reference(self) -> String {
+        format!("{GUID_PREFIX}{self}")
+    }
+}
+
+impl fmt::Display for AssetGuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for AssetGuid {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(AssetGuid)
+    }
+}
+
+impl From<AssetGuid> for String {
+    fn from(guid: AssetGuid) -> Self {
+        guid.to_string()
+    }
+}
+
+impl TryFrom<String> for AssetGuid {
+    type Error = std::num::ParseIntError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// FNV-1a, whose output doesn't change between Rust versions like `DefaultHasher`'s may,
+/// so hashes can be written to disk.
+#[derive(Debug, Clone, Copy)]
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// What the database knows about one asset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetRecord {
+    /// Current asset id, e.g. `vehicles.super_car.scene`.
+    pub id: String,
+    /// Hash of the asset's files when last imported, to recognize it after a move.
+    pub hash: u64,
+}
+
+/// Maps [`AssetGuid`]s to the current ids of their assets, plus the ids assets had before
+/// they moved. Written by `elements-import` to `assets/asset_db.ron`:
+///
+/// ```ron
+/// (
+///     assets: {
+///         "8c3f0e1d2b4a5968": (id: "vehicles.super_car.scene", hash: 1234),
+///     },
+///     redirects: {
+///         "super_car.scene": "8c3f0e1d2b4a5968",
+///     },
+/// )
+/// ```
+///
+/// [`AssetLoader::canonical_id`](crate::asset_loader::AssetLoader::canonical_id) resolves
+/// references through it, so scene files naming either a `guid:` or an old id keep
+/// loading after the asset is renamed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AssetDatabase {
+    #[serde(default)]
+    pub assets: BTreeMap<AssetGuid, AssetRecord>,
+    /// Ids that moved, to the GUID of the asset now elsewhere. Never an id in use.
+    #[serde(default)]
+    pub redirects: BTreeMap<String, AssetGuid>,
+}
+
+impl AssetDatabase {
+    /// The current id of `reference`: a `guid:` reference, an id an asset had before it
+    /// moved, or any other id, returned as-is. `None` for unknown GUIDs.
+    pub fn resolve<'a>(&'a self, reference: &'a str) -> Option<&'a str> {
+        match AssetGuid::from_reference(reference) {
+            Some(guid) => self.id(guid),
+            None => match self.redirects.get(reference) {
+                Some(&guid) => self.id(guid),
+                None => Some(reference),
+            },
+        }
+    }
+
+    pub fn id(&self, guid: AssetGuid) -> Option<&str> {
+        self.assets.get(&guid).map(|record| record.id.as_str())
+    }
+
+    /// The GUID of the asset currently or formerly at `id`.
+    pub fn guid(&self, id: &str) -> Option<AssetGuid> {
+        self.redirects.get(id).copied().or_else(|| {
+            self.assets
+                .iter()
+                .find(|(_, record)| record.id == id)
+                .map(|(&guid, _)| guid)
+        })
+    }
+}
+
+impl FileAsset for AssetDatabase {
+    const EXTENSION: &'static str = "ron";
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+}
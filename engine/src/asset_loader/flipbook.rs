@@ -0,0 +1,131 @@
+use assets_manager::{BoxedError, FileAsset};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// A texture atlas cut into a grid of equally sized frames, with named clips that play
+/// runs of them. Authored as RON next to the atlas, e.g. `assets/fx/explosion.flipbook`:
+///
+/// ```ron
+/// (
+///     texture: "fx.explosion",
+///     columns: 8,
+///     rows: 4,
+///     clips: {
+///         "burst": (first: 0, count: 24, fps: 30.0, looping: false),
+///         "smoke": (first: 24, count: 8, fps: 12.0),
+///     },
+/// )
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flipbook {
+    /// Asset id of the atlas image.
+    pub texture: String,
+    pub columns: u32,
+    pub rows: u32,
+    #[serde(default)]
+    pub clips: BTreeMap<String, FlipbookClip>,
+}
+
+/// A run of consecutive frames played at a fixed rate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FlipbookClip {
+    /// Index of the first frame, counted row by row from the top left of the atlas.
+    pub first: u32,
+    pub count: u32,
+    /// Frames per second.
+    pub fps: f32,
+    /// Wraps around at the end instead of holding the last frame. Defaults to `true`.
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+/// Part of a texture in UV coordinates, with `min` at the top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Flipbook {
+    pub fn frame_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&FlipbookClip> {
+        self.clips.get(name)
+    }
+
+    /// Where frame `index` sits in the atlas. Indices past the last frame wrap around.
+    pub fn frame_uv(&self, index: u32) -> UvRect {
+        let columns = self.columns.max(1);
+        let rows = self.rows.max(1);
+        let index = index % (columns * rows);
+        let size = Vec2::new(1.0 / columns as f32, 1.0 / rows as f32);
+        let min = Vec2::new((index % columns) as f32, (index / columns) as f32) * size;
+        UvRect {
+            min,
+            max: min + size,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.columns == 0 || self.rows == 0 {
+            return Err("Flipbook grid needs at least one column and row".to_owned());
+        }
+        for (name, clip) in &self.clips {
+            if clip.count == 0 || clip.first + clip.count > self.frame_count() {
+                return Err(format!(
+                    "Clip {name:?} plays frames {}..{} of a {}-frame grid",
+                    clip.first,
+                    clip.first + clip.count,
+                    self.frame_count()
+                ));
+            }
+            if clip.fps.is_nan() || clip.fps <= 0.0 {
+                return Err(format!("Clip {name:?} needs a positive fps"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FlipbookClip {
+    /// Seconds from the first frame to the end of the last.
+    pub fn duration(&self) -> f32 {
+        self.count as f32 / self.fps
+    }
+
+    /// Atlas frame shown `time` seconds into the clip. Stateless, so particles can pass
+    /// their age instead of keeping a player each.
+    pub fn frame_at(&self, time: f32) -> u32 {
+        let count = self.count.max(1);
+        let frame = (time.max(0.0) * self.fps) as u32;
+        let frame = if self.looping {
+            frame % count
+        } else {
+            frame.min(count - 1)
+        };
+        self.first + frame
+    }
+
+    /// Whether a clip that doesn't loop has reached its end `time` seconds in.
+    pub fn is_finished(&self, time: f32) -> bool {
+        !self.looping && time >= self.duration()
+    }
+}
+
+impl FileAsset for Flipbook {
+    const EXTENSION: &'static str = "flipbook";
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        let flipbook: Flipbook = ron::de::from_bytes(&bytes)?;
+        flipbook.validate()?;
+        Ok(flipbook)
+    }
+}
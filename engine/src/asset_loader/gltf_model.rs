@@ -1,19 +1,76 @@
-use crate::core::vertex::{ElmVec2, ElmVec3, ElmVertex};
+use crate::asset_loader::baked::{self, AssetManifest, BakedModel, BlockFormat};
+use crate::asset_loader::texture_settings::{self, ColorSpace, TextureSettings};
+use crate::core::bounds::Aabb;
+use crate::core::transform::Transform;
+use crate::core::vertex::{ElmVec2, ElmVec3, ElmVec4, ElmVertex};
+use crate::frame_arena::FrameArena;
+use crate::scene::{RenderLayers, Visibility};
 use anyhow::{Context, anyhow};
 use assets_manager::asset::Gltf;
+use assets_manager::source::{DirEntry, Source};
 use assets_manager::{Asset, AssetCache, BoxedError, SharedString};
-use glam::{Vec2, Vec3};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use gltf::animation::util::ReadOutputs;
 use gltf::image::Format;
+use gltf::material::AlphaMode;
 use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use tracing::warn;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Primitive {
     pub vertices: Vec<ElmVertex>,
     pub indices: Vec<u32>,
+    /// Bounds of the vertex positions in mesh space.
+    pub bounds: Aabb,
+    /// Index into [`GltfModel::materials`]; `None` uses the renderer's default material.
+    pub material: Option<usize>,
 }
 
-#[derive(Debug)]
+impl Primitive {
+    /// Fills in per-vertex tangents from positions, normals and texture coordinates for
+    /// vertices that have none. Slow enough that the importer does it offline.
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vec3::ZERO; self.vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| &self.vertices[triangle[k] as usize]);
+            let (edge1, edge2) = (*b.position - *a.position, *c.position - *a.position);
+            let (duv1, duv2) = (*b.tex_coord - *a.tex_coord, *c.tex_coord - *a.tex_coord);
+            let det = duv1.x * duv2.y - duv2.x * duv1.y;
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+            let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+            for &index in triangle {
+                tangents[index as usize] += tangent;
+                bitangents[index as usize] += bitangent;
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            if *vertex.tangent != Vec4::ZERO {
+                continue;
+            }
+            let normal = *vertex.normal;
+            // Gram-Schmidt against the normal; degenerate UVs get any perpendicular axis.
+            let tangent = (tangents[i] - normal * normal.dot(tangents[i]))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            vertex.tangent = ElmVec4::from(tangent.extend(handedness));
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Mesh {
     pub primitives: Vec<Primitive>,
 }
@@ -24,6 +81,52 @@ pub struct Image {
     pub width: u32,
     pub height: u32,
     pub format: Format,
+    /// Precomputed mip levels below `pixels`, largest first. Empty when the renderer
+    /// should generate them.
+    pub mips: Vec<Vec<u8>>,
+    /// `pixels` and `mips` hold blocks in this format instead of RGBA8. Only baked images
+    /// are compressed.
+    pub block_format: Option<BlockFormat>,
+    /// From the image's `.meta` sidecar, if it has one. Otherwise the color space follows
+    /// from [`Image::slots`].
+    pub settings: TextureSettings,
+    /// Material slots that sample this image. Empty for baked models, whose settings
+    /// were resolved at import.
+    pub slots: Vec<TextureSlot>,
+    /// `settings` came from a sidecar rather than defaults.
+    pub has_sidecar: bool,
+}
+
+/// A glTF material input that samples a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureSlot {
+    BaseColor,
+    Emissive,
+    Normal,
+    Occlusion,
+    MetallicRoughness,
+}
+
+impl TextureSlot {
+    /// The color space glTF stores this slot's texels in.
+    pub fn color_space(self) -> ColorSpace {
+        match self {
+            TextureSlot::BaseColor | TextureSlot::Emissive => ColorSpace::Srgb,
+            TextureSlot::Normal | TextureSlot::Occlusion | TextureSlot::MetallicRoughness => {
+                ColorSpace::Linear
+            }
+        }
+    }
+}
+
+/// An image sampled by a material slot that expects another color space than the image
+/// is tagged with. See [`GltfModel::color_space_audit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorSpaceMismatch {
+    /// Index into [`GltfModel::images`].
+    pub image: usize,
+    pub slot: TextureSlot,
+    pub tagged: ColorSpace,
 }
 
 #[derive(Debug)]
@@ -40,22 +143,94 @@ pub struct Texture {
     pub sampler: Sampler,
 }
 
-#[derive(Debug)]
+/// Surface parameters of a glTF material that the renderer understands.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Material {
-    pub textures: Vec<u32>,
+    pub base_color_factor: Vec4,
+    /// Index into [`GltfModel::textures`].
+    pub base_color_texture: Option<usize>,
+    /// Draw back faces too instead of culling them.
+    pub double_sided: bool,
+    /// `alphaMode: BLEND`; the renderer sorts these and blends them over opaque meshes.
+    /// `MASK` is drawn opaque, see `alpha_cutoff`.
+    pub blend: bool,
+    /// `alphaCutoff` of an `alphaMode: MASK` material.
+    pub alpha_cutoff: Option<f32>,
+    /// Uses `KHR_materials_unlit`.
+    pub unlit: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Node {
     pub mesh_id: Option<usize>,
     pub children: Vec<usize>, // Indices of child nodes in the Scene's nodes vector
+    /// Rest pose relative to the parent node.
+    pub transform: Transform,
+    /// Hides the node's meshes and, unless they say otherwise, its children's. Read from
+    /// the glTF node's extras, e.g. `"extras": {"visibility": "Hidden"}`.
+    pub visibility: Visibility,
+    /// Cameras that draw the node's meshes, from e.g. `"extras": {"render_layers": 4}`.
+    /// `None` takes the parent's; roots take those of the entity carrying the model.
+    pub layers: Option<RenderLayers>,
 }
 
-#[derive(Debug)]
+/// The parts of a glTF node's `extras` the engine reads.
+#[derive(Debug, Default, Deserialize)]
+struct NodeExtras {
+    #[serde(default)]
+    visibility: Visibility,
+    #[serde(default)]
+    render_layers: Option<RenderLayers>,
+}
+
+impl Node {
+    /// Transform relative to the parent node.
+    pub fn local_transform(&self) -> Mat4 {
+        self.transform.compute_matrix()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Scene {
     pub nodes: Vec<usize>, // Only root node indices
 }
 
+/// Node property driven by an animation channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnimatedProperty {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    /// Each keyframe stores `[in_tangent, value, out_tangent]` in [`Channel::values`].
+    CubicSpline,
+}
+
+/// Keyframes for one property of one node.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Channel {
+    pub node: usize,
+    pub property: AnimatedProperty,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds, ascending.
+    pub times: Vec<f32>,
+    /// Keyframe values; translations and scales leave `w` at zero, rotations are `xyzw`.
+    pub values: Vec<Vec4>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Animation {
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+    /// Time of the last keyframe across all channels.
+    pub duration: f32,
+}
+
 #[derive(Debug)]
 pub struct GltfModel {
     pub scenes: Vec<Scene>,
@@ -64,15 +239,224 @@ pub struct GltfModel {
     pub images: Vec<Image>,
     pub textures: Vec<Texture>,
     pub materials: Vec<Material>,
+    pub animations: Vec<Animation>,
+}
+
+impl GltfModel {
+    /// Nodes that start a hierarchy: the first scene's roots, or every node that isn't
+    /// someone's child when the file has no scenes.
+    pub fn root_nodes(&self) -> Vec<usize> {
+        if let Some(scene) = self.scenes.first() {
+            return scene.nodes.clone();
+        }
+        let mut is_child = vec![false; self.nodes.len()];
+        for child in self.nodes.iter().flat_map(|node| &node.children) {
+            is_child[*child] = true;
+        }
+        (0..self.nodes.len()).filter(|&i| !is_child[i]).collect()
+    }
+
+    /// Bounds of every mesh in model space, or `None` for a model without meshes. Built
+    /// from the per-primitive bounds, so rotated nodes make it a little loose.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let transforms = self.world_transforms();
+        self.nodes
+            .iter()
+            .zip(transforms)
+            .filter_map(|(node, transform)| Some((self.meshes.get(node.mesh_id?)?, transform?)))
+            .flat_map(|(mesh, transform)| {
+                mesh.primitives
+                    .iter()
+                    .map(move |primitive| primitive.bounds.transformed(&transform))
+            })
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Which nodes can move after loading: targets of any animation's channels, the nodes
+    /// in `moved`, and everything below them. The rest can be baked into static batches.
+    pub fn dynamic_nodes(&self, moved: &[usize]) -> Vec<bool> {
+        let mut dynamic = vec![false; self.nodes.len()];
+        let animated = self
+            .animations
+            .iter()
+            .flat_map(|animation| &animation.channels)
+            .map(|channel| channel.node);
+        let mut stack = animated.chain(moved.iter().copied()).collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if let Some(flag) = dynamic.get_mut(node)
+                && !*flag
+            {
+                *flag = true;
+                stack.extend(&self.nodes[node].children);
+            }
+        }
+        dynamic
+    }
+
+    /// Whether each node is drawn and on which layers, when the model's entity is
+    /// `visible` on `layers`. `overrides` replace nodes' own [`Visibility`]. Like entities,
+    /// the nearest visibility other than `Inherit` up the hierarchy decides.
+    pub fn node_visibility(
+        &self,
+        visible: bool,
+        layers: RenderLayers,
+        overrides: &HashMap<usize, Visibility>,
+    ) -> Vec<(bool, RenderLayers)> {
+        let mut shown = vec![(false, layers); self.nodes.len()];
+        let mut stack = self
+            .root_nodes()
+            .into_iter()
+            .map(|node| (node, (visible, layers)))
+            .collect::<Vec<_>>();
+        while let Some((node, (parent_visible, parent_layers))) = stack.pop() {
+            let Some(data) = self.nodes.get(node) else {
+                continue;
+            };
+            let visible = match overrides.get(&node).copied().unwrap_or(data.visibility) {
+                Visibility::Inherit => parent_visible,
+                Visibility::Visible => true,
+                Visibility::Hidden => false,
+            };
+            shown[node] = (visible, data.layers.unwrap_or(parent_layers));
+            stack.extend(data.children.iter().map(|&child| (child, shown[node])));
+        }
+        shown
+    }
+
+    /// Nodes whose own [`Visibility`], with `overrides` applied, or [`RenderLayers`] set
+    /// them apart from their parent.
+    pub fn nodes_with_visibility(&self, overrides: &HashMap<usize, Visibility>) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(index, node)| {
+                overrides.get(index).copied().unwrap_or(node.visibility) != Visibility::Inherit
+                    || node.layers.is_some()
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// World transform of every node, composed down the hierarchy from
+    /// [`GltfModel::root_nodes`]. `None` for nodes outside that hierarchy.
+    pub fn world_transforms(&self) -> Vec<Option<Mat4>> {
+        let local = self
+            .nodes
+            .iter()
+            .map(Node::local_transform)
+            .collect::<Vec<_>>();
+        self.world_transforms_from(&local)
+    }
+
+    /// Like [`GltfModel::world_transforms`], but with caller-provided local transforms
+    /// (one per node), e.g. an animated pose.
+    pub fn world_transforms_from(&self, local: &[Mat4]) -> Vec<Option<Mat4>> {
+        let mut world = vec![None; self.nodes.len()];
+        for node in self.root_nodes() {
+            self.compose(node, Mat4::IDENTITY, local, &mut world);
+        }
+        world
+    }
+
+    /// Like [`GltfModel::world_transforms_from`], allocated in `arena` for per-frame use.
+    pub fn world_transforms_in<'a>(
+        &self,
+        local: &[Mat4],
+        arena: &'a FrameArena,
+    ) -> &'a [Option<Mat4>] {
+        let world = arena.alloc_slice(std::iter::repeat_n(None, self.nodes.len()));
+        let roots = match self.scenes.first() {
+            Some(scene) => &scene.nodes[..],
+            None => arena.alloc_slice(self.root_nodes()),
+        };
+        for &node in roots {
+            self.compose(node, Mat4::IDENTITY, local, world);
+        }
+        world
+    }
+
+    /// Sets the world transform of `node` and its descendants.
+    fn compose(&self, node: usize, parent: Mat4, local: &[Mat4], world: &mut [Option<Mat4>]) {
+        let transform = parent * local[node];
+        world[node] = Some(transform);
+        for &child in &self.nodes[node].children {
+            self.compose(child, transform, local, world);
+        }
+    }
 }
 
 impl Asset for GltfModel {
     fn load(cache: &AssetCache, id: &SharedString) -> Result<Self, BoxedError> {
+        if let Some(model) = load_baked(cache, id)? {
+            return Ok(model);
+        }
         let handle = cache
             .load::<Gltf>(id)
             .with_context(|| "Gltf could not be loaded.")?;
         let gltf = handle.read();
+        let mut model = GltfModel::from_gltf(&gltf)?;
+        model.apply_texture_settings(cache, id, &gltf);
+        Ok(model)
+    }
+}
+
+/// Loads the baked version of `id` if the import manifest lists one. The manifest is
+/// loaded through the cache, so re-running the importer hot-reloads the model.
+fn load_baked(cache: &AssetCache, id: &str) -> anyhow::Result<Option<GltfModel>> {
+    if !cache
+        .source()
+        .exists(DirEntry::File(baked::MANIFEST_ID, "ron"))
+    {
+        return Ok(None);
+    }
+    let manifest = cache.load::<AssetManifest>(baked::MANIFEST_ID)?;
+    let Some(baked_id) = manifest.read().models.get(id).cloned() else {
+        return Ok(None);
+    };
+    let source = cache.source();
+    let bytes = source
+        .read(&baked_id, baked::MODEL_EXT)
+        .with_context(|| format!("Baked model {baked_id} could not be read"))?;
+    let model = BakedModel::decode(bytes.as_ref())?;
+    Ok(Some(model.into_model()?))
+}
+
+impl GltfModel {
+    /// Reads the `.meta` sidecar of every image stored as a file next to the model.
+    pub fn apply_texture_settings(&mut self, cache: &AssetCache, model_id: &str, gltf: &Gltf) {
+        for (image, source) in self.images.iter_mut().zip(gltf.document.images()) {
+            if let gltf::image::Source::Uri { uri, .. } = source.source()
+                && let Some(image_id) = texture_settings::image_asset_id(model_id, uri)
+                && let Some(settings) = TextureSettings::from_sidecar(cache, &image_id)
+            {
+                image.settings = settings;
+                image.has_sidecar = true;
+            }
+        }
+    }
 
+    /// Every image sampled as a different color space than it's tagged with, e.g. a
+    /// normal map whose sidecar says sRGB, or one image used for both color and data.
+    pub fn color_space_audit(&self) -> Vec<ColorSpaceMismatch> {
+        self.images
+            .iter()
+            .enumerate()
+            .flat_map(|(index, image)| {
+                image
+                    .slots
+                    .iter()
+                    .filter(|slot| slot.color_space() != image.settings.color_space)
+                    .map(move |&slot| ColorSpaceMismatch {
+                        image: index,
+                        slot,
+                        tagged: image.settings.color_space,
+                    })
+            })
+            .collect()
+    }
+
+    /// Converts a parsed glTF document. Used by the asset loader and the offline importer.
+    pub fn from_gltf(gltf: &Gltf) -> anyhow::Result<Self> {
         let mut scenes = Vec::new();
         for scene in gltf.document.scenes() {
             let mut root_nodes = Vec::new();
@@ -89,81 +473,123 @@ impl Asset for GltfModel {
             for child in node.children() {
                 child_indices.push(child.index());
             }
+            // Matrix transforms are decomposed so animation can drive each channel.
+            let (translation, rotation, scale) = node.transform().decomposed();
+            let extras = node
+                .extras()
+                .as_ref()
+                .map(|extras| serde_json::from_str::<NodeExtras>(extras.get()))
+                .transpose()
+                .unwrap_or_else(|e| {
+                    warn!("Ignoring extras of node {}: {e}", node.index());
+                    None
+                })
+                .unwrap_or_default();
             nodes.push(Node {
                 mesh_id,
                 children: child_indices,
+                transform: Transform::new(
+                    Vec3::from(translation),
+                    Quat::from_array(rotation),
+                    Vec3::from(scale),
+                ),
+                visibility: extras.visibility,
+                layers: extras.render_layers,
             });
         }
 
-        let mut meshes = Vec::new();
-        for mesh in gltf.document.meshes() {
-            let mut primitives = Vec::new();
-            for primitive in mesh.primitives() {
-                let reader =
-                    primitive.reader(|buffer| Some(gltf.get_buffer_by_index(buffer.index())));
-                let indices: Vec<u32> = reader
-                    .read_indices()
-                    .ok_or(anyhow!("No indices in mesh"))?
-                    .into_u32()
-                    .collect();
-                let positions: Vec<[f32; 3]> = reader
-                    .read_positions()
-                    .ok_or(anyhow!("No positions in mesh"))?
-                    .collect();
-                let tex_coords: Option<Vec<[f32; 2]>> =
-                    reader.read_tex_coords(0).map(|tc| tc.into_f32().collect());
-
-                let mut unique_vertices = HashMap::<ElmVertex, u32>::new();
-                let mut vertices: Vec<ElmVertex> = Vec::new();
-                let mut remapped_indices: Vec<u32> = Vec::with_capacity(indices.len());
-
-                for &i in &indices {
-                    let position = ElmVec3::from(Vec3::from(positions[i as usize]));
-                    let tex_coord = if let Some(ref tcs) = tex_coords {
-                        ElmVec2::from(Vec2::from(tcs[i as usize]))
-                    } else {
-                        ElmVec2::from(Vec2::new(0.0, 0.0))
-                    };
-                    let color = ElmVec3::from(Vec3::new(1.0, 1.0, 1.0)); // Default white color
-
-                    let vertex = ElmVertex {
-                        position,
-                        color,
-                        tex_coord,
-                    };
-
-                    let index = *unique_vertices.entry(vertex).or_insert_with(|| {
-                        let new_index = vertices.len() as u32;
-                        vertices.push(vertex);
-                        new_index
-                    });
-                    remapped_indices.push(index);
-                }
+        // Mesh deduplication and image decoding dominate load time, so both are spread
+        // across the task pool the loader was installed on.
+        let meshes = gltf
+            .document
+            .meshes()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map(|mesh| {
+                let primitives = mesh
+                    .primitives()
+                    .map(|primitive| load_primitive(gltf, &primitive))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok(Mesh {
+                    // Skip empty primitives
+                    primitives: primitives.into_iter().flatten().collect(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
-                if vertices.is_empty() {
-                    continue; // Skip empty meshes
+        let mut slots: Vec<Vec<TextureSlot>> = vec![Vec::new(); gltf.document.images().len()];
+        for material in gltf.document.materials() {
+            let pbr = material.pbr_metallic_roughness();
+            let sampled = [
+                (
+                    TextureSlot::BaseColor,
+                    pbr.base_color_texture().map(|info| info.texture()),
+                ),
+                (
+                    TextureSlot::Emissive,
+                    material.emissive_texture().map(|info| info.texture()),
+                ),
+                (
+                    TextureSlot::Normal,
+                    material.normal_texture().map(|info| info.texture()),
+                ),
+                (
+                    TextureSlot::Occlusion,
+                    material.occlusion_texture().map(|info| info.texture()),
+                ),
+                (
+                    TextureSlot::MetallicRoughness,
+                    pbr.metallic_roughness_texture().map(|info| info.texture()),
+                ),
+            ];
+            for (slot, texture) in sampled {
+                let Some(texture) = texture else {
+                    continue;
+                };
+                let image_slots = &mut slots[texture.source().index()];
+                if !image_slots.contains(&slot) {
+                    image_slots.push(slot);
                 }
-
-                primitives.push(Primitive {
-                    vertices,
-                    indices: remapped_indices,
-                });
             }
-            meshes.push(Mesh { primitives });
-        }
-
-        let mut images = Vec::new();
-        for image in gltf.document.images() {
-            let image_data = gltf.get_image(&image).to_rgba8();
-            let (width, height) = image_data.dimensions();
-            images.push(Image {
-                pixels: image_data.into_raw(),
-                width,
-                height,
-                format: Format::R8G8B8A8,
-            });
         }
 
+        let images = gltf
+            .document
+            .images()
+            .zip(slots)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|(image, slots)| {
+                let image_data = gltf.get_image(&image).to_rgba8();
+                let (width, height) = image_data.dimensions();
+                // Color wins for images shared between color and data slots; the audit
+                // reports those.
+                let color_space = if !slots.is_empty()
+                    && slots
+                        .iter()
+                        .all(|slot| slot.color_space() == ColorSpace::Linear)
+                {
+                    ColorSpace::Linear
+                } else {
+                    ColorSpace::Srgb
+                };
+                Image {
+                    pixels: image_data.into_raw(),
+                    width,
+                    height,
+                    format: Format::R8G8B8A8,
+                    mips: Vec::new(),
+                    block_format: None,
+                    settings: TextureSettings {
+                        color_space,
+                        ..Default::default()
+                    },
+                    slots,
+                    has_sidecar: false,
+                }
+            })
+            .collect::<Vec<_>>();
+
         let mut textures = Vec::new();
         for texture in gltf.document.textures() {
             let sampler = texture.sampler();
@@ -180,13 +606,168 @@ impl Asset for GltfModel {
             });
         }
 
+        let materials = gltf
+            .document
+            .materials()
+            .map(|material| {
+                let pbr = material.pbr_metallic_roughness();
+                Material {
+                    base_color_factor: Vec4::from(pbr.base_color_factor()),
+                    base_color_texture: pbr.base_color_texture().map(|info| info.texture().index()),
+                    double_sided: material.double_sided(),
+                    blend: material.alpha_mode() == AlphaMode::Blend,
+                    alpha_cutoff: (material.alpha_mode() == AlphaMode::Mask)
+                        .then(|| material.alpha_cutoff().unwrap_or(0.5)),
+                    unlit: material.unlit(),
+                }
+            })
+            .collect();
+
+        let animations = gltf
+            .document
+            .animations()
+            .map(|animation| load_animation(gltf, &animation))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
         Ok(GltfModel {
             scenes,
             nodes,
             meshes,
             images,
             textures,
-            materials: Vec::new(),
+            materials,
+            animations,
         })
     }
 }
+
+fn load_animation(gltf: &Gltf, animation: &gltf::Animation) -> anyhow::Result<Animation> {
+    let mut channels = Vec::new();
+    for channel in animation.channels() {
+        let reader = channel.reader(|buffer| Some(gltf.get_buffer_by_index(buffer.index())));
+        let times: Vec<f32> = reader
+            .read_inputs()
+            .ok_or(anyhow!("No keyframe times in animation channel"))?
+            .collect();
+        let (property, values) = match reader
+            .read_outputs()
+            .ok_or(anyhow!("No keyframe values in animation channel"))?
+        {
+            ReadOutputs::Translations(values) => (
+                AnimatedProperty::Translation,
+                values.map(|v| Vec3::from(v).extend(0.0)).collect(),
+            ),
+            ReadOutputs::Rotations(values) => (
+                AnimatedProperty::Rotation,
+                values.into_f32().map(Vec4::from).collect(),
+            ),
+            ReadOutputs::Scales(values) => (
+                AnimatedProperty::Scale,
+                values.map(|v| Vec3::from(v).extend(0.0)).collect(),
+            ),
+            // Morph targets aren't supported by the renderer yet.
+            ReadOutputs::MorphTargetWeights(_) => continue,
+        };
+        let interpolation = match channel.sampler().interpolation() {
+            gltf::animation::Interpolation::Linear => Interpolation::Linear,
+            gltf::animation::Interpolation::Step => Interpolation::Step,
+            gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+        };
+        channels.push(Channel {
+            node: channel.target().node().index(),
+            property,
+            interpolation,
+            times,
+            values,
+        });
+    }
+    let duration = channels
+        .iter()
+        .filter_map(|channel| channel.times.last().copied())
+        .fold(0.0, f32::max);
+    Ok(Animation {
+        name: animation.name().map(str::to_owned),
+        channels,
+        duration,
+    })
+}
+
+/// Face normal of `triangle`, used when the mesh has no `NORMAL` attribute. Vertices are
+/// deduplicated per normal too, so shared corners split and shading stays flat.
+fn flat_normal(positions: &[[f32; 3]], indices: &[u32], triangle: usize) -> Vec3 {
+    let Some(corners) = indices.get(triangle * 3..triangle * 3 + 3) else {
+        return Vec3::Z;
+    };
+    let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(positions[corners[k] as usize]));
+    (b - a).cross(c - a).try_normalize().unwrap_or(Vec3::Z)
+}
+
+fn load_primitive(gltf: &Gltf, primitive: &gltf::Primitive) -> anyhow::Result<Option<Primitive>> {
+    let reader = primitive.reader(|buffer| Some(gltf.get_buffer_by_index(buffer.index())));
+    let indices: Vec<u32> = reader
+        .read_indices()
+        .ok_or(anyhow!("No indices in mesh"))?
+        .into_u32()
+        .collect();
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or(anyhow!("No positions in mesh"))?
+        .collect();
+    let tex_coords: Option<Vec<[f32; 2]>> =
+        reader.read_tex_coords(0).map(|tc| tc.into_f32().collect());
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+    let colors: Option<Vec<[f32; 3]>> = reader.read_colors(0).map(|c| c.into_rgb_f32().collect());
+    let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|t| t.collect());
+
+    let mut unique_vertices = HashMap::<ElmVertex, u32>::new();
+    let mut vertices: Vec<ElmVertex> = Vec::new();
+    let mut remapped_indices: Vec<u32> = Vec::with_capacity(indices.len());
+
+    for (corner, &i) in indices.iter().enumerate() {
+        let position = ElmVec3::from(Vec3::from(positions[i as usize]));
+        let tex_coord = if let Some(ref tcs) = tex_coords {
+            ElmVec2::from(Vec2::from(tcs[i as usize]))
+        } else {
+            ElmVec2::from(Vec2::new(0.0, 0.0))
+        };
+        let color = match colors {
+            Some(ref colors) => ElmVec3::from(Vec3::from(colors[i as usize])),
+            None => ElmVec3::from(Vec3::new(1.0, 1.0, 1.0)), // Default white color
+        };
+        let normal = match normals {
+            Some(ref normals) => Vec3::from(normals[i as usize]),
+            None => flat_normal(&positions, &indices, corner / 3),
+        };
+
+        let vertex = ElmVertex {
+            position,
+            color,
+            tex_coord,
+            normal: ElmVec3::from(normal),
+            tangent: ElmVec4::from(match tangents {
+                Some(ref tangents) => Vec4::from(tangents[i as usize]),
+                None => Vec4::ZERO,
+            }),
+        };
+
+        let index = *unique_vertices.entry(vertex).or_insert_with(|| {
+            let new_index = vertices.len() as u32;
+            vertices.push(vertex);
+            new_index
+        });
+        remapped_indices.push(index);
+    }
+
+    if vertices.is_empty() {
+        return Ok(None);
+    }
+
+    let bounds = Aabb::from_points(vertices.iter().map(|vertex| *vertex.position))
+        .with_context(|| "Primitive has no vertices")?;
+    Ok(Some(Primitive {
+        vertices,
+        indices: remapped_indices,
+        bounds,
+        material: primitive.material().index(),
+    }))
+}
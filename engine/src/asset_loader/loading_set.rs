@@ -0,0 +1,90 @@
+use crate::asset_loader::{AssetLoader, Handle, LoadState};
+use assets_manager::{Asset, SharedString};
+use std::any::TypeId;
+use std::sync::Arc;
+
+/// Aggregate state of a [`LoadingSet`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize,
+    /// Bytes read from disk or paks for the assets loaded so far.
+    pub bytes: u64,
+}
+
+impl LoadProgress {
+    /// Share of assets that finished, successfully or not, in `[0, 1]`.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed == self.total
+    }
+}
+
+/// Group of assets loaded together on the task pool, e.g. everything a level needs,
+/// so a loading screen can show progress instead of the first frame freezing.
+///
+/// The set holds strong handles, so its assets stay loaded until it is dropped.
+#[derive(Default)]
+pub struct LoadingSet {
+    assets: Vec<(TypeId, Arc<SharedString>)>,
+}
+
+impl LoadingSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts loading every id as a `T`.
+    pub fn load<T: Asset>(loader: &AssetLoader, ids: &[&str]) -> Self {
+        let mut set = LoadingSet::new();
+        for id in ids {
+            set.add::<T>(loader, id);
+        }
+        set
+    }
+
+    /// Starts loading `id` as part of the set and returns its handle.
+    pub fn add<T: Asset>(&mut self, loader: &AssetLoader, id: &str) -> Handle<T> {
+        let handle = loader.load_async::<T>(id);
+        self.assets.push((TypeId::of::<T>(), handle.id.clone()));
+        handle
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    pub fn progress(&self, loader: &AssetLoader) -> LoadProgress {
+        let entries = loader.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut progress = LoadProgress {
+            total: self.assets.len(),
+            ..LoadProgress::default()
+        };
+        for (type_id, id) in &self.assets {
+            let Some(entry) = entries.get(&(*type_id, (**id).clone())) else {
+                continue;
+            };
+            match entry.state {
+                LoadState::Loaded => {
+                    progress.loaded += 1;
+                    progress.bytes += entry.bytes;
+                }
+                LoadState::Failed => progress.failed += 1,
+                LoadState::NotLoaded | LoadState::Loading => {}
+            }
+        }
+        progress
+    }
+}
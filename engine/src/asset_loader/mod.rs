@@ -1,20 +1,381 @@
-use assets_manager::AssetCache;
+use crate::engine::EngineConfig;
+use crate::events::{EventSender, Events};
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::task_pool::TaskPool;
+use anyhow::Result;
+use assets_manager::source::{DirEntry, Source as _};
+use assets_manager::{ArcHandle, Asset, AssetCache, ReloadId, SharedString};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::marker::PhantomData;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use tracing::{error, info, info_span, warn};
 
+pub mod audio;
+pub mod baked;
+pub mod database;
+pub mod flipbook;
 pub mod gltf_model;
+mod loading_set;
+pub mod shader;
+pub mod source;
+pub mod texture_settings;
+
+pub use database::{AssetDatabase, AssetGuid};
+pub use loading_set::{LoadProgress, LoadingSet};
+pub use source::{AssetMount, LayeredSource};
+
+/// Progress of an asset requested through [`AssetLoader::load_async`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    NotLoaded,
+    Loading,
+    Loaded,
+    Failed,
+}
+
+/// Counts of the assets tracked by an [`AssetLoader`], as shown by the performance overlay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AssetStats {
+    pub loading: usize,
+    pub loaded: usize,
+    pub failed: usize,
+    /// Bytes read from the asset sources for the loaded assets.
+    pub bytes: u64,
+}
+
+/// Strong, reference-counted reference to an asset that may still be loading. The asset
+/// stays in memory while at least one strong handle exists.
+pub struct Handle<T> {
+    id: Arc<SharedString>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> &SharedString {
+        &self.id
+    }
+
+    pub fn downgrade(&self) -> WeakHandle<T> {
+        WeakHandle {
+            id: (*self.id).clone(),
+            inner: Arc::downgrade(&self.id),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            id: self.id.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Handle").field(&*self.id).finish()
+    }
+}
+
+/// Non-owning reference to an asset. Doesn't keep it loaded.
+pub struct WeakHandle<T> {
+    id: SharedString,
+    inner: Weak<SharedString>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> WeakHandle<T> {
+    pub fn id(&self) -> &SharedString {
+        &self.id
+    }
+
+    /// A strong handle, or `None` once every strong handle was dropped.
+    pub fn upgrade(&self) -> Option<Handle<T>> {
+        self.inner.upgrade().map(|id| Handle {
+            id,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T> Clone for WeakHandle<T> {
+    fn clone(&self) -> Self {
+        WeakHandle {
+            id: self.id.clone(),
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for WeakHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("WeakHandle").field(&self.id).finish()
+    }
+}
+
+/// Sent on the [`Events`] bus when an async load finishes, a loaded asset changes on
+/// disk, or an asset is evicted after its last strong handle dropped.
+#[derive(Debug)]
+pub enum AssetEvent<T> {
+    Loaded(Handle<T>),
+    Failed(Handle<T>),
+    Reloaded(Handle<T>),
+    Unloaded(WeakHandle<T>),
+}
+
+impl<T> Clone for AssetEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            AssetEvent::Loaded(handle) => AssetEvent::Loaded(handle.clone()),
+            AssetEvent::Failed(handle) => AssetEvent::Failed(handle.clone()),
+            AssetEvent::Reloaded(handle) => AssetEvent::Reloaded(handle.clone()),
+            AssetEvent::Unloaded(handle) => AssetEvent::Unloaded(handle.clone()),
+        }
+    }
+}
+
+/// Checks one loaded asset for a hot reload, sending [`AssetEvent::Reloaded`] if it changed.
+type ReloadCheck = Box<dyn FnMut(&AssetCache, &EventSender) + Send>;
+
+/// Typed callbacks for one loaded asset, so the loader can manage assets of any type.
+struct AssetHooks {
+    check_reload: ReloadCheck,
+    /// Sends [`AssetEvent::Unloaded`].
+    unloaded: Box<dyn Fn(&EventSender) + Send>,
+}
+
+struct AssetEntry {
+    state: LoadState,
+    /// Bytes read from the asset source to load it, including dependencies.
+    bytes: u64,
+    owner: Weak<SharedString>,
+    /// Child of the root cache that holds only this asset, so dropping it evicts the CPU
+    /// data.
+    cache: AssetCache,
+    hooks: Option<AssetHooks>,
+}
+
+type AssetEntries = Arc<Mutex<HashMap<(TypeId, SharedString), AssetEntry>>>;
 
 pub struct AssetLoader {
     pub cache: AssetCache,
+    task_pool: TaskPool,
+    events: EventSender,
+    entries: AssetEntries,
 }
 
 impl AssetLoader {
-    pub fn new() -> Self {
+    /// Reads assets from `mounts`, lowest priority first.
+    pub fn new(mounts: &[AssetMount], task_pool: TaskPool, events: EventSender) -> Self {
         AssetLoader {
-            cache: AssetCache::new("assets").unwrap_or_else(|err| {
-                panic!("Failed to create asset cache for 'assets': {err}");
-            }),
+            cache: AssetCache::with_source(LayeredSource::new(mounts)),
+            task_pool,
+            events,
+            entries: AssetEntries::default(),
+        }
+    }
+
+    /// Loads `id` on the task pool and returns immediately. Poll [`AssetLoader::load_state`]
+    /// or listen for [`AssetEvent<T>`] to find out when it is ready. Requesting an asset
+    /// that is still alive returns another handle to it. `id` may also be a `guid:`
+    /// reference or an id the asset had before it moved; see [`AssetLoader::canonical_id`].
+    pub fn load_async<T: Asset>(&self, id: &str) -> Handle<T> {
+        let id = SharedString::from(self.canonical_id(id));
+        let key = (TypeId::of::<T>(), id.clone());
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get(&key)
+            && entry.state != LoadState::Failed
+            && let Some(owner) = entry.owner.upgrade()
+        {
+            return Handle {
+                id: owner,
+                _marker: PhantomData,
+            };
+        }
+
+        let handle = Handle {
+            id: Arc::new(id.clone()),
+            _marker: PhantomData,
+        };
+        let cache = self.cache.make_child();
+        entries.insert(
+            key.clone(),
+            AssetEntry {
+                state: LoadState::Loading,
+                bytes: 0,
+                owner: Arc::downgrade(&handle.id),
+                cache: cache.clone(),
+                hooks: None,
+            },
+        );
+        drop(entries);
+
+        let entries = self.entries.clone();
+        let events = self.events.clone();
+        let weak = handle.downgrade();
+        self.task_pool.spawn(move || {
+            let _span = info_span!("asset_io", subsystem = "assets").entered();
+            source::take_bytes_read();
+            let result = cache.load::<T>(&id).map(|loaded| loaded.last_reload_id());
+            let bytes = source::take_bytes_read();
+            let mut entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(handle) = weak.upgrade() else {
+                // Every handle was dropped while loading; nobody wants the asset anymore.
+                entries.remove(&key);
+                return;
+            };
+            let (state, hooks, event) = match result {
+                Ok(reload_id) => (
+                    LoadState::Loaded,
+                    Some(asset_hooks(&handle, reload_id)),
+                    AssetEvent::Loaded(handle),
+                ),
+                Err(e) => {
+                    error!("Failed to load {id}: {:?}", e);
+                    (LoadState::Failed, None, AssetEvent::Failed(handle))
+                }
+            };
+            if let Some(entry) = entries.get_mut(&key) {
+                entry.state = state;
+                entry.bytes = bytes;
+                entry.hooks = hooks;
+            }
+            events.send(event);
+        });
+        handle
+    }
+
+    pub fn load_state<T: Asset>(&self, handle: &Handle<T>) -> LoadState {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&(TypeId::of::<T>(), handle.id().clone()))
+            .map_or(LoadState::NotLoaded, |entry| entry.state)
+    }
+
+    /// Emits [`AssetEvent::Reloaded`] for every asset loaded through
+    /// [`AssetLoader::load_async`] that hot reloading replaced since the last call. Call
+    /// once per frame before [`Events::update`].
+    pub fn check_reloads(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        for entry in entries.values_mut() {
+            if let Some(hooks) = entry.hooks.as_mut() {
+                (hooks.check_reload)(&entry.cache, &self.events);
+            }
+        }
+    }
+
+    /// Evicts every loaded asset whose last strong [`Handle`] was dropped and sends
+    /// [`AssetEvent::Unloaded`] so GPU copies can be released too. Returns how many assets
+    /// were evicted.
+    pub fn unload_unused(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let before = entries.len();
+        entries.retain(|(_, id), entry| {
+            if entry.state == LoadState::Loading || entry.owner.strong_count() > 0 {
+                return true;
+            }
+            if let Some(hooks) = entry.hooks.as_ref() {
+                (hooks.unloaded)(&self.events);
+                info!("Unloaded asset {id}");
+            }
+            false
+        });
+        before - entries.len()
+    }
+
+    /// Counts of the assets requested through [`AssetLoader::load_async`] that are still
+    /// tracked, i.e. not yet unloaded.
+    pub fn stats(&self) -> AssetStats {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stats = AssetStats::default();
+        for entry in entries.values() {
+            match entry.state {
+                LoadState::Loading => stats.loading += 1,
+                LoadState::Loaded => {
+                    stats.loaded += 1;
+                    stats.bytes += entry.bytes;
+                }
+                LoadState::Failed => stats.failed += 1,
+                LoadState::NotLoaded => {}
+            }
+        }
+        stats
+    }
+
+    /// The current id of `reference`, looked up in the [`AssetDatabase`] if the assets
+    /// have one: `guid:` references and ids of moved assets are resolved, other ids are
+    /// returned as-is. Unknown GUIDs are logged and returned unchanged, so loading them
+    /// fails with the reference in the error.
+    pub fn canonical_id(&self, reference: &str) -> String {
+        if !self
+            .cache
+            .source()
+            .exists(DirEntry::File(database::DATABASE_ID, "ron"))
+        {
+            return reference.to_owned();
+        }
+        let database = match self.cache.load::<AssetDatabase>(database::DATABASE_ID) {
+            Ok(database) => database.read(),
+            Err(e) => {
+                error!("Failed to load the asset database: {:?}", e);
+                return reference.to_owned();
+            }
+        };
+        match database.resolve(reference) {
+            Some(id) => id.to_owned(),
+            None => {
+                warn!("Asset {reference} isn't in the asset database");
+                reference.to_owned()
+            }
         }
     }
+
+    /// The mount that the file `id.ext` is read from, to debug which root or pak wins.
+    pub fn resolve(&self, id: &str, ext: &str) -> Option<PathBuf> {
+        self.cache
+            .downcast_raw_source::<LayeredSource>()?
+            .resolve(DirEntry::File(id, ext))
+            .map(Path::to_path_buf)
+    }
+
+    /// The loaded asset, or `None` while it is still loading, if loading failed or once
+    /// it was unloaded.
+    pub fn asset<T: Asset>(&self, handle: &Handle<T>) -> Option<ArcHandle<T>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&(TypeId::of::<T>(), handle.id().clone()))
+            .and_then(|entry| entry.cache.get::<T>(handle.id()))
+            .map(|loaded| loaded.strong())
+    }
+}
+
+fn asset_hooks<T: Asset>(handle: &Handle<T>, mut last_reload: ReloadId) -> AssetHooks {
+    let reloaded = handle.downgrade();
+    let unloaded = handle.downgrade();
+    AssetHooks {
+        check_reload: Box::new(move |cache, events| {
+            let Some(loaded) = cache.get::<T>(reloaded.id()) else {
+                return;
+            };
+            let reload = loaded.last_reload_id();
+            if reload != last_reload {
+                last_reload = reload;
+                info!("Asset {} changed on disk", reloaded.id());
+                if let Some(handle) = reloaded.upgrade() {
+                    events.send(AssetEvent::Reloaded(handle));
+                }
+            }
+        }),
+        unloaded: Box::new(move |events| events.send(AssetEvent::Unloaded(unloaded.clone()))),
+    }
 }
 
 impl Deref for AssetLoader {
@@ -25,8 +386,18 @@ impl Deref for AssetLoader {
     }
 }
 
-impl Default for AssetLoader {
-    fn default() -> Self {
-        Self::new()
+impl FromResources for AssetLoader {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        resources.init::<TaskPool>()?;
+        resources.init::<Events>()?;
+        let mounts = resources
+            .try_get::<EngineConfig>()
+            .map(|config| config.asset_mounts.clone())
+            .unwrap_or_else(|| EngineConfig::default().asset_mounts);
+        Ok(AssetLoader::new(
+            &mounts,
+            resources.get::<TaskPool>().clone(),
+            resources.get::<Events>().sender(),
+        ))
     }
 }
@@ -0,0 +1,32 @@
+use assets_manager::{BoxedError, FileAsset};
+use std::borrow::Cow;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Precompiled SPIR-V module loaded from a `.spv` file (e.g. built with `glslc`).
+///
+/// Dropping one into `assets/shaders` overrides the matching built-in shader, and edits
+/// to it are picked up by hot reload. Only the magic number is checked, so the module
+/// must be valid SPIR-V; run `spirv-val` on hand-edited ones.
+#[derive(Debug)]
+pub struct SpirvShader {
+    pub words: Vec<u32>,
+}
+
+impl FileAsset for SpirvShader {
+    const EXTENSION: &'static str = "spv";
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        if !bytes.len().is_multiple_of(4) {
+            return Err("SPIR-V size is not a multiple of 4 bytes".into());
+        }
+        let words = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect::<Vec<_>>();
+        if words.first() != Some(&SPIRV_MAGIC) {
+            return Err("Missing SPIR-V magic number".into());
+        }
+        Ok(SpirvShader { words })
+    }
+}
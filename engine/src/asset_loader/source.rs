@@ -0,0 +1,154 @@
+use assets_manager::BoxedError;
+use assets_manager::hot_reloading::EventSender;
+use assets_manager::source::{DirEntry, FileContent, FileSystem, Source, Zip};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+thread_local! {
+    /// Bytes this thread read through a [`LayeredSource`]; see [`take_bytes_read`].
+    static BYTES_READ: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Bytes read on the current thread since the last call. Loads run to completion on one
+/// pool thread, so this measures an asset and its dependencies.
+pub(crate) fn take_bytes_read() -> u64 {
+    BYTES_READ.with(|bytes| bytes.replace(0))
+}
+
+/// Where assets are read from. See [`EngineConfig::asset_mounts`](crate::engine::EngineConfig).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetMount {
+    /// Loose files on disk, hot-reloaded when they change.
+    Directory(PathBuf),
+    /// A zip archive (a "pak"), stored or deflate-compressed.
+    Archive(PathBuf),
+}
+
+impl AssetMount {
+    /// An archive when the path ends in `.pak` or `.zip`, otherwise a directory.
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let is_archive = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pak") || ext.eq_ignore_ascii_case("zip"));
+        if is_archive {
+            AssetMount::Archive(path)
+        } else {
+            AssetMount::Directory(path)
+        }
+    }
+}
+
+/// Asset source stacking several mounts. A file in a later mount shadows the same file
+/// in earlier ones, so patches and mods can override a base pak or the `assets/` folder.
+pub struct LayeredSource {
+    layers: Vec<(PathBuf, Box<dyn Source + Send + Sync>)>,
+}
+
+impl LayeredSource {
+    /// Opens every mount, lowest priority first. Mounts that can't be opened are skipped
+    /// with a warning, so a missing `assets/` folder in a shipped build isn't fatal.
+    pub fn new(mounts: &[AssetMount]) -> Self {
+        let mut layers: Vec<(PathBuf, Box<dyn Source + Send + Sync>)> = Vec::new();
+        for mount in mounts {
+            let (path, layer) = match mount {
+                AssetMount::Directory(path) => (
+                    path,
+                    FileSystem::new(path).map(|fs| Box::new(fs) as Box<dyn Source + Send + Sync>),
+                ),
+                AssetMount::Archive(path) => (
+                    path,
+                    Zip::open(path).map(|zip| Box::new(zip) as Box<dyn Source + Send + Sync>),
+                ),
+            };
+            match layer {
+                Ok(layer) => {
+                    info!("Mounted assets from {}", path.display());
+                    layers.push((path.clone(), layer));
+                }
+                Err(e) => warn!("Skipping asset mount {}: {e}", path.display()),
+            }
+        }
+        LayeredSource { layers }
+    }
+
+    /// Mounted paths, lowest priority first.
+    pub fn mounts(&self) -> impl Iterator<Item = &PathBuf> {
+        self.layers.iter().map(|(path, _)| path)
+    }
+
+    /// The mount that `entry` resolves to, i.e. the highest-priority one containing it.
+    pub fn resolve(&self, entry: DirEntry) -> Option<&Path> {
+        self.layers
+            .iter()
+            .rev()
+            .find(|(_, source)| source.exists(entry))
+            .map(|(path, _)| path.as_path())
+    }
+
+    fn sources(&self) -> impl Iterator<Item = &(dyn Source + Send + Sync)> {
+        self.layers.iter().rev().map(|(_, source)| source.as_ref())
+    }
+}
+
+impl Source for LayeredSource {
+    fn read(&self, id: &str, ext: &str) -> io::Result<FileContent<'_>> {
+        let mut last_error = None;
+        for source in self.sources() {
+            match source.read(id, ext) {
+                Ok(content) => {
+                    let len = content.as_ref().len() as u64;
+                    BYTES_READ.with(|bytes| bytes.set(bytes.get() + len));
+                    return Ok(content);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No asset mounts for {id}.{ext}"),
+            )
+        }))
+    }
+
+    fn read_dir(&self, id: &str, f: &mut dyn FnMut(DirEntry)) -> io::Result<()> {
+        let mut seen = HashSet::new();
+        let mut found = false;
+        let mut last_error = None;
+        for source in self.sources() {
+            let result = source.read_dir(id, &mut |entry| {
+                let key = match entry {
+                    DirEntry::File(id, ext) => (id.to_owned(), Some(ext.to_owned())),
+                    DirEntry::Directory(id) => (id.to_owned(), None),
+                };
+                if seen.insert(key) {
+                    f(entry);
+                }
+            });
+            match result {
+                Ok(()) => found = true,
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) if !found => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn exists(&self, entry: DirEntry) -> bool {
+        self.sources().any(|source| source.exists(entry))
+    }
+
+    fn configure_hot_reloading(&self, events: EventSender) -> Result<(), BoxedError> {
+        for source in self.sources() {
+            source.configure_hot_reloading(events.clone())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use assets_manager::source::{DirEntry, Source};
+use assets_manager::{AssetCache, BoxedError, FileAsset};
+use glam::Vec3;
+use gltf::texture::WrappingMode;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use tracing::warn;
+
+/// Sidecar extension: `textures/rock_normal.png` is configured by `textures/rock_normal.meta`.
+pub const SIDECAR_EXT: &str = "meta";
+
+/// How 8-bit color values are encoded. Decides between `_SRGB` and `_UNORM` formats when
+/// an image is uploaded; shaders always see linear values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorSpace {
+    /// Color textures authored in sRGB; decoded to linear when sampled.
+    #[default]
+    Srgb,
+    /// Normal maps, roughness and other data sampled as-is. Also what vertex colors are
+    /// stored in.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Converts `color`, encoded in this color space, to linear RGB.
+    pub fn to_linear(self, color: Vec3) -> Vec3 {
+        match self {
+            ColorSpace::Srgb => Vec3::new(
+                srgb_to_linear(color.x),
+                srgb_to_linear(color.y),
+                srgb_to_linear(color.z),
+            ),
+            ColorSpace::Linear => color,
+        }
+    }
+}
+
+/// Decodes one sRGB channel in `0..=1`.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Wrap {
+    Repeat,
+    ClampToEdge,
+    MirroredRepeat,
+}
+
+impl From<Wrap> for WrappingMode {
+    fn from(wrap: Wrap) -> Self {
+        match wrap {
+            Wrap::Repeat => WrappingMode::Repeat,
+            Wrap::ClampToEdge => WrappingMode::ClampToEdge,
+            Wrap::MirroredRepeat => WrappingMode::MirroredRepeat,
+        }
+    }
+}
+
+/// Per-image import settings, read from an optional RON sidecar next to the image:
+///
+/// ```ron
+/// (color_space: Linear, generate_mips: true, wrap: Some(ClampToEdge), block_compression: true)
+/// ```
+///
+/// Missing fields keep their defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureSettings {
+    pub color_space: ColorSpace,
+    pub generate_mips: bool,
+    /// Overrides the glTF sampler's wrap mode on both axes.
+    pub wrap: Option<Wrap>,
+    /// Deflate the baked copy written by `elements-import`.
+    pub compression: bool,
+    /// Bake 8-bit images to BC1, or BC3 if they have alpha, when `elements-import` is built
+    /// with its `block-compression` feature. A quarter of the memory or less, but lossy;
+    /// GPUs without BC support get them decoded at load.
+    pub block_compression: bool,
+}
+
+impl Default for TextureSettings {
+    fn default() -> Self {
+        TextureSettings {
+            color_space: ColorSpace::Srgb,
+            generate_mips: true,
+            wrap: None,
+            compression: true,
+            block_compression: false,
+        }
+    }
+}
+
+impl TextureSettings {
+    /// Settings from the sidecar of the image `image_id`, or defaults without one. Loading
+    /// through `cache` makes edits to the sidecar hot-reload whatever depends on it.
+    pub fn for_image(cache: &AssetCache, image_id: &str) -> Self {
+        Self::from_sidecar(cache, image_id).unwrap_or_default()
+    }
+
+    /// Like [`TextureSettings::for_image`], but `None` without a sidecar so the caller can
+    /// pick its own defaults.
+    pub fn from_sidecar(cache: &AssetCache, image_id: &str) -> Option<Self> {
+        if !cache.source().exists(DirEntry::File(image_id, SIDECAR_EXT)) {
+            return None;
+        }
+        match cache.load::<TextureSettings>(image_id) {
+            Ok(settings) => Some(*settings.read()),
+            Err(e) => {
+                warn!("Ignoring invalid texture settings for {image_id}: {e}");
+                None
+            }
+        }
+    }
+}
+
+impl FileAsset for TextureSettings {
+    const EXTENSION: &'static str = SIDECAR_EXT;
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+}
+
+/// Asset id of an image that the glTF `model_id` references by relative `uri`, e.g.
+/// `textures/base.png` from `cars.sedan` is `cars.textures.base`. `None` for embedded
+/// data and paths leaving the model's folder.
+pub fn image_asset_id(model_id: &str, uri: &str) -> Option<String> {
+    if uri.contains(':') || uri.split('/').any(|part| part == ".." || part.is_empty()) {
+        return None;
+    }
+    let stem = uri.rsplit_once('.').map_or(uri, |(stem, _)| stem);
+    if stem.contains('.') {
+        return None;
+    }
+    let relative = stem.replace('/', ".");
+    Some(match model_id.rsplit_once('.') {
+        Some((folder, _)) => format!("{folder}.{relative}"),
+        None => relative,
+    })
+}
@@ -0,0 +1,187 @@
+use crate::camera::Camera;
+use crate::frame_arena::{ArenaVec, FrameArena};
+use crate::physics::{PhysicsWorld, Ray};
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::{Entity, RenderLayers, World};
+use anyhow::Result;
+use glam::{Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+/// Component drawing a camera-facing marker over its entity: a name tag, a health bar
+/// or a waypoint. Registered as `billboard` for scene files, e.g.
+/// `"billboard": (content: Bar(fraction: 0.75), offset: (0.0, 0.0, 2.0))`.
+///
+/// Billboards are drawn on the UI layer, so they always face the camera and are never
+/// hidden by the depth buffer. They follow their entity's
+/// [`Visibility`](crate::scene::Visibility) and [`RenderLayers`]. With
+/// `occlude_by_colliders` on, one is hidden instead while a
+/// [`Collider`](crate::physics::Collider) of another entity is in front of its anchor;
+/// meshes without a collider never hide it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Billboard {
+    pub content: BillboardContent,
+    /// Anchor relative to the entity's origin, in world units, e.g. above a head.
+    #[serde(default)]
+    pub offset: Vec3,
+    #[serde(default)]
+    pub size: BillboardSize,
+    /// Linear RGBA tint of the text, bar fill or marker.
+    #[serde(default = "default_color")]
+    pub color: Vec4,
+    /// Hidden while a collider is between the camera and the anchor. Defaults to `true`.
+    #[serde(default = "default_occlude_by_colliders")]
+    pub occlude_by_colliders: bool,
+}
+
+/// What a [`Billboard`] shows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BillboardContent {
+    /// A line of text, `size` tall.
+    Label(String),
+    /// A bar filled to `fraction`, between 0 and 1.
+    Bar { fraction: f32 },
+    /// A filled circle fitting in `size`.
+    Marker,
+}
+
+/// How big a [`Billboard`] is on screen, as width and height.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BillboardSize {
+    /// Fixed size in UI points, whatever the distance.
+    Pixels(Vec2),
+    /// Size in world units at the anchor, shrinking with distance like the scene.
+    World(Vec2),
+}
+
+impl Default for BillboardSize {
+    fn default() -> Self {
+        BillboardSize::Pixels(Vec2::new(80.0, 14.0))
+    }
+}
+
+fn default_color() -> Vec4 {
+    Vec4::ONE
+}
+
+fn default_occlude_by_colliders() -> bool {
+    true
+}
+
+/// A billboard placed on screen for this frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenBillboard<'a> {
+    pub entity: Entity,
+    pub billboard: &'a Billboard,
+    /// Center in UI points from the top left of the viewport.
+    pub center: Vec2,
+    /// Width and height in UI points.
+    pub size: Vec2,
+    /// Distance from the camera to the anchor.
+    pub distance: f32,
+}
+
+/// Places [`Billboard`] components on screen. Also registers the component.
+#[derive(Debug, Default)]
+pub struct Billboards {
+    /// Skips every billboard, e.g. for clean screenshots.
+    pub hidden: bool,
+}
+
+impl Billboards {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Billboards in front of the camera, visible to it and not occluded, sorted back to
+    /// front.
+    /// `viewport` is the screen size in UI points. The list is allocated in `arena`.
+    pub fn visible<'a>(
+        &self,
+        world: &'a World,
+        camera: &Camera,
+        physics: &PhysicsWorld,
+        viewport: Vec2,
+        arena: &'a FrameArena,
+    ) -> ArenaVec<'a, ScreenBillboard<'a>> {
+        let mut visible = arena.vec();
+        if self.hidden || viewport.x <= 0.0 || viewport.y <= 0.0 {
+            return visible;
+        }
+        let view_projection =
+            camera.projection_matrix(viewport.x / viewport.y) * camera.view_matrix();
+        // UI points per world unit at a view depth of 1.
+        let points_per_unit = viewport.y / (2.0 * (camera.fov_y * 0.5).tan());
+        let eye = camera.transform.translation;
+
+        visible.extend(
+            world
+                .query::<Billboard>()
+                .filter(|&(entity, _)| {
+                    world.is_visible(entity)
+                        && world
+                            .get::<RenderLayers>(entity)
+                            .copied()
+                            .unwrap_or_default()
+                            .intersects(camera.layers)
+                })
+                .filter_map(|(entity, billboard)| {
+                    let anchor = world
+                        .world_transform(entity)?
+                        .transform_point3(billboard.offset);
+                    let clip = view_projection * anchor.extend(1.0);
+                    if clip.w < camera.near {
+                        return None;
+                    }
+                    let ndc = clip.truncate() / clip.w;
+                    if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 || ndc.z > 1.0 {
+                        return None;
+                    }
+                    let distance = eye.distance(anchor);
+                    if billboard.occlude_by_colliders
+                        && is_occluded(physics, entity, eye, anchor, distance)
+                    {
+                        return None;
+                    }
+                    let size = match billboard.size {
+                        BillboardSize::Pixels(size) => size,
+                        BillboardSize::World(size) => size * points_per_unit / clip.w,
+                    };
+                    Some(ScreenBillboard {
+                        entity,
+                        billboard,
+                        center: (Vec2::new(ndc.x, ndc.y) + 1.0) * 0.5 * viewport,
+                        size,
+                        distance,
+                    })
+                }),
+        );
+        visible.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+        visible
+    }
+}
+
+/// Whether a collider other than the billboard's own entity blocks the view of `anchor`.
+fn is_occluded(
+    physics: &PhysicsWorld,
+    entity: Entity,
+    eye: Vec3,
+    anchor: Vec3,
+    distance: f32,
+) -> bool {
+    let Some(direction) = (anchor - eye).try_normalize() else {
+        return false;
+    };
+    physics
+        .raycast(&Ray::new(eye, direction).with_max_distance(distance))
+        .is_some_and(|hit| hit.entity != entity)
+}
+
+impl FromResources for Billboards {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        resources.init::<World>()?;
+        resources
+            .get_mut::<World>()
+            .register_component::<Billboard>("billboard");
+        Ok(Billboards::new())
+    }
+}
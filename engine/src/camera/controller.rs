@@ -0,0 +1,137 @@
+use crate::camera::Camera;
+use crate::core::transform::Transform;
+use crate::input::Input;
+use glam::Vec3;
+use std::f32::consts::FRAC_PI_2;
+use winit::event::MouseButton;
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+/// Keeps the pitch just short of straight up or down, where the view direction would be
+/// parallel to `+Z`, the up axis of both controllers and the default [`Camera`].
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// Orbits a target point: drag with the left mouse button to rotate, scroll to zoom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCameraController {
+    pub target: Vec3,
+    pub distance: f32,
+    /// Angle around `+Z` from `+X`, in radians.
+    pub yaw: f32,
+    /// Angle above the XY plane, in radians.
+    pub pitch: f32,
+    /// Radians per pixel of mouse movement.
+    pub sensitivity: f32,
+    /// Share of the distance covered per scrolled line.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl OrbitCameraController {
+    /// Orbits `target` at the distance and angle `camera` currently looks at it from.
+    pub fn from_camera(camera: &Camera, target: Vec3) -> Self {
+        let offset = camera.transform.translation - target;
+        let distance = offset.length();
+        OrbitCameraController {
+            target,
+            distance,
+            yaw: offset.y.atan2(offset.x),
+            pitch: (offset.z / distance.max(f32::EPSILON))
+                .clamp(-1.0, 1.0)
+                .asin(),
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            min_distance: 0.1,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+
+    pub fn update(&mut self, input: &Input, camera: &mut Camera) {
+        if input.is_mouse_button_pressed(MouseButton::Left) {
+            let delta = input.mouse_delta() * self.sensitivity;
+            self.yaw -= if self.invert_x { -delta.x } else { delta.x };
+            self.pitch += if self.invert_y { -delta.y } else { delta.y };
+            self.pitch = self.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        let zoom = 1.0 - input.scroll_delta() * self.zoom_speed;
+        self.distance = (self.distance * zoom.max(0.1)).max(self.min_distance);
+
+        let position = self.target + self.distance * direction(self.yaw, self.pitch);
+        camera.transform = Transform {
+            translation: position,
+            ..camera.transform
+        }
+        .looking_at(self.target, Vec3::Z);
+    }
+}
+
+/// Free-flying camera: hold the right mouse button to look around, move with WASD, go
+/// down and up with Q and E, and hold Shift to move faster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlyCameraController {
+    /// Angle around `+Z` from `+X`, in radians.
+    pub yaw: f32,
+    /// Angle above the XY plane, in radians.
+    pub pitch: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Speed multiplier while Shift is held.
+    pub boost: f32,
+    /// Radians per pixel of mouse movement.
+    pub sensitivity: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+
+impl FlyCameraController {
+    /// Flies on from where `camera` is and looks where it looks.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let forward = camera.transform.forward();
+        FlyCameraController {
+            yaw: forward.y.atan2(forward.x),
+            pitch: forward.z.clamp(-1.0, 1.0).asin(),
+            speed: 5.0,
+            boost: 4.0,
+            sensitivity: 0.003,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+
+    pub fn update(&mut self, input: &Input, delta_secs: f32, camera: &mut Camera) {
+        if input.is_mouse_button_pressed(MouseButton::Right) {
+            let delta = input.mouse_delta() * self.sensitivity;
+            self.yaw -= if self.invert_x { -delta.x } else { delta.x };
+            self.pitch -= if self.invert_y { -delta.y } else { delta.y };
+            self.pitch = self.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        let position = camera.transform.translation;
+        camera
+            .transform
+            .look_at(position + direction(self.yaw, self.pitch), Vec3::Z);
+
+        let key = |code| input.is_key_pressed(PhysicalKey::Code(code));
+        let axis = |positive, negative| key(positive) as i32 as f32 - key(negative) as i32 as f32;
+        let movement = camera.transform.forward() * axis(KeyCode::KeyW, KeyCode::KeyS)
+            + camera.transform.right() * axis(KeyCode::KeyD, KeyCode::KeyA)
+            + Vec3::Z * axis(KeyCode::KeyE, KeyCode::KeyQ);
+        let boost = if key(KeyCode::ShiftLeft) || key(KeyCode::ShiftRight) {
+            self.boost
+        } else {
+            1.0
+        };
+        camera.transform.translation +=
+            movement.normalize_or_zero() * self.speed * boost * delta_secs;
+    }
+}
+
+/// Unit vector at `yaw` around `+Z` and `pitch` above the XY plane.
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        yaw.cos() * pitch.cos(),
+        yaw.sin() * pitch.cos(),
+        pitch.sin(),
+    )
+}
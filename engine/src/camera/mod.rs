@@ -0,0 +1,125 @@
+use crate::core::bounds::{Frustum, Ray};
+use crate::core::transform::Transform;
+use crate::input::Input;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::RenderLayers;
+use crate::window::Window;
+use anyhow::Result;
+use glam::{Mat4, Vec2, Vec3};
+
+mod controller;
+
+pub use controller::{FlyCameraController, OrbitCameraController};
+
+/// The viewpoint the scene is rendered from. Move it directly or through a
+/// [`CameraController`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub transform: Transform,
+    /// Vertical field of view in radians.
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Entities are drawn if their [`RenderLayers`] share a layer with these. Layer 0 by
+    /// default.
+    pub layers: RenderLayers,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Camera {
+            transform: Transform::from_translation(Vec3::splat(5.0))
+                .looking_at(Vec3::ZERO, Vec3::Z),
+            fov_y: 45.0f32.to_radians(),
+            near: 0.5,
+            far: 20.0,
+            layers: RenderLayers::default(),
+        }
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        self.transform.view_matrix()
+    }
+
+    /// Perspective projection with Vulkan's downward Y axis and `[0, 1]` depth range.
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> Mat4 {
+        let mut projection = Mat4::perspective_rh(self.fov_y, aspect_ratio, self.near, self.far);
+        projection.y_axis.y *= -1.0;
+        projection
+    }
+
+    /// What the camera sees, for culling.
+    pub fn frustum(&self, aspect_ratio: f32) -> Frustum {
+        Frustum::from_view_projection(&(self.projection_matrix(aspect_ratio) * self.view_matrix()))
+    }
+
+    /// Ray from the near plane through `cursor_pos` to the far plane, for mouse picking
+    /// with [`PhysicsWorld::raycast`](crate::physics::PhysicsWorld::raycast) or the
+    /// bounds' ray tests. `cursor_pos` is in physical pixels from the window's top left,
+    /// like [`Input::mouse_position`]; multiply logical positions, e.g. egui's, by
+    /// [`Window::scale_factor`] first. `None` while the window has no area.
+    pub fn viewport_to_world_ray(&self, cursor_pos: Vec2, window: &Window) -> Option<Ray> {
+        let (width, height) = window.get_size();
+        self.viewport_ray(cursor_pos, Vec2::new(width as f32, height as f32))
+    }
+
+    /// Like [`Camera::viewport_to_world_ray`] for a viewport of `size`, in the same units
+    /// as `position`, e.g. an editor preview image.
+    pub fn viewport_ray(&self, position: Vec2, size: Vec2) -> Option<Ray> {
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return None;
+        }
+        let inverse = (self.projection_matrix(size.x / size.y) * self.view_matrix()).inverse();
+        // Vulkan's NDC has Y pointing down like window coordinates, and depth in [0, 1].
+        let ndc = position / size * 2.0 - 1.0;
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        Some(Ray::new(near, far - near).with_max_distance(near.distance(far)))
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for Camera {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Camera::new())
+    }
+}
+
+/// Which built-in controller moves the [`Camera`] each frame. Set it to
+/// [`CameraController::None`] when game code drives the camera itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraController {
+    None,
+    Orbit(OrbitCameraController),
+    Fly(FlyCameraController),
+}
+
+impl CameraController {
+    pub fn update(&mut self, input: &Input, delta_secs: f32, camera: &mut Camera) {
+        match self {
+            CameraController::None => {}
+            CameraController::Orbit(orbit) => orbit.update(input, camera),
+            CameraController::Fly(fly) => fly.update(input, delta_secs, camera),
+        }
+    }
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController::Orbit(OrbitCameraController::from_camera(
+            &Camera::new(),
+            Vec3::ZERO,
+        ))
+    }
+}
+
+impl FromResources for CameraController {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(CameraController::default())
+    }
+}
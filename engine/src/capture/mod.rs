@@ -0,0 +1,250 @@
+use crate::engine::EngineConfig;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+/// What a recording is encoded to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// One numbered PNG per frame in a new directory. Turn it into a video with e.g.
+    /// `ffmpeg -framerate 60 -i frame_%06d.png capture.mp4`.
+    #[default]
+    Png,
+    /// A single animated GIF. Needs the `capture-gif` feature.
+    Gif,
+}
+
+impl CaptureFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "png" => Ok(CaptureFormat::Png),
+            "gif" => Ok(CaptureFormat::Gif),
+            _ => bail!("Unknown capture format {value:?}, expected png or gif"),
+        }
+    }
+}
+
+/// One presented frame copied back from the GPU.
+#[derive(Clone)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed 8-bit pixels, four channels each.
+    pub pixels: Vec<u8>,
+    /// Channel order is BGRA instead of RGBA.
+    pub bgra: bool,
+}
+
+struct Recording {
+    frames: Sender<(CapturedFrame, Duration)>,
+    encoder: JoinHandle<()>,
+    last_frame: Option<Instant>,
+}
+
+/// Records presented frames while enabled. The renderer copies each frame to host
+/// memory and hands it over with [`FrameCapture::submit`]; encoding happens on a
+/// background thread so the frame rate stays close to normal. Toggled with F9.
+pub struct FrameCapture {
+    dir: PathBuf,
+    format: CaptureFormat,
+    recording: Option<Recording>,
+    /// A single frame was asked for with [`FrameCapture::request_screenshot`].
+    screenshot_requested: bool,
+    screenshot: Option<CapturedFrame>,
+}
+
+impl FrameCapture {
+    /// Writes recordings into `dir`, one directory or file per recording.
+    pub fn new(dir: impl Into<PathBuf>, format: CaptureFormat) -> Self {
+        FrameCapture {
+            dir: dir.into(),
+            format,
+            recording: None,
+            screenshot_requested: false,
+            screenshot: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Whether the renderer should copy the next presented frame back.
+    pub fn wants_frame(&self) -> bool {
+        self.is_recording() || self.screenshot_requested
+    }
+
+    /// Keeps the next submitted frame for [`FrameCapture::take_screenshot`]. Frames
+    /// arrive a few frames after they were presented.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// The frame kept since [`FrameCapture::request_screenshot`], once it has arrived.
+    pub fn take_screenshot(&mut self) -> Option<CapturedFrame> {
+        self.screenshot.take()
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        if self.recording.is_some() {
+            return Ok(());
+        }
+        if self.format == CaptureFormat::Gif && !cfg!(feature = "capture-gif") {
+            bail!("GIF capture needs the capture-gif feature");
+        }
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = match self.format {
+            CaptureFormat::Png => self.dir.join(format!("capture-{timestamp}")),
+            CaptureFormat::Gif => self.dir.join(format!("capture-{timestamp}.gif")),
+        };
+        let (frames, receiver) = mpsc::channel();
+        let format = self.format;
+        let target = path.clone();
+        let encoder = thread::Builder::new()
+            .name("elements-capture".to_string())
+            .spawn(move || {
+                if let Err(e) = encode(format, &target, receiver) {
+                    error!("Frame capture failed: {e:#}");
+                }
+            })
+            .with_context(|| "Failed to start the capture thread")?;
+        info!("Recording frames to {}", path.display());
+        self.recording = Some(Recording {
+            frames,
+            encoder,
+            last_frame: None,
+        });
+        Ok(())
+    }
+
+    /// Stops recording and waits for the encoder to write the remaining frames.
+    pub fn stop(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        drop(recording.frames);
+        if recording.encoder.join().is_err() {
+            error!("Frame capture thread panicked");
+        }
+        info!("Recording stopped");
+    }
+
+    pub fn toggle(&mut self) -> Result<()> {
+        if self.is_recording() {
+            self.stop();
+            Ok(())
+        } else {
+            self.start()
+        }
+    }
+
+    /// Queues a frame for encoding and keeps it if a screenshot was requested. Ignored
+    /// otherwise.
+    pub fn submit(&mut self, frame: CapturedFrame) {
+        if std::mem::take(&mut self.screenshot_requested) {
+            if !self.is_recording() {
+                self.screenshot = Some(frame);
+                return;
+            }
+            self.screenshot = Some(frame.clone());
+        }
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
+        let now = Instant::now();
+        let delay = recording
+            .last_frame
+            .map_or(Duration::ZERO, |last| now.duration_since(last));
+        recording.last_frame = Some(now);
+        if recording.frames.send((frame, delay)).is_err() {
+            // The encoder gave up and logged why.
+            self.recording = None;
+        }
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl FromResources for FrameCapture {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        let config = resources
+            .try_get::<EngineConfig>()
+            .cloned()
+            .unwrap_or_default();
+        Ok(FrameCapture::new(config.capture_dir, config.capture_format))
+    }
+}
+
+fn encode(
+    format: CaptureFormat,
+    path: &Path,
+    frames: Receiver<(CapturedFrame, Duration)>,
+) -> Result<()> {
+    match format {
+        CaptureFormat::Png => {
+            fs::create_dir_all(path)?;
+            for (index, (frame, _)) in frames.into_iter().enumerate() {
+                let file = path.join(format!("frame_{index:06}.png"));
+                to_rgba(frame)?
+                    .save(&file)
+                    .with_context(|| format!("Failed to write {}", file.display()))?;
+            }
+            Ok(())
+        }
+        #[cfg(feature = "capture-gif")]
+        CaptureFormat::Gif => {
+            use image::codecs::gif::{GifEncoder, Repeat};
+            use image::{Delay, Frame};
+
+            let file = fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let mut encoder = GifEncoder::new_with_speed(std::io::BufWriter::new(file), 10);
+            encoder.set_repeat(Repeat::Infinite)?;
+            // Each frame is shown until the next one was presented, so it is written once
+            // the next one arrives.
+            let mut pending = None;
+            let mut last_delay = Duration::ZERO;
+            for (frame, delay) in frames {
+                if let Some(previous) = pending.replace(to_rgba(frame)?) {
+                    let delay = Delay::from_saturating_duration(delay);
+                    encoder.encode_frame(Frame::from_parts(previous, 0, 0, delay))?;
+                }
+                last_delay = delay;
+            }
+            if let Some(last) = pending {
+                let delay = Delay::from_saturating_duration(last_delay);
+                encoder.encode_frame(Frame::from_parts(last, 0, 0, delay))?;
+            }
+            Ok(())
+        }
+        #[cfg(not(feature = "capture-gif"))]
+        CaptureFormat::Gif => bail!("GIF capture needs the capture-gif feature"),
+    }
+}
+
+pub(crate) fn to_rgba(frame: CapturedFrame) -> Result<image::RgbaImage> {
+    let mut pixels = frame.pixels;
+    for pixel in pixels.chunks_exact_mut(4) {
+        if frame.bgra {
+            pixel.swap(0, 2);
+        }
+        // The swapchain's alpha isn't meaningful once presented.
+        pixel[3] = u8::MAX;
+    }
+    image::RgbaImage::from_raw(frame.width, frame.height, pixels)
+        .with_context(|| "Captured frame has the wrong size")
+}
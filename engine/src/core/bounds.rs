@@ -0,0 +1,238 @@
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
+
+/// Half-line starting at `origin`, limited to `max_distance` along the normalized
+/// `direction`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub max_distance: f32,
+}
+
+impl Ray {
+    /// Unbounded ray. `direction` doesn't need to be normalized.
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Ray {
+            origin,
+            direction: direction.normalize_or_zero(),
+            max_distance: f32::INFINITY,
+        }
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+}
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn from_center_half_extents(center: Vec3, half_extents: Vec3) -> Self {
+        Aabb::new(center - half_extents, center + half_extents)
+    }
+
+    /// Smallest box containing every point, or `None` for no points.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(first, first), |aabb, point| {
+            Aabb::new(aabb.min.min(point), aabb.max.max(point))
+        }))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn inflate(&self, amount: f32) -> Aabb {
+        Aabb::new(
+            self.min - Vec3::splat(amount),
+            self.max + Vec3::splat(amount),
+        )
+    }
+
+    /// Box around all eight corners after `transform`.
+    pub fn transformed(&self, transform: &Mat4) -> Aabb {
+        let corners = (0..8).map(|i| {
+            let pick = |bit: usize, axis: usize| {
+                if i & bit == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            };
+            transform.transform_point3(Vec3::new(pick(1, 0), pick(2, 1), pick(4, 2)))
+        });
+        Aabb::from_points(corners).unwrap_or(*self)
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        point.clamp(self.min, self.max)
+    }
+
+    /// Slab test. Returns the entry distance along `direction` and the face normal, or
+    /// distance 0 and the reversed direction if `origin` is inside. `direction` needn't
+    /// be normalized; the distance is in multiples of it.
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<(f32, Vec3)> {
+        let mut t_min = f32::NEG_INFINITY;
+        let mut t_max = f32::INFINITY;
+        let mut normal = Vec3::ZERO;
+        for axis in 0..3 {
+            if direction[axis].abs() < f32::EPSILON {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inverse = 1.0 / direction[axis];
+            let mut near = (self.min[axis] - origin[axis]) * inverse;
+            let mut far = (self.max[axis] - origin[axis]) * inverse;
+            let mut sign = -1.0;
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+                sign = 1.0;
+            }
+            if near > t_min {
+                t_min = near;
+                normal = Vec3::ZERO;
+                normal[axis] = sign;
+            }
+            t_max = t_max.min(far);
+            if t_min > t_max || t_max < 0.0 {
+                return None;
+            }
+        }
+        if t_min < 0.0 {
+            return Some((0.0, -direction.normalize_or_zero()));
+        }
+        Some((t_min, normal))
+    }
+}
+
+/// Bounding sphere.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Sphere { center, radius }
+    }
+
+    /// Sphere through the corners of `aabb`.
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        Sphere::new(aabb.center(), aabb.half_extents().length())
+    }
+
+    /// Sphere containing this one after `transform`. Non-uniform scale grows the radius
+    /// by the largest axis.
+    pub fn transformed(&self, transform: &Mat4) -> Sphere {
+        let (scale, _, _) = transform.to_scale_rotation_translation();
+        Sphere::new(
+            transform.transform_point3(self.center),
+            self.radius * scale.abs().max_element(),
+        )
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.center.distance_squared(point) <= self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &Sphere) -> bool {
+        self.center.distance_squared(other.center) <= (self.radius + other.radius).powi(2)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.contains(aabb.closest_point(self.center))
+    }
+
+    /// Entry distance of `ray` into the sphere, or 0 if it starts inside.
+    pub fn ray_intersection(&self, ray: &Ray) -> Option<f32> {
+        let offset = ray.origin - self.center;
+        let c = offset.length_squared() - self.radius * self.radius;
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+        let b = offset.dot(ray.direction);
+        let discriminant = b * b - c;
+        if b > 0.0 || discriminant < 0.0 {
+            return None;
+        }
+        Some(-b - discriminant.sqrt())
+    }
+}
+
+/// The six planes bounding what a camera sees, pointing inwards. Each plane is
+/// `(normal, distance)` with `normal.dot(point) + distance >= 0` on the inside.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of a projection times view matrix with Vulkan's `[0, 1]`
+    /// depth range, as built by `Mat4::perspective_rh`.
+    pub fn from_view_projection(view_projection: &Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_projection.row(i));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.xyz().length();
+            if length > 0.0 { plane / length } else { plane }
+        });
+        Frustum { planes }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(point) + plane.w >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    /// Conservative: boxes near a frustum corner may pass without being visible.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            // The corner furthest along the plane normal.
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
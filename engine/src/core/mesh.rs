@@ -0,0 +1,286 @@
+use crate::asset_loader::texture_settings::ColorSpace;
+use crate::core::bounds::Aabb;
+use crate::core::vertex::{ElmVec2, ElmVec3, ElmVec4, ElmVertex};
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+/// Indexed triangle list ready for [`crate::renderer::Renderer::create_mesh`].
+///
+/// The generators are Z-up like the default [`crate::camera::Camera`]: flat shapes face
+/// `+Z` and round ones are built around the Z axis, centered on the origin. Triangles
+/// wind counter-clockwise seen from outside and texture coordinates start at the top.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<ElmVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// A single rectangle of `size`.
+    pub fn quad(size: Vec2) -> Self {
+        Self::plane(size, 0)
+    }
+
+    /// A rectangle of `size` split into `subdivisions + 1` cells along each side.
+    pub fn plane(size: Vec2, subdivisions: u32) -> Self {
+        let mut builder = MeshBuilder::new();
+        builder.add_grid(
+            Vec3::ZERO,
+            Vec3::X * size.x,
+            Vec3::Y * size.y,
+            subdivisions + 1,
+            subdivisions + 1,
+        );
+        builder.build()
+    }
+
+    /// A box of `size` with hard edges; each face maps the whole texture.
+    pub fn cube(size: Vec3) -> Self {
+        let half = size / 2.0;
+        let (x, y, z) = (Vec3::X * size.x, Vec3::Y * size.y, Vec3::Z * size.z);
+        let mut builder = MeshBuilder::new();
+        builder.add_grid(Vec3::X * half.x, y, z, 1, 1);
+        builder.add_grid(Vec3::NEG_X * half.x, -y, z, 1, 1);
+        builder.add_grid(Vec3::Y * half.y, -x, z, 1, 1);
+        builder.add_grid(Vec3::NEG_Y * half.y, x, z, 1, 1);
+        builder.add_grid(Vec3::Z * half.z, x, y, 1, 1);
+        builder.add_grid(Vec3::NEG_Z * half.z, x, -y, 1, 1);
+        builder.build()
+    }
+
+    /// A sphere with `sectors` segments around the Z axis and `stacks` from pole to pole.
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+        let (sectors, stacks) = (sectors.max(3), stacks.max(2));
+        let profile = (0..=stacks)
+            .map(|stack| {
+                let angle = PI * stack as f32 / stacks as f32;
+                let normal = Vec2::new(angle.sin(), angle.cos());
+                (normal * radius, normal)
+            })
+            .collect::<Vec<_>>();
+        let mut builder = MeshBuilder::new();
+        builder.add_revolution(&profile, sectors);
+        builder.build()
+    }
+
+    /// A closed cylinder of `height` along the Z axis.
+    pub fn cylinder(radius: f32, height: f32, sectors: u32) -> Self {
+        let sectors = sectors.max(3);
+        let half = height / 2.0;
+        let mut builder = MeshBuilder::new();
+        builder.add_revolution(
+            &[
+                (Vec2::new(radius, half), Vec2::X),
+                (Vec2::new(radius, -half), Vec2::X),
+            ],
+            sectors,
+        );
+        builder.add_disk(Vec3::Z * half, radius, true, sectors);
+        builder.add_disk(Vec3::NEG_Z * half, radius, false, sectors);
+        builder.build()
+    }
+
+    /// A cylinder with hemispherical ends. `length` is the distance between the centers of
+    /// the two hemispheres, each made of `stacks` rings.
+    pub fn capsule(radius: f32, length: f32, sectors: u32, stacks: u32) -> Self {
+        let (sectors, stacks) = (sectors.max(3), stacks.max(1));
+        let half = length / 2.0;
+        let ring = |stack: u32, offset: f32| {
+            let angle = FRAC_PI_2 * stack as f32 / stacks as f32;
+            let normal = Vec2::new(angle.sin(), angle.cos());
+            (normal * radius + Vec2::new(0.0, offset), normal)
+        };
+        let top = (0..=stacks).map(|stack| ring(stack, half));
+        let bottom = (stacks..=2 * stacks).map(|stack| ring(stack, -half));
+        let mut builder = MeshBuilder::new();
+        builder.add_revolution(&top.chain(bottom).collect::<Vec<_>>(), sectors);
+        builder.build()
+    }
+
+    /// Bounds of the vertex positions, or `None` without vertices.
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|vertex| *vertex.position))
+    }
+
+    /// Appends another triangle list moved by `transform`, e.g. to merge meshes that
+    /// never move into one draw. Mirroring transforms keep their triangles facing out and
+    /// their bitangents the right way round.
+    pub fn append(&mut self, vertices: &[ElmVertex], indices: &[u32], transform: Mat4) {
+        let first = self.vertices.len() as u32;
+        let linear = Mat3::from_mat4(transform);
+        let normal_matrix = linear.inverse().transpose();
+        let mirrored = linear.determinant() < 0.0;
+        let handedness = if mirrored { -1.0 } else { 1.0 };
+        self.vertices.extend(vertices.iter().map(|vertex| {
+            let tangent = (linear * vertex.tangent.truncate()).normalize_or_zero();
+            ElmVertex {
+                position: ElmVec3::from(transform.transform_point3(*vertex.position)),
+                normal: ElmVec3::from((normal_matrix * *vertex.normal).normalize_or_zero()),
+                tangent: ElmVec4::from(tangent.extend(vertex.tangent.w * handedness)),
+                ..*vertex
+            }
+        }));
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| first + index);
+            if mirrored {
+                self.indices.extend([a, c, b]);
+            } else {
+                self.indices.extend([a, b, c]);
+            }
+        }
+    }
+}
+
+/// Collects vertices and triangles into a [`MeshData`]. Used by the [`MeshData`]
+/// generators and for meshes built by hand.
+#[derive(Debug, Clone)]
+pub struct MeshBuilder {
+    data: MeshData,
+    color: Vec3,
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        MeshBuilder {
+            data: MeshData::default(),
+            color: Vec3::ONE,
+        }
+    }
+
+    /// Linear vertex color for the vertices added from now on. White by default.
+    pub fn with_color(mut self, color: Vec3) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Like [`MeshBuilder::with_color`], for a color picked in sRGB, e.g. from a color
+    /// picker or a hex code.
+    pub fn with_srgb_color(self, color: Vec3) -> Self {
+        self.with_color(ColorSpace::Srgb.to_linear(color))
+    }
+
+    /// Adds a vertex and returns its index.
+    pub fn add_vertex(&mut self, position: Vec3, normal: Vec3, tex_coord: Vec2) -> u32 {
+        self.data.vertices.push(ElmVertex {
+            position: ElmVec3::from(position),
+            color: ElmVec3::from(self.color),
+            tex_coord: ElmVec2::from(tex_coord),
+            normal: ElmVec3::from(normal),
+            tangent: ElmVec4::from(Vec4::ZERO),
+        });
+        self.data.vertices.len() as u32 - 1
+    }
+
+    /// Adds a triangle of counter-clockwise vertex indices.
+    pub fn add_triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.data.indices.extend([a, b, c]);
+    }
+
+    /// Adds two triangles for counter-clockwise corners `a`, `b`, `c`, `d`.
+    pub fn add_quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.add_triangle(a, b, c);
+        self.add_triangle(a, c, d);
+    }
+
+    /// Adds a flat rectangle centered on `center` and spanned by `u` and `v`, split into
+    /// `columns` by `rows` cells. It faces `u × v`; the texture's left edge runs along
+    /// `v` and its top edge is at `center + v / 2`.
+    pub fn add_grid(&mut self, center: Vec3, u: Vec3, v: Vec3, columns: u32, rows: u32) {
+        let normal = u.cross(v).normalize_or_zero();
+        let origin = center - (u + v) / 2.0;
+        let first = self.data.vertices.len() as u32;
+        for row in 0..=rows {
+            let t = row as f32 / rows as f32;
+            for column in 0..=columns {
+                let s = column as f32 / columns as f32;
+                self.add_vertex(origin + u * s + v * t, normal, Vec2::new(s, 1.0 - t));
+            }
+        }
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + row * (columns + 1) + column;
+                let d = a + columns + 1;
+                self.add_quad(a, a + 1, d + 1, d);
+            }
+        }
+    }
+
+    /// Revolves a profile around the Z axis. Each point is `(radius, z)` with its normal
+    /// in the same plane, listed from top to bottom. Points with zero radius become poles;
+    /// the texture wraps once around and runs down the profile by arc length.
+    pub fn add_revolution(&mut self, profile: &[(Vec2, Vec2)], sectors: u32) {
+        let lengths = profile
+            .iter()
+            .scan((0.0, None), |(length, previous), &(point, _)| {
+                if let Some(previous) = previous.replace(point) {
+                    *length += point.distance(previous);
+                }
+                Some(*length)
+            })
+            .collect::<Vec<f32>>();
+        let total = lengths.last().copied().unwrap_or(0.0).max(f32::EPSILON);
+
+        let first = self.data.vertices.len() as u32;
+        for (&(point, normal), length) in profile.iter().zip(&lengths) {
+            for sector in 0..=sectors {
+                let s = sector as f32 / sectors as f32;
+                let (sin, cos) = (TAU * s).sin_cos();
+                self.add_vertex(
+                    Vec3::new(point.x * cos, point.x * sin, point.y),
+                    Vec3::new(normal.x * cos, normal.x * sin, normal.y).normalize_or_zero(),
+                    Vec2::new(s, length / total),
+                );
+            }
+        }
+        let row = sectors + 1;
+        for (ring, pair) in profile.windows(2).enumerate() {
+            let (upper, lower) = (pair[0].0, pair[1].0);
+            for sector in 0..sectors {
+                let a = first + ring as u32 * row + sector;
+                let b = a + row;
+                if upper.x > 0.0 {
+                    self.add_triangle(a, b + 1, a + 1);
+                }
+                if lower.x > 0.0 {
+                    self.add_triangle(a, b, b + 1);
+                }
+            }
+        }
+    }
+
+    /// Adds a flat disk around the Z axis, facing `+Z` if `up` or `-Z` otherwise.
+    pub fn add_disk(&mut self, center: Vec3, radius: f32, up: bool, sectors: u32) {
+        let (normal, flip) = if up {
+            (Vec3::Z, 1.0)
+        } else {
+            (Vec3::NEG_Z, -1.0)
+        };
+        let middle = self.add_vertex(center, normal, Vec2::splat(0.5));
+        let first = self.data.vertices.len() as u32;
+        for sector in 0..=sectors {
+            let (sin, cos) = (TAU * sector as f32 / sectors as f32).sin_cos();
+            self.add_vertex(
+                center + Vec3::new(cos, sin, 0.0) * radius,
+                normal,
+                Vec2::new(0.5 + cos / 2.0, 0.5 - flip * sin / 2.0),
+            );
+        }
+        for sector in first..first + sectors {
+            if up {
+                self.add_triangle(middle, sector, sector + 1);
+            } else {
+                self.add_triangle(middle, sector + 1, sector);
+            }
+        }
+    }
+
+    pub fn build(self) -> MeshData {
+        self.data
+    }
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
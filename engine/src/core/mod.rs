@@ -1,2 +1,5 @@
+pub mod bounds;
+pub mod mesh;
+pub mod transform;
 pub mod ubo;
 pub mod vertex;
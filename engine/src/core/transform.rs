@@ -0,0 +1,123 @@
+use glam::{Mat3, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Translation, rotation and scale of an object relative to its parent. Uses the glTF
+/// convention: right-handed, with `-Z` forward, `+X` right and `+Y` up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Transform {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Transform {
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_scale(scale: Vec3) -> Self {
+        Transform {
+            scale,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Decomposes an affine matrix. Shear, e.g. from non-uniformly scaled parents, is lost.
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Matrix that scales, then rotates, then translates.
+    pub fn compute_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.rotation * Vec3::NEG_Z
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.rotation * Vec3::X
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.rotation * Vec3::Y
+    }
+
+    /// Rotates so [`Transform::forward`] points at `target`, keeping [`Transform::up`]
+    /// as close to `up` as possible. Does nothing if `target` is at the translation.
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let forward = (target - self.translation).normalize_or_zero();
+        if forward == Vec3::ZERO {
+            return;
+        }
+        let right = forward.cross(up).normalize_or_zero();
+        // `up` is parallel to the view direction; any perpendicular right vector will do.
+        let right = if right == Vec3::ZERO {
+            forward.any_orthonormal_vector()
+        } else {
+            right
+        };
+        let up = right.cross(forward);
+        self.rotation = Quat::from_mat3(&Mat3::from_cols(right, up, -forward));
+    }
+
+    /// This transform turned towards `target`. See [`Transform::look_at`].
+    pub fn looking_at(mut self, target: Vec3, up: Vec3) -> Self {
+        self.look_at(target, up);
+        self
+    }
+
+    /// Interpolates translation and scale linearly and rotation spherically.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Self {
+        Transform {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.translation + self.rotation * (self.scale * point)
+    }
+
+    /// The view matrix of a camera placed at this transform.
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_to_rh(self.translation, self.forward(), self.up())
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
@@ -1,4 +1,4 @@
-use glam::Mat4;
+use glam::{Mat4, Vec4};
 use vulkano::buffer::BufferContents;
 
 #[derive(BufferContents, Clone, Copy, Default)]
@@ -7,4 +7,47 @@ pub struct UniformBufferObject {
     pub model: Mat4,
     pub view: Mat4,
     pub proj: Mat4,
+    /// Ambient light color in `rgb`.
+    pub ambient: Vec4,
+    /// Number of lights used from the light storage buffer.
+    pub light_count: u32,
+    pub _padding: [u32; 3],
+}
+
+/// One entry of the per-frame light storage buffer, laid out for std430.
+#[derive(BufferContents, Clone, Copy, Default, Debug, PartialEq)]
+#[repr(C)]
+pub struct LightData {
+    /// World position in `xyz`, range in `w`. Unused by directional lights.
+    pub position_range: Vec4,
+    /// World direction the light shines in `xyz`, kind in `w`.
+    pub direction_kind: Vec4,
+    /// Color premultiplied by intensity in `rgb`.
+    pub color: Vec4,
+    /// Cosines of a spot light's inner and outer cone half-angles in `x` and `y`.
+    pub spot_cos: Vec4,
+}
+
+impl LightData {
+    pub const DIRECTIONAL: u32 = 0;
+    pub const POINT: u32 = 1;
+    pub const SPOT: u32 = 2;
+}
+
+/// Per-draw data pushed before each mesh instance is drawn.
+#[derive(BufferContents, Clone, Copy, Default)]
+#[repr(C)]
+pub struct MeshPushConstants {
+    /// World transform of the glTF node the mesh belongs to.
+    pub node: Mat4,
+}
+
+/// Per-material parameter block, bound next to the material's textures.
+#[derive(BufferContents, Clone, Copy, Default)]
+#[repr(C)]
+pub struct MaterialParams {
+    pub base_color_factor: Vec4,
+    /// Alpha below which alpha-tested variants discard fragments.
+    pub alpha_cutoff: f32,
+    pub _padding: [u32; 3],
 }
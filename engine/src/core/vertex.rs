@@ -1,11 +1,38 @@
-use glam::{Vec2, Vec3};
+use glam::{Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use vulkano::buffer::BufferContents;
 use vulkano::pipeline::graphics::vertex_input::Vertex;
 
 #[repr(C)]
-#[derive(BufferContents, PartialEq, Debug, Clone, Copy)]
+#[derive(BufferContents, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ElmVec4(Vec4);
+
+impl Deref for ElmVec4 {
+    type Target = glam::Vec4;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec4> for ElmVec4 {
+    fn from(v: Vec4) -> Self {
+        Self(v)
+    }
+}
+
+impl Eq for ElmVec4 {}
+impl Hash for ElmVec4 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for f in &self.to_array() {
+            f.to_bits().hash(state);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(BufferContents, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ElmVec3(Vec3);
 
 impl Deref for ElmVec3 {
@@ -32,7 +59,7 @@ impl Hash for ElmVec3 {
 }
 
 #[repr(C)]
-#[derive(BufferContents, PartialEq, Debug, Clone, Copy)]
+#[derive(BufferContents, PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ElmVec2(Vec2);
 
 impl Deref for ElmVec2 {
@@ -58,7 +85,9 @@ impl Hash for ElmVec2 {
 }
 
 #[repr(C)]
-#[derive(BufferContents, Vertex, Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(
+    BufferContents, Vertex, Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize,
+)]
 pub struct ElmVertex {
     // Every field needs to explicitly state the desired shader input format
     // The `name` attribute can be used to specify shader input names to match.
@@ -67,6 +96,8 @@ pub struct ElmVertex {
     #[format(R32G32B32_SFLOAT)]
     pub position: ElmVec3,
 
+    /// Linear RGB, as glTF stores it. Convert sRGB colors with
+    /// [`ColorSpace::to_linear`](crate::asset_loader::texture_settings::ColorSpace::to_linear).
     #[name("inColor")]
     #[format(R32G32B32_SFLOAT)]
     pub color: ElmVec3,
@@ -74,4 +105,14 @@ pub struct ElmVertex {
     #[name("inTexCoord")]
     #[format(R32G32_SFLOAT)]
     pub tex_coord: ElmVec2,
+
+    #[name("inNormal")]
+    #[format(R32G32B32_SFLOAT)]
+    pub normal: ElmVec3,
+
+    /// Tangent with the bitangent sign in `w`. Zero when the source had none and it
+    /// wasn't baked.
+    #[name("inTangent")]
+    #[format(R32G32B32A32_SFLOAT)]
+    pub tangent: ElmVec4,
 }
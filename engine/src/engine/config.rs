@@ -0,0 +1,185 @@
+use crate::asset_loader::AssetMount;
+use crate::capture::CaptureFormat;
+use crate::logger::{LogFormat, LoggerConfig};
+use crate::power::PowerSettings;
+use crate::telemetry::MetricsExport;
+use crate::time::TickMode;
+use crate::window::WindowDescriptor;
+use anyhow::{Context, Result, bail};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// When the event loop runs a frame. Also a resource, so it can be switched at runtime,
+/// e.g. to [`UpdateMode::Reactive`] while an editor window sits idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateMode {
+    /// Run frames back to back, as fast as presentation allows.
+    #[default]
+    Continuous,
+    /// Sleep until a window or input event arrives, then run one frame. Animation and
+    /// background loads don't advance while idle.
+    Reactive,
+    /// Run frames at most `fps` times per second, sleeping in between.
+    WaitUntil { fps: u32 },
+}
+
+impl UpdateMode {
+    /// Time between frames in [`UpdateMode::WaitUntil`].
+    pub fn frame_interval(&self) -> Option<Duration> {
+        match self {
+            UpdateMode::WaitUntil { fps } => {
+                Some(Duration::from_secs_f64(1.0 / (*fps).max(1) as f64))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Startup options for the [`Engine`](crate::engine::Engine).
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// How frame deltas are produced. See [`TickMode`].
+    pub tick_mode: TickMode,
+    /// Initial [`UpdateMode`].
+    pub update_mode: UpdateMode,
+    /// Seed for the global [`Rng`](crate::rng::Rng) resource. `None` seeds from the clock.
+    pub rng_seed: Option<u64>,
+    /// Worker threads in the [`TaskPool`](crate::task_pool::TaskPool). `0` picks one per
+    /// logical core, minus one for the main thread.
+    pub worker_threads: usize,
+    /// Log file and crash report locations.
+    pub logger: LoggerConfig,
+    /// Game logic built as a `cdylib`, hot-reloaded when the file changes.
+    /// See [`GameModule`](crate::game_module::GameModule).
+    pub game_module: Option<PathBuf>,
+    /// Asset directories and paks, lowest priority first: a file in a later mount shadows
+    /// the same file in earlier ones. A typical order is engine builtins, game assets,
+    /// then user mods.
+    pub asset_mounts: Vec<AssetMount>,
+    /// Panic on the next frame after a Vulkan validation error, so CI runs fail loudly.
+    /// Only has an effect in debug builds, where validation layers are enabled.
+    pub panic_on_validation_error: bool,
+    /// Where [`FrameCapture`](crate::capture::FrameCapture) recordings are written.
+    pub capture_dir: PathBuf,
+    pub capture_format: CaptureFormat,
+    /// Title, size and style of the main window.
+    pub window: WindowDescriptor,
+    /// Asset id of the model loaded at startup.
+    pub scene: String,
+    /// Where [`Telemetry`](crate::telemetry::Telemetry) publishes metrics. Off by default.
+    pub metrics: Option<MetricsExport>,
+    /// When to throttle for an occluded window or battery saver. See
+    /// [`PowerSaving`](crate::power::PowerSaving).
+    pub power: PowerSettings,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            tick_mode: TickMode::default(),
+            update_mode: UpdateMode::default(),
+            rng_seed: None,
+            worker_threads: 0,
+            logger: LoggerConfig::default(),
+            game_module: None,
+            asset_mounts: vec![AssetMount::Directory(PathBuf::from("assets"))],
+            panic_on_validation_error: false,
+            capture_dir: PathBuf::from("captures"),
+            capture_format: CaptureFormat::default(),
+            window: WindowDescriptor::default(),
+            scene: "super_car.scene".to_owned(),
+            metrics: None,
+            power: PowerSettings::default(),
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Fixed-step simulation with a fixed seed, so identical input produces identical runs.
+    pub fn deterministic(delta: Duration, rng_seed: u64) -> Self {
+        EngineConfig {
+            tick_mode: TickMode::Fixed { delta },
+            rng_seed: Some(rng_seed),
+            ..Default::default()
+        }
+    }
+
+    /// Applies command-line options on top of this config, in order:
+    ///
+    /// - `--assets <path>` replaces every mount with a single asset root.
+    /// - `--mount <path>` adds a mount above the existing ones, e.g. a mod.
+    /// - `--log-filter <path>` reads log filter directives from a file.
+    /// - `--log-format <pretty|json>` picks the log output format.
+    /// - `--strict-validation` panics on Vulkan validation errors.
+    /// - `--update-mode <continuous|reactive|fps>` picks the [`UpdateMode`], e.g. `30`.
+    /// - `--capture-format <png|gif>` picks what F9 recordings are encoded to.
+    /// - `--scene <asset id>` picks the model loaded at startup.
+    /// - `--metrics-addr <addr>` serves Prometheus metrics, e.g. `127.0.0.1:9100`.
+    /// - `--metrics-file <path>` appends JSON metrics to a file every second.
+    /// - `--no-power-saving` keeps full speed when occluded or on battery saver.
+    ///
+    /// Paths ending in `.pak` or `.zip` mount as archives. Other arguments are left to
+    /// the game.
+    pub fn with_args<I: IntoIterator<Item = String>>(mut self, args: I) -> Result<Self> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--assets" => {
+                    let path = args.next().context("--assets needs a path")?;
+                    self.asset_mounts = vec![AssetMount::from_path(path)];
+                }
+                "--mount" => {
+                    let path = args.next().context("--mount needs a path")?;
+                    self.asset_mounts.push(AssetMount::from_path(path));
+                }
+                "--log-filter" => {
+                    let path = args.next().context("--log-filter needs a path")?;
+                    self.logger.filter_file = Some(PathBuf::from(path));
+                }
+                "--strict-validation" => self.panic_on_validation_error = true,
+                "--update-mode" => {
+                    self.update_mode = match args.next().as_deref() {
+                        Some("continuous") => UpdateMode::Continuous,
+                        Some("reactive") => UpdateMode::Reactive,
+                        Some(fps) => match fps.parse() {
+                            Ok(fps) => UpdateMode::WaitUntil { fps },
+                            Err(_) => bail!("Invalid --update-mode {fps:?}"),
+                        },
+                        None => bail!("--update-mode needs `continuous`, `reactive` or an FPS"),
+                    };
+                }
+                "--capture-format" => {
+                    let format = args.next().context("--capture-format needs png or gif")?;
+                    self.capture_format = CaptureFormat::parse(&format)?;
+                }
+                "--scene" => {
+                    self.scene = args.next().context("--scene needs an asset id")?;
+                }
+                "--metrics-addr" => {
+                    let addr = args.next().context("--metrics-addr needs an address")?;
+                    let addr = addr
+                        .parse()
+                        .with_context(|| format!("Invalid --metrics-addr {addr:?}"))?;
+                    self.metrics = Some(MetricsExport::Prometheus { addr });
+                }
+                "--metrics-file" => {
+                    let path = args.next().context("--metrics-file needs a path")?;
+                    self.metrics = Some(MetricsExport::JsonFile {
+                        path: PathBuf::from(path),
+                        interval: Duration::from_secs(1),
+                    });
+                }
+                "--no-power-saving" => self.power = PowerSettings::disabled(),
+                "--log-format" => {
+                    self.logger.format = match args.next().as_deref() {
+                        Some("pretty") => LogFormat::Pretty,
+                        Some("json") => LogFormat::Json,
+                        _ => bail!("--log-format needs `pretty` or `json`"),
+                    };
+                }
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+}
@@ -1,45 +1,254 @@
 use crate::asset_loader::gltf_model::GltfModel;
+use crate::asset_loader::shader::SpirvShader;
+use crate::camera::{Camera, CameraController};
+use crate::capture::FrameCapture;
 use crate::renderer::renderer_vulkan::VulkanRenderer;
 use crate::{
-    asset_loader::AssetLoader, input::Input, logger::Logger, renderer::Renderer,
-    resource_manager::ResourceManager, window::Window,
+    animation::{AnimationPlayer, advance_flipbooks},
+    asset_loader::{AssetEvent, AssetLoader, Handle, LoadProgress, LoadingSet},
+    billboard::Billboards,
+    core::{mesh::MeshData, transform::Transform},
+    events::{EventReader, Events},
+    frame_arena::FrameArena,
+    frame_stats::FrameStats,
+    game_module::GameModule,
+    input::{Gamepads, Gestures, Input},
+    lighting::Lighting,
+    logger::{CrashReporter, LogBuffer, LogFilter, Logger},
+    network::Network,
+    overlay::{
+        CONSOLE_HELP, ConsoleCommand, DebugOverlay, EditorPreview, InspectorEdit, SceneView,
+        Selection,
+    },
+    physics::{PhysicsWorld, raycast_meshes},
+    power::PowerSaving,
+    profiler::Profiler,
+    renderer::{
+        GraphicsQuality, MaterialDesc, MaterialHandle, Renderer, TextureUpload,
+        render_world::RenderWorld,
+    },
+    resource_manager::{FromResources, ResourceManager},
+    rng::Rng,
+    save_game::SaveGame,
+    scene::{Model, RenderLayers, Visibility, World},
+    sequencer::Sequencer,
+    task_pool::TaskPool,
+    telemetry::Telemetry,
+    time::Time,
+    tween::Tweens,
+    window::{Window, WindowDescriptor},
 };
+use assets_manager::source::{DirEntry, Source};
+use egui_winit_vulkano::egui;
+use glam::{Mat4, Vec2, Vec3};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::{debug, error};
-use winit::event::WindowEvent;
-use winit::window::Window as WinitWindow;
+use std::time::Instant;
+use tracing::{debug, error, info, info_span, warn};
+use winit::event::{MouseButton, WindowEvent};
+use winit::event_loop::ActiveEventLoop;
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::{CursorGrabMode, Window as WinitWindow};
+
+mod config;
+
+pub use crate::renderer::SwapchainChanged;
+pub use config::{EngineConfig, UpdateMode};
+
+type ShutdownHook = Box<dyn FnOnce(&mut ResourceManager)>;
+
+/// Renderer material of each glTF material, with what it was created from.
+type ModelMaterials = Vec<(MaterialHandle, MaterialDesc)>;
+
+/// The model currently on screen and which renderer instance draws each of its nodes.
+struct SceneModel {
+    handle: Handle<GltfModel>,
+    /// Instances of the nodes that can move; static nodes may be merged into batches.
+    instances: Vec<(usize, usize)>,
+    /// Instances of the merged static batches.
+    batches: Vec<usize>,
+    /// Visibility and layers of the entity whose [`Model`] this is, as last applied to
+    /// the instances. `None` until they're applied.
+    shown: Option<(bool, RenderLayers)>,
+    materials: ModelMaterials,
+    /// Node transforms changed in the inspector, replacing the model's and animation's.
+    node_overrides: HashMap<usize, Transform>,
+    /// Node visibilities changed in the inspector, replacing the model's.
+    node_visibility: HashMap<usize, Visibility>,
+    /// Nodes kept out of static batches, see [`GltfModel::dynamic_nodes`]: those that can
+    /// move and those whose visibility or layers differ from the model's entity.
+    dynamic: Vec<bool>,
+}
+
+/// Primitives up to this many vertices on nodes that never move are merged per material
+/// into one mesh at upload, so a scene of many small props takes a few draw calls.
+const MAX_BATCHED_PRIMITIVE_VERTICES: usize = 4096;
+/// Vertices per merged mesh; larger batches would make culling too coarse.
+const MAX_BATCH_VERTICES: usize = 65536;
+
+/// Asset ids of the SPIR-V files that replace the built-in mesh shaders (vertex, fragment).
+const SHADER_OVERRIDES: [&str; 2] = ["shaders.mesh_vert", "shaders.mesh_frag"];
 
 pub struct Engine {
+    config: EngineConfig,
     resources: ResourceManager,
     _logger: Logger,
     renderer: Option<Box<dyn Renderer>>,
+    game_module: Option<GameModule>,
+    model_events: EventReader<AssetEvent<GltfModel>>,
+    /// Startup assets, shown as a loading window until they finish.
+    loading: Option<LoadingSet>,
+    scene: Option<SceneModel>,
+    shaders: Vec<Handle<SpirvShader>>,
+    shader_events: EventReader<AssetEvent<SpirvShader>>,
+    swapchain_events: EventReader<SwapchainChanged>,
+    shutdown_hooks: Vec<ShutdownHook>,
+    is_shut_down: bool,
 }
 
 impl Engine {
     pub fn new() -> Engine {
-        let _logger = Logger::new();
+        Self::with_config(EngineConfig::default())
+    }
+
+    pub fn with_config(config: EngineConfig) -> Engine {
+        let _logger = Logger::with_config(config.logger.clone());
         let mut resources = ResourceManager::new();
-        resources.add(Input::new());
-        resources.add(AssetLoader::new());
+        resources.add(_logger.profiler());
+        resources.add(_logger.crash_reporter());
+        resources.add(_logger.filter());
+        resources.add(_logger.log_buffer());
+        resources.add(config.clone());
+        resources.add(config.update_mode);
+        if let Err(e) = Self::init_resources(&mut resources) {
+            panic!("Failed to initialize engine resources: {e:?}");
+        }
+        info!("Tick mode: {:?}", config.tick_mode);
+        let game_module = config.game_module.clone().map(GameModule::new);
         Engine {
+            config,
             resources,
             _logger,
             renderer: None,
+            game_module,
+            model_events: EventReader::new(),
+            loading: None,
+            scene: None,
+            shaders: Vec::new(),
+            shader_events: EventReader::new(),
+            swapchain_events: EventReader::new(),
+            shutdown_hooks: Vec::new(),
+            is_shut_down: false,
         }
     }
 
+    /// Registers the built-in resources. Order doesn't matter: each one initializes what
+    /// it depends on.
+    fn init_resources(resources: &mut ResourceManager) -> anyhow::Result<()> {
+        resources.init::<Events>()?;
+        resources.init::<DebugOverlay>()?;
+        resources.init::<TaskPool>()?;
+        resources.init::<Time>()?;
+        resources.init::<Rng>()?;
+        resources.init::<Input>()?;
+        resources.init::<Gamepads>()?;
+        resources.init::<Gestures>()?;
+        resources.init::<AssetLoader>()?;
+        resources.init::<FrameStats>()?;
+        resources.init::<FrameArena>()?;
+        resources.init::<PowerSaving>()?;
+        resources.init::<AnimationPlayer>()?;
+        resources.init::<Tweens>()?;
+        resources.init::<Sequencer>()?;
+        resources.init::<World>()?;
+        resources.init::<PhysicsWorld>()?;
+        resources.init::<Billboards>()?;
+        resources.init::<EditorPreview>()?;
+        resources.init::<Network>()?;
+        resources.init::<SaveGame>()?;
+        resources.init::<Telemetry>()?;
+        resources.init::<Lighting>()?;
+        resources.init::<GraphicsQuality>()?;
+        resources.init::<FrameCapture>()?;
+        resources.init::<Camera>()?;
+        resources.init::<CameraController>()?;
+        resources.init::<RenderWorld>()?;
+        resources.register_snapshot::<Rng>("rng");
+        resources.register_snapshot::<DebugOverlay>("debug_overlay");
+        Ok(())
+    }
+
     pub fn set_window(&mut self, window: Arc<WinitWindow>) {
-        let app_window = Window::new(window);
+        let crash_window = window.clone();
+        self.resources
+            .get::<CrashReporter>()
+            .on_crash_restore(move || {
+                crash_window.set_cursor_visible(true);
+                let _ = crash_window.set_cursor_grab(CursorGrabMode::None);
+                crash_window.set_fullscreen(None);
+            });
+        let app_window = Window::new(window, &self.config.window);
         self.resources.add(app_window);
-        let renderer = VulkanRenderer::new(&mut self.resources);
-        self.renderer = Some(Box::new(renderer));
+        self.create_renderer();
+    }
+
+    /// Creates the renderer without a window: frames are drawn into offscreen images of
+    /// the configured window size and never presented, e.g. for screenshot tests on
+    /// machines without a display. Call instead of [`Engine::set_window`]; there is no
+    /// debug overlay.
+    pub fn set_headless(&mut self) {
+        self.create_renderer();
+    }
+
+    fn create_renderer(&mut self) {
+        match VulkanRenderer::from_resources(&mut self.resources) {
+            Ok(renderer) => self.renderer = Some(Box::new(renderer)),
+            Err(e) => error!("Failed to create renderer: {:?}", e),
+        }
+    }
+
+    /// How the platform should create the main window.
+    pub fn window_descriptor(&self) -> &WindowDescriptor {
+        &self.config.window
+    }
+
+    /// Creates the debug overlay. Needs the event loop, so the platform calls this once
+    /// the window exists and [`Engine::run`] has set up the renderer.
+    pub fn init_ui(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(renderer) = self.renderer.as_mut()
+            && let Err(e) = renderer.init_ui(event_loop)
+        {
+            error!("Failed to initialize debug overlay: {:?}", e);
+        }
+    }
+
+    /// Recreates the debug overlay after the swapchain format changed under it. The
+    /// platform calls this every loop iteration, as it needs the event loop too.
+    pub fn refresh_ui(&mut self, event_loop: &ActiveEventLoop) {
+        let format_changed = self
+            .swapchain_events
+            .read(self.resources.get::<Events>())
+            .any(|changed| changed.format_changed);
+        if format_changed {
+            info!("Swapchain format changed; recreating the debug overlay");
+            self.init_ui(event_loop);
+        }
     }
 
     pub fn handle_window_event(&mut self, event: WindowEvent) {
+        if let Some(renderer) = self.renderer.as_mut()
+            && renderer.handle_ui_event(&event)
+        {
+            return;
+        }
         match event {
             WindowEvent::Focused(is_focused) => {
                 self.resources.get_mut::<Window>().set_focused(is_focused);
             }
+            WindowEvent::Occluded(occluded) => {
+                let (power, events) = self.resources.get_many_mut::<(PowerSaving, Events)>();
+                power.set_occluded(occluded, events);
+            }
             WindowEvent::Resized(new_size) => {
                 self.resources
                     .get_mut::<Window>()
@@ -60,6 +269,9 @@ impl Engine {
             WindowEvent::CursorMoved { position, .. } => {
                 self.resources.get_mut::<Input>().handle_cursor(position);
             }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.resources.get_mut::<Input>().handle_mouse_wheel(delta);
+            }
             _ => (),
         }
     }
@@ -69,71 +281,414 @@ impl Engine {
             .renderer
             .as_mut()
             .expect("Renderer must be initialized before running the engine");
-        let asset_loader = self.resources.get_mut::<AssetLoader>();
-        let handle = asset_loader.load::<GltfModel>("super_car.scene");
-        if let Ok(handle) = handle {
-            let model = handle.read();
-            // info!("Model: {:?}", model);
-            for mesh in model.meshes.iter() {
-                for primitive in mesh.primitives.iter() {
-                    if let Err(e) = renderer.upload_mesh(&primitive.vertices, &primitive.indices) {
-                        error!("Failed to upload mesh: {:?}", e);
-                    }
-                }
-            }
-            for texture in model.textures.iter() {
-                debug!("Texture: {:?}", texture);
-                if let Some(index) = texture.image {
-                    let image = &model.images[index];
-                    if let Err(e) = renderer.upload_texture(
-                        &image.pixels,
-                        image.width,
-                        image.height,
-                        (texture.sampler.mag_filter, texture.sampler.min_filter),
-                        (texture.sampler.wrap_s, texture.sampler.wrap_t),
-                    ) {
-                        error!("Failed to upload texture: {:?}", e);
-                    }
-                }
-            }
-        } else {
-            error!("Failed to load super_car.scene: {:?}", handle);
+        let asset_loader = self.resources.get::<AssetLoader>();
+        let mut loading = LoadingSet::new();
+        loading.add::<GltfModel>(asset_loader, &self.config.scene);
+        // Shader overrides are optional; only watch them when they exist.
+        if SHADER_OVERRIDES
+            .iter()
+            .all(|id| asset_loader.source().exists(DirEntry::File(id, "spv")))
+        {
+            self.shaders = SHADER_OVERRIDES
+                .iter()
+                .map(|id| loading.add::<SpirvShader>(asset_loader, id))
+                .collect();
         }
+        self.loading = Some(loading);
 
         if let Err(e) = renderer.run() {
             error!("Renderer encountered an error: {:?}", e);
         }
     }
 
+    /// Registers a callback run during [`Engine::shutdown`], after the GPU is idle but
+    /// before the renderer is torn down. Hooks run in reverse registration order.
+    pub fn add_shutdown_hook(&mut self, hook: impl FnOnce(&mut ResourceManager) + 'static) {
+        self.shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// Waits for in-flight GPU work, runs shutdown hooks and releases the renderer.
+    /// Safe to call more than once; only the first call does anything.
+    pub fn shutdown(&mut self) {
+        if self.is_shut_down {
+            return;
+        }
+        self.is_shut_down = true;
+        info!("Shutting down engine");
+
+        if let Some(renderer) = self.renderer.as_mut()
+            && let Err(e) = renderer.wait_idle()
+        {
+            error!("Failed to wait for GPU idle: {:?}", e);
+        }
+        self.resources.get_mut::<FrameCapture>().stop();
+
+        if let Some(game_module) = self.game_module.as_mut() {
+            game_module.unload(&mut self.resources);
+        }
+
+        while let Some(hook) = self.shutdown_hooks.pop() {
+            hook(&mut self.resources);
+        }
+
+        if let Some(mut renderer) = self.renderer.take()
+            && let Err(e) = renderer.shutdown()
+        {
+            error!("Renderer shutdown error: {:?}", e);
+        }
+    }
+
+    /// The engine's resources, e.g. to drive it from a test.
+    pub fn resources_mut(&mut self) -> &mut ResourceManager {
+        &mut self.resources
+    }
+
+    /// Whether the startup assets are still loading.
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.is_shut_down
+    }
+
+    /// Lets [`EventSender`](crate::events::EventSender)s on other threads wake the
+    /// platform's event loop.
+    pub fn set_waker(&mut self, waker: impl Fn() + Send + Sync + 'static) {
+        self.resources.get::<Events>().set_waker(waker);
+    }
+
+    /// The [`UpdateMode`] resource, throttled by [`PowerSaving`] while the window is
+    /// occluded or the system saves battery.
+    pub fn update_mode(&self) -> UpdateMode {
+        let requested = *self.resources.get::<UpdateMode>();
+        self.resources.get::<PowerSaving>().update_mode(requested)
+    }
+
+    /// Asks the platform to present a frame via [`Engine::render`].
+    pub fn request_redraw(&self) {
+        if let Some(window) = self.resources.try_get::<Window>() {
+            window.get_winit_window().request_redraw();
+        }
+    }
+
+    /// Runs one simulation tick; drawing happens in [`Engine::render`]. The order of the
+    /// steps below is fixed so that, in a deterministic [`TickMode`](crate::time::TickMode),
+    /// the same inputs always produce the same frame.
     pub fn on_update(&mut self) {
+        if self.is_shut_down {
+            return;
+        }
         let renderer = self
             .renderer
             .as_mut()
             .expect("Renderer must be initialized before updating the engine");
-        let start_time = std::time::Instant::now();
+        let update_start = Instant::now();
+        self.resources.get_mut::<FrameStats>().begin_frame();
+        self.resources.get::<Profiler>().new_frame();
+        let frame = self.resources.get::<FrameStats>().frame_count();
+        let _span_update = info_span!("update", frame).entered();
 
-        if let Err(e) = renderer.on_update() {
-            error!("Renderer update error: {:?}", e);
-            panic!("Renderer update failed");
+        self.resources.get_mut::<Time>().tick();
+        self.resources.get_mut::<FrameArena>().reset();
+        self.resources.get::<AssetLoader>().check_reloads();
+        self.resources.get::<AssetLoader>().unload_unused();
+        self.resources.get_mut::<Events>().update();
+        {
+            let (power, events) = self.resources.get_many_mut::<(PowerSaving, Events)>();
+            power.poll(events);
+        }
+        {
+            let _span = info_span!("network", subsystem = "network").entered();
+            let (network, events) = self.resources.get_many_mut::<(Network, Events)>();
+            network.poll(events);
+        }
+        {
+            let (gamepads, input, events) =
+                self.resources.get_many_mut::<(Gamepads, Input, Events)>();
+            gamepads.poll(input, events);
+        }
+        {
+            let (gestures, input, time, events) = self
+                .resources
+                .get_many_mut::<(Gestures, Input, Time, Events)>();
+            gestures.update(input, time.elapsed(), events);
+        }
+
+        let model_events = self
+            .model_events
+            .read(self.resources.get::<Events>())
+            .cloned()
+            .collect::<Vec<_>>();
+        for event in model_events {
+            let handle = match event {
+                AssetEvent::Loaded(handle) => {
+                    // A different model replaces the current scene or placeholder and
+                    // releases its meshes.
+                    if self
+                        .scene
+                        .as_ref()
+                        .is_none_or(|scene| scene.handle.id() != handle.id())
+                    {
+                        if let Err(e) = renderer.clear_scene() {
+                            error!("Failed to clear scene: {:?}", e);
+                        }
+                        self.scene = None;
+                    }
+                    handle
+                }
+                AssetEvent::Reloaded(handle) => {
+                    if let Err(e) = renderer.clear_scene() {
+                        error!("Failed to clear scene for reload: {:?}", e);
+                    }
+                    handle
+                }
+                AssetEvent::Unloaded(handle) => {
+                    if self
+                        .scene
+                        .as_ref()
+                        .is_some_and(|scene| scene.handle.id() == handle.id())
+                    {
+                        if let Err(e) = renderer.clear_scene() {
+                            error!("Failed to clear unloaded scene: {:?}", e);
+                        }
+                        self.scene = None;
+                    }
+                    continue;
+                }
+                AssetEvent::Failed(handle) => {
+                    // Keeps a scene already shown; otherwise marks the missing model.
+                    if self.scene.is_none() {
+                        warn!(
+                            "Model {} failed to load, showing a placeholder",
+                            handle.id()
+                        );
+                        place_placeholder(renderer.as_mut());
+                    }
+                    continue;
+                }
+            };
+            let (asset_loader, player) = self
+                .resources
+                .get_many_mut::<(AssetLoader, AnimationPlayer)>();
+            if let Some(model) = asset_loader.asset(&handle) {
+                let model = model.read();
+                let scene = upload_model(
+                    renderer.as_mut(),
+                    handle,
+                    &model,
+                    HashMap::new(),
+                    HashMap::new(),
+                );
+                if !model.animations.is_empty() {
+                    player.play(0);
+                }
+                self.scene = Some(scene);
+            }
+        }
+        let shaders_changed = self
+            .shader_events
+            .read(self.resources.get::<Events>())
+            .any(|event| matches!(event, AssetEvent::Loaded(_) | AssetEvent::Reloaded(_)));
+        if shaders_changed {
+            reload_shaders(
+                renderer.as_mut(),
+                self.resources.get::<AssetLoader>(),
+                &self.shaders,
+            );
         }
 
         {
-            let window = self.resources.get_mut::<Window>();
-            window.get_winit_window().request_redraw();
+            let delta = self.resources.get::<Time>().delta_secs();
+            let (controller, input, camera) = self
+                .resources
+                .get_many_mut::<(CameraController, Input, Camera)>();
+            controller.update(input, delta, camera);
+        }
+
+        {
+            let (physics, world) = self.resources.get_many_mut::<(PhysicsWorld, World)>();
+            physics.sync(world);
+        }
+        if self.resources.get::<DebugOverlay>().show_inspector {
+            pick_entity(&mut self.resources);
+        }
+
+        if let Some(game_module) = self.game_module.as_mut() {
+            let _span = info_span!("game_module", subsystem = "game").entered();
+            game_module.reload_if_changed(&mut self.resources);
+            let delta = self.resources.get::<Time>().delta_secs();
+            game_module.update(&mut self.resources, delta);
+        }
+
+        {
+            let _span = info_span!("animation", subsystem = "animation").entered();
+            if let Some(scene) = self.scene.as_ref() {
+                animate_scene(renderer.as_mut(), &mut self.resources, scene);
+            }
+            let delta = self.resources.get::<Time>().delta_secs();
+            advance_flipbooks(self.resources.get_mut::<World>(), delta);
+            let (tweens, world, events) = self.resources.get_many_mut::<(Tweens, World, Events)>();
+            tweens.update(world, delta, events);
+            let (sequencer, world, camera, asset_loader, events) =
+                self.resources
+                    .get_many_mut::<(Sequencer, World, Camera, AssetLoader, Events)>();
+            sequencer.update(world, camera, asset_loader, delta, events);
+        }
+        if let Some(scene) = self.scene.as_mut() {
+            show_scene(
+                renderer.as_mut(),
+                self.resources.get::<World>(),
+                self.resources.get::<AssetLoader>(),
+                scene,
+            );
+        }
+
+        if self
+            .resources
+            .get::<Input>()
+            .was_key_just_pressed(PhysicalKey::Code(KeyCode::F2))
+        {
+            self.resources.get_mut::<DebugOverlay>().toggle_profiler();
+        }
+        if self
+            .resources
+            .get::<Input>()
+            .was_key_just_pressed(PhysicalKey::Code(KeyCode::F3))
+        {
+            self.resources
+                .get_mut::<DebugOverlay>()
+                .toggle_performance();
+        }
+        if self
+            .resources
+            .get::<Input>()
+            .was_key_just_pressed(PhysicalKey::Code(KeyCode::F4))
+        {
+            self.resources.get_mut::<DebugOverlay>().toggle_inspector();
+        }
+        if self
+            .resources
+            .get::<Input>()
+            .was_key_just_pressed(PhysicalKey::Code(KeyCode::F6))
+        {
+            self.resources.get_mut::<EditorPreview>().toggle();
+        }
+        if self
+            .resources
+            .get::<Input>()
+            .was_key_just_pressed(PhysicalKey::Code(KeyCode::F9))
+            && let Err(e) = self.resources.get_mut::<FrameCapture>().toggle()
+        {
+            error!("Failed to start recording: {e:#}");
+        }
+        if self
+            .resources
+            .get::<Input>()
+            .was_key_just_pressed(PhysicalKey::Code(KeyCode::Backquote))
+        {
+            self.resources.get_mut::<DebugOverlay>().toggle_console();
+        }
+        let loading = self
+            .loading
+            .as_ref()
+            .map(|loading| loading.progress(self.resources.get::<AssetLoader>()));
+        if let Some(progress) = loading.filter(LoadProgress::is_done) {
+            info!(
+                "Startup assets ready: {} loaded, {} failed, {} KiB",
+                progress.loaded,
+                progress.failed,
+                progress.bytes / 1024
+            );
+            self.loading = None;
         }
 
         let input = self.resources.get_mut::<Input>();
         input.prepare_for_next_frame();
 
-        let end_time = std::time::Instant::now();
-        let frame_duration = end_time.duration_since(start_time);
-        let ms = frame_duration.as_secs_f64() * 1000.0;
-        let fps = if ms > 0.0 { 1000.0 / ms } else { 0.0 };
-
-        // Update title with timing info
         {
-            let window = self.resources.get_mut::<Window>();
-            window.set_title(&format!("Elements | {:>5.2} ms | {:>5.1} FPS", ms, fps));
+            let (lighting, world, camera, arena) =
+                self.resources
+                    .get_many_mut::<(Lighting, World, Camera, FrameArena)>();
+            lighting.sync(world, camera.transform.translation, arena);
+        }
+        {
+            let (render_world, camera, preview, lighting, time) =
+                self.resources
+                    .get_many_mut::<(RenderWorld, Camera, EditorPreview, Lighting, Time)>();
+            render_world.extract(camera, preview, lighting, time);
+        }
+
+        self.resources
+            .get_mut::<FrameStats>()
+            .record_update_time(update_start.elapsed());
+        let (telemetry, stats, assets, logs) =
+            self.resources
+                .get_many_mut::<(Telemetry, FrameStats, AssetLoader, LogBuffer)>();
+        telemetry.update(stats, assets, logs);
+    }
+
+    /// Draws the overlay and hands the last extracted [`RenderWorld`] to the renderer,
+    /// which presents it on its own thread. Separate from
+    /// [`Engine::on_update`] so simulation keeps ticking when the compositor holds back
+    /// redraws, e.g. for a hidden or occluded window.
+    pub fn render(&mut self) {
+        if self.is_shut_down {
+            return;
+        }
+        let renderer = self
+            .renderer
+            .as_mut()
+            .expect("Renderer must be initialized before rendering");
+        if self.resources.get::<PowerSaving>().skips_rendering() {
+            return;
+        }
+        let _span_render = info_span!("render").entered();
+        let loading = self
+            .loading
+            .as_ref()
+            .map(|loading| loading.progress(self.resources.get::<AssetLoader>()));
+        let assets = self.resources.get::<AssetLoader>().stats();
+        let mut command = None;
+        let mut edits = Vec::new();
+        if let Some(ctx) = renderer.begin_ui() {
+            {
+                let screen = ctx.screen_rect().size();
+                let visible = self.resources.get::<Billboards>().visible(
+                    self.resources.get::<World>(),
+                    self.resources.get::<Camera>(),
+                    self.resources.get::<PhysicsWorld>(),
+                    Vec2::new(screen.x, screen.y),
+                    self.resources.get::<FrameArena>(),
+                );
+                DebugOverlay::draw_billboards(&ctx, &visible);
+            }
+            let (overlay, profiler, log, stats) =
+                self.resources
+                    .get_many_mut::<(DebugOverlay, Profiler, LogBuffer, FrameStats)>();
+            overlay.draw(&ctx, &profiler.last_frame(), stats, &assets);
+            command = overlay.console.draw(&ctx, log);
+            if let Some(progress) = loading.filter(|progress| !progress.is_done()) {
+                DebugOverlay::draw_loading(&ctx, &progress);
+            }
+            if overlay.show_inspector {
+                edits = draw_inspector(&ctx, &mut self.resources, self.scene.as_ref());
+            }
+            let (preview, camera) = self.resources.get_many_mut::<(EditorPreview, Camera)>();
+            DebugOverlay::draw_preview(&ctx, preview, camera);
+        }
+        if let Some(line) = command {
+            run_console_command(renderer.as_mut(), &mut self.resources, &line);
+        }
+        if let Some(scene) = self.scene.as_mut() {
+            for edit in edits {
+                apply_inspector_edit(renderer.as_mut(), &mut self.resources, scene, edit);
+            }
+        }
+
+        if let Err(e) = renderer.on_update(&mut self.resources) {
+            error!("Renderer update error: {:?}", e);
+            panic!("Renderer update failed");
         }
     }
 }
@@ -143,3 +698,479 @@ impl Default for Engine {
         Self::new()
     }
 }
+
+/// Runs a line typed into the in-game console. Output and errors go to the log, which
+/// the console displays.
+fn run_console_command(renderer: &mut dyn Renderer, resources: &mut ResourceManager, line: &str) {
+    info!("> {line}");
+    let command = match ConsoleCommand::parse(line) {
+        Ok(command) => command,
+        Err(e) => {
+            warn!("{e}");
+            return;
+        }
+    };
+    let result = match command {
+        ConsoleCommand::Log(directives) => resources
+            .get::<LogFilter>()
+            .set(&directives)
+            .map(|()| info!("Log filter set to {directives}")),
+        ConsoleCommand::Wireframe(enabled) => {
+            let enabled = enabled.unwrap_or(!renderer.is_wireframe());
+            renderer
+                .set_wireframe(enabled)
+                .map(|()| info!("Wireframe {}", if enabled { "on" } else { "off" }))
+        }
+        ConsoleCommand::Quality(preset) => {
+            if let Some(quality) = GraphicsQuality::preset(&preset) {
+                *resources.get_mut::<GraphicsQuality>() = quality;
+            }
+            info!("Graphics quality set to {preset}");
+            Ok(())
+        }
+        ConsoleCommand::Spawn(id) => {
+            // The renderer shows one model at a time, so this also replaces the scene
+            // once the model has loaded.
+            let handle = resources.get::<AssetLoader>().load_async::<GltfModel>(&id);
+            let world = resources.get_mut::<World>();
+            let entity = world.spawn(Some(id.clone()));
+            world.insert(entity, Model { handle });
+            info!("Spawned {id} as {entity:?}");
+            Ok(())
+        }
+        ConsoleCommand::Save(path) => SaveGame::write(resources, &path),
+        ConsoleCommand::Load(path) => SaveGame::load(resources, &path),
+        ConsoleCommand::Clear => {
+            resources.get::<LogBuffer>().clear();
+            Ok(())
+        }
+        ConsoleCommand::Help => {
+            for line in CONSOLE_HELP.lines() {
+                info!("{line}");
+            }
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        warn!("{e:#}");
+    }
+}
+
+/// Sends every mesh, texture and material of `model` to the GPU and places the meshes
+/// at their nodes' world transforms. Small opaque primitives of nodes that never move
+/// are merged per material into static batches; the nodes of `node_overrides` count as
+/// moving. Nodes with a visibility or layers of their own, from the model or
+/// `node_visibility`, stay apart too, so they can be shown on their own. Returns the
+/// scene with `(node, instance)` pairs so animated nodes can move their instances.
+fn upload_model(
+    renderer: &mut dyn Renderer,
+    handle: Handle<GltfModel>,
+    model: &GltfModel,
+    node_overrides: HashMap<usize, Transform>,
+    node_visibility: HashMap<usize, Visibility>,
+) -> SceneModel {
+    let _span = info_span!("upload", subsystem = "renderer").entered();
+    let materials = upload_materials(renderer, model);
+    let mut apart = node_overrides.keys().copied().collect::<Vec<_>>();
+    apart.extend(model.nodes_with_visibility(&node_visibility));
+    let dynamic = model.dynamic_nodes(&apart);
+    // Primitives are uploaded once, the first time a node draws them on its own.
+    let mut uploaded = HashMap::new();
+    let mut batches: Vec<(MaterialHandle, MeshData)> = Vec::new();
+    let mut batched = 0;
+    let mut instances = Vec::new();
+    let world_transforms = model.world_transforms();
+    for (index, (node, transform)) in model.nodes.iter().zip(world_transforms).enumerate() {
+        let (Some(mesh_id), Some(transform)) = (node.mesh_id, transform) else {
+            continue;
+        };
+        for (primitive_index, primitive) in model.meshes[mesh_id].primitives.iter().enumerate() {
+            let (material, blend) = primitive
+                .material
+                .and_then(|material| materials.get(material))
+                .map_or((MaterialHandle::DEFAULT, false), |(handle, desc)| {
+                    (*handle, desc.blend)
+                });
+            // Blended primitives are sorted back to front one by one, so they stay apart.
+            if !dynamic[index]
+                && !blend
+                && primitive.vertices.len() <= MAX_BATCHED_PRIMITIVE_VERTICES
+            {
+                let batch = match batches.iter().rposition(|(batched, mesh)| {
+                    *batched == material
+                        && mesh.vertices.len() + primitive.vertices.len() <= MAX_BATCH_VERTICES
+                }) {
+                    Some(batch) => batch,
+                    None => {
+                        batches.push((material, MeshData::default()));
+                        batches.len() - 1
+                    }
+                };
+                batches[batch]
+                    .1
+                    .append(&primitive.vertices, &primitive.indices, transform);
+                batched += 1;
+                continue;
+            }
+            let gpu_mesh = *uploaded
+                .entry((mesh_id, primitive_index))
+                .or_insert_with(|| {
+                    renderer
+                        .upload_mesh(&primitive.vertices, &primitive.indices)
+                        .inspect_err(|e| error!("Failed to upload mesh: {:?}", e))
+                        .ok()
+                });
+            let Some(gpu_mesh) = gpu_mesh else {
+                continue;
+            };
+            match renderer.add_mesh_instance(gpu_mesh, material, transform) {
+                Ok(instance) => instances.push((index, instance)),
+                Err(e) => error!("Failed to place mesh: {:?}", e),
+            }
+        }
+    }
+    let mut batch_instances = Vec::new();
+    for (material, mesh) in &batches {
+        let placed = renderer
+            .create_mesh(mesh)
+            .and_then(|gpu_mesh| renderer.add_mesh_instance(gpu_mesh, *material, Mat4::IDENTITY));
+        match placed {
+            Ok(instance) => batch_instances.push(instance),
+            Err(e) => error!("Failed to place static batch: {:?}", e),
+        }
+    }
+    if batched > 0 {
+        debug!(
+            primitives = batched,
+            batches = batches.len(),
+            "Merged static meshes"
+        );
+    }
+    SceneModel {
+        handle,
+        instances,
+        batches: batch_instances,
+        shown: None,
+        materials,
+        node_overrides,
+        node_visibility,
+        dynamic,
+    }
+}
+
+/// Draws a cube with [`MaterialHandle::ERROR`] at the origin, in place of a model that
+/// failed to load.
+fn place_placeholder(renderer: &mut dyn Renderer) {
+    let placed = renderer
+        .create_mesh(&MeshData::cube(Vec3::ONE))
+        .and_then(|mesh| renderer.add_mesh_instance(mesh, MaterialHandle::ERROR, Mat4::IDENTITY));
+    if let Err(e) = placed {
+        error!("Failed to place placeholder: {:?}", e);
+    }
+}
+
+/// Uploads the model's textures and creates a renderer material for each glTF material,
+/// in the model's order, paired with what they were created from. Materials that fail or
+/// whose texture failed use [`MaterialHandle::ERROR`].
+fn upload_materials(renderer: &mut dyn Renderer, model: &GltfModel) -> ModelMaterials {
+    for mismatch in model.color_space_audit() {
+        let image = &model.images[mismatch.image];
+        warn!(
+            "Image {} is tagged {:?}{} but sampled as {:?} by {:?} textures",
+            mismatch.image,
+            mismatch.tagged,
+            if image.has_sidecar {
+                " by its sidecar"
+            } else {
+                ""
+            },
+            mismatch.slot.color_space(),
+            mismatch.slot
+        );
+    }
+    let (sources, uploads): (Vec<usize>, Vec<TextureUpload>) = model
+        .textures
+        .iter()
+        .enumerate()
+        .filter_map(|(index, texture)| {
+            debug!("Texture: {:?}", texture);
+            let image = &model.images[texture.image?];
+            let upload = TextureUpload {
+                pixels: &image.pixels,
+                width: image.width,
+                height: image.height,
+                mips: &image.mips,
+                block_format: image.block_format,
+                color_space: image.settings.color_space,
+                generate_mips: image.settings.generate_mips,
+                filter: (texture.sampler.mag_filter, texture.sampler.min_filter),
+                wrap: match image.settings.wrap {
+                    Some(wrap) => (wrap.into(), wrap.into()),
+                    None => (texture.sampler.wrap_s, texture.sampler.wrap_t),
+                },
+            };
+            Some((index, upload))
+        })
+        .unzip();
+    let mut texture_ids = vec![None; model.textures.len()];
+    match renderer.upload_textures(&uploads) {
+        Ok(ids) => {
+            for (source, id) in sources.into_iter().zip(ids) {
+                texture_ids[source] = Some(id);
+            }
+        }
+        Err(e) => error!("Failed to upload textures: {:?}", e),
+    }
+    model
+        .materials
+        .iter()
+        .enumerate()
+        .map(|(index, material)| {
+            let texture = material
+                .base_color_texture
+                .map(|texture| texture_ids.get(texture).copied().flatten());
+            let desc = MaterialDesc {
+                base_color_factor: material.base_color_factor,
+                base_color_texture: texture.flatten(),
+                double_sided: material.double_sided,
+                blend: material.blend,
+                alpha_cutoff: material.alpha_cutoff,
+                unlit: material.unlit,
+            };
+            if texture == Some(None) {
+                warn!("Texture of material {index} is missing, using the error material");
+                return (MaterialHandle::ERROR, desc);
+            }
+            let handle = renderer
+                .create_material(&desc)
+                .inspect_err(|e| error!("Failed to create material: {:?}", e))
+                .unwrap_or(MaterialHandle::ERROR);
+            (handle, desc)
+        })
+        .collect()
+}
+
+/// Draws the inspector panel over the scene and returns the edits made in it.
+fn draw_inspector(
+    ctx: &egui::Context,
+    resources: &mut ResourceManager,
+    scene: Option<&SceneModel>,
+) -> Vec<InspectorEdit> {
+    let mut names = resources.type_names().collect::<Vec<_>>();
+    names.sort_unstable();
+    let (overlay, world, asset_loader) =
+        resources.get_many_mut::<(DebugOverlay, World, AssetLoader)>();
+    let model = scene.and_then(|scene| Some((scene, asset_loader.asset(&scene.handle)?)));
+    let model = model.as_ref().map(|(scene, model)| (scene, model.read()));
+    let view = model.as_ref().map(|(scene, model)| SceneView {
+        model,
+        node_overrides: &scene.node_overrides,
+        node_visibility: &scene.node_visibility,
+        materials: &scene.materials,
+    });
+    overlay.draw_inspector(ctx, world, view, &names)
+}
+
+/// Selects the entity under the cursor in the inspector when the scene is clicked. Hits
+/// colliders and the bounds of loaded models; clicks on empty space keep the selection.
+fn pick_entity(resources: &mut ResourceManager) {
+    let input = resources.get::<Input>();
+    if !input.was_mouse_button_just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(ray) = input.mouse_position().and_then(|cursor| {
+        resources
+            .get::<Camera>()
+            .viewport_to_world_ray(cursor, resources.get::<Window>())
+    }) else {
+        return;
+    };
+    let collider = resources.get::<PhysicsWorld>().raycast(&ray);
+    let model = raycast_meshes(
+        resources.get::<World>(),
+        resources.get::<AssetLoader>(),
+        &ray,
+    );
+    if let Some(hit) = collider
+        .into_iter()
+        .chain(model)
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    {
+        resources
+            .get_mut::<DebugOverlay>()
+            .inspector
+            .select(Some(Selection::Entity(hit.entity)));
+    }
+}
+
+/// Applies an inspector edit to the scene model and the renderer.
+fn apply_inspector_edit(
+    renderer: &mut dyn Renderer,
+    resources: &mut ResourceManager,
+    scene: &mut SceneModel,
+    edit: InspectorEdit,
+) {
+    match edit {
+        InspectorEdit::NodeTransform(node, transform) => {
+            scene.node_overrides.insert(node, transform);
+            let (asset_loader, player, arena, world) =
+                resources.get_many_mut::<(AssetLoader, AnimationPlayer, FrameArena, World)>();
+            if let Some(model) = asset_loader.asset(&scene.handle) {
+                let model = model.read();
+                if !scene.dynamic.get(node).copied().unwrap_or(true) {
+                    reupload_scene(renderer, scene, &model);
+                    show_scene(renderer, world, asset_loader, scene);
+                }
+                let local = player.local_transforms_in(&model, arena);
+                place_scene(renderer, scene, &model, local, arena);
+            }
+        }
+        InspectorEdit::NodeVisibility(node, visibility) => {
+            scene.node_visibility.insert(node, visibility);
+            let (asset_loader, player, arena, world) =
+                resources.get_many_mut::<(AssetLoader, AnimationPlayer, FrameArena, World)>();
+            if let Some(model) = asset_loader.asset(&scene.handle) {
+                let model = model.read();
+                if !scene.dynamic.get(node).copied().unwrap_or(true) {
+                    reupload_scene(renderer, scene, &model);
+                    let local = player.local_transforms_in(&model, arena);
+                    place_scene(renderer, scene, &model, local, arena);
+                }
+            }
+            scene.shown = None;
+            show_scene(renderer, world, asset_loader, scene);
+        }
+        InspectorEdit::Material(index, desc) => {
+            let Some((handle, current)) = scene.materials.get_mut(index) else {
+                return;
+            };
+            match renderer.update_material(*handle, &desc) {
+                Ok(()) => *current = desc,
+                Err(e) => error!("Failed to update material: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Uploads the scene again after an edit moved a node out of the static batches, then
+/// restores the materials edited so far.
+fn reupload_scene(renderer: &mut dyn Renderer, scene: &mut SceneModel, model: &GltfModel) {
+    if let Err(e) = renderer.clear_scene() {
+        error!("Failed to clear scene: {:?}", e);
+    }
+    let edited = std::mem::take(&mut scene.materials);
+    let overrides = std::mem::take(&mut scene.node_overrides);
+    let visibility = std::mem::take(&mut scene.node_visibility);
+    *scene = upload_model(renderer, scene.handle.clone(), model, overrides, visibility);
+    for ((handle, current), (_, desc)) in scene.materials.iter_mut().zip(edited) {
+        if *current != desc {
+            match renderer.update_material(*handle, &desc) {
+                Ok(()) => *current = desc,
+                Err(e) => error!("Failed to update material: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Advances the animation player and moves the scene's mesh instances to the new pose.
+fn animate_scene(renderer: &mut dyn Renderer, resources: &mut ResourceManager, scene: &SceneModel) {
+    let delta = resources.get::<Time>().delta_secs();
+    let (asset_loader, player, arena) =
+        resources.get_many_mut::<(AssetLoader, AnimationPlayer, FrameArena)>();
+    if !player.is_playing() {
+        return;
+    }
+    let Some(model) = asset_loader.asset(&scene.handle) else {
+        return;
+    };
+    let model = model.read();
+    player.advance(delta, &model);
+    let local = player.local_transforms_in(&model, arena);
+    place_scene(renderer, scene, &model, local, arena);
+}
+
+/// Applies the [`Visibility`] and [`RenderLayers`] of the entity carrying the scene's
+/// [`Model`] to the instances of the scene, combined with those of their nodes. A scene
+/// without such an entity, e.g. the configured startup one, is visible on layer 0.
+/// Static batches only hold nodes without their own, so they follow the entity.
+fn show_scene(
+    renderer: &mut dyn Renderer,
+    world: &World,
+    asset_loader: &AssetLoader,
+    scene: &mut SceneModel,
+) {
+    let owner = world
+        .query::<Model>()
+        .find(|(_, model)| model.handle.id() == scene.handle.id())
+        .map(|(entity, _)| entity);
+    let shown = owner.map_or((true, RenderLayers::default()), |entity| {
+        (
+            world.is_visible(entity),
+            world
+                .get::<RenderLayers>(entity)
+                .copied()
+                .unwrap_or_default(),
+        )
+    });
+    if scene.shown == Some(shown) {
+        return;
+    }
+    let Some(model) = asset_loader.asset(&scene.handle) else {
+        return;
+    };
+    scene.shown = Some(shown);
+    let nodes = model
+        .read()
+        .node_visibility(shown.0, shown.1, &scene.node_visibility);
+    let instances = scene
+        .instances
+        .iter()
+        .map(|&(node, instance)| (instance, nodes.get(node).copied().unwrap_or(shown)));
+    let batches = scene.batches.iter().map(|&instance| (instance, shown));
+    for (instance, (visible, layers)) in instances.chain(batches) {
+        if let Err(e) = renderer.set_mesh_instance_visibility(instance, visible, layers) {
+            error!("Failed to show mesh instance: {:?}", e);
+        }
+    }
+}
+
+/// Moves the scene's mesh instances to the pose given by the nodes' `local` transforms,
+/// with the inspector's overrides applied on top.
+fn place_scene(
+    renderer: &mut dyn Renderer,
+    scene: &SceneModel,
+    model: &GltfModel,
+    local: &mut [Mat4],
+    arena: &FrameArena,
+) {
+    for (&node, transform) in &scene.node_overrides {
+        if let Some(local) = local.get_mut(node) {
+            *local = transform.compute_matrix();
+        }
+    }
+    let world = model.world_transforms_in(local, arena);
+    for &(node, instance) in &scene.instances {
+        if let Some(transform) = world[node]
+            && let Err(e) = renderer.set_mesh_instance_transform(instance, transform)
+        {
+            error!("Failed to update animated mesh: {:?}", e);
+        }
+    }
+}
+
+/// Rebuilds the mesh pipeline once both shader overrides are loaded.
+fn reload_shaders(
+    renderer: &mut dyn Renderer,
+    asset_loader: &AssetLoader,
+    shaders: &[Handle<SpirvShader>],
+) {
+    let [vertex, fragment] = shaders else {
+        return;
+    };
+    if let (Some(vertex), Some(fragment)) =
+        (asset_loader.asset(vertex), asset_loader.asset(fragment))
+        && let Err(e) = renderer.reload_shaders(&vertex.read().words, &fragment.read().words)
+    {
+        error!("Failed to reload shaders: {:?}", e);
+    }
+}
@@ -0,0 +1,200 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, OnceLock};
+
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use tracing::warn;
+
+type PendingEvent = Box<dyn FnOnce(&mut Events) + Send>;
+type WakeFn = Box<dyn Fn() + Send + Sync>;
+
+/// Kept on the heap rather than in the [`FrameArena`](crate::frame_arena::FrameArena):
+/// events stay readable for two updates, and the arena is reset after one.
+struct EventQueue<E> {
+    /// Events with their sequence number and the update they were sent in.
+    events: Vec<(u64, u64, E)>,
+}
+
+/// Typed event bus shared by engine systems and game code.
+///
+/// Events stay readable for the update they were sent in and the one after, so a system
+/// running early in the frame still sees what a later system sent the frame before.
+/// Use an [`EventReader`] to see each event exactly once.
+pub struct Events {
+    queues: HashMap<TypeId, Box<dyn ErasedQueue>>,
+    next_id: u64,
+    update: u64,
+    sender: Sender<PendingEvent>,
+    receiver: Receiver<PendingEvent>,
+    waker: Arc<OnceLock<WakeFn>>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Events {
+            queues: HashMap::new(),
+            next_id: 0,
+            update: 0,
+            sender,
+            receiver,
+            waker: Arc::default(),
+        }
+    }
+
+    pub fn send<E: 'static>(&mut self, event: E) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let update = self.update;
+        self.queue_mut::<E>().events.push((id, update, event));
+    }
+
+    /// A handle that lets other threads send events. They are delivered on the next
+    /// [`Events::update`].
+    pub fn sender(&self) -> EventSender {
+        EventSender {
+            sender: self.sender.clone(),
+            waker: self.waker.clone(),
+        }
+    }
+
+    /// Called after every [`EventSender::send`], so the platform can wake an event loop
+    /// that is sleeping in a non-continuous [`UpdateMode`](crate::engine::UpdateMode).
+    /// Can only be set once.
+    pub fn set_waker(&self, waker: impl Fn() + Send + Sync + 'static) {
+        if self.waker.set(Box::new(waker)).is_err() {
+            warn!("Event bus waker is already set");
+        }
+    }
+
+    /// Advances to the next frame: drops events older than the previous update and
+    /// delivers everything sent from other threads since the last call.
+    pub fn update(&mut self) {
+        self.update += 1;
+        let oldest = self.update.saturating_sub(1);
+        for queue in self.queues.values_mut() {
+            queue.retain_since(oldest);
+        }
+        while let Ok(pending) = self.receiver.try_recv() {
+            pending(self);
+        }
+    }
+
+    /// Every event of type `E` that is still retained, oldest first.
+    pub fn iter<E: 'static>(&self) -> impl Iterator<Item = &E> {
+        self.queue::<E>()
+            .into_iter()
+            .flat_map(|queue| queue.events.iter().map(|(_, _, event)| event))
+    }
+
+    fn queue<E: 'static>(&self) -> Option<&EventQueue<E>> {
+        self.queues
+            .get(&TypeId::of::<E>())
+            .and_then(|queue| queue.as_any().downcast_ref::<EventQueue<E>>())
+    }
+
+    fn queue_mut<E: 'static>(&mut self) -> &mut EventQueue<E> {
+        self.queues
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(EventQueue::<E> { events: Vec::new() }))
+            .as_any_mut()
+            .downcast_mut::<EventQueue<E>>()
+            .expect("Event queue stored under the wrong type")
+    }
+}
+
+/// Type-erased access to [`EventQueue::events`] for [`Events::update`].
+trait ErasedQueue {
+    fn retain_since(&mut self, update: u64);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<E: 'static> ErasedQueue for EventQueue<E> {
+    fn retain_since(&mut self, update: u64) {
+        self.events.retain(|(_, sent, _)| *sent >= update);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Thread-safe handle for sending events into [`Events`] from background work.
+#[derive(Clone)]
+pub struct EventSender {
+    sender: Sender<PendingEvent>,
+    waker: Arc<OnceLock<WakeFn>>,
+}
+
+impl EventSender {
+    /// Queues `event` and wakes the event loop so it is delivered promptly.
+    pub fn send<E: Send + 'static>(&self, event: E) {
+        if self
+            .sender
+            .send(Box::new(move |events: &mut Events| events.send(event)))
+            .is_err()
+        {
+            warn!("Event bus is gone, dropping {}", std::any::type_name::<E>());
+            return;
+        }
+        if let Some(wake) = self.waker.get() {
+            wake();
+        }
+    }
+}
+
+/// Tracks which events of type `E` a consumer has already seen.
+pub struct EventReader<E> {
+    last_read: Option<u64>,
+    _marker: PhantomData<fn() -> E>,
+}
+
+impl<E: 'static> EventReader<E> {
+    pub fn new() -> Self {
+        EventReader {
+            last_read: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Events sent since the previous call. Events that expired in between are missed.
+    pub fn read<'a>(&mut self, events: &'a Events) -> impl Iterator<Item = &'a E> + use<'a, E> {
+        let last_read = self.last_read;
+        let unread = events
+            .queue::<E>()
+            .map(|queue| queue.events.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(move |(id, _, _)| last_read.is_none_or(|last| *id > last));
+        if let Some((id, _, _)) = unread.clone().last() {
+            self.last_read = Some(*id);
+        }
+        unread.map(|(_, _, event)| event)
+    }
+}
+
+impl<E: 'static> Default for EventReader<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for Events {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Events::new())
+    }
+}
@@ -0,0 +1,73 @@
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use bumpalo::Bump;
+
+/// Growable vector living in a [`FrameArena`].
+pub type ArenaVec<'a, T> = bumpalo::collections::Vec<'a, T>;
+
+/// Bump allocator for data that lives no longer than one frame, e.g. animation poses, the
+/// lights ranked during render extraction and the billboards placed on screen. Allocating
+/// is a pointer bump and the engine frees everything at once at the start of each update,
+/// so the memory is reused frame after frame instead of going back to the heap.
+///
+/// Values put in the arena directly with [`FrameArena::alloc`] are never dropped, so only
+/// store plain data there; an [`ArenaVec`] drops its elements as usual.
+///
+/// ```ignore
+/// let arena = resources.get::<FrameArena>();
+/// let mut nearby = arena.vec();
+/// nearby.extend(world.query::<Enemy>().filter(|(_, enemy)| enemy.alert));
+/// ```
+#[derive(Default)]
+pub struct FrameArena {
+    bump: Bump,
+}
+
+impl FrameArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        self.bump.alloc(value)
+    }
+
+    pub fn vec<T>(&self) -> ArenaVec<'_, T> {
+        ArenaVec::new_in(&self.bump)
+    }
+
+    pub fn vec_with_capacity<T>(&self, capacity: usize) -> ArenaVec<'_, T> {
+        ArenaVec::with_capacity_in(capacity, &self.bump)
+    }
+
+    /// A slice of every item of `iter`, which must report its exact length.
+    pub fn alloc_slice<T, I>(&self, iter: I) -> &mut [T]
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        self.bump.alloc_slice_fill_iter(iter)
+    }
+
+    /// The underlying allocator, for `bumpalo` collections such as strings.
+    pub fn bump(&self) -> &Bump {
+        &self.bump
+    }
+
+    /// Memory the arena holds. It stops growing once it fits a whole frame's allocations.
+    pub fn allocated_bytes(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+
+    /// Frees everything allocated so far. The engine calls this at the start of every
+    /// update; the borrow checker makes sure nothing still points into the arena.
+    pub fn reset(&mut self) {
+        self.bump.reset();
+    }
+}
+
+impl FromResources for FrameArena {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(FrameArena::new())
+    }
+}
@@ -0,0 +1,182 @@
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of frames kept for FPS averages and percentile lows.
+const HISTORY_LEN: usize = 300;
+
+/// Per-frame timing and rendering counters, updated by the engine and renderer every frame.
+/// Read-only consumers (overlay, logging, telemetry) pull from here
+/// instead of measuring on their own.
+pub struct FrameStats {
+    last_frame_start: Option<Instant>,
+    frame_time: Duration,
+    history: VecDeque<Duration>,
+    update_time: Duration,
+    render_time: Duration,
+    gpu_time: Option<Duration>,
+    draw_calls: u32,
+    triangles: u64,
+    culled: u32,
+    gpu_memory_used: u64,
+    gpu_memory_total: u64,
+    frame_count: u64,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        FrameStats {
+            last_frame_start: None,
+            frame_time: Duration::ZERO,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            update_time: Duration::ZERO,
+            render_time: Duration::ZERO,
+            gpu_time: None,
+            draw_calls: 0,
+            triangles: 0,
+            culled: 0,
+            gpu_memory_used: 0,
+            gpu_memory_total: 0,
+            frame_count: 0,
+        }
+    }
+
+    /// Marks the start of a new frame; the frame time is the wall-clock time since the
+    /// previous call.
+    pub fn begin_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame_start {
+            self.frame_time = now.duration_since(last);
+            if self.history.len() == HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(self.frame_time);
+        }
+        self.last_frame_start = Some(now);
+        self.frame_count += 1;
+    }
+
+    /// Records the draw work of the last frame the renderer submitted. Kept until the next
+    /// submitted frame, so ticks without a redraw don't reset it. `culled` counts the mesh
+    /// instances skipped for being outside the view frustum.
+    pub fn record_draws(&mut self, draw_calls: u32, triangles: u64, culled: u32) {
+        self.draw_calls = draw_calls;
+        self.triangles = triangles;
+        self.culled = culled;
+    }
+
+    /// CPU time spent in the last simulation tick.
+    pub fn record_update_time(&mut self, time: Duration) {
+        self.update_time = time;
+    }
+
+    /// CPU time the renderer spent recording and submitting the last frame, not counting
+    /// waits on the swapchain or the GPU.
+    pub fn record_render_time(&mut self, time: Duration) {
+        self.render_time = time;
+    }
+
+    /// GPU time of the scene pass of a recently finished frame, read back from timestamp
+    /// queries.
+    pub fn record_gpu_time(&mut self, time: Duration) {
+        self.gpu_time = Some(time);
+    }
+
+    /// Bytes of GPU memory held by the renderer, and the size of the device-local heaps.
+    pub fn record_gpu_memory(&mut self, used: u64, total: u64) {
+        self.gpu_memory_used = used;
+        self.gpu_memory_total = total;
+    }
+
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    pub fn frame_time_ms(&self) -> f64 {
+        self.frame_time.as_secs_f64() * 1000.0
+    }
+
+    /// Average FPS over the recent history.
+    pub fn fps(&self) -> f64 {
+        let total: Duration = self.history.iter().sum();
+        if total.is_zero() {
+            return 0.0;
+        }
+        self.history.len() as f64 / total.as_secs_f64()
+    }
+
+    /// FPS of the slowest 1% of recent frames.
+    pub fn one_percent_low_fps(&self) -> f64 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        let mut sorted: Vec<Duration> = self.history.iter().copied().collect();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        let count = (sorted.len() / 100).max(1);
+        let slowest: Duration = sorted[..count].iter().sum();
+        let average = slowest.as_secs_f64() / count as f64;
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+
+    /// CPU time of the last update plus render.
+    pub fn cpu_time(&self) -> Duration {
+        self.update_time + self.render_time
+    }
+
+    pub fn update_time(&self) -> Duration {
+        self.update_time
+    }
+
+    pub fn render_time(&self) -> Duration {
+        self.render_time
+    }
+
+    /// `None` until the first timestamp query was read back, or if the graphics queue
+    /// doesn't support timestamps.
+    pub fn gpu_time(&self) -> Option<Duration> {
+        self.gpu_time
+    }
+
+    pub fn draw_calls(&self) -> u32 {
+        self.draw_calls
+    }
+
+    pub fn triangles(&self) -> u64 {
+        self.triangles
+    }
+
+    /// Mesh instances frustum-culled in the last submitted frame.
+    pub fn culled(&self) -> u32 {
+        self.culled
+    }
+
+    pub fn gpu_memory_used(&self) -> u64 {
+        self.gpu_memory_used
+    }
+
+    pub fn gpu_memory_total(&self) -> u64 {
+        self.gpu_memory_total
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Recent frame times, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.history.iter().copied()
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for FrameStats {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(FrameStats::new())
+    }
+}
@@ -0,0 +1,238 @@
+use crate::resource_manager::ResourceManager;
+use anyhow::{Context, Result, anyhow};
+use libloading::{Library, Symbol};
+use std::ffi::c_void;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{error, info, warn};
+
+/// Bumped whenever [`GameModuleApi`] changes layout. Modules built against another version
+/// are refused instead of crashing.
+pub const GAME_MODULE_ABI_VERSION: u32 = 1;
+
+/// Name of the function every game module exports. Use [`export_game_module!`] rather
+/// than writing it by hand.
+pub const GAME_MODULE_ENTRY_POINT: &[u8] = b"elements_game_module";
+
+/// How often the module file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// C-ABI function table returned by a game module's entry point.
+///
+/// Every callback receives an opaque pointer to the engine's [`ResourceManager`]; turn it
+/// back into a reference with [`resources_from_raw`]. The resource manager is owned by
+/// the engine, so anything stored in it survives a reload. Resources whose *type* is
+/// defined inside the game module must be removed in `unload`, since their drop code goes
+/// away with the old library.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GameModuleApi {
+    pub abi_version: u32,
+    /// Called after the module is (re)loaded.
+    pub load: extern "C" fn(resources: *mut c_void),
+    /// Called once per frame with the frame delta in seconds.
+    pub update: extern "C" fn(resources: *mut c_void, delta_seconds: f32),
+    /// Called before the module is unloaded or replaced.
+    pub unload: extern "C" fn(resources: *mut c_void),
+}
+
+/// Recovers the resource manager passed to a [`GameModuleApi`] callback.
+///
+/// # Safety
+/// `resources` must be the pointer the engine passed to the callback, and the returned
+/// reference must not outlive that call.
+pub unsafe fn resources_from_raw<'a>(resources: *mut c_void) -> &'a mut ResourceManager {
+    unsafe { &mut *(resources as *mut ResourceManager) }
+}
+
+/// Exports the entry point of a game module built as a `cdylib`.
+///
+/// ```ignore
+/// fn load(resources: &mut ResourceManager) {}
+/// fn update(resources: &mut ResourceManager, dt: f32) {}
+/// fn unload(resources: &mut ResourceManager) {}
+///
+/// elements_engine::export_game_module!(load, update, unload);
+/// ```
+#[macro_export]
+macro_rules! export_game_module {
+    ($load:path, $update:path, $unload:path) => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn elements_game_module() -> $crate::game_module::GameModuleApi {
+            extern "C" fn load(resources: *mut ::std::ffi::c_void) {
+                $load(unsafe { $crate::game_module::resources_from_raw(resources) });
+            }
+            extern "C" fn update(resources: *mut ::std::ffi::c_void, delta_seconds: f32) {
+                $update(
+                    unsafe { $crate::game_module::resources_from_raw(resources) },
+                    delta_seconds,
+                );
+            }
+            extern "C" fn unload(resources: *mut ::std::ffi::c_void) {
+                $unload(unsafe { $crate::game_module::resources_from_raw(resources) });
+            }
+            $crate::game_module::GameModuleApi {
+                abi_version: $crate::game_module::GAME_MODULE_ABI_VERSION,
+                load,
+                update,
+                unload,
+            }
+        }
+    };
+}
+
+struct LoadedModule {
+    api: GameModuleApi,
+    // Must outlive every call through `api`.
+    _library: Library,
+    copy_path: PathBuf,
+}
+
+/// Game logic loaded from a dynamic library and reloaded whenever the file changes.
+pub struct GameModule {
+    source: PathBuf,
+    loaded: Option<LoadedModule>,
+    /// Modification time of the file last tried, whether it loaded or not.
+    last_modified: Option<SystemTime>,
+    last_poll: Option<Instant>,
+    generation: u32,
+}
+
+impl GameModule {
+    pub fn new(source: impl Into<PathBuf>) -> Self {
+        GameModule {
+            source: source.into(),
+            loaded: None,
+            last_modified: None,
+            last_poll: None,
+            generation: 0,
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.is_some()
+    }
+
+    /// Reloads the module if its file changed since the last attempt (or it was never
+    /// tried). A file that failed to load isn't retried until it changes again.
+    pub fn reload_if_changed(&mut self, resources: &mut ResourceManager) {
+        if self
+            .last_poll
+            .is_some_and(|last_poll| last_poll.elapsed() < POLL_INTERVAL)
+        {
+            return;
+        }
+        self.last_poll = Some(Instant::now());
+
+        let Ok(modified) = fs::metadata(&self.source).and_then(|m| m.modified()) else {
+            return;
+        };
+        if self.last_modified == Some(modified) {
+            return;
+        }
+
+        if self.loaded.is_some() {
+            info!("Game module {} changed; reloading", self.source.display());
+        }
+        self.last_modified = Some(modified);
+        self.unload(resources);
+        if let Err(err) = self.load(resources) {
+            // A half-written file gets a newer modification time once the compiler is
+            // done, so it's retried then.
+            error!("Failed to load game module: {:?}", err);
+        }
+    }
+
+    pub fn update(&mut self, resources: &mut ResourceManager, delta_seconds: f32) {
+        if let Some(module) = &self.loaded {
+            (module.api.update)(
+                resources as *mut ResourceManager as *mut c_void,
+                delta_seconds,
+            );
+        }
+    }
+
+    pub fn unload(&mut self, resources: &mut ResourceManager) {
+        if let Some(module) = self.loaded.take() {
+            (module.api.unload)(resources as *mut ResourceManager as *mut c_void);
+            let copy_path = module.copy_path.clone();
+            drop(module);
+            if let Err(err) = fs::remove_file(&copy_path) {
+                warn!("Failed to remove {}: {err}", copy_path.display());
+            }
+        }
+    }
+
+    fn load(&mut self, resources: &mut ResourceManager) -> Result<()> {
+        // Load a copy so the build can overwrite the original while it is in use (Windows
+        // locks loaded DLLs).
+        self.generation += 1;
+        let copy_path = shadow_copy_path(&self.source, self.generation);
+        let opened = fs::copy(&self.source, &copy_path)
+            .with_context(|| format!("Failed to copy {}", self.source.display()))
+            .and_then(|_| open_library(&copy_path));
+        let (api, library) = match opened {
+            Ok(opened) => opened,
+            Err(err) => {
+                // The library, if any, was closed on the way out, so the copy can go.
+                if copy_path.exists()
+                    && let Err(remove_err) = fs::remove_file(&copy_path)
+                {
+                    warn!("Failed to remove {}: {remove_err}", copy_path.display());
+                }
+                return Err(err);
+            }
+        };
+
+        (api.load)(resources as *mut ResourceManager as *mut c_void);
+        self.loaded = Some(LoadedModule {
+            api,
+            _library: library,
+            copy_path,
+        });
+        info!(
+            "Loaded game module {} (generation {})",
+            self.source.display(),
+            self.generation
+        );
+        Ok(())
+    }
+}
+
+/// Loads the library at `path` and checks its entry point and ABI version.
+fn open_library(path: &Path) -> Result<(GameModuleApi, Library)> {
+    // SAFETY: loading arbitrary code is inherently unsafe; game modules are trusted and
+    // must be built with the same compiler and engine version as the host.
+    let library = unsafe { Library::new(path) }
+        .with_context(|| format!("Failed to load {}", path.display()))?;
+    let api = unsafe {
+        let entry: Symbol<extern "C" fn() -> GameModuleApi> = library
+            .get(GAME_MODULE_ENTRY_POINT)
+            .with_context(|| "Game module has no `elements_game_module` entry point")?;
+        entry()
+    };
+    if api.abi_version != GAME_MODULE_ABI_VERSION {
+        return Err(anyhow!(
+            "Game module ABI version {} does not match engine version {}",
+            api.abi_version,
+            GAME_MODULE_ABI_VERSION
+        ));
+    }
+    Ok((api, library))
+}
+
+fn shadow_copy_path(source: &Path, generation: u32) -> PathBuf {
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "game".to_string());
+    let extension = source
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    std::env::temp_dir().join(format!(
+        "{stem}-{}-{generation}{extension}",
+        std::process::id()
+    ))
+}
@@ -0,0 +1,160 @@
+use crate::events::Events;
+use crate::input::Input;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+
+/// Index of a connected gamepad. Stays the same while it's connected; a pad plugged back
+/// in may get a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GamepadId(pub usize);
+
+/// A gamepad button, named by position on a standard layout: `South` is A on an Xbox
+/// pad and Cross on a PlayStation one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    LeftTrigger,
+    RightBumper,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    /// Pressing the left stick in.
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+/// Sent on the event bus when a gamepad is plugged in or out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+}
+
+/// Reads gamepads into [`Input`] every tick, before [`Gestures`](crate::input::Gestures)
+/// are detected. Needs the `gamepad` feature, which links `libudev` on Linux; without it
+/// no gamepad ever connects.
+pub struct Gamepads {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Gamepads {
+    #[cfg(feature = "gamepad")]
+    pub fn new() -> Self {
+        let gilrs = gilrs::Gilrs::new()
+            .inspect_err(|e| tracing::warn!("Gamepads unavailable: {e}"))
+            .ok();
+        Gamepads { gilrs }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn new() -> Self {
+        Gamepads {}
+    }
+
+    /// Applies the gamepad events since the last poll to `input`, and sends a
+    /// [`GamepadEvent`] for each pad plugged in or out.
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self, input: &mut Input, events: &mut Events) {
+        use gilrs::{Axis, EventType};
+
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return;
+        };
+        while let Some(event) = gilrs.next_event() {
+            let gamepad = GamepadId(event.id.into());
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        input.handle_gamepad_button(gamepad, button, true);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        input.handle_gamepad_button(gamepad, button, false);
+                    }
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    let (stick, axis) = match axis {
+                        Axis::LeftStickX => (Stick::Left, 0),
+                        Axis::LeftStickY => (Stick::Left, 1),
+                        Axis::RightStickX => (Stick::Right, 0),
+                        Axis::RightStickY => (Stick::Right, 1),
+                        _ => continue,
+                    };
+                    input.handle_gamepad_axis(gamepad, stick, axis, value);
+                }
+                EventType::Connected => {
+                    tracing::info!(
+                        "Gamepad {} connected: {}",
+                        gamepad.0,
+                        gilrs.gamepad(event.id).name()
+                    );
+                    events.send(GamepadEvent::Connected(gamepad));
+                }
+                EventType::Disconnected => {
+                    tracing::info!("Gamepad {} disconnected", gamepad.0);
+                    input.remove_gamepad(gamepad);
+                    events.send(GamepadEvent::Disconnected(gamepad));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self, _input: &mut Input, _events: &mut Events) {}
+}
+
+#[cfg(feature = "gamepad")]
+fn map_button(button: gilrs::Button) -> Option<GamepadButton> {
+    use gilrs::Button;
+
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::RightTrigger2 => GamepadButton::RightTrigger,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::Mode => GamepadButton::Mode,
+        Button::LeftThumb => GamepadButton::LeftThumb,
+        Button::RightThumb => GamepadButton::RightThumb,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for Gamepads {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Gamepads::new())
+    }
+}
@@ -0,0 +1,186 @@
+use crate::events::Events;
+use crate::input::{Button, Input, Stick};
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use glam::Vec2;
+use std::time::Duration;
+use winit::event::MouseButton;
+
+/// A timing pattern on a button, the mouse or a gamepad stick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// Two presses with at most `window` between them.
+    DoubleTap { button: Button, window: Duration },
+    /// Held down for `duration`. Fires once per press, while still held.
+    Hold { button: Button, duration: Duration },
+    /// A quick drag: `button` pressed, the mouse moved at least `min_distance` pixels,
+    /// and released within `max_duration`.
+    MouseFlick {
+        button: MouseButton,
+        min_distance: f32,
+        max_duration: Duration,
+    },
+    /// A quick push of a gamepad stick: from rest to at least `min_magnitude` (0 to 1)
+    /// within `max_duration`. Fires once, then the stick has to return to rest.
+    Flick {
+        stick: Stick,
+        min_magnitude: f32,
+        max_duration: Duration,
+    },
+}
+
+/// Stick deflection below which a stick counts as at rest for [`Gesture::Flick`].
+pub const STICK_REST: f32 = 0.2;
+
+/// Sent on the event bus when a gesture added to [`Gestures`] completes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GestureEvent {
+    /// Name the gesture was added under.
+    pub name: String,
+    /// Unit direction of a flick: in window space with y pointing down for
+    /// [`Gesture::MouseFlick`], in stick space with y pointing up for [`Gesture::Flick`].
+    pub direction: Option<Vec2>,
+}
+
+/// Progress of one gesture, in [`Time::elapsed`](crate::time::Time::elapsed) terms.
+#[derive(Debug, Default)]
+struct Tracker {
+    /// Last press, for double taps; start of the current press, for holds and flicks.
+    pressed_at: Option<Duration>,
+    /// A hold already fired for the current press, or a stick flick since the stick left rest.
+    fired: bool,
+    /// Mouse movement since a flick's press.
+    drag: Vec2,
+}
+
+/// Turns raw [`Input`] into named gestures, so gameplay code reacts to a double tap or
+/// a flick instead of tracking timings itself. The engine updates it every tick with
+/// simulation time, so gestures replay the same in a deterministic tick mode.
+///
+/// ```ignore
+/// resources.get_mut::<Gestures>().add(
+///     "dodge",
+///     Gesture::DoubleTap {
+///         button: Button::Key(KeyCode::ShiftLeft.into()),
+///         window: Duration::from_millis(250),
+///     },
+/// );
+/// ```
+#[derive(Default)]
+pub struct Gestures {
+    gestures: Vec<(String, Gesture, Tracker)>,
+}
+
+impl Gestures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts detecting `gesture`, replacing one already added under `name`.
+    pub fn add(&mut self, name: impl Into<String>, gesture: Gesture) {
+        let name = name.into();
+        self.remove(&name);
+        self.gestures.push((name, gesture, Tracker::default()));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.gestures.retain(|(existing, _, _)| existing != name);
+    }
+
+    /// Advances every gesture with this tick's input and sends a [`GestureEvent`] for
+    /// each one that completed. `now` is the elapsed simulation time.
+    pub fn update(&mut self, input: &Input, now: Duration, events: &mut Events) {
+        for (name, gesture, tracker) in &mut self.gestures {
+            let direction = match *gesture {
+                Gesture::DoubleTap { button, window } => {
+                    if !input.was_just_pressed(button) {
+                        continue;
+                    }
+                    match tracker.pressed_at {
+                        Some(last) if now.saturating_sub(last) <= window => {
+                            tracker.pressed_at = None;
+                            None
+                        }
+                        _ => {
+                            tracker.pressed_at = Some(now);
+                            continue;
+                        }
+                    }
+                }
+                Gesture::Hold { button, duration } => {
+                    if input.was_just_pressed(button) {
+                        tracker.pressed_at = Some(now);
+                        tracker.fired = false;
+                    }
+                    if !input.is_pressed(button) {
+                        tracker.pressed_at = None;
+                        continue;
+                    }
+                    match tracker.pressed_at {
+                        Some(start) if !tracker.fired && now.saturating_sub(start) >= duration => {
+                            tracker.fired = true;
+                            None
+                        }
+                        _ => continue,
+                    }
+                }
+                Gesture::MouseFlick {
+                    button,
+                    min_distance,
+                    max_duration,
+                } => {
+                    if input.was_mouse_button_just_pressed(button) {
+                        tracker.pressed_at = Some(now);
+                        tracker.drag = Vec2::ZERO;
+                    } else if input.is_mouse_button_pressed(button) {
+                        tracker.drag += input.mouse_delta();
+                    }
+                    if !input.was_mouse_button_just_released(button) {
+                        continue;
+                    }
+                    tracker.drag += input.mouse_delta();
+                    let Some(start) = tracker.pressed_at.take() else {
+                        continue;
+                    };
+                    if now.saturating_sub(start) > max_duration
+                        || tracker.drag.length() < min_distance
+                    {
+                        continue;
+                    }
+                    Some(tracker.drag.normalize_or_zero())
+                }
+                Gesture::Flick {
+                    stick,
+                    min_magnitude,
+                    max_duration,
+                } => {
+                    let position = input.stick(stick);
+                    if position.length() < STICK_REST {
+                        tracker.pressed_at = None;
+                        tracker.fired = false;
+                        continue;
+                    }
+                    let start = *tracker.pressed_at.get_or_insert(now);
+                    if tracker.fired
+                        || now.saturating_sub(start) > max_duration
+                        || position.length() < min_magnitude
+                    {
+                        continue;
+                    }
+                    tracker.fired = true;
+                    Some(position.normalize_or_zero())
+                }
+            };
+            events.send(GestureEvent {
+                name: name.clone(),
+                direction,
+            });
+        }
+    }
+}
+
+impl FromResources for Gestures {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Gestures::new())
+    }
+}
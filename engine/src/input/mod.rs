@@ -1,19 +1,48 @@
-use std::collections::HashSet;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use glam::Vec2;
+use std::collections::{HashMap, HashSet};
 
 use tracing::debug;
 use winit::{
     dpi::PhysicalPosition,
-    event::{DeviceId, ElementState, KeyEvent, MouseButton},
+    event::{DeviceId, ElementState, KeyEvent, MouseButton, MouseScrollDelta},
     keyboard::PhysicalKey,
 };
 
+mod gamepad;
+mod gesture;
+
+pub use gamepad::{GamepadButton, GamepadEvent, GamepadId, Gamepads, Stick};
+pub use gesture::{Gesture, GestureEvent, Gestures, STICK_REST};
+
+/// A key, mouse button or gamepad button, for code that accepts any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(PhysicalKey),
+    Mouse(MouseButton),
+    /// The button on any connected gamepad.
+    Gamepad(GamepadButton),
+}
+
 #[derive(Default)]
 pub struct Input {
     keys_pressed: HashSet<PhysicalKey>,
     keys_just_pressed: HashSet<PhysicalKey>,
     keys_just_released: HashSet<PhysicalKey>,
     mouse_buttons_pressed: HashSet<MouseButton>,
-    mouse_pos: (f64, f64),
+    mouse_buttons_just_pressed: HashSet<MouseButton>,
+    mouse_buttons_just_released: HashSet<MouseButton>,
+    mouse_pos: Option<(f64, f64)>,
+    /// Cursor movement in pixels since the last frame.
+    mouse_delta: Vec2,
+    /// Scroll wheel movement in lines since the last frame; positive scrolls up.
+    scroll_delta: f32,
+    gamepad_buttons_pressed: HashSet<(GamepadId, GamepadButton)>,
+    gamepad_buttons_just_pressed: HashSet<(GamepadId, GamepadButton)>,
+    gamepad_buttons_just_released: HashSet<(GamepadId, GamepadButton)>,
+    /// Stick positions from -1 to 1, with y pointing up.
+    sticks: HashMap<(GamepadId, Stick), Vec2>,
 }
 
 impl Input {
@@ -24,6 +53,12 @@ impl Input {
     pub fn prepare_for_next_frame(&mut self) {
         self.keys_just_pressed.clear();
         self.keys_just_released.clear();
+        self.mouse_buttons_just_pressed.clear();
+        self.mouse_buttons_just_released.clear();
+        self.gamepad_buttons_just_pressed.clear();
+        self.gamepad_buttons_just_released.clear();
+        self.mouse_delta = Vec2::ZERO;
+        self.scroll_delta = 0.0;
     }
 
     pub fn is_key_pressed(&self, key: PhysicalKey) -> bool {
@@ -34,6 +69,99 @@ impl Input {
         self.keys_just_pressed.contains(&key)
     }
 
+    pub fn was_key_just_released(&self, key: PhysicalKey) -> bool {
+        self.keys_just_released.contains(&key)
+    }
+
+    pub fn is_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons_pressed.contains(&button)
+    }
+
+    pub fn was_mouse_button_just_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons_just_pressed.contains(&button)
+    }
+
+    pub fn was_mouse_button_just_released(&self, button: MouseButton) -> bool {
+        self.mouse_buttons_just_released.contains(&button)
+    }
+
+    pub fn is_gamepad_button_pressed(&self, gamepad: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad_buttons_pressed.contains(&(gamepad, button))
+    }
+
+    pub fn was_gamepad_button_just_pressed(
+        &self,
+        gamepad: GamepadId,
+        button: GamepadButton,
+    ) -> bool {
+        self.gamepad_buttons_just_pressed
+            .contains(&(gamepad, button))
+    }
+
+    pub fn was_gamepad_button_just_released(
+        &self,
+        gamepad: GamepadId,
+        button: GamepadButton,
+    ) -> bool {
+        self.gamepad_buttons_just_released
+            .contains(&(gamepad, button))
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        match button {
+            Button::Key(key) => self.is_key_pressed(key),
+            Button::Mouse(button) => self.is_mouse_button_pressed(button),
+            Button::Gamepad(button) => any_pad(&self.gamepad_buttons_pressed, button),
+        }
+    }
+
+    pub fn was_just_pressed(&self, button: Button) -> bool {
+        match button {
+            Button::Key(key) => self.was_key_just_pressed(key),
+            Button::Mouse(button) => self.was_mouse_button_just_pressed(button),
+            Button::Gamepad(button) => any_pad(&self.gamepad_buttons_just_pressed, button),
+        }
+    }
+
+    pub fn was_just_released(&self, button: Button) -> bool {
+        match button {
+            Button::Key(key) => self.was_key_just_released(key),
+            Button::Mouse(button) => self.was_mouse_button_just_released(button),
+            Button::Gamepad(button) => any_pad(&self.gamepad_buttons_just_released, button),
+        }
+    }
+
+    /// Position of `stick` on one gamepad, from -1 to 1 with y pointing up.
+    pub fn gamepad_stick(&self, gamepad: GamepadId, stick: Stick) -> Vec2 {
+        self.sticks
+            .get(&(gamepad, stick))
+            .copied()
+            .unwrap_or(Vec2::ZERO)
+    }
+
+    /// Position of `stick` on whichever connected gamepad deflects it the most.
+    pub fn stick(&self, stick: Stick) -> Vec2 {
+        self.sticks
+            .iter()
+            .filter(|((_, s), _)| *s == stick)
+            .map(|(_, position)| *position)
+            .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+            .unwrap_or(Vec2::ZERO)
+    }
+
+    /// Cursor position in physical pixels, once the cursor entered the window.
+    pub fn mouse_position(&self) -> Option<Vec2> {
+        self.mouse_pos.map(|(x, y)| Vec2::new(x as f32, y as f32))
+    }
+
+    pub fn mouse_delta(&self) -> Vec2 {
+        self.mouse_delta
+    }
+
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll_delta
+    }
+
     pub fn handle_keyboard_input(&mut self, device_id: DeviceId, event: KeyEvent) {
         let keycode = event.physical_key;
         debug!(
@@ -59,16 +187,85 @@ impl Input {
         debug!("Mouse pressed {:?}", button);
         match state {
             ElementState::Pressed => {
-                self.mouse_buttons_pressed.insert(button);
+                if self.mouse_buttons_pressed.insert(button) {
+                    self.mouse_buttons_just_pressed.insert(button);
+                }
             }
             ElementState::Released => {
                 self.mouse_buttons_pressed.remove(&button);
+                self.mouse_buttons_just_released.insert(button);
             }
         }
     }
 
     pub fn handle_cursor(&mut self, position: PhysicalPosition<f64>) {
         debug!("Mouse position {:?}", position);
-        self.mouse_pos = (position.x, position.y);
+        if let Some((x, y)) = self.mouse_pos {
+            self.mouse_delta += Vec2::new((position.x - x) as f32, (position.y - y) as f32);
+        }
+        self.mouse_pos = Some((position.x, position.y));
+    }
+
+    pub fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        self.scroll_delta += match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines,
+            // Roughly one line per 20 pixels, as on most platforms.
+            MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / 20.0,
+        };
+    }
+
+    pub fn handle_gamepad_button(
+        &mut self,
+        gamepad: GamepadId,
+        button: GamepadButton,
+        pressed: bool,
+    ) {
+        debug!(
+            "Gamepad {} button {:?} pressed: {}",
+            gamepad.0, button, pressed
+        );
+        if pressed {
+            if self.gamepad_buttons_pressed.insert((gamepad, button)) {
+                self.gamepad_buttons_just_pressed.insert((gamepad, button));
+            }
+        } else if self.gamepad_buttons_pressed.remove(&(gamepad, button)) {
+            self.gamepad_buttons_just_released.insert((gamepad, button));
+        }
+    }
+
+    /// Sets one axis of a stick: `0` for x, `1` for y.
+    pub fn handle_gamepad_axis(
+        &mut self,
+        gamepad: GamepadId,
+        stick: Stick,
+        axis: usize,
+        value: f32,
+    ) {
+        self.sticks.entry((gamepad, stick)).or_default()[axis] = value.clamp(-1.0, 1.0);
+    }
+
+    /// Forgets a disconnected gamepad, releasing its held buttons.
+    pub fn remove_gamepad(&mut self, gamepad: GamepadId) {
+        let held: Vec<_> = self
+            .gamepad_buttons_pressed
+            .iter()
+            .filter(|(pad, _)| *pad == gamepad)
+            .copied()
+            .collect();
+        for key in held {
+            self.gamepad_buttons_pressed.remove(&key);
+            self.gamepad_buttons_just_released.insert(key);
+        }
+        self.sticks.retain(|(pad, _), _| *pad != gamepad);
+    }
+}
+
+fn any_pad(buttons: &HashSet<(GamepadId, GamepadButton)>, button: GamepadButton) -> bool {
+    buttons.iter().any(|(_, b)| *b == button)
+}
+
+impl FromResources for Input {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Input::new())
     }
 }
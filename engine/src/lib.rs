@@ -1,10 +1,34 @@
+pub mod animation;
 pub mod application;
-mod asset_loader;
-mod core;
-mod engine;
-mod input;
+pub mod asset_loader;
+pub mod billboard;
+pub mod camera;
+pub mod capture;
+pub mod core;
+pub mod engine;
+pub mod events;
+pub mod frame_arena;
+pub mod frame_stats;
+pub mod game_module;
+pub mod input;
+pub mod lighting;
 pub mod logger;
+pub mod network;
+mod overlay;
+pub mod physics;
 mod platform;
+pub mod power;
+pub mod profiler;
 mod renderer;
 pub mod resource_manager;
+pub mod rng;
+pub mod save_game;
+pub mod scene;
+pub mod sequencer;
+pub mod task_pool;
+pub mod telemetry;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+pub mod time;
+pub mod tween;
 mod window;
@@ -0,0 +1,146 @@
+use crate::core::ubo::LightData;
+use crate::frame_arena::FrameArena;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::World;
+use anyhow::Result;
+use glam::{Mat4, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound for [`Lighting::max_lights`]; the renderer sizes its light buffers for it.
+pub const MAX_LIGHTS: usize = 256;
+
+/// Component making an entity emit light. Positions and directions come from the
+/// entity's world transform; directional and spot lights shine along its forward axis
+/// (`-Z`). Registered as `light` for scene files, e.g.
+/// `"light": Point(color: (1.0, 0.9, 0.8), intensity: 20.0, range: 10.0)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Light {
+    /// Parallel rays with no falloff, like sunlight.
+    Directional { color: Vec3, intensity: f32 },
+    /// Shines in all directions, fading with the square of the distance and reaching
+    /// zero at `range`.
+    Point {
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+    },
+    /// A point light limited to a cone. Full strength within `inner_angle` of the axis,
+    /// fading out towards `outer_angle`; both are half-angles in radians.
+    Spot {
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl Light {
+    /// Packs the light for the shader, placed by `transform`.
+    fn to_data(self, transform: &Mat4) -> LightData {
+        let position = transform.w_axis.truncate();
+        let direction = transform
+            .transform_vector3(Vec3::NEG_Z)
+            .normalize_or(Vec3::NEG_Z);
+        let (kind, color, intensity, range, cones) = match self {
+            Light::Directional { color, intensity } => {
+                (LightData::DIRECTIONAL, color, intensity, 0.0, Vec4::ZERO)
+            }
+            Light::Point {
+                color,
+                intensity,
+                range,
+            } => (LightData::POINT, color, intensity, range, Vec4::ZERO),
+            Light::Spot {
+                color,
+                intensity,
+                range,
+                inner_angle,
+                outer_angle,
+            } => (
+                LightData::SPOT,
+                color,
+                intensity,
+                range,
+                Vec4::new(inner_angle.cos(), outer_angle.cos(), 0.0, 0.0),
+            ),
+        };
+        LightData {
+            position_range: position.extend(range),
+            direction_kind: direction.extend(kind as f32),
+            color: (color * intensity).extend(1.0),
+            spot_cos: cones,
+        }
+    }
+}
+
+/// The lights the renderer shades with this frame, collected from [`Light`] components by
+/// [`Lighting::sync`], plus an ambient term.
+pub struct Lighting {
+    /// Light reaching every surface from all directions. Defaults to white, which draws
+    /// unlit scenes at full brightness; lower it once the scene has lights.
+    pub ambient: Vec3,
+    /// Most lights shaded per frame, at most [`MAX_LIGHTS`]. Directional lights are kept
+    /// first, then the ones closest to the camera.
+    pub max_lights: usize,
+    lights: Vec<LightData>,
+}
+
+impl Lighting {
+    pub fn new() -> Self {
+        Lighting {
+            ambient: Vec3::ONE,
+            max_lights: 64,
+            lights: Vec::new(),
+        }
+    }
+
+    /// Rebuilds the frame's light list from the current entity transforms. Every light
+    /// is ranked in `arena`; only the ones kept are copied out.
+    pub fn sync(&mut self, world: &World, camera_position: Vec3, arena: &FrameArena) {
+        let priority = |light: &LightData| {
+            if light.direction_kind.w == LightData::DIRECTIONAL as f32 {
+                -1.0
+            } else {
+                light
+                    .position_range
+                    .truncate()
+                    .distance_squared(camera_position)
+            }
+        };
+        let mut candidates = arena.vec();
+        candidates.extend(world.query::<Light>().filter_map(|(entity, light)| {
+            let data = light.to_data(&world.world_transform(entity)?);
+            Some((priority(&data), data))
+        }));
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.lights.clear();
+        self.lights.extend(
+            candidates
+                .iter()
+                .take(self.max_lights.min(MAX_LIGHTS))
+                .map(|&(_, data)| data),
+        );
+    }
+
+    /// Lights of the last [`Lighting::sync`], in shader layout.
+    pub fn lights(&self) -> &[LightData] {
+        &self.lights
+    }
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for Lighting {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        resources.init::<World>()?;
+        resources
+            .get_mut::<World>()
+            .register_component::<Light>("light");
+        Ok(Lighting::new())
+    }
+}
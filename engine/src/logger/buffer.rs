@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+
+/// Records kept by default before the oldest are dropped.
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// One captured log event.
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    /// The message followed by any other fields as `key=value`.
+    pub message: String,
+}
+
+struct BufferState {
+    records: VecDeque<LogRecord>,
+    capacity: usize,
+    /// Total records ever pushed, so readers can tell when new ones arrived.
+    pushed: u64,
+    /// Errors and warnings ever pushed, for telemetry.
+    errors: u64,
+    warnings: u64,
+}
+
+/// Bounded ring of the most recent log records, e.g. for the in-game console.
+/// Cheap to clone; all clones share the same records.
+#[derive(Clone)]
+pub struct LogBuffer {
+    state: Arc<Mutex<BufferState>>,
+}
+
+impl LogBuffer {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        LogBuffer {
+            state: Arc::new(Mutex::new(BufferState {
+                records: VecDeque::with_capacity(capacity),
+                capacity,
+                pushed: 0,
+                errors: 0,
+                warnings: 0,
+            })),
+        }
+    }
+
+    /// A `tracing` layer that appends every event to this buffer.
+    pub fn layer(&self) -> LogBufferLayer {
+        LogBufferLayer {
+            buffer: self.clone(),
+        }
+    }
+
+    /// Copies of the buffered records, oldest first.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.state
+            .lock()
+            .map(|state| state.records.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of records pushed since startup, including dropped ones.
+    pub fn pushed(&self) -> u64 {
+        self.state.lock().map(|state| state.pushed).unwrap_or(0)
+    }
+
+    /// Errors and warnings pushed since startup, including dropped ones.
+    pub fn problem_counts(&self) -> (u64, u64) {
+        self.state
+            .lock()
+            .map(|state| (state.errors, state.warnings))
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.records.clear();
+        }
+    }
+
+    fn push(&self, record: LogRecord) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        match record.level {
+            Level::ERROR => state.errors += 1,
+            Level::WARN => state.warnings += 1,
+            _ => {}
+        }
+        if state.records.len() == state.capacity {
+            state.records.pop_front();
+        }
+        if state.capacity > 0 {
+            state.records.push_back(record);
+        }
+        state.pushed += 1;
+    }
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `tracing` layer feeding a [`LogBuffer`].
+pub struct LogBufferLayer {
+    buffer: LogBuffer,
+}
+
+impl<S: Subscriber> Layer<S> for LogBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        self.buffer.push(LogRecord {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: visitor.message + &visitor.fields,
+        });
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::backtrace::Backtrace;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+type RestoreFn = Box<dyn Fn() + Send + Sync>;
+
+#[derive(Default)]
+struct CrashState {
+    context: BTreeMap<String, String>,
+    restore: Vec<RestoreFn>,
+    flush: Vec<RestoreFn>,
+}
+
+/// Shared crash-report state filled in by subsystems as they start up.
+///
+/// The panic hook installed by the [`Logger`](crate::logger::Logger) reads it to annotate
+/// the report (engine version, GPU, ...) and to put the window back into a usable state.
+#[derive(Clone, Default)]
+pub struct CrashReporter {
+    state: Arc<Mutex<CrashState>>,
+    crash_dir: Option<PathBuf>,
+}
+
+impl CrashReporter {
+    pub fn new(crash_dir: Option<PathBuf>) -> Self {
+        let reporter = CrashReporter {
+            state: Arc::default(),
+            crash_dir,
+        };
+        reporter.set_context("engine_version", env!("CARGO_PKG_VERSION"));
+        reporter.set_context("os", std::env::consts::OS);
+        reporter.set_context("arch", std::env::consts::ARCH);
+        reporter
+    }
+
+    /// Adds or replaces a `key: value` line in the crash report.
+    pub fn set_context(&self, key: impl Into<String>, value: impl Into<String>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.context.insert(key.into(), value.into());
+        }
+    }
+
+    /// Registers a callback that undoes global state on a crash, e.g. releasing a grabbed
+    /// cursor or leaving exclusive fullscreen.
+    pub fn on_crash_restore(&self, restore: impl Fn() + Send + Sync + 'static) {
+        if let Ok(mut state) = self.state.lock() {
+            state.restore.push(Box::new(restore));
+        }
+    }
+
+    pub(super) fn on_crash_flush(&self, flush: impl Fn() + Send + Sync + 'static) {
+        if let Ok(mut state) = self.state.lock() {
+            state.flush.push(Box::new(flush));
+        }
+    }
+
+    /// Chains a panic hook in front of the current one.
+    pub(super) fn install_panic_hook(&self) {
+        let reporter = self.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            reporter.report(info);
+            previous(info);
+        }));
+    }
+
+    fn report(&self, info: &PanicHookInfo) {
+        let backtrace = Backtrace::force_capture();
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_string());
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        let thread = std::thread::current()
+            .name()
+            .unwrap_or("unnamed")
+            .to_string();
+
+        error!(
+            panic.message = %message,
+            panic.location = %location,
+            panic.thread = %thread,
+            "Engine panicked\n{backtrace}"
+        );
+
+        // The lock may be poisoned by the very panic we're reporting; the data is still fine.
+        let state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for restore in &state.restore {
+            restore();
+        }
+
+        if let Some(dir) = &self.crash_dir {
+            match write_crash_dump(
+                dir,
+                &state.context,
+                &message,
+                &location,
+                &thread,
+                &backtrace,
+            ) {
+                Ok(path) => error!("Crash report written to {}", path.display()),
+                Err(err) => error!("Failed to write crash report: {err}"),
+            }
+        }
+
+        for flush in &state.flush {
+            flush();
+        }
+    }
+}
+
+fn write_crash_dump(
+    dir: &PathBuf,
+    context: &BTreeMap<String, String>,
+    message: &str,
+    location: &str,
+    thread: &str,
+    backtrace: &Backtrace,
+) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("crash-{timestamp}.txt"));
+    let mut file = fs::File::create(&path)?;
+    writeln!(file, "Elements crash report")?;
+    writeln!(file, "timestamp: {timestamp}")?;
+    for (key, value) in context {
+        writeln!(file, "{key}: {value}")?;
+    }
+    writeln!(file)?;
+    writeln!(file, "panic: {message}")?;
+    writeln!(file, "location: {location}")?;
+    writeln!(file, "thread: {thread}")?;
+    writeln!(file)?;
+    writeln!(file, "{backtrace}")?;
+    Ok(path)
+}
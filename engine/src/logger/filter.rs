@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::{EnvFilter, reload};
+
+/// Directives used when neither `RUST_LOG` nor a filter file set any.
+pub const DEFAULT_FILTER: &str = "debug";
+
+type ReloadFn = Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>;
+
+#[derive(Default)]
+struct FilterState {
+    directives: String,
+    layers: Vec<ReloadFn>,
+}
+
+/// Runtime handle to the log filter shared by the console and file output.
+///
+/// Takes [`EnvFilter`] directives, e.g. `info,elements_engine::input=warn` to keep
+/// per-frame input logs out of the console.
+#[derive(Clone, Default)]
+pub struct LogFilter {
+    state: Arc<Mutex<FilterState>>,
+}
+
+impl LogFilter {
+    /// Starts from `RUST_LOG` if set, then `filter_file`, then [`DEFAULT_FILTER`].
+    pub fn new(filter_file: Option<&Path>) -> Self {
+        let directives = std::env::var(EnvFilter::DEFAULT_ENV)
+            .ok()
+            .filter(|directives| !directives.trim().is_empty())
+            .or_else(|| {
+                let path = filter_file?;
+                match read_filter_file(path) {
+                    Ok(directives) if directives.is_empty() => None,
+                    Ok(directives) => Some(directives),
+                    Err(err) => {
+                        eprintln!("Failed to read log filter {}: {err:?}", path.display());
+                        None
+                    }
+                }
+            })
+            .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+        let directives = match EnvFilter::try_new(&directives) {
+            Ok(_) => directives,
+            Err(err) => {
+                eprintln!("Invalid log filter {directives:?}: {err}");
+                DEFAULT_FILTER.to_string()
+            }
+        };
+        LogFilter {
+            state: Arc::new(Mutex::new(FilterState {
+                directives,
+                layers: Vec::new(),
+            })),
+        }
+    }
+
+    /// The directives currently in effect.
+    pub fn directives(&self) -> String {
+        self.state.lock().unwrap().directives.clone()
+    }
+
+    /// Replaces the filter on every output. Invalid directives leave the old filter in place.
+    pub fn set(&self, directives: &str) -> Result<()> {
+        EnvFilter::try_new(directives)
+            .with_context(|| format!("Invalid log filter {directives:?}"))?;
+        let mut state = self.state.lock().unwrap();
+        for reload in &state.layers {
+            reload(EnvFilter::new(directives))?;
+        }
+        state.directives = directives.to_string();
+        Ok(())
+    }
+
+    /// A per-layer filter that follows [`LogFilter::set`].
+    pub(crate) fn layer<S: 'static>(&self) -> reload::Layer<EnvFilter, S> {
+        let mut state = self.state.lock().unwrap();
+        let (layer, handle) = reload::Layer::new(EnvFilter::new(&state.directives));
+        state
+            .layers
+            .push(Box::new(move |filter| handle.reload(filter)));
+        layer
+    }
+}
+
+/// One directive per line or comma-separated; `#` starts a comment.
+fn read_filter_file(path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(path)?;
+    let directives = contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    Ok(directives.join(","))
+}
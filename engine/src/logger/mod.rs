@@ -1,19 +1,180 @@
-use tracing::Level;
-use tracing_subscriber::FmtSubscriber;
+use crate::profiler::Profiler;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::writer::MutexGuardWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, registry};
 
-pub struct Logger;
+mod buffer;
+mod crash;
+mod filter;
+
+pub use buffer::{LogBuffer, LogBufferLayer, LogRecord};
+pub use crash::CrashReporter;
+pub use filter::{DEFAULT_FILTER, LogFilter};
+
+/// How log lines are formatted on the console and in the log file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Multi-line, colored output for reading in a terminal.
+    #[default]
+    Pretty,
+    /// One JSON object per line, including the fields of the enclosing spans (frame
+    /// number, subsystem), for log aggregation tools.
+    Json,
+}
+
+/// Where the [`Logger`] writes besides the console.
+#[derive(Debug, Clone, Default)]
+pub struct LoggerConfig {
+    pub format: LogFormat,
+    /// Also write plain-text logs to this file.
+    pub log_file: Option<PathBuf>,
+    /// Write a crash report into this directory when the engine panics.
+    pub crash_dir: Option<PathBuf>,
+    /// File of `EnvFilter` directives, one per line. `RUST_LOG` takes precedence.
+    pub filter_file: Option<PathBuf>,
+}
+
+/// Buffered log file shared between the file layer and the crash handler that flushes it.
+#[derive(Clone)]
+struct LogFile(Arc<Mutex<BufWriter<File>>>);
+
+impl<'a> MakeWriter<'a> for LogFile {
+    type Writer = MutexGuardWriter<'a, BufWriter<File>>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.0.make_writer()
+    }
+}
+
+pub struct Logger {
+    profiler: Profiler,
+    crash_reporter: CrashReporter,
+    filter: LogFilter,
+    log_buffer: LogBuffer,
+    #[cfg(feature = "profile-puffin")]
+    _puffin_server: Option<puffin_http::Server>,
+}
 
 impl Logger {
     pub fn new() -> Self {
-        let subscriber = FmtSubscriber::builder()
-            .with_max_level(Level::TRACE)
-            .pretty()
-            .finish();
+        Self::with_config(LoggerConfig::default())
+    }
 
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("setting default subscriber failed");
+    pub fn with_config(config: LoggerConfig) -> Self {
+        let profiler = Profiler::new();
+        let crash_reporter = CrashReporter::new(config.crash_dir);
+        let filter = LogFilter::new(config.filter_file.as_deref());
+        let log_buffer = LogBuffer::new();
+
+        let log_file = config.log_file.and_then(|path| {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            match File::create(&path) {
+                Ok(file) => Some(LogFile(Arc::new(Mutex::new(BufWriter::new(file))))),
+                Err(err) => {
+                    eprintln!("Failed to open log file {}: {err}", path.display());
+                    None
+                }
+            }
+        });
+        let json = config.format == LogFormat::Json;
+        let file_layer = log_file.clone().map(|file| {
+            let layer = fmt::layer().with_ansi(false).with_writer(file);
+            if json {
+                layer
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .boxed()
+            } else {
+                layer.boxed()
+            }
+            .with_filter(filter.layer())
+        });
+        let console_layer = if json {
+            fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .boxed()
+        } else {
+            fmt::layer().pretty().boxed()
+        };
+
+        let subscriber = registry()
+            .with(console_layer.with_filter(filter.layer()))
+            .with(file_layer)
+            .with(log_buffer.layer().with_filter(filter.layer()))
+            .with(profiler.layer());
+        #[cfg(feature = "profile-tracy")]
+        let subscriber = subscriber.with(tracing_tracy::TracyLayer::default());
+        #[cfg(feature = "profile-puffin")]
+        let subscriber = subscriber.with(crate::profiler::puffin_layer::PuffinLayer::new());
+
+        if tracing::subscriber::set_global_default(subscriber).is_err() {
+            // Another engine in the same process, e.g. in a test harness, installed its
+            // subscriber first. Logs keep going there.
+            tracing::warn!("A global log subscriber is already set; keeping it");
+        }
+
+        if let Some(file) = log_file {
+            crash_reporter.on_crash_flush(move || {
+                if let Ok(mut file) = file.0.lock() {
+                    let _ = file.flush();
+                }
+            });
+        }
+        crash_reporter.install_panic_hook();
+
+        #[cfg(feature = "profile-puffin")]
+        let _puffin_server = {
+            let addr = format!("127.0.0.1:{}", puffin_http::DEFAULT_PORT);
+            match puffin_http::Server::new(&addr) {
+                Ok(server) => {
+                    tracing::info!("Serving puffin profile data on {addr}");
+                    Some(server)
+                }
+                Err(err) => {
+                    tracing::error!("Failed to start puffin server: {err}");
+                    None
+                }
+            }
+        };
+
+        Self {
+            profiler,
+            crash_reporter,
+            filter,
+            log_buffer,
+            #[cfg(feature = "profile-puffin")]
+            _puffin_server,
+        }
+    }
+
+    /// Span timings captured by the logger's profiling layer.
+    pub fn profiler(&self) -> Profiler {
+        self.profiler.clone()
+    }
+
+    /// Crash report state shared with the panic hook.
+    pub fn crash_reporter(&self) -> CrashReporter {
+        self.crash_reporter.clone()
+    }
+
+    /// Handle for changing the log filter at runtime.
+    pub fn filter(&self) -> LogFilter {
+        self.filter.clone()
+    }
 
-        Self
+    /// Recent log records, as shown by the in-game console.
+    pub fn log_buffer(&self) -> LogBuffer {
+        self.log_buffer.clone()
     }
 
     // fn on_update(&self) {}
@@ -0,0 +1,561 @@
+//! Lightweight client/server transport over UDP for multiplayer prototypes.
+//!
+//! One [`Network`] resource either listens for clients or connects to a server. Game code
+//! registers its message types as named channels on both ends, sends them with
+//! [`Network::send`], and reads what arrives as [`NetworkMessage`] events on the
+//! [`Events`] bus, next to the [`NetworkEvent`]s for peers joining and leaving.
+//!
+//! Messages are serialized with bincode and must fit in one datagram. Reliable messages
+//! are resent until acknowledged but may arrive out of order. The socket is polled once
+//! per update, so a sleeping [`UpdateMode::Reactive`](crate::engine::UpdateMode) loop
+//! doesn't receive anything until the next input event.
+
+use crate::events::Events;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::{Context, Result, bail, ensure};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+mod packet;
+mod peer;
+
+use packet::{MAX_DATAGRAM, Packet};
+use peer::{Peer, Unacked};
+
+/// A remote end of the connection. Clients know the server as [`PeerId::SERVER`]; the
+/// server numbers its clients from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeerId(pub u32);
+
+impl PeerId {
+    pub const SERVER: PeerId = PeerId(0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Delivery {
+    /// Sent once; may be lost.
+    #[default]
+    Unreliable,
+    /// Resent until acknowledged. Not ordered relative to other messages.
+    Reliable,
+}
+
+/// Sent on the event bus when a peer joins or leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkEvent {
+    Connected(PeerId),
+    Disconnected(PeerId, DisconnectReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Either side closed the connection.
+    Closed,
+    /// Nothing arrived from the peer for [`NetworkConfig::timeout`], or the server never
+    /// answered.
+    TimedOut,
+    /// The server was full.
+    Refused,
+}
+
+/// A message of a registered channel, sent on the event bus when it arrives.
+#[derive(Debug, Clone)]
+pub struct NetworkMessage<M> {
+    pub peer: PeerId,
+    pub message: M,
+}
+
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    /// Peers that stay silent this long are dropped. Also bounds the connect handshake.
+    pub timeout: Duration,
+    /// Idle connections send a heartbeat this often so they don't time out.
+    pub heartbeat: Duration,
+    /// Unacknowledged reliable messages and unanswered connects are resent this often.
+    pub resend_interval: Duration,
+    /// Clients a server accepts at once.
+    pub max_peers: usize,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            timeout: Duration::from_secs(10),
+            heartbeat: Duration::from_secs(1),
+            resend_interval: Duration::from_millis(200),
+            max_peers: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Idle,
+    Server,
+    Client,
+}
+
+type Deliver = Box<dyn Fn(PeerId, &[u8], &mut Events) -> Result<()>>;
+
+struct Channel {
+    name: String,
+    deliver: Deliver,
+}
+
+/// The client or server end of a UDP transport. See the [module docs](self).
+pub struct Network {
+    pub config: NetworkConfig,
+    socket: Option<UdpSocket>,
+    role: Role,
+    peers: HashMap<PeerId, Peer>,
+    next_peer: u32,
+    channels: HashMap<u32, Channel>,
+    channel_ids: HashMap<TypeId, u32>,
+    /// Events raised outside of [`Network::poll`], sent on the next one.
+    pending: Vec<NetworkEvent>,
+}
+
+impl Network {
+    pub fn new(config: NetworkConfig) -> Self {
+        Network {
+            config,
+            socket: None,
+            role: Role::Idle,
+            peers: HashMap::new(),
+            next_peer: 1,
+            channels: HashMap::new(),
+            channel_ids: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Makes `M` sendable under `name`. Both ends must register the same types under the
+    /// same names; messages on channels the receiver doesn't know are dropped.
+    pub fn register<M>(&mut self, name: &str) -> Result<()>
+    where
+        M: Serialize + DeserializeOwned + 'static,
+    {
+        let id = channel_id(name);
+        if let Some(existing) = self.channels.get(&id) {
+            bail!(
+                "Channel {name:?} collides with the registered {:?}",
+                existing.name
+            );
+        }
+        let deliver: Deliver = Box::new(|peer, payload, events| {
+            let message = bincode::deserialize::<M>(payload)?;
+            events.send(NetworkMessage { peer, message });
+            Ok(())
+        });
+        self.channels.insert(
+            id,
+            Channel {
+                name: name.to_owned(),
+                deliver,
+            },
+        );
+        self.channel_ids.insert(TypeId::of::<M>(), id);
+        Ok(())
+    }
+
+    /// Starts a server on `addr`, closing any previous connection. Returns the bound
+    /// address, which tells the port picked for port 0.
+    pub fn listen(&mut self, addr: impl ToSocketAddrs) -> Result<SocketAddr> {
+        self.close();
+        let socket = UdpSocket::bind(addr).with_context(|| "Failed to bind server socket")?;
+        socket.set_nonblocking(true)?;
+        let local = socket.local_addr()?;
+        info!("Listening on {local}");
+        self.socket = Some(socket);
+        self.role = Role::Server;
+        Ok(local)
+    }
+
+    /// Starts connecting to the server at `addr`, closing any previous connection.
+    /// [`NetworkEvent::Connected`] follows once the server accepts.
+    pub fn connect(&mut self, addr: impl ToSocketAddrs) -> Result<()> {
+        self.close();
+        let server = addr
+            .to_socket_addrs()?
+            .next()
+            .with_context(|| "Server address resolved to nothing")?;
+        let local: SocketAddr = if server.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local).with_context(|| "Failed to bind client socket")?;
+        socket.set_nonblocking(true)?;
+        let now = Instant::now();
+        send_datagram(&socket, server, &Packet::Connect.encode()?)?;
+        info!("Connecting to {server}");
+        self.peers.insert(PeerId::SERVER, Peer::new(server, now));
+        self.socket = Some(socket);
+        self.role = Role::Client;
+        Ok(())
+    }
+
+    /// Tells every peer goodbye and closes the socket.
+    pub fn close(&mut self) {
+        let ids = self.peers.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            self.disconnect(id);
+        }
+        self.socket = None;
+        self.role = Role::Idle;
+    }
+
+    /// Closes the connection to one peer. On a client, that's the server.
+    pub fn disconnect(&mut self, id: PeerId) {
+        let Some(peer) = self.peers.remove(&id) else {
+            return;
+        };
+        if peer.connected {
+            if let Some(socket) = self.socket.as_ref()
+                && let Err(e) = Packet::Disconnect
+                    .encode()
+                    .and_then(|datagram| send_datagram(socket, peer.addr, &datagram))
+            {
+                warn!("Failed to send disconnect to {id:?}: {e:#}");
+            }
+            self.pending
+                .push(NetworkEvent::Disconnected(id, DisconnectReason::Closed));
+        }
+    }
+
+    /// Sends `message` to a connected peer. `M` must be [registered](Network::register).
+    pub fn send<M: Serialize + 'static>(
+        &mut self,
+        id: PeerId,
+        message: &M,
+        delivery: Delivery,
+    ) -> Result<()> {
+        let (channel, payload) = self.encode_message(message)?;
+        let socket = self.socket.as_ref().context("Network is not open")?;
+        let peer = self
+            .peers
+            .get_mut(&id)
+            .filter(|peer| peer.connected)
+            .with_context(|| format!("{id:?} is not connected"))?;
+        send_message(socket, peer, channel, payload, delivery)
+    }
+
+    /// Sends `message` to every connected peer.
+    pub fn broadcast<M: Serialize + 'static>(
+        &mut self,
+        message: &M,
+        delivery: Delivery,
+    ) -> Result<()> {
+        let (channel, payload) = self.encode_message(message)?;
+        let Some(socket) = self.socket.as_ref() else {
+            return Ok(());
+        };
+        for peer in self.peers.values_mut().filter(|peer| peer.connected) {
+            send_message(socket, peer, channel, payload.clone(), delivery)?;
+        }
+        Ok(())
+    }
+
+    fn encode_message<M: Serialize + 'static>(&self, message: &M) -> Result<(u32, Vec<u8>)> {
+        let channel = *self
+            .channel_ids
+            .get(&TypeId::of::<M>())
+            .with_context(|| format!("{} is not registered", std::any::type_name::<M>()))?;
+        let payload = bincode::serialize(message).with_context(|| "Failed to encode message")?;
+        Ok((channel, payload))
+    }
+
+    /// Connected peers.
+    pub fn peers(&self) -> impl Iterator<Item = PeerId> + '_ {
+        self.peers
+            .iter()
+            .filter(|(_, peer)| peer.connected)
+            .map(|(id, _)| *id)
+    }
+
+    /// Smoothed round-trip time to a peer, once a reliable message was acknowledged.
+    pub fn rtt(&self, id: PeerId) -> Option<Duration> {
+        self.peers.get(&id).and_then(|peer| peer.rtt)
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket
+            .as_ref()
+            .and_then(|socket| socket.local_addr().ok())
+    }
+
+    pub fn is_server(&self) -> bool {
+        self.role == Role::Server
+    }
+
+    /// Whether a client finished connecting to its server.
+    pub fn is_connected(&self) -> bool {
+        self.role == Role::Client && self.peers().next().is_some()
+    }
+
+    /// Receives everything that arrived since the last call and sends it on `events`, then
+    /// resends, heartbeats and drops timed out peers. The engine calls this every update.
+    pub fn poll(&mut self, events: &mut Events) {
+        for event in self.pending.drain(..) {
+            events.send(event);
+        }
+        let Some(socket) = self.socket.take() else {
+            return;
+        };
+        let now = Instant::now();
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            match socket.recv_from(&mut buffer) {
+                Ok((len, addr)) => match Packet::decode(&buffer[..len]) {
+                    Some(packet) => self.receive(&socket, addr, packet, now, events),
+                    None => debug!("Dropped {len} foreign bytes from {addr}"),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // Some platforms report an unreachable peer on the next receive; the
+                // timeout drops it.
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => {}
+                Err(e) => {
+                    warn!("Failed to receive datagram: {e}");
+                    break;
+                }
+            }
+        }
+        self.maintain(&socket, now, events);
+        self.socket = Some(socket);
+    }
+
+    fn receive(
+        &mut self,
+        socket: &UdpSocket,
+        addr: SocketAddr,
+        packet: Packet,
+        now: Instant,
+        events: &mut Events,
+    ) {
+        let id = self
+            .peers
+            .iter()
+            .find(|(_, peer)| peer.addr == addr)
+            .map(|(id, _)| *id);
+        let Some(id) = id else {
+            if self.role == Role::Server && matches!(packet, Packet::Connect) {
+                self.accept(socket, addr, now, events);
+            }
+            return;
+        };
+        let Some(peer) = self.peers.get_mut(&id) else {
+            return;
+        };
+        peer.last_received = now;
+        let reply = match packet {
+            Packet::Connect => Some(Packet::Accept),
+            Packet::Accept => {
+                if !peer.connected {
+                    peer.connected = true;
+                    info!("Connected to {addr}");
+                    events.send(NetworkEvent::Connected(id));
+                }
+                None
+            }
+            Packet::Refuse => {
+                self.peers.remove(&id);
+                warn!("{addr} refused the connection");
+                events.send(NetworkEvent::Disconnected(id, DisconnectReason::Refused));
+                return;
+            }
+            Packet::Disconnect => {
+                self.peers.remove(&id);
+                info!("{id:?} disconnected");
+                events.send(NetworkEvent::Disconnected(id, DisconnectReason::Closed));
+                return;
+            }
+            Packet::Heartbeat => None,
+            Packet::Ack(sequence) => {
+                peer.acknowledge(sequence, now);
+                None
+            }
+            Packet::Message {
+                channel,
+                sequence,
+                payload,
+            } => {
+                if !peer.connected {
+                    // The server's accept was lost but its traffic got through.
+                    peer.connected = true;
+                    events.send(NetworkEvent::Connected(id));
+                }
+                let fresh = sequence.is_none_or(|sequence| peer.receive(sequence));
+                if fresh {
+                    match self.channels.get(&channel) {
+                        Some(channel) => {
+                            if let Err(e) = (channel.deliver)(id, &payload, events) {
+                                warn!(
+                                    "Dropped malformed {:?} message from {id:?}: {e}",
+                                    channel.name
+                                );
+                            }
+                        }
+                        None => {
+                            warn!("Dropped message from {id:?} on unknown channel {channel:#x}")
+                        }
+                    }
+                }
+                sequence.map(Packet::Ack)
+            }
+        };
+        if let Some(reply) = reply
+            && let Some(peer) = self.peers.get_mut(&id)
+        {
+            peer.last_sent = now;
+            if let Err(e) = reply
+                .encode()
+                .and_then(|datagram| send_datagram(socket, addr, &datagram))
+            {
+                warn!("Failed to reply to {id:?}: {e:#}");
+            }
+        }
+    }
+
+    fn accept(&mut self, socket: &UdpSocket, addr: SocketAddr, now: Instant, events: &mut Events) {
+        let reply = if self.peers.len() >= self.config.max_peers {
+            warn!("Refused {addr}: server is full");
+            Packet::Refuse
+        } else {
+            let id = PeerId(self.next_peer);
+            self.next_peer += 1;
+            let mut peer = Peer::new(addr, now);
+            peer.connected = true;
+            self.peers.insert(id, peer);
+            info!("{addr} connected as {id:?}");
+            events.send(NetworkEvent::Connected(id));
+            Packet::Accept
+        };
+        if let Err(e) = reply
+            .encode()
+            .and_then(|datagram| send_datagram(socket, addr, &datagram))
+        {
+            warn!("Failed to answer {addr}: {e:#}");
+        }
+    }
+
+    /// Drops silent peers and resends whatever is due.
+    fn maintain(&mut self, socket: &UdpSocket, now: Instant, events: &mut Events) {
+        let config = &self.config;
+        let mut timed_out = Vec::new();
+        for (&id, peer) in &mut self.peers {
+            if now.duration_since(peer.last_received) > config.timeout {
+                timed_out.push(id);
+                continue;
+            }
+            let mut due = Vec::new();
+            if !peer.connected {
+                if now.duration_since(peer.last_sent) >= config.resend_interval {
+                    due.push(Packet::Connect.encode());
+                }
+            } else {
+                for unacked in peer.unacked.values_mut() {
+                    if now.duration_since(unacked.sent) >= config.resend_interval {
+                        unacked.sent = now;
+                        unacked.resends += 1;
+                        due.push(Ok(unacked.datagram.clone()));
+                    }
+                }
+                if due.is_empty() && now.duration_since(peer.last_sent) >= config.heartbeat {
+                    due.push(Packet::Heartbeat.encode());
+                }
+            }
+            for datagram in due {
+                peer.last_sent = now;
+                if let Err(e) =
+                    datagram.and_then(|datagram| send_datagram(socket, peer.addr, &datagram))
+                {
+                    warn!("Failed to send to {id:?}: {e:#}");
+                }
+            }
+        }
+        for id in timed_out {
+            self.peers.remove(&id);
+            warn!("{id:?} timed out");
+            events.send(NetworkEvent::Disconnected(id, DisconnectReason::TimedOut));
+        }
+    }
+}
+
+/// Stable id of a channel name, so both ends agree without exchanging a table.
+fn channel_id(name: &str) -> u32 {
+    // FNV-1a.
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn send_message(
+    socket: &UdpSocket,
+    peer: &mut Peer,
+    channel: u32,
+    payload: Vec<u8>,
+    delivery: Delivery,
+) -> Result<()> {
+    let sequence = (delivery == Delivery::Reliable).then(|| peer.next_sequence());
+    let datagram = Packet::Message {
+        channel,
+        sequence,
+        payload,
+    }
+    .encode()?;
+    ensure!(
+        datagram.len() <= MAX_DATAGRAM,
+        "Message of {} bytes doesn't fit in a {MAX_DATAGRAM} byte datagram",
+        datagram.len()
+    );
+    let now = Instant::now();
+    send_datagram(socket, peer.addr, &datagram)?;
+    peer.last_sent = now;
+    if let Some(sequence) = sequence {
+        peer.unacked.insert(
+            sequence,
+            Unacked {
+                datagram,
+                sent: now,
+                resends: 0,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Sends one datagram. A full send buffer drops it like the network would.
+fn send_datagram(socket: &UdpSocket, addr: SocketAddr, datagram: &[u8]) -> Result<()> {
+    match socket.send_to(datagram, addr) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+            debug!("Send buffer full, dropped a datagram to {addr}");
+            Ok(())
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to send to {addr}")),
+    }
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::new(NetworkConfig::default())
+    }
+}
+
+impl FromResources for Network {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Network::default())
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Tags every datagram, so stray traffic from other programs on the port is dropped.
+/// Bump it when [`Packet`] changes.
+const PROTOCOL_ID: u32 = 0x454c_0001;
+
+/// Largest datagram sent, which stays below common path MTUs so nothing fragments.
+pub const MAX_DATAGRAM: usize = 1200;
+
+/// What travels in one datagram.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Packet {
+    /// Client asking to join. Resent until answered.
+    Connect,
+    /// Server accepting a client. Resent for every repeated `Connect`.
+    Accept,
+    /// Server turning a client away because it's full.
+    Refuse,
+    /// Either side closing the connection.
+    Disconnect,
+    /// Keeps an idle connection from timing out.
+    Heartbeat,
+    Message {
+        channel: u32,
+        /// Sequence number the receiver acknowledges, for reliable messages.
+        sequence: Option<u32>,
+        payload: Vec<u8>,
+    },
+    /// Acknowledges the reliable message with this sequence number.
+    Ack(u32),
+}
+
+impl Packet {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        bincode::serialize(&(PROTOCOL_ID, self)).with_context(|| "Failed to encode packet")
+    }
+
+    /// Decodes a datagram, or returns `None` if it isn't one of ours.
+    pub fn decode(bytes: &[u8]) -> Option<Packet> {
+        match bincode::deserialize::<(u32, Packet)>(bytes) {
+            Ok((PROTOCOL_ID, packet)) => Some(packet),
+            _ => None,
+        }
+    }
+}
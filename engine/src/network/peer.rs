@@ -0,0 +1,79 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Reliable sequence numbers remembered per peer to drop resent duplicates.
+const RECEIVED_WINDOW: usize = 1024;
+
+/// A reliable message waiting for its acknowledgement.
+pub struct Unacked {
+    pub datagram: Vec<u8>,
+    /// When it was last sent.
+    pub sent: Instant,
+    pub resends: u32,
+}
+
+/// Connection state of one remote end.
+pub struct Peer {
+    pub addr: SocketAddr,
+    /// Handshake finished: the client got `Accept`, or the server sent it.
+    pub connected: bool,
+    pub last_received: Instant,
+    pub last_sent: Instant,
+    next_sequence: u32,
+    pub unacked: BTreeMap<u32, Unacked>,
+    received: HashSet<u32>,
+    received_order: VecDeque<u32>,
+    /// Smoothed round-trip time, from acknowledgements of messages sent once.
+    pub rtt: Option<Duration>,
+}
+
+impl Peer {
+    pub fn new(addr: SocketAddr, now: Instant) -> Self {
+        Peer {
+            addr,
+            connected: false,
+            last_received: now,
+            last_sent: now,
+            next_sequence: 0,
+            unacked: BTreeMap::new(),
+            received: HashSet::new(),
+            received_order: VecDeque::new(),
+            rtt: None,
+        }
+    }
+
+    pub fn next_sequence(&mut self) -> u32 {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        sequence
+    }
+
+    /// Records a reliable message as received. Returns `false` for a duplicate.
+    pub fn receive(&mut self, sequence: u32) -> bool {
+        if !self.received.insert(sequence) {
+            return false;
+        }
+        self.received_order.push_back(sequence);
+        if self.received_order.len() > RECEIVED_WINDOW
+            && let Some(oldest) = self.received_order.pop_front()
+        {
+            self.received.remove(&oldest);
+        }
+        true
+    }
+
+    pub fn acknowledge(&mut self, sequence: u32, now: Instant) {
+        let Some(unacked) = self.unacked.remove(&sequence) else {
+            return;
+        };
+        // A resent message can't tell which copy was acknowledged.
+        if unacked.resends == 0 {
+            let sample = now.duration_since(unacked.sent);
+            self.rtt = Some(match self.rtt {
+                Some(rtt) => rtt.mul_f32(0.875) + sample.mul_f32(0.125),
+                None => sample,
+            });
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::billboard::{BillboardContent, ScreenBillboard};
+use egui_winit_vulkano::egui;
+
+/// Paints billboards behind every window, in the order given.
+pub fn show(ctx: &egui::Context, billboards: &[ScreenBillboard]) {
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for billboard in billboards {
+        let center = egui::pos2(billboard.center.x, billboard.center.y);
+        let rect =
+            egui::Rect::from_center_size(center, egui::vec2(billboard.size.x, billboard.size.y));
+        let [r, g, b, a] = billboard.billboard.color.to_array();
+        let color: egui::Color32 = egui::Rgba::from_rgba_unmultiplied(r, g, b, a).into();
+        match &billboard.billboard.content {
+            BillboardContent::Label(text) => {
+                let font = egui::FontId::proportional(rect.height());
+                painter.text(
+                    center + egui::vec2(1.0, 1.0),
+                    egui::Align2::CENTER_CENTER,
+                    text,
+                    font.clone(),
+                    egui::Color32::BLACK,
+                );
+                painter.text(center, egui::Align2::CENTER_CENTER, text, font, color);
+            }
+            BillboardContent::Bar { fraction } => {
+                let mut fill = rect;
+                fill.set_width(rect.width() * fraction.clamp(0.0, 1.0));
+                painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(160));
+                painter.rect_filled(fill, 2.0, color);
+                painter.rect_stroke(
+                    rect,
+                    2.0,
+                    egui::Stroke::new(1.0, egui::Color32::BLACK),
+                    egui::StrokeKind::Outside,
+                );
+            }
+            BillboardContent::Marker => {
+                let radius = rect.width().min(rect.height()) * 0.5;
+                painter.circle(
+                    center,
+                    radius,
+                    color,
+                    egui::Stroke::new(1.0, egui::Color32::BLACK),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::logger::LogBuffer;
+use crate::renderer::GraphicsQuality;
+use anyhow::{Result, bail};
+use egui_winit_vulkano::egui;
+use tracing::Level;
+
+/// Shown by `help`.
+pub const HELP: &str = "Commands:
+  log <directives>      set the log filter, e.g. `log info,elements_engine::input=warn`
+  wireframe [on|off]    draw meshes as wireframe; toggles without an argument
+  quality <preset>      switch graphics quality to `low`, `medium` or `high`
+  spawn <model>         spawn an entity with the glTF model asset, e.g. `spawn super_car.scene`
+  save <path>           write the world and saved resources to a file, e.g. `save saves/slot1.ron`
+  load <path>           replace the world and saved resources with a save file
+  clear                 clear the console
+  help                  show this list";
+
+/// A line typed into the [`Console`], parsed but not yet run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleCommand {
+    Log(String),
+    /// `None` toggles.
+    Wireframe(Option<bool>),
+    /// Name of a [`GraphicsQuality`] preset.
+    Quality(String),
+    Spawn(String),
+    Save(String),
+    Load(String),
+    Clear,
+    Help,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        Ok(match name {
+            "log" if !args.is_empty() => ConsoleCommand::Log(args.to_string()),
+            "log" => bail!("Usage: log <directives>"),
+            "wireframe" => ConsoleCommand::Wireframe(match args {
+                "" => None,
+                "on" => Some(true),
+                "off" => Some(false),
+                _ => bail!("Usage: wireframe [on|off]"),
+            }),
+            "quality" if GraphicsQuality::preset(args).is_some() => {
+                ConsoleCommand::Quality(args.to_string())
+            }
+            "quality" => bail!("Usage: quality <low|medium|high>"),
+            "spawn" if !args.is_empty() => ConsoleCommand::Spawn(args.to_string()),
+            "spawn" => bail!("Usage: spawn <model>"),
+            "save" if !args.is_empty() => ConsoleCommand::Save(args.to_string()),
+            "save" => bail!("Usage: save <path>"),
+            "load" if !args.is_empty() => ConsoleCommand::Load(args.to_string()),
+            "load" => bail!("Usage: load <path>"),
+            "clear" => ConsoleCommand::Clear,
+            "help" => ConsoleCommand::Help,
+            _ => bail!("Unknown command `{name}`, try `help`"),
+        })
+    }
+}
+
+/// Drop-down window listing recent log records with a command line. Toggled with the
+/// backtick key.
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    /// Position in `history` while browsing it with the arrow keys.
+    history_index: Option<usize>,
+    /// Focus the command line on the next draw.
+    focus: bool,
+}
+
+impl Console {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.focus = self.open;
+    }
+
+    /// Draws the console if open. Returns the line submitted this frame, if any.
+    pub fn draw(&mut self, ctx: &egui::Context, log: &LogBuffer) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+        let mut submitted = None;
+        let mut open = self.open;
+        let mut close = false;
+        egui::Window::new("Console")
+            .open(&mut open)
+            .default_size([720.0, 320.0])
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 0.0])
+            .show(ctx, |ui| {
+                let input_height = ui.spacing().interact_size.y + ui.spacing().item_spacing.y;
+                egui::ScrollArea::vertical()
+                    .max_height(ui.available_height() - input_height)
+                    .auto_shrink([false, false])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for record in log.records() {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{:>5} {}: {}",
+                                    record.level, record.target, record.message
+                                ))
+                                .monospace()
+                                .color(level_color(record.level)),
+                            );
+                        }
+                    });
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.input)
+                        .desired_width(f32::INFINITY)
+                        .font(egui::TextStyle::Monospace)
+                        .hint_text("help"),
+                );
+                if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                    let line = std::mem::take(&mut self.input);
+                    if !line.trim().is_empty() {
+                        self.history.push(line.clone());
+                        submitted = Some(line);
+                    }
+                    self.history_index = None;
+                    response.request_focus();
+                } else if response.has_focus() {
+                    // egui swallows keys while the field has focus, so close from here.
+                    if ui.input(|input| input.key_pressed(egui::Key::Backtick)) {
+                        self.input.retain(|c| c != '`');
+                        close = true;
+                    }
+                    self.browse_history(ui);
+                }
+                if std::mem::take(&mut self.focus) {
+                    response.request_focus();
+                }
+            });
+        self.open = open && !close;
+        submitted
+    }
+
+    fn browse_history(&mut self, ui: &egui::Ui) {
+        let (up, down) = ui.input(|input| {
+            (
+                input.key_pressed(egui::Key::ArrowUp),
+                input.key_pressed(egui::Key::ArrowDown),
+            )
+        });
+        let index = match (up, down, self.history_index) {
+            (true, _, None) => self.history.len().checked_sub(1),
+            (true, _, Some(index)) => Some(index.saturating_sub(1)),
+            (false, true, Some(index)) if index + 1 < self.history.len() => Some(index + 1),
+            (false, true, Some(_)) => {
+                self.input.clear();
+                None
+            }
+            _ => return,
+        };
+        self.history_index = index;
+        if let Some(line) = index.and_then(|index| self.history.get(index)) {
+            self.input = line.clone();
+        }
+    }
+}
+
+fn level_color(level: Level) -> egui::Color32 {
+    match level {
+        Level::ERROR => egui::Color32::from_rgb(240, 90, 90),
+        Level::WARN => egui::Color32::from_rgb(230, 190, 80),
+        Level::INFO => egui::Color32::from_rgb(210, 210, 210),
+        Level::DEBUG => egui::Color32::from_rgb(140, 170, 220),
+        Level::TRACE => egui::Color32::GRAY,
+    }
+}
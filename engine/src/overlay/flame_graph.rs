@@ -0,0 +1,85 @@
+use crate::profiler::FrameProfile;
+use egui_winit_vulkano::egui::{self, Align2, Color32, FontId, Rect, Sense, Stroke, pos2, vec2};
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+const ROW_HEIGHT: f32 = 18.0;
+const THREAD_GAP: f32 = 6.0;
+
+/// Draws one frame's spans as a flame graph: time runs left to right, nested spans stack
+/// downwards, and each thread gets its own band.
+pub fn show(ui: &mut egui::Ui, profile: &FrameProfile) {
+    let frame_ms = profile.duration.as_secs_f64() * 1000.0;
+    ui.label(format!(
+        "Frame {} | {:.2} ms | {} spans",
+        profile.frame,
+        frame_ms,
+        profile.scopes.len()
+    ));
+
+    let mut threads: BTreeMap<&str, usize> = BTreeMap::new();
+    for scope in &profile.scopes {
+        let rows = threads.entry(scope.thread.as_str()).or_default();
+        *rows = (*rows).max(scope.depth + 1);
+    }
+    let total_rows: usize = threads.values().sum();
+    let height = total_rows as f32 * ROW_HEIGHT + threads.len() as f32 * THREAD_GAP;
+
+    let width = ui.available_width();
+    let (response, painter) =
+        ui.allocate_painter(vec2(width, height.max(ROW_HEIGHT)), Sense::hover());
+    let origin = response.rect.min;
+    let frame_secs = profile.duration.as_secs_f32().max(f32::EPSILON);
+    let hover = response.hover_pos();
+    let mut hovered_text = None;
+
+    let mut band_top = origin.y;
+    for (thread, rows) in &threads {
+        for scope in profile.scopes.iter().filter(|s| s.thread == *thread) {
+            let x0 = origin.x + scope.start.as_secs_f32() / frame_secs * width;
+            let x1 = x0 + (scope.duration.as_secs_f32() / frame_secs * width).max(1.0);
+            let y0 = band_top + scope.depth as f32 * ROW_HEIGHT;
+            let rect = Rect::from_min_max(pos2(x0, y0), pos2(x1, y0 + ROW_HEIGHT - 1.0));
+
+            painter.rect_filled(rect, 2.0, scope_color(scope.name));
+            let label = format!(
+                "{} {:.2} ms",
+                scope.name,
+                scope.duration.as_secs_f64() * 1000.0
+            );
+            if rect.width() > 40.0 {
+                painter.with_clip_rect(rect).text(
+                    rect.left_center() + vec2(4.0, 0.0),
+                    Align2::LEFT_CENTER,
+                    &label,
+                    FontId::monospace(11.0),
+                    Color32::BLACK,
+                );
+            }
+            if hover.is_some_and(|pos| rect.contains(pos)) {
+                hovered_text = Some(format!("{label} ({thread})"));
+            }
+        }
+        band_top += *rows as f32 * ROW_HEIGHT + THREAD_GAP;
+        painter.line_segment(
+            [
+                pos2(origin.x, band_top - THREAD_GAP / 2.0),
+                pos2(origin.x + width, band_top - THREAD_GAP / 2.0),
+            ],
+            Stroke::new(1.0, Color32::DARK_GRAY),
+        );
+    }
+
+    if let Some(text) = hovered_text {
+        response.on_hover_text_at_pointer(text);
+    }
+}
+
+/// Stable pastel color per span name, so the same span keeps its color between frames.
+fn scope_color(name: &str) -> Color32 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    let hash = hasher.finish();
+    let channel = |shift: u64| 140 + ((hash >> shift) & 0x5f) as u8;
+    Color32::from_rgb(channel(0), channel(8), channel(16))
+}
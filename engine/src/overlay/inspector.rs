@@ -0,0 +1,329 @@
+use crate::asset_loader::gltf_model::GltfModel;
+use crate::core::transform::Transform;
+use crate::renderer::{MaterialDesc, MaterialHandle};
+use crate::scene::{Entity, Visibility, World};
+use egui_winit_vulkano::egui::{self, collapsing_header::CollapsingState};
+use glam::{EulerRot, Quat, Vec3};
+use std::collections::HashMap;
+
+/// What the inspector shows the details of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Entity(Entity),
+    /// A node of the displayed model, by index into its nodes.
+    ModelNode(usize),
+    /// A material of the displayed model, by index into its materials.
+    Material(usize),
+}
+
+/// A change the engine applies after the inspector is drawn, since it owns the renderer.
+#[derive(Debug, Clone)]
+pub enum InspectorEdit {
+    /// New local transform of a model node.
+    NodeTransform(usize, Transform),
+    /// New visibility of a model node.
+    NodeVisibility(usize, Visibility),
+    Material(usize, MaterialDesc),
+}
+
+/// The model on screen, as far as the inspector shows it.
+pub struct SceneView<'a> {
+    pub model: &'a GltfModel,
+    /// Local transforms edited so far, replacing the nodes' own.
+    pub node_overrides: &'a HashMap<usize, Transform>,
+    /// Visibilities edited so far, replacing the nodes' own.
+    pub node_visibility: &'a HashMap<usize, Visibility>,
+    /// Renderer material of each glTF material, and what it was created with.
+    pub materials: &'a [(MaterialHandle, MaterialDesc)],
+}
+
+/// Lists the entity hierarchy, the displayed model's nodes and materials, and the
+/// engine's resources, and edits whatever is selected.
+#[derive(Default)]
+pub struct Inspector {
+    selected: Option<Selection>,
+}
+
+impl Inspector {
+    pub fn selected(&self) -> Option<Selection> {
+        self.selected
+    }
+
+    /// Selects something from outside the panel, e.g. an entity picked in the scene.
+    pub fn select(&mut self, selection: Option<Selection>) {
+        self.selected = selection;
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        world: &mut World,
+        scene: Option<SceneView>,
+        resources: &[&str],
+    ) -> Vec<InspectorEdit> {
+        let mut edits = Vec::new();
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() / 2.0)
+            .show(ui, |ui| {
+                egui::CollapsingHeader::new("Entities")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        let roots = world.roots().collect::<Vec<_>>();
+                        if roots.is_empty() {
+                            ui.weak("No entities");
+                        }
+                        for entity in roots {
+                            self.entity_tree(ui, world, entity);
+                        }
+                    });
+                if let Some(scene) = scene.as_ref() {
+                    egui::CollapsingHeader::new("Model").show(ui, |ui| {
+                        for node in scene.model.root_nodes() {
+                            self.node_tree(ui, scene.model, node);
+                        }
+                    });
+                    egui::CollapsingHeader::new("Materials").show(ui, |ui| {
+                        for (index, (handle, _)) in scene.materials.iter().enumerate() {
+                            let selection = Selection::Material(index);
+                            let label = if *handle == MaterialHandle::ERROR {
+                                format!("Material {index} (failed, uses error material)")
+                            } else {
+                                format!("Material {index}")
+                            };
+                            if ui
+                                .selectable_label(self.selected == Some(selection), label)
+                                .clicked()
+                            {
+                                self.selected = Some(selection);
+                            }
+                        }
+                    });
+                }
+                egui::CollapsingHeader::new(format!("Resources ({})", resources.len())).show(
+                    ui,
+                    |ui| {
+                        for name in resources {
+                            ui.monospace(*name);
+                        }
+                    },
+                );
+            });
+
+        ui.separator();
+        match self.selected {
+            Some(Selection::Entity(entity)) => match world.node_mut(entity) {
+                Some(node) => {
+                    ui.heading(entity_label(node.name.as_deref(), entity));
+                    transform_editor(ui, &mut node.transform);
+                }
+                None => self.selected = None,
+            },
+            Some(Selection::ModelNode(index)) => {
+                if let Some(scene) = scene.as_ref()
+                    && let Some(node) = scene.model.nodes.get(index)
+                {
+                    ui.heading(format!("Node {index}"));
+                    let mut transform = scene
+                        .node_overrides
+                        .get(&index)
+                        .copied()
+                        .unwrap_or(node.transform);
+                    if transform_editor(ui, &mut transform) {
+                        edits.push(InspectorEdit::NodeTransform(index, transform));
+                    }
+                    let mut visibility = scene
+                        .node_visibility
+                        .get(&index)
+                        .copied()
+                        .unwrap_or(node.visibility);
+                    if visibility_editor(ui, &mut visibility) {
+                        edits.push(InspectorEdit::NodeVisibility(index, visibility));
+                    }
+                }
+            }
+            Some(Selection::Material(index)) => {
+                if let Some(scene) = scene.as_ref()
+                    && let Some(&(handle, mut desc)) = scene.materials.get(index)
+                    && handle != MaterialHandle::ERROR
+                {
+                    ui.heading(format!("Material {index}"));
+                    if material_editor(ui, &mut desc) {
+                        edits.push(InspectorEdit::Material(index, desc));
+                    }
+                }
+            }
+            None => {
+                ui.weak("Select an entity, node or material");
+            }
+        }
+        edits
+    }
+
+    fn entity_tree(&mut self, ui: &mut egui::Ui, world: &World, entity: Entity) {
+        let Some(node) = world.node(entity) else {
+            return;
+        };
+        let selection = Selection::Entity(entity);
+        let label = entity_label(node.name.as_deref(), entity);
+        if node.children().is_empty() {
+            if ui
+                .selectable_label(self.selected == Some(selection), label)
+                .clicked()
+            {
+                self.selected = Some(selection);
+            }
+            return;
+        }
+        CollapsingState::load_with_default_open(ui.ctx(), ui.make_persistent_id(entity), false)
+            .show_header(ui, |ui| {
+                if ui
+                    .selectable_label(self.selected == Some(selection), label)
+                    .clicked()
+                {
+                    self.selected = Some(selection);
+                }
+            })
+            .body(|ui| {
+                for &child in node.children() {
+                    self.entity_tree(ui, world, child);
+                }
+            });
+    }
+
+    fn node_tree(&mut self, ui: &mut egui::Ui, model: &GltfModel, index: usize) {
+        let Some(node) = model.nodes.get(index) else {
+            return;
+        };
+        let selection = Selection::ModelNode(index);
+        let label = match node.mesh_id {
+            Some(mesh) => format!("Node {index} (mesh {mesh})"),
+            None => format!("Node {index}"),
+        };
+        if node.children.is_empty() {
+            if ui
+                .selectable_label(self.selected == Some(selection), label)
+                .clicked()
+            {
+                self.selected = Some(selection);
+            }
+            return;
+        }
+        let id = ui.make_persistent_id(("model_node", index));
+        CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                if ui
+                    .selectable_label(self.selected == Some(selection), label)
+                    .clicked()
+                {
+                    self.selected = Some(selection);
+                }
+            })
+            .body(|ui| {
+                for &child in &node.children {
+                    self.node_tree(ui, model, child);
+                }
+            });
+    }
+}
+
+fn entity_label(name: Option<&str>, entity: Entity) -> String {
+    match name {
+        Some(name) => name.to_owned(),
+        None => format!("{entity:?}"),
+    }
+}
+
+/// Edits translation, rotation as XYZ Euler angles in degrees, and scale. Returns
+/// whether anything changed.
+fn transform_editor(ui: &mut egui::Ui, transform: &mut Transform) -> bool {
+    let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
+    let mut rotation = Vec3::new(x, y, z) * (180.0 / std::f32::consts::PI);
+    let mut changed = false;
+    egui::Grid::new("transform").num_columns(2).show(ui, |ui| {
+        ui.label("Translation");
+        changed |= vec3_editor(ui, &mut transform.translation, 0.05);
+        ui.end_row();
+        ui.label("Rotation");
+        if vec3_editor(ui, &mut rotation, 1.0) {
+            let radians = rotation * (std::f32::consts::PI / 180.0);
+            transform.rotation = Quat::from_euler(EulerRot::XYZ, radians.x, radians.y, radians.z);
+            changed = true;
+        }
+        ui.end_row();
+        ui.label("Scale");
+        changed |= vec3_editor(ui, &mut transform.scale, 0.01);
+        ui.end_row();
+    });
+    changed
+}
+
+fn visibility_editor(ui: &mut egui::Ui, visibility: &mut Visibility) -> bool {
+    ui.horizontal(|ui| {
+        ui.label("Visibility");
+        let mut changed = false;
+        for (value, label) in [
+            (Visibility::Inherit, "Inherit"),
+            (Visibility::Visible, "Visible"),
+            (Visibility::Hidden, "Hidden"),
+        ] {
+            changed |= ui.selectable_value(visibility, value, label).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+fn vec3_editor(ui: &mut egui::Ui, value: &mut Vec3, speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        let mut changed = false;
+        for axis in [&mut value.x, &mut value.y, &mut value.z] {
+            changed |= ui
+                .add(egui::DragValue::new(axis).speed(speed).max_decimals(3))
+                .changed();
+        }
+        changed
+    })
+    .inner
+}
+
+/// Edits the parameters that don't need new textures. Returns whether anything changed.
+fn material_editor(ui: &mut egui::Ui, desc: &mut MaterialDesc) -> bool {
+    let mut changed = false;
+    egui::Grid::new("material").num_columns(2).show(ui, |ui| {
+        ui.label("Base color");
+        let mut color = desc.base_color_factor.to_array();
+        if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+            desc.base_color_factor = color.into();
+            changed = true;
+        }
+        ui.end_row();
+        ui.label("Texture");
+        ui.label(match desc.base_color_texture {
+            Some(texture) => format!("{texture}"),
+            None => "none".to_owned(),
+        });
+        ui.end_row();
+        ui.label("Double sided");
+        changed |= ui.checkbox(&mut desc.double_sided, "").changed();
+        ui.end_row();
+        ui.label("Blend");
+        changed |= ui.checkbox(&mut desc.blend, "").changed();
+        ui.end_row();
+        ui.label("Alpha test");
+        ui.horizontal(|ui| {
+            let mut alpha_test = desc.alpha_cutoff.is_some();
+            if ui.checkbox(&mut alpha_test, "").changed() {
+                desc.alpha_cutoff = alpha_test.then_some(0.5);
+                changed = true;
+            }
+            if let Some(cutoff) = desc.alpha_cutoff.as_mut() {
+                changed |= ui.add(egui::Slider::new(cutoff, 0.0..=1.0)).changed();
+            }
+        });
+        ui.end_row();
+        ui.label("Unlit");
+        changed |= ui.checkbox(&mut desc.unlit, "").changed();
+        ui.end_row();
+    });
+    changed
+}
@@ -0,0 +1,144 @@
+use crate::asset_loader::{AssetStats, LoadProgress};
+use crate::billboard::ScreenBillboard;
+use crate::camera::Camera;
+use crate::frame_stats::FrameStats;
+use crate::profiler::FrameProfile;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::World;
+use anyhow::Result;
+use egui_winit_vulkano::egui;
+use serde::{Deserialize, Serialize};
+
+mod billboard;
+mod console;
+mod flame_graph;
+mod inspector;
+mod performance;
+mod preview;
+
+pub use console::{Console, ConsoleCommand, HELP as CONSOLE_HELP};
+pub use inspector::{Inspector, InspectorEdit, SceneView, Selection};
+pub use preview::EditorPreview;
+
+/// Built-in debug windows drawn on top of the scene.
+#[derive(Default, Serialize, Deserialize)]
+pub struct DebugOverlay {
+    /// Flame graph of the previous frame's spans. Toggled with F2.
+    pub show_profiler: bool,
+    /// Frame times, draw counts, GPU memory and asset counts. Toggled with F3.
+    pub show_performance: bool,
+    /// Entity hierarchy, model nodes, materials and resources. Toggled with F4.
+    #[serde(default)]
+    pub show_inspector: bool,
+    /// Log viewer and command line. Toggled with the backtick key.
+    #[serde(skip)]
+    pub console: Console,
+    #[serde(skip)]
+    pub inspector: Inspector,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        DebugOverlay::default()
+    }
+
+    pub fn toggle_profiler(&mut self) {
+        self.show_profiler = !self.show_profiler;
+    }
+
+    pub fn toggle_performance(&mut self) {
+        self.show_performance = !self.show_performance;
+    }
+
+    pub fn toggle_inspector(&mut self) {
+        self.show_inspector = !self.show_inspector;
+    }
+
+    pub fn toggle_console(&mut self) {
+        self.console.toggle();
+    }
+
+    pub fn draw(
+        &mut self,
+        ctx: &egui::Context,
+        profile: &FrameProfile,
+        stats: &FrameStats,
+        assets: &AssetStats,
+    ) {
+        if self.show_profiler {
+            egui::Window::new("Profiler")
+                .open(&mut self.show_profiler)
+                .default_width(600.0)
+                .show(ctx, |ui| flame_graph::show(ui, profile));
+        }
+        if self.show_performance {
+            egui::Window::new("Performance")
+                .open(&mut self.show_performance)
+                .default_width(320.0)
+                .show(ctx, |ui| performance::show(ui, stats, assets));
+        }
+    }
+
+    /// Draws the inspector if it's open and returns the edits made in it this frame.
+    /// Entity transforms are edited in `world` directly.
+    pub fn draw_inspector(
+        &mut self,
+        ctx: &egui::Context,
+        world: &mut World,
+        scene: Option<SceneView>,
+        resources: &[&str],
+    ) -> Vec<InspectorEdit> {
+        let mut edits = Vec::new();
+        if self.show_inspector {
+            let inspector = &mut self.inspector;
+            egui::Window::new("Inspector")
+                .open(&mut self.show_inspector)
+                .default_width(320.0)
+                .show(ctx, |ui| {
+                    edits = inspector.show(ui, world, scene, resources)
+                });
+        }
+        edits
+    }
+
+    /// World-space markers placed by [`Billboards::visible`](crate::billboard::Billboards::visible).
+    pub fn draw_billboards(ctx: &egui::Context, billboards: &[ScreenBillboard]) {
+        billboard::show(ctx, billboards);
+    }
+
+    /// The [`EditorPreview`] window, if the preview is open.
+    pub fn draw_preview(ctx: &egui::Context, preview: &mut EditorPreview, game_camera: &Camera) {
+        if !preview.enabled {
+            return;
+        }
+        let mut open = true;
+        egui::Window::new("Editor camera")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| preview::show(ui, preview, game_camera));
+        preview.enabled = open;
+    }
+
+    /// Centered progress bar shown while startup assets load.
+    pub fn draw_loading(ctx: &egui::Context, progress: &LoadProgress) {
+        egui::Window::new("Loading")
+            .title_bar(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Loading assets {}/{} ({} KiB)",
+                    progress.loaded + progress.failed,
+                    progress.total,
+                    progress.bytes / 1024
+                ));
+                ui.add(egui::ProgressBar::new(progress.fraction()).desired_width(240.0));
+            });
+    }
+}
+
+impl FromResources for DebugOverlay {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(DebugOverlay::new())
+    }
+}
@@ -0,0 +1,106 @@
+use crate::asset_loader::AssetStats;
+use crate::frame_stats::FrameStats;
+use egui_winit_vulkano::egui::{self, Color32, Sense, Stroke, pos2, vec2};
+use std::time::Duration;
+
+const GRAPH_HEIGHT: f32 = 60.0;
+/// Frame time at the top of the graph; slower frames are clipped.
+const GRAPH_MAX_MS: f32 = 50.0;
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Draws frame timings, draw counts, GPU memory and asset counts, with a graph of recent
+/// frame times.
+pub fn show(ui: &mut egui::Ui, stats: &FrameStats, assets: &AssetStats) {
+    ui.label(format!(
+        "{:.1} FPS | {:.1} 1% low",
+        stats.fps(),
+        stats.one_percent_low_fps()
+    ));
+    frame_graph(ui, stats);
+
+    egui::Grid::new("performance")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Frame");
+            ui.label(format!("{:.2} ms", stats.frame_time_ms()));
+            ui.end_row();
+            ui.label("CPU");
+            ui.label(format!(
+                "{} (update {}, render {})",
+                ms(stats.cpu_time()),
+                ms(stats.update_time()),
+                ms(stats.render_time())
+            ));
+            ui.end_row();
+            ui.label("GPU");
+            ui.label(stats.gpu_time().map_or_else(|| "n/a".to_owned(), ms));
+            ui.end_row();
+            ui.label("Draws");
+            ui.label(format!(
+                "{} calls, {} triangles, {} culled",
+                stats.draw_calls(),
+                stats.triangles(),
+                stats.culled()
+            ));
+            ui.end_row();
+            ui.label("VRAM");
+            ui.label(format!(
+                "{:.1} / {:.0} MiB",
+                stats.gpu_memory_used() as f64 / MIB,
+                stats.gpu_memory_total() as f64 / MIB
+            ));
+            ui.end_row();
+            ui.label("Assets");
+            ui.label(format!(
+                "{} loaded, {} loading, {} failed, {:.1} MiB read",
+                assets.loaded,
+                assets.loading,
+                assets.failed,
+                assets.bytes as f64 / MIB
+            ));
+            ui.end_row();
+        });
+}
+
+/// Recent frame times as a line, newest on the right, with guides at 60 and 30 FPS.
+fn frame_graph(ui: &mut egui::Ui, stats: &FrameStats) {
+    let width = ui.available_width();
+    let (response, painter) = ui.allocate_painter(vec2(width, GRAPH_HEIGHT), Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, Color32::from_black_alpha(128));
+
+    let y_for = |ms: f32| rect.bottom() - (ms / GRAPH_MAX_MS).min(1.0) * rect.height();
+    for (ms, label) in [(1000.0 / 60.0, "60"), (1000.0 / 30.0, "30")] {
+        let y = y_for(ms);
+        painter.line_segment(
+            [pos2(rect.left(), y), pos2(rect.right(), y)],
+            Stroke::new(1.0, Color32::DARK_GRAY),
+        );
+        painter.text(
+            pos2(rect.left() + 2.0, y),
+            egui::Align2::LEFT_BOTTOM,
+            label,
+            egui::FontId::monospace(9.0),
+            Color32::GRAY,
+        );
+    }
+
+    let history: Vec<f32> = stats
+        .history()
+        .map(|time| time.as_secs_f32() * 1000.0)
+        .collect();
+    let step = rect.width() / history.len().saturating_sub(1).max(1) as f32;
+    let points = history
+        .iter()
+        .enumerate()
+        .map(|(i, ms)| pos2(rect.left() + i as f32 * step, y_for(*ms)))
+        .collect::<Vec<_>>();
+    painter.add(egui::Shape::line(
+        points,
+        Stroke::new(1.0, Color32::LIGHT_GREEN),
+    ));
+}
+
+fn ms(time: Duration) -> String {
+    format!("{:.2} ms", time.as_secs_f64() * 1000.0)
+}
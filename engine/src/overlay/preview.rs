@@ -0,0 +1,74 @@
+use crate::camera::Camera;
+use crate::core::transform::Transform;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::RenderLayers;
+use anyhow::Result;
+use egui_winit_vulkano::egui;
+use glam::Vec3;
+
+/// A second camera drawn into an offscreen image and shown in the "Editor camera"
+/// window, to look at the scene from outside the game camera, e.g. while checking
+/// culling. Toggled with F6. While open, the scene is drawn twice per frame.
+#[derive(Debug, Clone)]
+pub struct EditorPreview {
+    pub enabled: bool,
+    pub camera: Camera,
+    /// Size of the offscreen image in pixels.
+    pub size: [u32; 2],
+    /// Overlay texture of the image, set by the renderer while the preview is open.
+    pub texture: Option<egui::TextureId>,
+}
+
+impl EditorPreview {
+    /// A closed preview looking down at the origin from further out than the default
+    /// [`Camera`].
+    pub fn new() -> Self {
+        EditorPreview {
+            enabled: false,
+            camera: Camera {
+                transform: Transform::from_translation(Vec3::new(-8.0, -8.0, 10.0))
+                    .looking_at(Vec3::ZERO, Vec3::Z),
+                far: 50.0,
+                // The editor sees everything, whichever camera it's meant for.
+                layers: RenderLayers::ALL,
+                ..Camera::new()
+            },
+            size: [480, 270],
+            texture: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+}
+
+impl Default for EditorPreview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for EditorPreview {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(EditorPreview::new())
+    }
+}
+
+/// Draws the preview image at its pixel size, and a button moving the preview camera to
+/// `game_camera`.
+pub fn show(ui: &mut egui::Ui, preview: &mut EditorPreview, game_camera: &Camera) {
+    let size =
+        egui::vec2(preview.size[0] as f32, preview.size[1] as f32) / ui.ctx().pixels_per_point();
+    match preview.texture {
+        Some(texture) => {
+            ui.image((texture, size));
+        }
+        None => {
+            ui.allocate_ui(size, |ui| ui.label("Waiting for the renderer..."));
+        }
+    }
+    if ui.button("Match game camera").clicked() {
+        preview.camera = *game_camera;
+    }
+}
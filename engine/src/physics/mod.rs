@@ -0,0 +1,238 @@
+use crate::asset_loader::AssetLoader;
+use crate::core::bounds::Sphere;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::{Entity, Model, World};
+use anyhow::Result;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+pub use crate::core::bounds::{Aabb, Ray};
+
+/// Collision shape in the local space of its entity.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Sphere { radius: f32 },
+    Box { half_extents: Vec3 },
+}
+
+impl Shape {
+    /// Radius of a sphere around the origin that contains the shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            Shape::Sphere { radius } => *radius,
+            Shape::Box { half_extents } => half_extents.length(),
+        }
+    }
+}
+
+/// Component making an entity visible to [`PhysicsWorld`] queries. Registered as
+/// `collider` for scene files, e.g. `"collider": (shape: Sphere(radius: 0.5))`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Collider {
+    pub shape: Shape,
+}
+
+/// Closest intersection found by a query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub entity: Entity,
+    /// Distance along the ray from its origin.
+    pub distance: f32,
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+/// Collider in world space, as of the last [`PhysicsWorld::sync`].
+#[derive(Debug, Clone, Copy)]
+enum Body {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    Box {
+        to_world: Mat4,
+        to_local: Mat4,
+        local: Aabb,
+        world: Aabb,
+    },
+}
+
+impl Body {
+    fn new(shape: Shape, transform: Mat4) -> Self {
+        match shape {
+            Shape::Sphere { radius } => {
+                let (scale, _, translation) = transform.to_scale_rotation_translation();
+                Body::Sphere {
+                    center: translation,
+                    radius: radius * scale.abs().max_element(),
+                }
+            }
+            Shape::Box { half_extents } => {
+                let local = Aabb::from_center_half_extents(Vec3::ZERO, half_extents);
+                Body::Box {
+                    to_world: transform,
+                    to_local: transform.inverse(),
+                    local,
+                    world: local.transformed(&transform),
+                }
+            }
+        }
+    }
+
+    /// Distance and normal where `ray` enters this body grown by `margin`. Boxes with a
+    /// margin are tested through their world-space bounds, which is conservative.
+    fn cast(&self, ray: &Ray, margin: f32) -> Option<(f32, Vec3)> {
+        match *self {
+            Body::Sphere { center, radius } => {
+                let distance = Sphere::new(center, radius + margin).ray_intersection(ray)?;
+                let normal = (ray.at(distance) - center).normalize_or(-ray.direction);
+                Some((distance, normal))
+            }
+            Body::Box { world, .. } if margin > 0.0 => world
+                .inflate(margin)
+                .ray_intersection(ray.origin, ray.direction),
+            Body::Box {
+                to_local, local, ..
+            } => {
+                // In local space the box is axis-aligned; the unnormalized local direction
+                // keeps distances in world units.
+                let origin = to_local.transform_point3(ray.origin);
+                let direction = to_local.transform_vector3(ray.direction);
+                let (distance, normal) = local.ray_intersection(origin, direction)?;
+                let normal = to_local
+                    .transpose()
+                    .transform_vector3(normal)
+                    .normalize_or(-ray.direction);
+                Some((distance, normal))
+            }
+        }
+    }
+
+    fn overlaps_sphere(&self, center: Vec3, radius: f32) -> bool {
+        match *self {
+            Body::Sphere {
+                center: body_center,
+                radius: body_radius,
+            } => center.distance_squared(body_center) <= (radius + body_radius).powi(2),
+            Body::Box {
+                to_world,
+                to_local,
+                local,
+                ..
+            } => {
+                let closest = local.closest_point(to_local.transform_point3(center));
+                to_world.transform_point3(closest).distance_squared(center) <= radius * radius
+            }
+        }
+    }
+
+    fn overlaps_aabb(&self, aabb: &Aabb) -> bool {
+        match *self {
+            Body::Sphere { center, radius } => Sphere::new(center, radius).intersects_aabb(aabb),
+            Body::Box { world, .. } => world.intersects(aabb),
+        }
+    }
+}
+
+/// Spatial queries against the [`Collider`]s of the [`World`].
+///
+/// There is no simulation yet; this only answers raycasts, shape casts and overlap tests
+/// for picking, shooting and line of sight. Colliders are snapshotted by
+/// [`PhysicsWorld::sync`], which the engine runs at the start of every update.
+#[derive(Default)]
+pub struct PhysicsWorld {
+    bodies: Vec<(Entity, Body)>,
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rebuilds the world-space colliders from the current entity transforms.
+    pub fn sync(&mut self, world: &World) {
+        self.bodies.clear();
+        for (entity, collider) in world.query::<Collider>() {
+            if let Some(transform) = world.world_transform(entity) {
+                self.bodies
+                    .push((entity, Body::new(collider.shape, transform)));
+            }
+        }
+    }
+
+    /// Nearest collider hit by `ray` within its max distance.
+    pub fn raycast(&self, ray: &Ray) -> Option<Hit> {
+        self.cast(ray, 0.0)
+    }
+
+    /// Sweeps `shape` from the ray origin along the ray and returns the first collider it
+    /// touches. The swept shape is approximated by its bounding sphere, so hits may come
+    /// slightly early but are never missed.
+    pub fn shape_cast(&self, shape: Shape, ray: &Ray) -> Option<Hit> {
+        self.cast(ray, shape.bounding_radius())
+    }
+
+    /// Entities whose colliders touch `shape` placed at `position` (boxes axis-aligned).
+    pub fn overlap(&self, shape: Shape, position: Vec3) -> Vec<Entity> {
+        self.bodies
+            .iter()
+            .filter(|(_, body)| match shape {
+                Shape::Sphere { radius } => body.overlaps_sphere(position, radius),
+                Shape::Box { half_extents } => {
+                    body.overlaps_aabb(&Aabb::from_center_half_extents(position, half_extents))
+                }
+            })
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    fn cast(&self, ray: &Ray, margin: f32) -> Option<Hit> {
+        self.bodies
+            .iter()
+            .filter_map(|(entity, body)| {
+                let (distance, normal) = body.cast(ray, margin)?;
+                (distance <= ray.max_distance).then_some(Hit {
+                    entity: *entity,
+                    distance,
+                    point: ray.at(distance),
+                    normal,
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
+impl FromResources for PhysicsWorld {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        resources.init::<World>()?;
+        resources
+            .get_mut::<World>()
+            .register_component::<Collider>("collider");
+        Ok(PhysicsWorld::new())
+    }
+}
+
+/// Raycast against the bounding boxes of loaded [`Model`]s, for picking entities that
+/// have no [`Collider`]. Bounds are computed per call, so keep it to occasional queries.
+pub fn raycast_meshes(world: &World, assets: &AssetLoader, ray: &Ray) -> Option<Hit> {
+    world
+        .query::<Model>()
+        .filter_map(|(entity, model)| {
+            let bounds = assets.asset(&model.handle)?.read().bounds()?;
+            let transform = world.world_transform(entity)?;
+            let body = Body::new(
+                Shape::Box {
+                    half_extents: bounds.half_extents(),
+                },
+                transform * Mat4::from_translation(bounds.center()),
+            );
+            let (distance, normal) = body.cast(ray, 0.0)?;
+            (distance <= ray.max_distance).then_some(Hit {
+                entity,
+                distance,
+                point: ray.at(distance),
+                normal,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
@@ -10,7 +10,11 @@ pub trait Platform {
     where
         Self: Sized;
 
-    /// Runs the engine, taking over the main thread.
-    /// This function will never return.
-    fn run(self);
+    /// Runs the engine, taking over the main thread until the window closes.
+    /// Borrowed rather than consumed so [`Application`](crate::application::Application)
+    /// can still shut the engine down afterwards.
+    fn run(&mut self);
+
+    /// Shuts the engine down cleanly. Called when the window closes or the loop exits.
+    fn shutdown(&mut self);
 }
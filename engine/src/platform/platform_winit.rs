@@ -1,28 +1,61 @@
 use std::sync::Arc;
+use std::time::Instant;
 
-use crate::engine::Engine;
+use crate::engine::{Engine, UpdateMode};
 use crate::platform::Platform;
 use tracing::info;
 use winit::application::ApplicationHandler;
 use winit::event::WindowEvent;
-use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::window::{Window as WinitWindow, WindowId};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy};
+use winit::window::WindowId;
+
+/// Custom events delivered through winit's [`EventLoopProxy`].
+#[derive(Debug, Clone, Copy)]
+pub enum PlatformEvent {
+    /// An event was queued from another thread; run a frame to deliver it.
+    Wake,
+}
 
 pub struct WinitPlatform {
     app: Engine,
+    /// When the next tick is due in [`UpdateMode::WaitUntil`].
+    next_frame: Instant,
+    /// An input or wake-up arrived that [`UpdateMode::Reactive`] should tick for.
+    update_pending: bool,
+    /// Set once the window and renderer exist.
+    started: bool,
+}
+
+impl WinitPlatform {
+    /// Hands the engine a waker backed by `proxy`, so background threads can interrupt
+    /// the event loop while it waits.
+    fn connect_proxy(&mut self, proxy: EventLoopProxy<PlatformEvent>) {
+        self.app.set_waker(move || {
+            // Fails only once the loop has exited, when nobody is listening anyway.
+            let _ = proxy.send_event(PlatformEvent::Wake);
+        });
+    }
 }
 
-impl ApplicationHandler for WinitPlatform {
+impl ApplicationHandler<PlatformEvent> for WinitPlatform {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let winit_window = Arc::new(
             event_loop
-                .create_window(WinitWindow::default_attributes())
+                .create_window(self.app.window_descriptor().attributes())
                 .unwrap(),
         );
         info!("Created window with ID: {:?}", winit_window.id());
-        winit_window.set_title("Elements");
         self.app.set_window(winit_window);
         self.app.run();
+        self.app.init_ui(event_loop);
+        self.started = true;
+        self.update_pending = true;
+    }
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: PlatformEvent) {
+        match event {
+            PlatformEvent::Wake => self.update_pending = true,
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
@@ -30,26 +63,73 @@ impl ApplicationHandler for WinitPlatform {
         match event {
             WindowEvent::CloseRequested => {
                 info!("The close button was pressed; stopping");
+                self.app.shutdown();
                 event_loop.exit();
             }
-            WindowEvent::RedrawRequested => {
-                self.app.on_update();
+            WindowEvent::RedrawRequested => self.app.render(),
+            other => {
+                self.app.handle_window_event(other);
+                self.update_pending = true;
             }
-            other => self.app.handle_window_event(other),
         }
     }
+
+    /// Ticks the simulation once all pending events are handled, then asks for a redraw
+    /// to present it. Ticking here rather than on `RedrawRequested` keeps the simulation
+    /// running when the compositor withholds redraws.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.started || self.app.is_shut_down() {
+            return;
+        }
+        let mode = self.app.update_mode();
+        let now = Instant::now();
+        let pending = std::mem::take(&mut self.update_pending);
+        let due = match mode {
+            UpdateMode::Continuous => true,
+            UpdateMode::Reactive => pending,
+            UpdateMode::WaitUntil { .. } => now >= self.next_frame,
+        };
+        if due {
+            if let Some(interval) = mode.frame_interval() {
+                self.next_frame = now + interval;
+            }
+            self.app.on_update();
+            self.app.refresh_ui(event_loop);
+            self.app.request_redraw();
+        }
+        event_loop.set_control_flow(match mode {
+            UpdateMode::Continuous => ControlFlow::Poll,
+            UpdateMode::Reactive => ControlFlow::Wait,
+            UpdateMode::WaitUntil { .. } => ControlFlow::WaitUntil(self.next_frame),
+        });
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.app.shutdown();
+    }
 }
 
 impl Platform for WinitPlatform {
     fn new(app: Engine) -> Self {
-        Self { app }
+        Self {
+            app,
+            next_frame: Instant::now(),
+            update_pending: false,
+            started: false,
+        }
     }
 
     fn run(&mut self) {
         info!("Starting event loop...");
 
-        let event_loop = EventLoop::new().unwrap();
-        event_loop.set_control_flow(ControlFlow::Poll);
+        let event_loop = EventLoop::<PlatformEvent>::with_user_event()
+            .build()
+            .unwrap();
+        self.connect_proxy(event_loop.create_proxy());
         let _ = event_loop.run_app(self);
     }
+
+    fn shutdown(&mut self) {
+        self.app.shutdown();
+    }
 }
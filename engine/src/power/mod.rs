@@ -0,0 +1,169 @@
+use crate::engine::{EngineConfig, UpdateMode};
+use crate::events::Events;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+mod status;
+
+pub use status::PowerStatus;
+
+/// How often the system's power status is read.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// When the engine saves power. Part of the [`EngineConfig`] and of the [`PowerSaving`]
+/// resource, where it can be changed at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerSettings {
+    /// Skip rendering and tick at `occluded_fps` while the window is fully covered,
+    /// minimized or on a hidden workspace. On by default.
+    pub throttle_when_occluded: bool,
+    /// Cap ticks and frames at `battery_saver_fps` while the system saves battery. On by
+    /// default.
+    pub throttle_on_battery_saver: bool,
+    pub occluded_fps: u32,
+    pub battery_saver_fps: u32,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        PowerSettings {
+            throttle_when_occluded: true,
+            throttle_on_battery_saver: true,
+            occluded_fps: 5,
+            battery_saver_fps: 30,
+        }
+    }
+}
+
+impl PowerSettings {
+    /// Never throttles, e.g. for benchmarks.
+    pub fn disabled() -> Self {
+        PowerSettings {
+            throttle_when_occluded: false,
+            throttle_on_battery_saver: false,
+            ..Default::default()
+        }
+    }
+}
+
+/// Sent on the event bus when the window's occlusion or the system's power state changes,
+/// e.g. to pause music while nobody is looking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerEvent {
+    /// The window became fully hidden, or visible again.
+    Occluded(bool),
+    /// The system started or stopped running on battery.
+    OnBattery(bool),
+    /// The system's battery saver turned on or off.
+    BatterySaver(bool),
+}
+
+/// Tracks window occlusion and the system's power state, and throttles the engine
+/// according to its [`PowerSettings`]. The engine reads
+/// [`PowerSaving::update_mode`] instead of the [`UpdateMode`] resource directly.
+pub struct PowerSaving {
+    pub settings: PowerSettings,
+    occluded: bool,
+    status: PowerStatus,
+    /// Last time the power status was read; `None` before the first read.
+    last_poll: Option<Instant>,
+}
+
+impl PowerSaving {
+    pub fn new(settings: PowerSettings) -> Self {
+        PowerSaving {
+            settings,
+            occluded: false,
+            status: PowerStatus::default(),
+            last_poll: None,
+        }
+    }
+
+    /// Whether the window is fully hidden, as last reported by the platform.
+    pub fn is_occluded(&self) -> bool {
+        self.occluded
+    }
+
+    /// The system's power state as of the last poll.
+    pub fn status(&self) -> PowerStatus {
+        self.status
+    }
+
+    /// Records a change of the window's occlusion, sending a [`PowerEvent::Occluded`].
+    pub fn set_occluded(&mut self, occluded: bool, events: &mut Events) {
+        if occluded != self.occluded {
+            self.occluded = occluded;
+            info!(occluded, "Window occlusion changed");
+            events.send(PowerEvent::Occluded(occluded));
+        }
+    }
+
+    /// Reads the system's power state every few seconds and sends a [`PowerEvent`] for
+    /// each part that changed.
+    pub fn poll(&mut self, events: &mut Events) {
+        let now = Instant::now();
+        if self
+            .last_poll
+            .is_some_and(|last| now.duration_since(last) < POLL_INTERVAL)
+        {
+            return;
+        }
+        self.last_poll = Some(now);
+        let status = PowerStatus::query();
+        if status.on_battery != self.status.on_battery {
+            info!(on_battery = status.on_battery, "Power source changed");
+            events.send(PowerEvent::OnBattery(status.on_battery));
+        }
+        if status.battery_saver != self.status.battery_saver {
+            info!(
+                battery_saver = status.battery_saver,
+                "Battery saver changed"
+            );
+            events.send(PowerEvent::BatterySaver(status.battery_saver));
+        }
+        self.status = status;
+    }
+
+    /// Whether frames are skipped because nobody can see them.
+    pub fn skips_rendering(&self) -> bool {
+        self.occluded && self.settings.throttle_when_occluded
+    }
+
+    /// Highest tick rate power saving currently allows, if it limits it.
+    pub fn fps_cap(&self) -> Option<u32> {
+        let occluded = self.skips_rendering().then_some(self.settings.occluded_fps);
+        let battery_saver = (self.status.battery_saver && self.settings.throttle_on_battery_saver)
+            .then_some(self.settings.battery_saver_fps);
+        occluded.into_iter().chain(battery_saver).min()
+    }
+
+    /// `requested` with the current cap applied. A reactive loop already idles, so it's
+    /// left alone.
+    pub fn update_mode(&self, requested: UpdateMode) -> UpdateMode {
+        match (requested, self.fps_cap()) {
+            (UpdateMode::Reactive, _) | (_, None) => requested,
+            (UpdateMode::Continuous, Some(cap)) => UpdateMode::WaitUntil { fps: cap },
+            (UpdateMode::WaitUntil { fps }, Some(cap)) => {
+                UpdateMode::WaitUntil { fps: fps.min(cap) }
+            }
+        }
+    }
+}
+
+impl Default for PowerSaving {
+    fn default() -> Self {
+        Self::new(PowerSettings::default())
+    }
+}
+
+impl FromResources for PowerSaving {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        let settings = resources
+            .try_get::<EngineConfig>()
+            .map(|config| config.power)
+            .unwrap_or_default();
+        Ok(PowerSaving::new(settings))
+    }
+}
@@ -0,0 +1,58 @@
+/// What the system reports about its power supply. Everything is `false` where it can't
+/// be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    /// The user or the OS asked apps to save power, e.g. Windows' battery saver or the
+    /// `low-power` platform profile on Linux.
+    pub battery_saver: bool,
+}
+
+impl PowerStatus {
+    #[cfg(windows)]
+    pub fn query() -> Self {
+        use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+        // SAFETY: SYSTEM_POWER_STATUS is plain data, and the call only writes to it.
+        let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+        if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+            return PowerStatus::default();
+        }
+        PowerStatus {
+            on_battery: status.ACLineStatus == 0,
+            battery_saver: status.SystemStatusFlag == 1,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn query() -> Self {
+        use std::fs;
+        use std::path::Path;
+
+        let read = |path: &Path| fs::read_to_string(path).map(|text| text.trim().to_owned());
+        let mut has_battery = false;
+        let mut on_mains = false;
+        if let Ok(supplies) = fs::read_dir("/sys/class/power_supply") {
+            for supply in supplies.flatten() {
+                let path = supply.path();
+                match read(&path.join("type")).as_deref() {
+                    Ok("Battery") => has_battery = true,
+                    Ok("Mains") => {
+                        on_mains |= read(&path.join("online")).is_ok_and(|online| online == "1")
+                    }
+                    _ => {}
+                }
+            }
+        }
+        PowerStatus {
+            on_battery: has_battery && !on_mains,
+            battery_saver: read(Path::new("/sys/firmware/acpi/platform_profile"))
+                .is_ok_and(|profile| profile == "low-power"),
+        }
+    }
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    pub fn query() -> Self {
+        PowerStatus::default()
+    }
+}
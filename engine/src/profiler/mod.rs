@@ -0,0 +1,157 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::span;
+use tracing::subscriber::Subscriber;
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+#[cfg(feature = "profile-puffin")]
+pub mod puffin_layer;
+
+/// A single timed span inside a [`FrameProfile`].
+#[derive(Debug, Clone)]
+pub struct ProfileScope {
+    pub name: &'static str,
+    pub thread: String,
+    /// Nesting depth; top-level spans are 0.
+    pub depth: usize,
+    /// Offset from the start of the frame.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// All spans closed during one frame.
+#[derive(Debug, Clone, Default)]
+pub struct FrameProfile {
+    pub frame: u64,
+    pub duration: Duration,
+    pub scopes: Vec<ProfileScope>,
+}
+
+struct ProfilerState {
+    frame: u64,
+    frame_start: Instant,
+    current: Vec<ProfileScope>,
+    last_frame: FrameProfile,
+}
+
+/// Collects span timings recorded by [`ProfilerLayer`] and hands them out one frame at a
+/// time. Cheap to clone; all clones share the same data.
+#[derive(Clone)]
+pub struct Profiler {
+    state: Arc<Mutex<ProfilerState>>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            state: Arc::new(Mutex::new(ProfilerState {
+                frame: 0,
+                frame_start: Instant::now(),
+                current: Vec::new(),
+                last_frame: FrameProfile::default(),
+            })),
+        }
+    }
+
+    /// A `tracing` layer that feeds span timings into this profiler.
+    pub fn layer(&self) -> ProfilerLayer {
+        ProfilerLayer {
+            profiler: self.clone(),
+        }
+    }
+
+    /// Closes the current frame, making it available through [`Profiler::last_frame`].
+    pub fn new_frame(&self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        let scopes = std::mem::take(&mut state.current);
+        state.last_frame = FrameProfile {
+            frame: state.frame,
+            duration: now.duration_since(state.frame_start),
+            scopes,
+        };
+        state.frame += 1;
+        state.frame_start = now;
+
+        #[cfg(feature = "profile-puffin")]
+        puffin::GlobalProfiler::lock().new_frame();
+    }
+
+    pub fn last_frame(&self) -> FrameProfile {
+        self.state
+            .lock()
+            .map(|state| state.last_frame.clone())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, name: &'static str, depth: usize, start: Instant, end: Instant) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let thread = std::thread::current()
+            .name()
+            .unwrap_or("unnamed")
+            .to_string();
+        let scope = ProfileScope {
+            name,
+            thread,
+            depth,
+            start: start.saturating_duration_since(state.frame_start),
+            duration: end.duration_since(start),
+        };
+        state.current.push(scope);
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct ScopeStart {
+    start: Instant,
+    depth: usize,
+}
+
+/// `tracing` layer that times every span of this crate between enter and exit.
+pub struct ProfilerLayer {
+    profiler: Profiler,
+}
+
+impl<S> Layer<S> for ProfilerLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if !span
+            .metadata()
+            .target()
+            .starts_with(env!("CARGO_CRATE_NAME"))
+        {
+            return;
+        }
+        let depth = span.scope().skip(1).count();
+        span.extensions_mut().insert(ScopeStart {
+            start: Instant::now(),
+            depth,
+        });
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(scope) = span.extensions_mut().remove::<ScopeStart>() {
+            self.profiler
+                .record(span.name(), scope.depth, scope.start, Instant::now());
+        }
+    }
+}
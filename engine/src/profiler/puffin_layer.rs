@@ -0,0 +1,64 @@
+use puffin::{ScopeId, ThreadProfiler};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::callsite::Identifier;
+use tracing::span;
+use tracing::subscriber::Subscriber;
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+thread_local! {
+    static OPEN_SCOPES: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Mirrors `tracing` spans as puffin scopes so they show up in `puffin_viewer`.
+#[derive(Default)]
+pub struct PuffinLayer {
+    scope_ids: Mutex<HashMap<Identifier, ScopeId>>,
+}
+
+impl PuffinLayer {
+    pub fn new() -> Self {
+        puffin::set_scopes_on(true);
+        Self::default()
+    }
+
+    fn scope_id(&self, metadata: &'static tracing::Metadata<'static>) -> Option<ScopeId> {
+        let mut ids = self.scope_ids.lock().ok()?;
+        let id = *ids.entry(metadata.callsite()).or_insert_with(|| {
+            ThreadProfiler::call(|tp| {
+                tp.register_named_scope(
+                    metadata.name(),
+                    metadata.target(),
+                    metadata.file().unwrap_or_default(),
+                    metadata.line().unwrap_or_default(),
+                )
+            })
+        });
+        Some(id)
+    }
+}
+
+impl<S> Layer<S> for PuffinLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let Some(scope_id) = self.scope_id(span.metadata()) else {
+            return;
+        };
+        let offset = ThreadProfiler::call(|tp| tp.begin_scope(scope_id, ""));
+        OPEN_SCOPES.with(|scopes| scopes.borrow_mut().push(offset));
+    }
+
+    fn on_exit(&self, _id: &span::Id, _ctx: Context<'_, S>) {
+        if let Some(offset) = OPEN_SCOPES.with(|scopes| scopes.borrow_mut().pop()) {
+            ThreadProfiler::call(|tp| tp.end_scope(offset));
+        }
+    }
+}
@@ -1,17 +1,164 @@
+use crate::asset_loader::baked::BlockFormat;
+use crate::asset_loader::texture_settings::ColorSpace;
+use crate::core::mesh::MeshData;
 use crate::core::vertex::ElmVertex;
 use crate::resource_manager::ResourceManager;
+use crate::scene::RenderLayers;
 use anyhow::Result;
+use egui_winit_vulkano::egui;
+use glam::{Mat4, Vec4};
 use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+use winit::event::WindowEvent;
+use winit::event_loop::ActiveEventLoop;
 
+mod quality;
+pub mod render_world;
 pub mod renderer_vulkan;
 
+pub use quality::GraphicsQuality;
+
+/// CPU-side texture data handed to [`Renderer::upload_textures`].
+pub struct TextureUpload<'a> {
+    pub pixels: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    /// Precomputed mip levels below `pixels`. Empty to generate them on the GPU.
+    pub mips: &'a [Vec<u8>],
+    /// `pixels` and `mips` are blocks in this format rather than RGBA8. Compressed
+    /// textures never get generated mips.
+    pub block_format: Option<BlockFormat>,
+    /// How the pixels are encoded, which picks the image format. Color textures are
+    /// usually [`ColorSpace::Srgb`], normal maps and other data [`ColorSpace::Linear`].
+    pub color_space: ColorSpace,
+    /// Generate a mip chain when `mips` is empty. Off leaves the texture single-level.
+    pub generate_mips: bool,
+    pub filter: (Option<MagFilter>, Option<MinFilter>),
+    pub wrap: (WrappingMode, WrappingMode),
+}
+
+/// Sent on the event bus when the swapchain was recreated with a different format, color
+/// space or present mode, e.g. after the window moved to an HDR monitor or the driver
+/// reset. Plain resizes don't send it. The mesh pipeline and the debug overlay are
+/// rebuilt by the engine; passes of your own that render to the swapchain format should
+/// rebuild too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapchainChanged {
+    pub format: vulkano::format::Format,
+    pub color_space: vulkano::swapchain::ColorSpace,
+    pub present_mode: vulkano::swapchain::PresentMode,
+    /// Image size in pixels.
+    pub extent: [u32; 2],
+    /// The format differs from the previous swapchain's, not just the color space or
+    /// present mode.
+    pub format_changed: bool,
+}
+
+/// Identifies a material created with [`Renderer::create_material`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(pub(crate) usize);
+
+impl MaterialHandle {
+    /// Untextured white; always available, also after [`Renderer::clear_scene`].
+    pub const DEFAULT: MaterialHandle = MaterialHandle(0);
+    /// Magenta checkerboard standing in for materials and textures that failed to load, and
+    /// drawn for instances whose material is missing. Always available.
+    pub const ERROR: MaterialHandle = MaterialHandle(1);
+}
+
+impl Default for MaterialHandle {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// How a surface looks, handed to [`Renderer::create_material`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialDesc {
+    /// Multiplied with the base color texture and the vertex color.
+    pub base_color_factor: Vec4,
+    /// Texture id from [`Renderer::upload_textures`]; `None` samples plain white.
+    pub base_color_texture: Option<usize>,
+    /// Draws back faces too, using a pipeline variant without culling.
+    pub double_sided: bool,
+    /// Alpha-blends over what is behind, using the base color's alpha. Drawn after opaque
+    /// meshes, sorted back to front, without writing depth.
+    pub blend: bool,
+    /// Discards fragments whose alpha is below this and draws the rest opaque, e.g. for
+    /// foliage and fences. Uses an alpha-tested pipeline variant.
+    pub alpha_cutoff: Option<f32>,
+    /// Shows the base color as is, without lights or ambient, using an unlit pipeline
+    /// variant.
+    pub unlit: bool,
+}
+
+impl MaterialDesc {
+    pub fn new() -> Self {
+        MaterialDesc {
+            base_color_factor: Vec4::ONE,
+            base_color_texture: None,
+            double_sided: false,
+            blend: false,
+            alpha_cutoff: None,
+            unlit: false,
+        }
+    }
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait Renderer {
     fn new(resource_manager: &mut ResourceManager) -> Self
     where
         Self: std::marker::Sized;
     fn run(&mut self) -> Result<()>;
-    fn on_update(&mut self) -> Result<()>;
-    fn upload_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<()>;
+    /// Hands over a frame of the [`RenderWorld`](render_world::RenderWorld) resource. It
+    /// may still be rendering when this returns; its statistics are recorded by the next
+    /// call.
+    fn on_update(&mut self, resource_manager: &mut ResourceManager) -> Result<()>;
+    /// Uploads mesh data and returns an id for [`Renderer::add_mesh_instance`].
+    fn upload_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<usize>;
+    /// Uploads a mesh that will be changed often with [`Renderer::update_mesh`], e.g.
+    /// editable terrain or cloth. Slower to draw than [`Renderer::upload_mesh`] on GPUs
+    /// without host-visible video memory.
+    fn upload_dynamic_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<usize>;
+    /// Replaces an uploaded mesh's geometry. Safe while frames that draw it are in flight;
+    /// instances of the mesh show the new geometry from the next frame on.
+    fn update_mesh(&mut self, mesh: usize, vertices: &[ElmVertex], indices: &[u32]) -> Result<()>;
+    /// Uploads generated or hand-built mesh data, see [`MeshData`].
+    fn create_mesh(&mut self, mesh: &MeshData) -> Result<usize> {
+        self.upload_mesh(&mesh.vertices, &mesh.indices)
+    }
+    /// Draws an uploaded mesh every frame with the given material and world transform.
+    /// Returns an id for [`Renderer::set_mesh_instance_transform`]. An unknown mesh draws a
+    /// fallback unit cube and an unknown material [`MaterialHandle::ERROR`], with a warning.
+    fn add_mesh_instance(
+        &mut self,
+        mesh: usize,
+        material: MaterialHandle,
+        transform: Mat4,
+    ) -> Result<usize>;
+    /// Moves an existing mesh instance, e.g. to follow an animated node.
+    fn set_mesh_instance_transform(&mut self, instance: usize, transform: Mat4) -> Result<()>;
+    /// Draws an existing mesh instance with a different material.
+    fn set_mesh_instance_material(
+        &mut self,
+        instance: usize,
+        material: MaterialHandle,
+    ) -> Result<()>;
+    /// Hides or shows an existing mesh instance, and picks the cameras that draw it by
+    /// their [`Camera::layers`](crate::camera::Camera::layers). New instances are visible
+    /// on layer 0.
+    fn set_mesh_instance_visibility(
+        &mut self,
+        instance: usize,
+        visible: bool,
+        layers: RenderLayers,
+    ) -> Result<()>;
+    /// Uploads a texture and returns its id for [`MaterialDesc::base_color_texture`].
     fn upload_texture(
         &mut self,
         image_data: &[u8],
@@ -19,5 +166,34 @@ pub trait Renderer {
         height: u32,
         filter: (Option<MagFilter>, Option<MinFilter>),
         wrap: (WrappingMode, WrappingMode),
-    ) -> Result<()>;
+    ) -> Result<usize>;
+    /// Uploads a batch of textures, recording their transfer commands in parallel. Returns
+    /// their ids in the order of `textures`.
+    fn upload_textures(&mut self, textures: &[TextureUpload]) -> Result<Vec<usize>>;
+    /// Creates a material from uploaded textures. Unknown texture ids sample the magenta
+    /// checkerboard of [`MaterialHandle::ERROR`], with a warning.
+    fn create_material(&mut self, desc: &MaterialDesc) -> Result<MaterialHandle>;
+    /// Changes a material in place; every instance using it shows the change from the
+    /// next frame. Fails if `material` is unknown.
+    fn update_material(&mut self, material: MaterialHandle, desc: &MaterialDesc) -> Result<()>;
+    /// Drops every uploaded mesh, instance, texture and material except
+    /// [`MaterialHandle::DEFAULT`] and [`MaterialHandle::ERROR`], e.g. before re-uploading a
+    /// reloaded model.
+    fn clear_scene(&mut self) -> Result<()>;
+    /// Rebuilds the mesh pipeline from SPIR-V words. Keeps the current pipeline on error.
+    fn reload_shaders(&mut self, vertex: &[u32], fragment: &[u32]) -> Result<()>;
+    /// Draws meshes as lines instead of filled triangles. Fails if the GPU can't.
+    fn set_wireframe(&mut self, enabled: bool) -> Result<()>;
+    fn is_wireframe(&self) -> bool;
+    /// Creates the egui overlay. Must be called after [`Renderer::run`].
+    fn init_ui(&mut self, event_loop: &ActiveEventLoop) -> Result<()>;
+    /// Forwards a window event to the overlay. Returns `true` if the overlay consumed it.
+    fn handle_ui_event(&mut self, event: &WindowEvent) -> bool;
+    /// Starts a new overlay frame; the returned context is drawn with the frame handed over
+    /// by the next `on_update`.
+    fn begin_ui(&mut self) -> Option<egui::Context>;
+    /// Blocks until the GPU has finished all submitted work.
+    fn wait_idle(&mut self) -> Result<()>;
+    /// Releases every GPU object in dependency order. The renderer is unusable afterwards.
+    fn shutdown(&mut self) -> Result<()>;
 }
@@ -0,0 +1,77 @@
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Renderer settings traded against speed, for a game's graphics options menu. The
+/// renderer checks the resource every frame and rebuilds what a change affects: texture
+/// samplers and materials for `anisotropy` and `mip_bias`, the render targets and mesh
+/// pipeline for `msaa`. Values the device can't do are clamped to the nearest it can.
+///
+/// Serializable, so a settings menu can save it next to its other options.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GraphicsQuality {
+    /// Samples of anisotropic texture filtering; 1 turns it off.
+    pub anisotropy: f32,
+    /// Added to the mip level textures are sampled at. Positive values are blurrier and
+    /// cheaper, negative ones sharper and prone to shimmering.
+    pub mip_bias: f32,
+    /// Edge length of shadow maps in pixels, for when the renderer draws shadows; nothing
+    /// reads it yet.
+    pub shadow_resolution: u32,
+    /// Samples per pixel of the scene pass; 1 turns multisampling off.
+    pub msaa: u32,
+}
+
+impl GraphicsQuality {
+    /// The most the renderer does: 16x anisotropy and 8x MSAA.
+    pub fn new() -> Self {
+        GraphicsQuality {
+            anisotropy: 16.0,
+            mip_bias: 0.0,
+            shadow_resolution: 2048,
+            msaa: 8,
+        }
+    }
+
+    /// Settings for integrated GPUs: no anisotropy or multisampling, slightly blurrier
+    /// textures.
+    pub fn low() -> Self {
+        GraphicsQuality {
+            anisotropy: 1.0,
+            mip_bias: 0.5,
+            shadow_resolution: 512,
+            msaa: 1,
+        }
+    }
+
+    pub fn medium() -> Self {
+        GraphicsQuality {
+            anisotropy: 4.0,
+            mip_bias: 0.0,
+            shadow_resolution: 1024,
+            msaa: 4,
+        }
+    }
+
+    /// The preset called `low`, `medium` or `high`.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "low" => Some(Self::low()),
+            "medium" => Some(Self::medium()),
+            "high" => Some(Self::new()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for GraphicsQuality {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for GraphicsQuality {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(GraphicsQuality::new())
+    }
+}
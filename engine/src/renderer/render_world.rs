@@ -0,0 +1,65 @@
+use crate::camera::Camera;
+use crate::core::ubo::LightData;
+use crate::lighting::Lighting;
+use crate::overlay::EditorPreview;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::time::Time;
+use anyhow::Result;
+use glam::Vec3;
+use std::time::Duration;
+
+/// The simulation state a frame is drawn from, copied out at the end of
+/// [`Engine::on_update`](crate::engine::Engine::on_update). The renderer only reads this
+/// snapshot, so it can draw one frame on its own thread while the next one updates.
+#[derive(Debug, Clone)]
+pub struct RenderWorld {
+    pub camera: Camera,
+    /// Second camera, drawn into the [`EditorPreview`] target while the preview is open.
+    pub preview: Option<Camera>,
+    /// Lights of the last [`Lighting::sync`], in shader layout.
+    pub lights: Vec<LightData>,
+    pub ambient: Vec3,
+    /// Time since startup, for animated shading.
+    pub elapsed: Duration,
+}
+
+impl RenderWorld {
+    pub fn new() -> Self {
+        RenderWorld {
+            camera: Camera::default(),
+            preview: None,
+            lights: Vec::new(),
+            ambient: Vec3::ONE,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Copies the current cameras, lights and time over the last frame's, reusing its
+    /// light list.
+    pub fn extract(
+        &mut self,
+        camera: &Camera,
+        preview: &EditorPreview,
+        lighting: &Lighting,
+        time: &Time,
+    ) {
+        self.camera = *camera;
+        self.preview = preview.enabled.then_some(preview.camera);
+        self.lights.clear();
+        self.lights.extend_from_slice(lighting.lights());
+        self.ambient = lighting.ambient;
+        self.elapsed = time.elapsed();
+    }
+}
+
+impl Default for RenderWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for RenderWorld {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(RenderWorld::new())
+    }
+}
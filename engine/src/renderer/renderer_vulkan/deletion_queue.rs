@@ -0,0 +1,57 @@
+use tracing::debug;
+
+/// GPU objects that were replaced or removed while frames may still read them, kept
+/// until those frames' fences signal.
+///
+/// Vulkano's command buffers already hold on to what they use, so this doesn't guard
+/// against use-after-free. It makes release points explicit: replaced objects are
+/// dropped on the render thread right after a fence wait, instead of on whichever thread
+/// happened to replace them, possibly while a frame records.
+#[derive(Default)]
+pub struct DeferredDeletionQueue {
+    /// Objects with the number of the last frame submitted when they were enqueued.
+    pending: Vec<(u64, Box<dyn Send + Sync>)>,
+    /// Frames submitted so far; frame numbers start at 1.
+    submitted: u64,
+    /// Frames whose fences have signalled.
+    completed: u64,
+}
+
+impl DeferredDeletionQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps `object` until every frame submitted so far has finished. Dropped right away
+    /// if none is in flight.
+    pub fn push(&mut self, object: impl Send + Sync + 'static) {
+        if self.submitted > self.completed {
+            self.pending.push((self.submitted, Box::new(object)));
+        }
+    }
+
+    /// Records a submitted frame and returns its number, for
+    /// [`DeferredDeletionQueue::frame_completed`].
+    pub fn frame_submitted(&mut self) -> u64 {
+        self.submitted += 1;
+        self.submitted
+    }
+
+    /// Drops the objects frame `frame` and the ones before it could still use.
+    pub fn frame_completed(&mut self, frame: u64) {
+        self.completed = self.completed.max(frame);
+        let before = self.pending.len();
+        let completed = self.completed;
+        self.pending.retain(|(enqueued, _)| *enqueued > completed);
+        let released = before - self.pending.len();
+        if released > 0 {
+            debug!(released, frame, "Released deferred GPU objects");
+        }
+    }
+
+    /// Drops everything. Only once the device is idle.
+    pub fn flush(&mut self) {
+        self.pending.clear();
+        self.completed = self.submitted;
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::layout::{DescriptorSetLayout, DescriptorSetLayoutCreateInfo};
+use vulkano::descriptor_set::{DescriptorSet, WriteDescriptorSet, WriteDescriptorSetElements};
+use vulkano::device::Device;
+
+/// A set layout and the resources written to it, by address. Cached sets keep their
+/// layout and resources alive, so an address can't be reused while its entry exists.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SetKey {
+    layout: usize,
+    writes: Vec<(u32, u32, Vec<u64>)>,
+}
+
+impl SetKey {
+    fn new(layout: &Arc<DescriptorSetLayout>, writes: &[WriteDescriptorSet]) -> Self {
+        let address = |ptr: *const ()| ptr as usize as u64;
+        let writes = writes
+            .iter()
+            .map(|write| {
+                let resources = match write.elements() {
+                    WriteDescriptorSetElements::None(count) => vec![*count as u64],
+                    WriteDescriptorSetElements::Buffer(infos) => infos
+                        .iter()
+                        .flat_map(|info| {
+                            [
+                                address(Arc::as_ptr(info.buffer.buffer()).cast()),
+                                info.buffer.offset(),
+                                info.range.start,
+                                info.range.end,
+                            ]
+                        })
+                        .collect(),
+                    WriteDescriptorSetElements::BufferView(views) => views
+                        .iter()
+                        .map(|view| address(Arc::as_ptr(view).cast()))
+                        .collect(),
+                    WriteDescriptorSetElements::ImageView(infos) => infos
+                        .iter()
+                        .flat_map(|info| {
+                            [
+                                address(Arc::as_ptr(&info.image_view).cast()),
+                                info.image_layout as u64,
+                            ]
+                        })
+                        .collect(),
+                    WriteDescriptorSetElements::ImageViewSampler(infos) => infos
+                        .iter()
+                        .flat_map(|(info, sampler)| {
+                            [
+                                address(Arc::as_ptr(&info.image_view).cast()),
+                                info.image_layout as u64,
+                                address(Arc::as_ptr(sampler).cast()),
+                            ]
+                        })
+                        .collect(),
+                    WriteDescriptorSetElements::Sampler(samplers) => samplers
+                        .iter()
+                        .map(|sampler| address(Arc::as_ptr(sampler).cast()))
+                        .collect(),
+                    WriteDescriptorSetElements::InlineUniformBlock(data) => {
+                        data.iter().map(|&byte| byte as u64).collect()
+                    }
+                    WriteDescriptorSetElements::AccelerationStructure(structures) => structures
+                        .iter()
+                        .map(|structure| address(Arc::as_ptr(structure).cast()))
+                        .collect(),
+                };
+                (write.binding(), write.first_array_element(), resources)
+            })
+            .collect();
+        SetKey {
+            layout: Arc::as_ptr(layout) as usize,
+            writes,
+        }
+    }
+}
+
+/// Deduplicates descriptor set layouts and the descriptor sets written against them.
+///
+/// Layouts are shared by definition, so pipelines built from different shader modules
+/// with the same bindings get the same layout object. Sets are shared by layout and
+/// bound resources.
+pub struct DescriptorCache {
+    device: Arc<Device>,
+    allocator: Arc<StandardDescriptorSetAllocator>,
+    layouts: Vec<Arc<DescriptorSetLayout>>,
+    sets: HashMap<SetKey, Arc<DescriptorSet>>,
+}
+
+impl DescriptorCache {
+    pub fn new(device: Arc<Device>, allocator: Arc<StandardDescriptorSetAllocator>) -> Self {
+        DescriptorCache {
+            device,
+            allocator,
+            layouts: Vec::new(),
+            sets: HashMap::new(),
+        }
+    }
+
+    /// A layout with `create_info`'s flags and bindings, created the first time it's asked
+    /// for.
+    pub fn layout(
+        &mut self,
+        create_info: DescriptorSetLayoutCreateInfo,
+    ) -> Result<Arc<DescriptorSetLayout>> {
+        if let Some(layout) = self.layouts.iter().find(|layout| {
+            layout.flags() == create_info.flags && *layout.bindings() == create_info.bindings
+        }) {
+            return Ok(layout.clone());
+        }
+        let layout = DescriptorSetLayout::new(self.device.clone(), create_info)?;
+        debug!(
+            bindings = layout.bindings().len(),
+            "Created descriptor set layout"
+        );
+        self.layouts.push(layout.clone());
+        Ok(layout)
+    }
+
+    /// A set of `layout` with `writes`, reused if the same resources were written to that
+    /// layout before. Writes to bindings the layout doesn't have are skipped, since
+    /// shaders that don't use a binding leave it out of the reflected layout.
+    pub fn set(
+        &mut self,
+        layout: &Arc<DescriptorSetLayout>,
+        writes: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Result<Arc<DescriptorSet>> {
+        let writes = writes
+            .into_iter()
+            .filter(|write| layout.bindings().contains_key(&write.binding()))
+            .collect::<Vec<_>>();
+        let key = SetKey::new(layout, &writes);
+        if let Some(set) = self.sets.get(&key) {
+            return Ok(set.clone());
+        }
+        let set = DescriptorSet::new(self.allocator.clone(), layout.clone(), writes, [])?;
+        self.sets.insert(key, set.clone());
+        Ok(set)
+    }
+
+    /// Forgets `set`, e.g. once it was replaced. Frames in flight keep it alive until
+    /// they finish.
+    pub fn forget(&mut self, set: &Arc<DescriptorSet>) {
+        self.sets.retain(|_, cached| !Arc::ptr_eq(cached, set));
+    }
+
+    /// Forgets every cached set, releasing the resources only they kept alive.
+    pub fn clear_sets(&mut self) {
+        self.sets.clear();
+    }
+}
@@ -1,79 +1,308 @@
-use crate::renderer::Renderer;
-use crate::renderer::renderer_vulkan::render_context::FrameState;
+use crate::renderer::render_world::RenderWorld;
+use crate::renderer::{MaterialDesc, MaterialHandle, Renderer, TextureUpload};
+use crate::scene::RenderLayers;
 pub(crate) use crate::{
+    capture::FrameCapture,
+    events::Events,
+    frame_stats::FrameStats,
+    logger::CrashReporter,
+    overlay::EditorPreview,
+    renderer::GraphicsQuality,
     renderer::renderer_vulkan::{
         pipeline::VulkanPipeline,
-        render_context::{ActiveFrame, RenderContext},
-        resources::{ElmVertex, VulkanResources},
+        render_context::RenderContext,
+        render_thread::{
+            FrameReport, FrameRequest, RenderCommand, RenderSetup, RenderThread, lock,
+        },
+        resources::{
+            ElmVertex, MeshInstance, MeshPushConstants, TextureDesc, UniformBufferObject,
+            VulkanResources,
+        },
         swapchain::VulkanSwapchain,
+        target::{FrameTarget, OffscreenImages},
     },
-    resource_manager::ResourceManager,
+    resource_manager::{FromResources, ResourceManager},
+    task_pool::TaskPool,
     window::Window,
 };
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, ensure};
+use egui_winit_vulkano::{Gui, GuiConfig, egui};
+use glam::Mat4;
 use gltf::texture::{MagFilter, MinFilter, WrappingMode};
-use std::time::Duration;
-use std::{sync::Arc, thread, time::Instant};
 #[cfg(debug_assertions)]
-use tracing::debug;
-use tracing::{Level, info, span};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(debug_assertions)]
+use tracing::{error, trace};
+use tracing::{info, info_span, warn};
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocatorCreateInfo;
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
 use vulkano::device::DeviceFeatures;
-use vulkano::image::sampler::{Filter, SamplerAddressMode};
+use vulkano::format::Format;
+use vulkano::image::SampleCount;
+use vulkano::image::sampler::{Filter, SamplerAddressMode, SamplerCreateInfo};
 #[cfg(debug_assertions)]
 use vulkano::instance::debug::{
-    DebugUtilsMessageSeverity, DebugUtilsMessenger, DebugUtilsMessengerCallback,
-    DebugUtilsMessengerCreateInfo,
+    DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+    DebugUtilsMessengerCallback, DebugUtilsMessengerCallbackData, DebugUtilsMessengerCreateInfo,
 };
 use vulkano::{
-    Validated, VulkanError, VulkanLibrary,
+    DeviceSize, Validated, VulkanError, VulkanLibrary,
     command_buffer::allocator::StandardCommandBufferAllocator,
     descriptor_set::{DescriptorSet, WriteDescriptorSet},
     device::{
         Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
         physical::PhysicalDeviceType,
     },
-    instance::{Instance, InstanceCreateFlags, InstanceCreateInfo},
+    instance::{Instance, InstanceCreateFlags, InstanceCreateInfo, InstanceExtensions},
     pipeline::graphics::viewport::Viewport,
+    query::{QueryPool, QueryPoolCreateInfo, QueryType},
+    shader::{ShaderModule, ShaderModuleCreateInfo},
     swapchain::Surface,
     sync::GpuFuture,
 };
+use winit::event::WindowEvent;
+use winit::event_loop::ActiveEventLoop;
 use winit::window::Window as WinitWindow;
 
+mod deletion_queue;
+mod descriptor_cache;
 mod pipeline;
 mod render_context;
+mod render_queue;
+mod render_thread;
 pub mod resources;
 mod shaders;
 mod swapchain;
+mod target;
+mod uniform_ring;
 
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
-
+/// Bytes of the uniform ring each frame in flight may fill.
+const UNIFORM_RING_FRAME_SIZE: DeviceSize = 64 * 1024;
+/// Size of the offscreen images when rendering headless without a configured window size.
+const DEFAULT_HEADLESS_EXTENT: [u32; 2] = [1280, 720];
+/// Format of the offscreen images when rendering headless, matching the swapchain's
+/// preferred one.
+const HEADLESS_FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+/// Draws into the [`Window`] resource's swapchain, or headless into offscreen images of
+/// [`EngineConfig::window`](crate::engine::EngineConfig::window)'s size when there is no
+/// window. Headless rendering needs no display and has no overlay.
 pub struct VulkanRenderer {
-    winit_window: Arc<WinitWindow>,
+    /// `None` when rendering headless.
+    winit_window: Option<Arc<WinitWindow>>,
+    /// Size of the offscreen images when rendering headless.
+    headless_extent: [u32; 2],
+    /// The window was created transparent, so the swapchain should blend with the desktop.
+    transparent_window: bool,
     instance: Arc<Instance>,
     #[cfg(debug_assertions)]
     _debug_callback: DebugUtilsMessenger,
+    /// Validation errors reported so far. Counted here because panics inside the debug
+    /// callback are swallowed by vulkano.
+    #[cfg(debug_assertions)]
+    validation_errors: Arc<AtomicU32>,
+    #[cfg(debug_assertions)]
+    panic_on_validation_error: bool,
     device: Arc<Device>,
     graphics_queue: Arc<Queue>,
+    /// Same queue as `graphics_queue` unless the device presents from another family.
+    present_queue: Arc<Queue>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
-    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
-    resources: VulkanResources,
-    render_context: Option<RenderContext>,
+    task_pool: TaskPool,
+    /// Shared with the render thread, which locks it while recording a frame.
+    resources: Arc<Mutex<VulkanResources>>,
+    render_thread: Option<RenderThread>,
+    /// Report of the frame collected from the render thread, until `on_update` applies it.
+    finished_frame: Option<Result<FrameReport>>,
+    /// Drawn by the render thread onto frames handed over after `begin_ui`.
+    gui: Arc<Mutex<Option<Gui>>>,
+    /// Set between `begin_ui` and the overlay being drawn into a presented frame.
+    ui_frame_open: bool,
+    /// Overlay texture of the [`EditorPreview`] target, with the size, format and sample
+    /// count it was created with.
+    preview: Option<(egui::TextureId, [u32; 2], Format, SampleCount)>,
+    /// The [`GraphicsQuality`] last applied.
+    quality: Option<GraphicsQuality>,
 }
 
 impl VulkanRenderer {
-    pub fn resources(&self) -> &VulkanResources {
-        &self.resources
+    /// Locks the GPU resources. Waits while the render thread records a frame.
+    pub fn resources(&self) -> MutexGuard<'_, VulkanResources> {
+        lock(&self.resources)
+    }
+
+    /// Waits for the frame being rendered, if any, and keeps its report for `on_update`.
+    /// The render thread leaves the overlay alone afterwards.
+    fn sync_render_thread(&mut self) {
+        let Some(report) = self
+            .render_thread
+            .as_mut()
+            .and_then(RenderThread::finish_frame)
+        else {
+            return;
+        };
+        if report.as_ref().is_ok_and(|report| report.ui_drawn) {
+            self.ui_frame_open = false;
+        }
+        self.finished_frame = Some(report);
+    }
+
+    /// Keeps the [`EditorPreview`] target matching its size and the swapchain format,
+    /// registered with the overlay, and releases it while the preview is off or there is
+    /// no overlay to show it in.
+    fn sync_preview(&mut self, resource_manager: &mut ResourceManager) -> Result<()> {
+        let Some(preview) = resource_manager.try_get_mut::<EditorPreview>() else {
+            return Ok(());
+        };
+        let mut gui = lock(&self.gui);
+        let mut resources = lock(&self.resources);
+        let samples = resources.msaa_samples();
+        let wanted = self
+            .render_thread
+            .as_ref()
+            .map(|render_thread| (preview.size, render_thread.format, samples))
+            .filter(|(size, _, _)| preview.enabled && gui.is_some() && size[0] > 0 && size[1] > 0);
+        if self
+            .preview
+            .map(|(_, size, format, samples)| (size, format, samples))
+            != wanted
+        {
+            if let Some((texture, _, _, _)) = self.preview.take() {
+                if let Some(gui) = gui.as_mut() {
+                    gui.unregister_user_image(texture);
+                }
+                resources.drop_preview_target();
+            }
+            if let (Some((size, format, samples)), Some(gui)) = (wanted, gui.as_mut()) {
+                let view = resources.create_preview_target(size, format)?;
+                let texture = gui.register_user_image_view(
+                    view,
+                    SamplerCreateInfo::simple_repeat_linear_no_mipmap(),
+                );
+                self.preview = Some((texture, size, format, samples));
+            }
+        }
+        preview.texture = self.preview.map(|(texture, _, _, _)| texture);
+        Ok(())
+    }
+
+    /// Applies a changed [`GraphicsQuality`]: texture samplers are rebuilt right away,
+    /// MSAA by the render thread before the next frame.
+    fn sync_quality(&mut self, resource_manager: &ResourceManager) -> Result<()> {
+        let Some(&quality) = resource_manager.try_get::<GraphicsQuality>() else {
+            return Ok(());
+        };
+        let previous = self.quality.replace(quality);
+        if previous == Some(quality) {
+            return Ok(());
+        }
+        let mut resources = lock(&self.resources);
+        if previous.is_none_or(|previous| {
+            (previous.anisotropy, previous.mip_bias) != (quality.anisotropy, quality.mip_bias)
+        }) {
+            resources.set_sampling(quality.anisotropy, quality.mip_bias)?;
+        }
+        let samples = resources.supported_msaa(quality.msaa);
+        if samples != resources.msaa_samples() {
+            self.render_thread
+                .as_ref()
+                .ok_or_else(|| anyhow!("Render context not initialized"))?
+                .send(RenderCommand::SetMsaa(samples))?;
+        }
+        info!(?quality, "Graphics quality applied");
+        Ok(())
+    }
+}
+
+/// The distinct queue families used for rendering and presenting.
+fn queue_families(graphics_family: u32, present_family: u32) -> Vec<u32> {
+    if graphics_family == present_family {
+        vec![graphics_family]
+    } else {
+        vec![graphics_family, present_family]
+    }
+}
+
+/// One descriptor set per frame in flight, binding the uniform ring and that frame's
+/// light buffer. The frame's uniforms are picked with a dynamic offset when binding.
+/// Textures are bound per material instead.
+fn create_descriptor_sets(
+    resources: &mut VulkanResources,
+    pipeline: &VulkanPipeline,
+) -> Result<Vec<Arc<DescriptorSet>>> {
+    let ubo = resources
+        .uniform_ring()
+        .with_context(|| "Uniform ring not created")?
+        .binding::<UniformBufferObject>();
+    let layout = pipeline.layout().set_layouts()[pipeline::FRAME_SET].clone();
+    (0..MAX_FRAMES_IN_FLIGHT)
+        .map(|i| {
+            let lights = resources
+                .get_light_buffer(i)
+                .with_context(|| format!("Light buffer {i} not found"))?;
+            resources.descriptor_cache_mut().set(
+                &layout,
+                [
+                    WriteDescriptorSet::buffer_with_range(0, ubo.clone()),
+                    WriteDescriptorSet::buffer(1, lights),
+                ],
+            )
+        })
+        .collect()
+}
+
+/// Records what the render thread measured and captured during a frame.
+fn apply_frame_report(report: FrameReport, resource_manager: &mut ResourceManager) {
+    let stats = resource_manager.get_mut::<FrameStats>();
+    if let Some(gpu_time) = report.gpu_time {
+        stats.record_gpu_time(gpu_time);
     }
+    if let Some((used, total)) = report.gpu_memory {
+        stats.record_gpu_memory(used, total);
+    }
+    if let Some((draw_calls, triangles, culled)) = report.draws {
+        stats.record_draws(draw_calls, triangles, culled);
+    }
+    if let Some(render_time) = report.render_time {
+        stats.record_render_time(render_time);
+    }
+    if let Some(changed) = report.swapchain_changed {
+        resource_manager.get_mut::<Events>().send(changed);
+    }
+    let capture = resource_manager.get_mut::<FrameCapture>();
+    if let Some(frame) = report.captured {
+        capture.submit(frame);
+    }
+    if report.capture_unsupported {
+        warn!("The swapchain images can't be copied; stopping frame capture");
+        capture.stop();
+    }
+}
 
-    pub fn resources_mut(&mut self) -> &mut VulkanResources {
-        &mut self.resources
+impl FromResources for VulkanRenderer {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        resources.init::<TaskPool>()?;
+        resources.require::<CrashReporter>()?;
+        Ok(VulkanRenderer::new(resources))
     }
 }
+
 impl Renderer for VulkanRenderer {
     fn new(resource_manager: &mut ResourceManager) -> Self {
-        let winit_window = resource_manager.get::<Window>().get_winit_window();
+        let window = resource_manager.try_get::<Window>();
+        let winit_window = window.map(Window::get_winit_window);
+        let transparent_window = window.is_some_and(Window::is_transparent);
+        let headless_extent = resource_manager
+            .try_get::<crate::engine::EngineConfig>()
+            .and_then(|config| config.window.size)
+            .unwrap_or(DEFAULT_HEADLESS_EXTENT);
+        let task_pool = resource_manager.get::<TaskPool>().clone();
+        #[cfg(debug_assertions)]
+        let panic_on_validation_error = resource_manager
+            .get::<crate::engine::EngineConfig>()
+            .panic_on_validation_error;
 
         let vk_lib = match VulkanLibrary::new() {
             Ok(lib) => lib,
@@ -87,7 +316,13 @@ impl Renderer for VulkanRenderer {
         } else {
             Vec::new()
         };
-        let mut required_extensions = Surface::required_extensions(&winit_window).unwrap();
+        let mut required_extensions = match &winit_window {
+            Some(winit_window) => Surface::required_extensions(winit_window).unwrap(),
+            None => {
+                info!("No window; rendering headless");
+                InstanceExtensions::empty()
+            }
+        };
         if enable_validation {
             required_extensions.ext_debug_utils = true;
             info!("Vulkan validation layers enabled");
@@ -105,54 +340,42 @@ impl Renderer for VulkanRenderer {
         .unwrap();
 
         #[cfg(debug_assertions)]
-        let _debug_callback = DebugUtilsMessenger::new(
-            instance.clone(),
-            DebugUtilsMessengerCreateInfo::user_callback(unsafe {
-                DebugUtilsMessengerCallback::new(|message_severity, message_type, callback_data| {
-                    match message_severity {
-                        DebugUtilsMessageSeverity::ERROR => {
-                            debug!(
-                                "Vulkan Debug - ERROR - {:?} - {:?}: {}",
-                                message_type, message_severity, callback_data.message
-                            );
-                        }
-                        DebugUtilsMessageSeverity::WARNING => {
-                            debug!(
-                                "Vulkan Debug - WARNING - {:?} - {:?}: {}",
-                                message_type, message_severity, callback_data.message
-                            );
-                        }
-                        DebugUtilsMessageSeverity::INFO => {
-                            debug!(
-                                "Vulkan Debug - INFO - {:?} - {:?}: {}",
-                                message_type, message_severity, callback_data.message
-                            );
-                        }
-                        DebugUtilsMessageSeverity::VERBOSE => {
-                            debug!(
-                                "Vulkan Debug - VERBOSE - {:?} - {:?}: {}",
-                                message_type, message_severity, callback_data.message
-                            );
-                        }
-                        _ => {
-                            debug!(
-                                "Vulkan Debug - UNKNOWN - {:?} - {:?}: {}",
-                                message_type, message_severity, callback_data.message
-                            );
-                        }
-                    }
-                })
-            }),
-        )
-        .with_context(|| "Failed to create debug callback")
-        .unwrap();
+        let validation_errors = Arc::new(AtomicU32::new(0));
+        #[cfg(debug_assertions)]
+        let _debug_callback = {
+            let validation_errors = validation_errors.clone();
+            DebugUtilsMessenger::new(
+                instance.clone(),
+                DebugUtilsMessengerCreateInfo {
+                    message_severity: DebugUtilsMessageSeverity::ERROR
+                        | DebugUtilsMessageSeverity::WARNING
+                        | DebugUtilsMessageSeverity::INFO
+                        | DebugUtilsMessageSeverity::VERBOSE,
+                    message_type: DebugUtilsMessageType::GENERAL
+                        | DebugUtilsMessageType::VALIDATION
+                        | DebugUtilsMessageType::PERFORMANCE,
+                    ..DebugUtilsMessengerCreateInfo::user_callback(unsafe {
+                        DebugUtilsMessengerCallback::new(
+                            move |message_severity, message_type, callback_data| {
+                                if message_severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+                                    validation_errors.fetch_add(1, Ordering::Relaxed);
+                                }
+                                log_debug_message(message_severity, message_type, callback_data);
+                            },
+                        )
+                    })
+                },
+            )
+            .with_context(|| "Failed to create debug callback")
+            .unwrap()
+        };
 
         let device_extensions = DeviceExtensions {
-            khr_swapchain: true,
+            khr_swapchain: winit_window.is_some(),
             ..DeviceExtensions::empty()
         };
 
-        let (physical_device, queue_family_index) = instance
+        let (physical_device, graphics_family, present_family) = instance
             .enumerate_physical_devices()
             .unwrap()
             .filter(|p| p.supported_extensions().contains(&device_extensions))
@@ -162,17 +385,31 @@ impl Renderer for VulkanRenderer {
                     p.properties().device_name,
                     p.properties().device_type
                 );
-                p.queue_family_properties()
-                    .iter()
-                    .enumerate()
-                    .position(|(i, q)| {
-                        q.queue_flags.intersects(QueueFlags::GRAPHICS)
-                            && p.presentation_support(i as u32, &winit_window)
-                                .unwrap_or(false)
+                let family_count = p.queue_family_properties().len() as u32;
+                let graphics = |i: &u32| {
+                    p.queue_family_properties()[*i as usize]
+                        .queue_flags
+                        .intersects(QueueFlags::GRAPHICS)
+                };
+                // Headless, any family "presents", so the graphics family is picked.
+                let presents = |i: &u32| {
+                    winit_window.as_ref().is_none_or(|winit_window| {
+                        p.presentation_support(*i, winit_window).unwrap_or(false)
                     })
-                    .map(|i| (p, i as u32))
+                };
+                // One family doing both is preferred, so the swapchain images aren't shared
+                // between queues.
+                let (graphics_family, present_family) =
+                    match (0..family_count).find(|i| graphics(i) && presents(i)) {
+                        Some(i) => (i, i),
+                        None => (
+                            (0..family_count).find(graphics)?,
+                            (0..family_count).find(presents)?,
+                        ),
+                    };
+                Some((p, graphics_family, present_family))
             })
-            .min_by_key(|(p, _)| match p.properties().device_type {
+            .min_by_key(|(p, _, _)| match p.properties().device_type {
                 PhysicalDeviceType::DiscreteGpu => 0,
                 PhysicalDeviceType::IntegratedGpu => 1,
                 PhysicalDeviceType::VirtualGpu => 2,
@@ -188,19 +425,48 @@ impl Renderer for VulkanRenderer {
             physical_device.properties().device_name,
             physical_device.properties().device_type,
         );
+        if graphics_family != present_family {
+            info!(
+                graphics_family,
+                present_family, "Rendering and presenting on separate queue families"
+            );
+        }
+        let crash_reporter = resource_manager.get::<CrashReporter>();
+        crash_reporter.set_context("gpu", &physical_device.properties().device_name);
+        crash_reporter.set_context(
+            "gpu_driver",
+            format!(
+                "{} ({:?})",
+                physical_device.properties().driver_version,
+                physical_device.properties().driver_id
+            ),
+        );
+        crash_reporter.set_context(
+            "vulkan_api",
+            physical_device.properties().api_version.to_string(),
+        );
 
+        // Only needed for the debug wireframe view, so optional.
+        let fill_mode_non_solid = physical_device.supported_features().fill_mode_non_solid;
+        // Without it, block-compressed textures are decoded on upload.
+        let texture_compression_bc = physical_device.supported_features().texture_compression_bc;
         let (device, mut queues_iter) = Device::new(
             physical_device,
             DeviceCreateInfo {
                 enabled_extensions: device_extensions,
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
+                queue_create_infos: queue_families(graphics_family, present_family)
+                    .into_iter()
+                    .map(|queue_family_index| QueueCreateInfo {
+                        queue_family_index,
+                        ..Default::default()
+                    })
+                    .collect(),
                 enabled_features: DeviceFeatures {
                     dynamic_rendering: true,
                     sampler_anisotropy: true,
                     sample_rate_shading: true,
+                    fill_mode_non_solid,
+                    texture_compression_bc,
                     ..Default::default()
                 },
                 ..Default::default()
@@ -211,6 +477,14 @@ impl Renderer for VulkanRenderer {
             .next()
             .with_context(|| "No queue found")
             .unwrap();
+        let present_queue = if present_family == graphics_family {
+            graphics_queue.clone()
+        } else {
+            queues_iter
+                .next()
+                .with_context(|| "No present queue found")
+                .unwrap()
+        };
 
         let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
             device.clone(),
@@ -226,201 +500,277 @@ impl Renderer for VulkanRenderer {
             device.clone(),
             graphics_queue.clone(),
             command_buffer_allocator.clone(),
-        );
+            descriptor_set_allocator,
+        )
+        .with_context(|| "Failed to create renderer resources")
+        .unwrap();
 
         VulkanRenderer {
             winit_window,
+            headless_extent,
+            transparent_window,
             instance,
             #[cfg(debug_assertions)]
             _debug_callback,
+            #[cfg(debug_assertions)]
+            validation_errors,
+            #[cfg(debug_assertions)]
+            panic_on_validation_error,
             device,
             graphics_queue,
+            present_queue,
             command_buffer_allocator,
-            descriptor_set_allocator,
-            resources,
-            render_context: None,
+            task_pool,
+            resources: Arc::new(Mutex::new(resources)),
+            render_thread: None,
+            finished_frame: None,
+            gui: Arc::new(Mutex::new(None)),
+            ui_frame_open: false,
+            preview: None,
+            quality: None,
         }
     }
 
     fn run(&mut self) -> Result<()> {
-        let surface = Surface::from_window(self.instance.clone(), self.winit_window.clone())?;
-        let window_size = self.winit_window.inner_size();
-
-        let swapchain =
-            VulkanSwapchain::new(self.device.clone(), surface.clone(), window_size.into())?;
-
-        self.resources
-            .create_color_resources(swapchain.extent, swapchain.format)?;
-        self.resources.create_depth_resources(swapchain.extent)?;
-
-        let pipeline = VulkanPipeline::new(
-            self.device.clone(),
-            swapchain.format,
-            self.resources.msaa_samples(),
-            self.resources.find_depth_format()?,
-        )?;
+        let (target, surface) = match &self.winit_window {
+            Some(winit_window) => {
+                let surface = Surface::from_window(self.instance.clone(), winit_window.clone())?;
+                let swapchain = VulkanSwapchain::new(
+                    self.device.clone(),
+                    surface.clone(),
+                    winit_window.inner_size().into(),
+                    self.transparent_window,
+                    &queue_families(
+                        self.graphics_queue.queue_family_index(),
+                        self.present_queue.queue_family_index(),
+                    ),
+                )?;
+                (FrameTarget::Swapchain(swapchain), Some(surface))
+            }
+            None => {
+                let image_views = self.resources().create_offscreen_images(
+                    self.headless_extent,
+                    HEADLESS_FORMAT,
+                    MAX_FRAMES_IN_FLIGHT,
+                )?;
+                let offscreen =
+                    OffscreenImages::new(image_views, HEADLESS_FORMAT, self.headless_extent);
+                (FrameTarget::Offscreen(offscreen), None)
+            }
+        };
+        let extent = target.extent();
+        let format = target.format();
+
+        let (pipeline, descriptor_sets) = {
+            let mut resources = self.resources();
+            resources.create_color_resources(extent, format)?;
+            resources.create_depth_resources(extent)?;
+
+            let msaa_samples = resources.msaa_samples();
+            let depth_format = resources.find_depth_format()?;
+            let pipeline = VulkanPipeline::new(
+                self.device.clone(),
+                resources.descriptor_cache_mut(),
+                format,
+                msaa_samples,
+                depth_format,
+            )?;
+
+            resources.create_uniform_buffers(MAX_FRAMES_IN_FLIGHT, UNIFORM_RING_FRAME_SIZE)?;
+            let descriptor_sets = create_descriptor_sets(&mut resources, &pipeline)?;
+            (pipeline, descriptor_sets)
+        };
 
         let viewport = Viewport {
             offset: [0.0, 0.0],
-            extent: window_size.into(),
+            extent: [extent[0] as f32, extent[1] as f32],
             depth_range: 0.0..=1.0,
         };
 
-        self.resources
-            .create_uniform_buffers(MAX_FRAMES_IN_FLIGHT)?;
-
-        let descriptor_set = (0..MAX_FRAMES_IN_FLIGHT)
-            .map(|i| {
-                let mut descriptor_writes = vec![];
-
-                let ubo = self
-                    .resources
-                    .get_uniform_buffer(i)
-                    .with_context(|| format!("Uniform buffer {i} not found"))?;
-                descriptor_writes.push(WriteDescriptorSet::buffer(0, ubo));
-
-                for (tex_index, texture) in self.resources.textures.iter().enumerate() {
-                    let binding = 1 + tex_index as u32;
-                    descriptor_writes.push(WriteDescriptorSet::image_view_sampler(
-                        binding,
-                        texture.image_view.clone(),
-                        texture.sampler.clone(),
-                    ));
-                }
-
-                let set = DescriptorSet::new(
-                    self.descriptor_set_allocator.clone(),
-                    pipeline.layout().set_layouts()[0].clone(),
-                    descriptor_writes,
-                    [],
-                )?;
-                Ok(set)
+        let timestamps_supported = self.device.physical_device().queue_family_properties()
+            [self.graphics_queue.queue_family_index() as usize]
+            .timestamp_valid_bits
+            .is_some();
+        let frames = descriptor_sets
+            .into_iter()
+            .map(|descriptor_set| {
+                let timestamps = if timestamps_supported {
+                    Some(QueryPool::new(
+                        self.device.clone(),
+                        QueryPoolCreateInfo {
+                            query_count: 2,
+                            ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+                        },
+                    )?)
+                } else {
+                    None
+                };
+                Ok((descriptor_set, timestamps))
             })
             .collect::<Result<Vec<_>>>()?;
+        if !timestamps_supported {
+            info!("Graphics queue doesn't support timestamps; GPU frame time is unavailable");
+        }
 
-        let frames = (0..MAX_FRAMES_IN_FLIGHT)
-            .map(|i| FrameState {
-                in_flight_future: None,
-                descriptor_sets: vec![descriptor_set[i].clone()],
-            })
-            .collect::<Vec<_>>();
-
-        let recreate_swapchain = false;
-
-        let start_time = Instant::now();
-
-        self.render_context = Some(RenderContext {
-            swapchain,
-            pipeline,
-            viewport,
-            recreate_swapchain,
-            frames,
-            current_frame: 0,
-            start_time,
-        });
+        let supports_wireframe = pipeline.supports_wireframe();
+        let timestamp_period = self.device.physical_device().properties().timestamp_period;
+        let setup = RenderSetup {
+            graphics_queue: self.graphics_queue.clone(),
+            present_queue: self.present_queue.clone(),
+            command_buffer_allocator: self.command_buffer_allocator.clone(),
+            resources: self.resources.clone(),
+            gui: self.gui.clone(),
+            context: Box::new(move || {
+                RenderContext::new(target, pipeline, viewport, frames, timestamp_period)
+            }),
+        };
+        self.render_thread = Some(RenderThread::spawn(
+            setup,
+            surface,
+            format,
+            supports_wireframe,
+        )?);
         Ok(())
     }
 
-    fn on_update(&mut self) -> Result<()> {
-        let rcx = match self.render_context.as_mut() {
-            Some(rcx) => rcx,
-            None => {
-                return Err(anyhow!("Render context not initialized"));
+    /// Applies the report of the frame handed over last, then hands over the frame
+    /// described by the [`RenderWorld`] resource. Returns before it has been drawn.
+    fn on_update(&mut self, resource_manager: &mut ResourceManager) -> Result<()> {
+        #[cfg(debug_assertions)]
+        if self.panic_on_validation_error {
+            let errors = self.validation_errors.load(Ordering::Relaxed);
+            assert!(
+                errors == 0,
+                "{errors} Vulkan validation error(s), see the log"
+            );
+        }
+        self.sync_render_thread();
+        if let Some(report) = self.finished_frame.take() {
+            let report = report?;
+            if let Some(changed) = report
+                .swapchain_changed
+                .filter(|changed| changed.format_changed)
+                && let Some(render_thread) = self.render_thread.as_mut()
+            {
+                render_thread.format = changed.format;
+                // egui's pipeline targets the old format. The engine calls `init_ui` again
+                // once it sees the event; until then there is no overlay.
+                *lock(&self.gui) = None;
+                self.ui_frame_open = false;
+                // Its texture went with the overlay.
+                self.preview = None;
+                self.resources().drop_preview_target();
             }
+            apply_frame_report(report, resource_manager);
+        }
+        self.sync_quality(resource_manager)?;
+        self.sync_preview(resource_manager)?;
+
+        let request = FrameRequest {
+            world: resource_manager
+                .try_get::<RenderWorld>()
+                .cloned()
+                .unwrap_or_default(),
+            window_size: self
+                .winit_window
+                .as_ref()
+                .map_or(self.headless_extent, |window| window.inner_size().into()),
+            minimized: self
+                .winit_window
+                .as_ref()
+                .is_some_and(|window| window.is_minimized().unwrap_or(false)),
+            capture: resource_manager.get::<FrameCapture>().wants_frame(),
+            draw_ui: self.ui_frame_open,
         };
+        self.render_thread
+            .as_mut()
+            .ok_or_else(|| anyhow!("Render context not initialized"))?
+            .submit_frame(request)
+    }
 
-        let is_minimized = self.winit_window.is_minimized();
-        let window_size = self.winit_window.inner_size();
+    fn upload_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<usize> {
+        let _span = info_span!("upload", subsystem = "renderer").entered();
+        self.resources().upload_mesh(vertices, indices)
+    }
 
-        let _span_draw_frame = span!(
-            Level::INFO,
-            "VulkanRenderer::draw_frame",
-            FrameIndex = rcx.current_frame
-        )
-        .entered();
+    fn upload_dynamic_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<usize> {
+        let _span = info_span!("upload", subsystem = "renderer").entered();
+        self.resources().upload_dynamic_mesh(vertices, indices)
+    }
 
-        if is_minimized.is_some_and(|minimized| minimized)
-            || window_size.width == 0
-            || window_size.height == 0
-        {
-            // If the window is minimized, we skip rendering this frame.
-            thread::sleep(Duration::from_millis(50));
-            rcx.recreate_swapchain = true;
-            return Ok(());
-        }
+    fn update_mesh(&mut self, mesh: usize, vertices: &[ElmVertex], indices: &[u32]) -> Result<()> {
+        let _span = info_span!("upload", subsystem = "renderer").entered();
+        self.resources().update_mesh(mesh, vertices, indices)
+    }
 
-        // Whenever the window resizes we need to recreate everything dependent on the
-        // window size. In this example that includes the swapchain, the framebuffers and
-        // the dynamic state viewport.
-        if rcx.recreate_swapchain {
-            info!(
-                "Recreating swapchain for new window size: {:?}",
-                window_size
-            );
-            rcx.swapchain.recreate(window_size.into())?;
-            self.resources
-                .create_color_resources(rcx.swapchain.extent, rcx.swapchain.format)?;
-            self.resources
-                .create_depth_resources(rcx.swapchain.extent)?;
-            rcx.viewport.extent = window_size.into();
-            rcx.recreate_swapchain = false;
+    fn add_mesh_instance(
+        &mut self,
+        mesh: usize,
+        material: MaterialHandle,
+        transform: Mat4,
+    ) -> Result<usize> {
+        let mut resources = self.resources();
+        if resources.get_mesh(mesh).is_none() {
+            warn!("Mesh {mesh} has not been uploaded, drawing the fallback cube");
         }
-
-        let (image_index, suboptimal, acquire_future) = match rcx
-            .swapchain
-            .acquire_next_image()
-            .map_err(Validated::unwrap)
-        {
-            Ok(r) => r,
-            Err(VulkanError::OutOfDate) => {
-                rcx.recreate_swapchain = true;
-                return Ok(());
-            }
-            Err(e) => {
-                return Err(e.into());
-            }
+        let material = if resources.get_material(material).is_some() {
+            material
+        } else {
+            warn!("Material {material:?} has not been created, drawing the error material");
+            MaterialHandle::ERROR
         };
+        resources.instances.push(MeshInstance {
+            mesh,
+            material,
+            transform,
+            visible: true,
+            layers: RenderLayers::default(),
+        });
+        Ok(resources.instances.len() - 1)
+    }
 
-        if suboptimal {
-            info!("Swapchain is suboptimal; recreating");
-            rcx.recreate_swapchain = true;
-            return Ok(());
-        }
-
-        rcx.update_uniform_buffer(
-            self.resources
-                .get_uniform_buffer(rcx.current_frame)
-                .with_context(|| "Uniform buffer not found")?,
-        )
-        .with_context(|| "Failed to update uniform buffer")?;
+    fn set_mesh_instance_transform(&mut self, instance: usize, transform: Mat4) -> Result<()> {
+        self.resources()
+            .instances
+            .get_mut(instance)
+            .ok_or_else(|| anyhow!("Mesh instance {instance} not found"))?
+            .transform = transform;
+        Ok(())
+    }
 
-        match rcx.build_command_buffer(
-            self.command_buffer_allocator.clone(),
-            self.graphics_queue.clone(),
-            self.resources.get_color_resources()?,
-            self.resources.get_depth_resources()?,
-            image_index,
-        ) {
-            Ok(builder) => {
-                let mut active_frame = ActiveFrame {
-                    rcx,
-                    resources: &self.resources,
-                    builder: Some(builder),
-                    image_index,
-                    acquire_future: Some(acquire_future.boxed()),
-                };
-                active_frame.draw().with_context(|| "Failed to draw mesh")?;
-                active_frame
-                    .execute_command_buffer(&self.graphics_queue)
-                    .with_context(|| "Failed to execute command buffer")?;
-                Ok(())
-            }
-            Err(err) => Err(anyhow!("Failed to build command buffer: {:?}", err)),
-        }
+    fn set_mesh_instance_material(
+        &mut self,
+        instance: usize,
+        material: MaterialHandle,
+    ) -> Result<()> {
+        let mut resources = self.resources();
+        let material = if resources.get_material(material).is_some() {
+            material
+        } else {
+            warn!("Material {material:?} has not been created, drawing the error material");
+            MaterialHandle::ERROR
+        };
+        resources
+            .instances
+            .get_mut(instance)
+            .ok_or_else(|| anyhow!("Mesh instance {instance} not found"))?
+            .material = material;
+        Ok(())
     }
 
-    fn upload_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<()> {
-        self.resources.upload_mesh(vertices, indices)?;
+    fn set_mesh_instance_visibility(
+        &mut self,
+        instance: usize,
+        visible: bool,
+        layers: RenderLayers,
+    ) -> Result<()> {
+        let mut resources = self.resources();
+        let instance = resources
+            .instances
+            .get_mut(instance)
+            .ok_or_else(|| anyhow!("Mesh instance {instance} not found"))?;
+        instance.visible = visible;
+        instance.layers = layers;
         Ok(())
     }
 
@@ -431,39 +781,237 @@ impl Renderer for VulkanRenderer {
         height: u32,
         filter: (Option<MagFilter>, Option<MinFilter>),
         wrap: (WrappingMode, WrappingMode),
-    ) -> Result<()> {
-        // Do mapping of filtering and wrapping modes to Vulkan
-        let vk_mag_filter = match filter.0 {
-            Some(MagFilter::Nearest) => Filter::Nearest,
-            Some(MagFilter::Linear) | None => Filter::Linear,
-        };
-        let vk_min_filter = match filter.1 {
-            Some(MinFilter::Nearest) => Filter::Nearest,
-            Some(MinFilter::Linear) => Filter::Linear,
-            _ => Filter::Linear, // Simplified for brevity
-        };
-        let vk_address_mode_s = match wrap.0 {
-            WrappingMode::ClampToEdge => SamplerAddressMode::ClampToEdge,
-            WrappingMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
-            WrappingMode::Repeat => SamplerAddressMode::Repeat,
-        };
-        let vk_address_mode_t = match wrap.1 {
-            WrappingMode::ClampToEdge => SamplerAddressMode::ClampToEdge,
-            WrappingMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
-            WrappingMode::Repeat => SamplerAddressMode::Repeat,
-        };
-        self.resources.upload_texture(
+    ) -> Result<usize> {
+        let (vk_mag_filter, vk_min_filter, address_mode) = vk_sampler_params(filter, wrap);
+        self.resources().upload_texture(
             image_data,
             width,
             height,
             vk_mag_filter,
             vk_min_filter,
-            [
-                vk_address_mode_s,
-                vk_address_mode_t,
-                SamplerAddressMode::Repeat,
-            ],
-        )?;
+            address_mode,
+        )
+    }
+
+    fn upload_textures(&mut self, textures: &[TextureUpload]) -> Result<Vec<usize>> {
+        let _span = info_span!("upload", subsystem = "renderer").entered();
+        let descs = textures
+            .iter()
+            .map(|texture| {
+                let (mag_filter, min_filter, address_mode) =
+                    vk_sampler_params(texture.filter, texture.wrap);
+                TextureDesc {
+                    pixels: texture.pixels,
+                    width: texture.width,
+                    height: texture.height,
+                    mips: texture.mips,
+                    block_format: texture.block_format,
+                    color_space: texture.color_space,
+                    generate_mips: texture.generate_mips,
+                    mag_filter,
+                    min_filter,
+                    address_mode,
+                }
+            })
+            .collect::<Vec<_>>();
+        self.resources().upload_textures(&self.task_pool, &descs)
+    }
+
+    fn create_material(&mut self, desc: &MaterialDesc) -> Result<MaterialHandle> {
+        self.resources().create_material(desc)
+    }
+
+    fn update_material(&mut self, material: MaterialHandle, desc: &MaterialDesc) -> Result<()> {
+        self.resources().update_material(material, desc)
+    }
+
+    fn clear_scene(&mut self) -> Result<()> {
+        self.resources().clear_scene();
+        Ok(())
+    }
+
+    fn reload_shaders(&mut self, vertex: &[u32], fragment: &[u32]) -> Result<()> {
+        // SAFETY: `ShaderModule::new` needs valid SPIR-V, and nothing here checks it:
+        // `SpirvShader` only looks at the magic number. The words are shader overrides
+        // from `assets/shaders` or a mounted mod, which are trusted like the game's own
+        // code; a truncated or hand-edited module is undefined behavior.
+        let vs =
+            unsafe { ShaderModule::new(self.device.clone(), ShaderModuleCreateInfo::new(vertex)) }
+                .with_context(|| "Failed to create vertex shader module")?;
+        let fs = unsafe {
+            ShaderModule::new(self.device.clone(), ShaderModuleCreateInfo::new(fragment))
+        }
+        .with_context(|| "Failed to create fragment shader module")?;
+        let render_thread = self
+            .render_thread
+            .as_ref()
+            .ok_or_else(|| anyhow!("Render context not initialized"))?;
+        let (pipeline, descriptor_sets) = {
+            let mut resources = lock(&self.resources);
+            let msaa_samples = resources.msaa_samples();
+            let depth_format = resources.find_depth_format()?;
+            let pipeline = VulkanPipeline::with_shaders(
+                self.device.clone(),
+                vs,
+                fs,
+                resources.descriptor_cache_mut(),
+                render_thread.format,
+                msaa_samples,
+                depth_format,
+            )?;
+            let descriptor_sets = create_descriptor_sets(&mut resources, &pipeline)?;
+            (pipeline, descriptor_sets)
+        };
+        // Takes effect from the next frame the render thread picks up.
+        render_thread.send(RenderCommand::SetPipeline(pipeline, descriptor_sets))?;
+        info!("Reloaded mesh shaders");
+        Ok(())
+    }
+
+    fn set_wireframe(&mut self, enabled: bool) -> Result<()> {
+        let render_thread = self
+            .render_thread
+            .as_mut()
+            .ok_or_else(|| anyhow!("Render context not initialized"))?;
+        ensure!(
+            !enabled || render_thread.supports_wireframe,
+            "Wireframe needs the fill_mode_non_solid device feature"
+        );
+        render_thread.send(RenderCommand::SetWireframe(enabled))?;
+        render_thread.wireframe = enabled;
         Ok(())
     }
+
+    fn is_wireframe(&self) -> bool {
+        self.render_thread
+            .as_ref()
+            .is_some_and(|render_thread| render_thread.wireframe)
+    }
+
+    fn init_ui(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
+        let render_thread = self
+            .render_thread
+            .as_ref()
+            .ok_or_else(|| anyhow!("Render context not initialized"))?;
+        let surface = render_thread
+            .surface
+            .clone()
+            .ok_or_else(|| anyhow!("There is no overlay when rendering headless"))?;
+        let gui = Gui::new(
+            event_loop,
+            surface,
+            self.graphics_queue.clone(),
+            render_thread.format,
+            GuiConfig {
+                // The swapchain prefers an sRGB format; egui copes with slightly off blending.
+                allow_srgb_render_target: true,
+                is_overlay: true,
+                ..Default::default()
+            },
+        );
+        *lock(&self.gui) = Some(gui);
+        Ok(())
+    }
+
+    fn handle_ui_event(&mut self, event: &WindowEvent) -> bool {
+        lock(&self.gui)
+            .as_mut()
+            .is_some_and(|gui| gui.update(event))
+    }
+
+    fn begin_ui(&mut self) -> Option<egui::Context> {
+        // The render thread may still be drawing the previous overlay frame.
+        self.sync_render_thread();
+        let mut gui = lock(&self.gui);
+        let gui = gui.as_mut()?;
+        if self.ui_frame_open {
+            // The previous frame was skipped (minimized, swapchain out of date) before the
+            // overlay got drawn; close its pass so egui doesn't nest them.
+            let _ = gui.context().end_pass();
+        }
+        gui.begin_frame();
+        self.ui_frame_open = true;
+        Some(gui.context())
+    }
+
+    fn wait_idle(&mut self) -> Result<()> {
+        self.sync_render_thread();
+        // SAFETY: no other thread submits to this device's queues while we wait; the
+        // render thread is idle once its last frame was collected.
+        unsafe { self.device.wait_idle()? };
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        self.wait_idle()?;
+        // Everything referencing swapchain images goes before the swapchain, and all of it
+        // before the device-level resources.
+        *lock(&self.gui) = None;
+        self.ui_frame_open = false;
+        self.preview = None;
+        if let Some(render_thread) = self.render_thread.take() {
+            render_thread.join()?;
+        }
+        self.finished_frame = None;
+        self.resources().clear();
+        info!("Vulkan renderer shut down");
+        Ok(())
+    }
+}
+
+/// Maps glTF filtering and wrapping modes to Vulkan sampler parameters.
+fn vk_sampler_params(
+    filter: (Option<MagFilter>, Option<MinFilter>),
+    wrap: (WrappingMode, WrappingMode),
+) -> (Filter, Filter, [SamplerAddressMode; 3]) {
+    let vk_mag_filter = match filter.0 {
+        Some(MagFilter::Nearest) => Filter::Nearest,
+        Some(MagFilter::Linear) | None => Filter::Linear,
+    };
+    let vk_min_filter = match filter.1 {
+        Some(MinFilter::Nearest) => Filter::Nearest,
+        Some(MinFilter::Linear) => Filter::Linear,
+        _ => Filter::Linear, // Simplified for brevity
+    };
+    let vk_address_mode_s = match wrap.0 {
+        WrappingMode::ClampToEdge => SamplerAddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
+        WrappingMode::Repeat => SamplerAddressMode::Repeat,
+    };
+    let vk_address_mode_t = match wrap.1 {
+        WrappingMode::ClampToEdge => SamplerAddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
+        WrappingMode::Repeat => SamplerAddressMode::Repeat,
+    };
+    (
+        vk_mag_filter,
+        vk_min_filter,
+        [
+            vk_address_mode_s,
+            vk_address_mode_t,
+            SamplerAddressMode::Repeat,
+        ],
+    )
+}
+
+/// Forwards a debug-utils message to `tracing` under the `vulkan` target, so validation
+/// errors and warnings survive release-level filters.
+#[cfg(debug_assertions)]
+fn log_debug_message(
+    severity: DebugUtilsMessageSeverity,
+    message_type: DebugUtilsMessageType,
+    data: DebugUtilsMessengerCallbackData<'_>,
+) {
+    let id = data.message_id_name.unwrap_or_default();
+    let id_number = data.message_id_number;
+    let message = data.message;
+    if severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+        error!(target: "vulkan", id, id_number, ?message_type, "{message}");
+    } else if severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+        warn!(target: "vulkan", id, id_number, ?message_type, "{message}");
+    } else if severity.intersects(DebugUtilsMessageSeverity::INFO) {
+        info!(target: "vulkan", id, id_number, ?message_type, "{message}");
+    } else {
+        trace!(target: "vulkan", id, id_number, ?message_type, "{message}");
+    }
 }
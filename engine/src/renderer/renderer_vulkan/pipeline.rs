@@ -1,168 +1,367 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+use crate::renderer::MaterialDesc;
 use crate::renderer::renderer_vulkan::{
-    ElmVertex,
+    ElmVertex, MeshPushConstants,
+    descriptor_cache::DescriptorCache,
+    render_thread::lock,
     shaders::{fs, vs},
 };
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
+use tracing::debug;
 use vulkano::image::SampleCount;
 use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthState, DepthStencilState};
 use vulkano::pipeline::graphics::subpass::PipelineRenderingCreateInfo;
 use vulkano::{
-    descriptor_set::layout::{
-        DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo,
-        DescriptorType,
-    },
+    descriptor_set::layout::{DescriptorSetLayout, DescriptorType},
     device::Device,
     format::Format,
     pipeline::{
         DynamicState, GraphicsPipeline, Pipeline, PipelineLayout, PipelineShaderStageCreateInfo,
         graphics::{
             GraphicsPipelineCreateInfo,
-            color_blend::{ColorBlendAttachmentState, ColorBlendState},
+            color_blend::{AttachmentBlend, ColorBlendAttachmentState, ColorBlendState},
             input_assembly::InputAssemblyState,
             multisample::MultisampleState,
             rasterization::{CullMode, FrontFace, PolygonMode, RasterizationState},
-            vertex_input::{Vertex, VertexDefinition},
+            vertex_input::{Vertex, VertexDefinition, VertexInputState},
             viewport::ViewportState,
         },
-        layout::PipelineLayoutCreateInfo,
+        layout::{
+            PipelineDescriptorSetLayoutCreateInfo, PipelineLayoutCreateInfo, PushConstantRange,
+        },
     },
-    shader::ShaderStages,
+    shader::{ShaderModule, ShaderStages, SpecializationConstant},
 };
 
+/// Set of the mesh pipeline holding the frame's uniforms and lights.
+pub const FRAME_SET: usize = 0;
+/// Set of the mesh pipeline holding a material's parameters and textures.
+pub const MATERIAL_SET: usize = 1;
+
+/// `(set, binding)` of uniform buffers bound with a dynamic offset. Reflection can't tell
+/// them from plain uniform buffers.
+const DYNAMIC_UNIFORM_BUFFERS: &[(usize, u32)] = &[(FRAME_SET, 0)];
+
+/// Set layouts of the built-in mesh shaders, in set order.
+pub fn default_set_layouts(
+    device: Arc<Device>,
+    cache: &mut DescriptorCache,
+) -> Result<Vec<Arc<DescriptorSetLayout>>> {
+    let stages = shader_stages(vs::load(device.clone())?, fs::load(device)?)?;
+    set_layouts(cache, &stages)
+}
+
+/// Derives the set layouts from the shaders' reflection data, shared through `cache`.
+fn set_layouts(
+    cache: &mut DescriptorCache,
+    stages: &[PipelineShaderStageCreateInfo],
+) -> Result<Vec<Arc<DescriptorSetLayout>>> {
+    let mut create_info = PipelineDescriptorSetLayoutCreateInfo::from_stages(stages);
+    for &(set, binding) in DYNAMIC_UNIFORM_BUFFERS {
+        if let Some(binding) = create_info
+            .set_layouts
+            .get_mut(set)
+            .and_then(|layout| layout.bindings.get_mut(&binding))
+        {
+            binding.descriptor_type = DescriptorType::UniformBufferDynamic;
+        }
+    }
+    create_info
+        .set_layouts
+        .into_iter()
+        .map(|layout| cache.layout(layout))
+        .collect()
+}
+
+fn shader_stages(
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+) -> Result<[PipelineShaderStageCreateInfo; 2]> {
+    let vs = vs
+        .entry_point("main")
+        .ok_or(anyhow!("No main entry point in vertex shader"))?;
+    let fs = fs
+        .entry_point("main")
+        .ok_or(anyhow!("No main entry point in fragment shader"))?;
+    Ok([
+        PipelineShaderStageCreateInfo::new(vs),
+        PipelineShaderStageCreateInfo::new(fs),
+    ])
+}
+
+/// Feature bits of a mesh pipeline variant. Culling, blending and polygon mode follow
+/// from them, as do the fragment shader's specialization constants, so one shader source
+/// serves every combination. Variants are built the first time a key is drawn with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PipelineKey(u32);
+
+impl PipelineKey {
+    /// Back faces are drawn instead of culled.
+    pub const DOUBLE_SIDED: PipelineKey = PipelineKey(1);
+    /// Alpha-blended, testing depth without writing it.
+    pub const BLEND: PipelineKey = PipelineKey(1 << 1);
+    /// Fragments below the material's alpha cutoff are discarded.
+    pub const ALPHA_TEST: PipelineKey = PipelineKey(1 << 2);
+    /// Base color only, without lights or ambient.
+    pub const UNLIT: PipelineKey = PipelineKey(1 << 3);
+    /// Lines instead of filled triangles. Ignored if the device can't draw them.
+    pub const WIREFRAME: PipelineKey = PipelineKey(1 << 4);
+
+    /// The variant drawing `desc`, as lines if `wireframe`.
+    pub fn for_material(desc: &MaterialDesc, wireframe: bool) -> Self {
+        PipelineKey::default()
+            .with(PipelineKey::DOUBLE_SIDED, desc.double_sided)
+            .with(PipelineKey::BLEND, desc.blend)
+            .with(PipelineKey::ALPHA_TEST, desc.alpha_cutoff.is_some())
+            .with(PipelineKey::UNLIT, desc.unlit)
+            .with(PipelineKey::WIREFRAME, wireframe)
+    }
+
+    /// These features plus `feature` if `enabled`, without it otherwise.
+    pub const fn with(self, feature: PipelineKey, enabled: bool) -> Self {
+        if enabled {
+            PipelineKey(self.0 | feature.0)
+        } else {
+            PipelineKey(self.0 & !feature.0)
+        }
+    }
+
+    pub const fn contains(self, feature: PipelineKey) -> bool {
+        self.0 & feature.0 == feature.0
+    }
+}
+
+/// Fragment shader specialization constant ids, in `layout(constant_id = ...)` order.
+const ALPHA_TEST_CONSTANT: u32 = 0;
+const UNLIT_CONSTANT: u32 = 1;
+
 pub struct VulkanPipeline {
-    pipeline: Arc<GraphicsPipeline>,
+    device: Arc<Device>,
+    /// Kept to build variants and to rebuild the pipeline for another swapchain format.
+    shaders: (Arc<ShaderModule>, Arc<ShaderModule>),
+    layout: Arc<PipelineLayout>,
+    vertex_input_state: VertexInputState,
+    rendering: PipelineRenderingCreateInfo,
+    msaa_samples: SampleCount,
+    /// Whether the device has `fill_mode_non_solid`.
+    wireframe: bool,
+    /// Variants built so far. Locked while recording, so a new variant may be built from
+    /// the render thread.
+    variants: Mutex<HashMap<PipelineKey, Arc<GraphicsPipeline>>>,
 }
 
 impl VulkanPipeline {
+    /// Set layouts come from the shaders' reflection data through `cache`, so they're
+    /// shared with materials' descriptor sets.
     pub fn new(
         device: Arc<Device>,
+        cache: &mut DescriptorCache,
+        format: Format,
+        msaa_samples: SampleCount,
+        depth_format: Format,
+    ) -> Result<Self> {
+        let vs = vs::load(device.clone())?;
+        let fs = fs::load(device.clone())?;
+        Self::with_shaders(device, vs, fs, cache, format, msaa_samples, depth_format)
+    }
+
+    /// Builds the pipeline from caller-provided shader modules, e.g. SPIR-V reloaded from
+    /// disk. They must match the built-in shaders' material set, which existing
+    /// materials were written against. The opaque, blended and double-sided variants are
+    /// built right away, so broken shaders fail here rather than mid-frame.
+    pub fn with_shaders(
+        device: Arc<Device>,
+        vs: Arc<ShaderModule>,
+        fs: Arc<ShaderModule>,
+        cache: &mut DescriptorCache,
         format: Format,
         msaa_samples: SampleCount,
         depth_format: Format,
     ) -> Result<Self> {
-        let pipeline = {
-            let vs = vs::load(device.clone())?
-                .entry_point("main")
-                .ok_or(anyhow!("No main entry point in vertex shader"))?;
-            let fs = fs::load(device.clone())?
-                .entry_point("main")
-                .ok_or(anyhow!("No main entry point in fragment shader"))?;
-
-            let vertex_input_state = ElmVertex::per_vertex().definition(&vs)?;
-
-            let stages = [
-                PipelineShaderStageCreateInfo::new(vs),
-                PipelineShaderStageCreateInfo::new(fs),
-            ];
-
-            let rasterization_state = RasterizationState {
-                polygon_mode: PolygonMode::Fill,
-                line_width: 1.0,
-                cull_mode: CullMode::Back,
-                front_face: FrontFace::CounterClockwise,
-                ..RasterizationState::default()
-            };
-
-            let multisample_state = MultisampleState {
-                rasterization_samples: msaa_samples,
-                sample_shading: Some(0.2),
-                ..MultisampleState::default()
-            };
-
-            let mut ubo_layout_binding =
-                DescriptorSetLayoutBinding::descriptor_type(DescriptorType::UniformBuffer);
-            ubo_layout_binding.stages = ShaderStages::VERTEX | ShaderStages::FRAGMENT;
-
-            let mut sampler_layout_binding =
-                DescriptorSetLayoutBinding::descriptor_type(DescriptorType::CombinedImageSampler);
-            sampler_layout_binding.stages = ShaderStages::FRAGMENT;
-
-            let descriptor_set_layout = DescriptorSetLayout::new(
-                device.clone(),
-                DescriptorSetLayoutCreateInfo {
-                    bindings: vec![(0, ubo_layout_binding), (1, sampler_layout_binding)]
-                        .into_iter()
-                        .collect(),
-                    ..Default::default()
-                },
-            )?;
-
-            let layout = PipelineLayout::new(
-                device.clone(),
-                PipelineLayoutCreateInfo {
-                    set_layouts: vec![descriptor_set_layout],
-                    ..Default::default()
-                },
-            )?;
-
-            let pipeline_rendering_create_info = PipelineRenderingCreateInfo {
+        let stages = shader_stages(vs.clone(), fs.clone())?;
+        let vertex_input_state = ElmVertex::per_vertex().definition(&stages[0].entry_point)?;
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineLayoutCreateInfo {
+                set_layouts: set_layouts(cache, &stages)?,
+                push_constant_ranges: vec![PushConstantRange {
+                    stages: ShaderStages::VERTEX,
+                    offset: 0,
+                    size: size_of::<MeshPushConstants>() as u32,
+                }],
+                ..Default::default()
+            },
+        )?;
+        let pipeline = VulkanPipeline {
+            wireframe: device.enabled_features().fill_mode_non_solid,
+            device,
+            shaders: (vs, fs),
+            layout,
+            vertex_input_state,
+            rendering: PipelineRenderingCreateInfo {
                 color_attachment_formats: vec![Some(format)],
                 depth_attachment_format: Some(depth_format),
                 ..Default::default()
-            };
-
-            let depth_stencil_state = DepthStencilState {
-                depth: Some(DepthState {
-                    write_enable: true,
-                    compare_op: CompareOp::LessOrEqual,
-                }),
-                ..DepthStencilState::default()
-            };
-
-            // Finally, create the pipeline.
-            GraphicsPipeline::new(
-                device.clone(),
-                None,
-                GraphicsPipelineCreateInfo {
-                    stages: stages.into_iter().collect(),
-                    // How vertex data is read from the vertex buffers into the vertex shader.
-                    vertex_input_state: Some(vertex_input_state),
-                    // How vertices are arranged into primitive shapes. The default primitive shape
-                    // is a triangle.
-                    input_assembly_state: Some(InputAssemblyState::default()),
-                    // How primitives are transformed and clipped to fit the framebuffer. We use a
-                    // resizable viewport, set to draw over the entire window.
-                    viewport_state: Some(ViewportState::default()),
-                    // How polygons are culled and converted into a raster of pixels. The default
-                    // value does not perform any culling.
-                    rasterization_state: Some(rasterization_state),
-                    // How multiple fragment shader samples are converted to a single pixel value.
-                    // The default value does not perform any multisampling.
-                    multisample_state: Some(multisample_state),
-                    // How pixel values are combined with the values already present in the
-                    // framebuffer. The default value overwrites the old value with the new one,
-                    // without any blending.
-                    color_blend_state: Some(ColorBlendState::with_attachment_states(
-                        pipeline_rendering_create_info
-                            .color_attachment_formats
-                            .len() as u32,
-                        ColorBlendAttachmentState::default(),
-                    )),
-                    depth_stencil_state: Some(depth_stencil_state),
-                    // Dynamic states allows us to specify parts of the pipeline settings when
-                    // recording the command buffer, before we perform drawing. Here, we specify
-                    // that the viewport should be dynamic.
-                    dynamic_state: [DynamicState::Viewport].into_iter().collect(),
-                    subpass: Some(pipeline_rendering_create_info.into()),
-                    ..GraphicsPipelineCreateInfo::layout(layout)
-                },
-            )?
+            },
+            msaa_samples,
+            variants: Mutex::new(HashMap::new()),
         };
+        for double_sided in [false, true] {
+            for blend in [false, true] {
+                let key = PipelineKey::default()
+                    .with(PipelineKey::DOUBLE_SIDED, double_sided)
+                    .with(PipelineKey::BLEND, blend);
+                pipeline.pipeline_for(key)?;
+            }
+        }
+        if pipeline.wireframe {
+            pipeline.pipeline_for(PipelineKey::WIREFRAME)?;
+        }
+        Ok(pipeline)
+    }
+
+    /// The same shaders built for a different color attachment, e.g. after the swapchain
+    /// format changed.
+    pub fn with_format(
+        &self,
+        device: Arc<Device>,
+        cache: &mut DescriptorCache,
+        format: Format,
+        msaa_samples: SampleCount,
+        depth_format: Format,
+    ) -> Result<Self> {
+        let (vs, fs) = self.shaders.clone();
+        Self::with_shaders(device, vs, fs, cache, format, msaa_samples, depth_format)
+    }
 
-        Ok(VulkanPipeline { pipeline })
+    /// The variant for `key`, built and cached on first use. Wireframe is dropped from
+    /// the key if the device can't draw lines.
+    pub fn pipeline_for(&self, key: PipelineKey) -> Result<Arc<GraphicsPipeline>> {
+        let key = key.with(
+            PipelineKey::WIREFRAME,
+            self.wireframe && key.contains(PipelineKey::WIREFRAME),
+        );
+        let mut variants = lock(&self.variants);
+        if let Some(pipeline) = variants.get(&key) {
+            return Ok(pipeline.clone());
+        }
+        let pipeline = self
+            .build(key)
+            .with_context(|| format!("Failed to build pipeline variant {key:?}"))?;
+        debug!(
+            ?key,
+            variants = variants.len() + 1,
+            "Built pipeline variant"
+        );
+        variants.insert(key, pipeline.clone());
+        Ok(pipeline)
     }
 
-    pub fn pipeline(&self) -> Arc<GraphicsPipeline> {
-        self.pipeline.clone()
+    fn build(&self, key: PipelineKey) -> Result<Arc<GraphicsPipeline>> {
+        let (vs, fs) = &self.shaders;
+        let vs = vs
+            .entry_point("main")
+            .ok_or(anyhow!("No main entry point in vertex shader"))?;
+        // Ids the shader doesn't declare are ignored, so reloaded shaders without the
+        // constants still build.
+        let fs = fs
+            .specialize(
+                [
+                    (
+                        ALPHA_TEST_CONSTANT,
+                        SpecializationConstant::Bool(key.contains(PipelineKey::ALPHA_TEST)),
+                    ),
+                    (
+                        UNLIT_CONSTANT,
+                        SpecializationConstant::Bool(key.contains(PipelineKey::UNLIT)),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            )?
+            .entry_point("main")
+            .ok_or(anyhow!("No main entry point in fragment shader"))?;
+        let blend = key.contains(PipelineKey::BLEND);
+        // Blended variants test depth but don't write it, so sorted transparent surfaces
+        // don't hide each other.
+        let depth_stencil_state = DepthStencilState {
+            depth: Some(DepthState {
+                write_enable: !blend,
+                compare_op: CompareOp::LessOrEqual,
+            }),
+            ..DepthStencilState::default()
+        };
+        let attachment_state = ColorBlendAttachmentState {
+            blend: blend.then(AttachmentBlend::alpha),
+            ..ColorBlendAttachmentState::default()
+        };
+        let polygon_mode = if key.contains(PipelineKey::WIREFRAME) {
+            PolygonMode::Line
+        } else {
+            PolygonMode::Fill
+        };
+        let cull_mode = if key.contains(PipelineKey::DOUBLE_SIDED) {
+            CullMode::None
+        } else {
+            CullMode::Back
+        };
+        Ok(GraphicsPipeline::new(
+            self.device.clone(),
+            None,
+            GraphicsPipelineCreateInfo {
+                stages: [
+                    PipelineShaderStageCreateInfo::new(vs),
+                    PipelineShaderStageCreateInfo::new(fs),
+                ]
+                .into_iter()
+                .collect(),
+                // How vertex data is read from the vertex buffers into the vertex shader.
+                vertex_input_state: Some(self.vertex_input_state.clone()),
+                // How vertices are arranged into primitive shapes. The default primitive
+                // shape is a triangle.
+                input_assembly_state: Some(InputAssemblyState::default()),
+                // How primitives are transformed and clipped to fit the framebuffer. We use
+                // a resizable viewport, set to draw over the entire window.
+                viewport_state: Some(ViewportState::default()),
+                // How polygons are culled and converted into a raster of pixels.
+                rasterization_state: Some(RasterizationState {
+                    polygon_mode,
+                    line_width: 1.0,
+                    cull_mode,
+                    front_face: FrontFace::CounterClockwise,
+                    ..RasterizationState::default()
+                }),
+                // How multiple fragment shader samples are converted to a single pixel
+                // value.
+                multisample_state: Some(MultisampleState {
+                    rasterization_samples: self.msaa_samples,
+                    sample_shading: Some(0.2),
+                    ..MultisampleState::default()
+                }),
+                // How pixel values are combined with the values already present in the
+                // framebuffer: overwritten, or alpha-blended for transparent materials.
+                color_blend_state: Some(ColorBlendState::with_attachment_states(
+                    self.rendering.color_attachment_formats.len() as u32,
+                    attachment_state,
+                )),
+                depth_stencil_state: Some(depth_stencil_state),
+                // Dynamic states allows us to specify parts of the pipeline settings
+                // when recording the command buffer, before we perform drawing. Here, we
+                // specify that the viewport should be dynamic.
+                dynamic_state: [DynamicState::Viewport].into_iter().collect(),
+                subpass: Some(self.rendering.clone().into()),
+                ..GraphicsPipelineCreateInfo::layout(self.layout.clone())
+            },
+        )?)
     }
 
-    // pub fn render_pass(&self) -> Arc<RenderPass> {
-    //     self.render_pass.clone()
-    // }
+    pub fn supports_wireframe(&self) -> bool {
+        self.wireframe
+    }
 
     pub fn layout(&self) -> Arc<PipelineLayout> {
-        self.pipeline.layout().clone()
+        self.layout.clone()
     }
 }
@@ -1,79 +1,263 @@
+use crate::camera::Camera;
+use crate::capture::CapturedFrame;
+use crate::core::transform::Transform;
 use crate::renderer::renderer_vulkan::resources::VulkanResources;
 use crate::renderer::renderer_vulkan::{
-    MAX_FRAMES_IN_FLIGHT, pipeline::VulkanPipeline, resources::UniformBufferObject,
-    swapchain::VulkanSwapchain,
+    MAX_FRAMES_IN_FLIGHT,
+    pipeline::{PipelineKey, VulkanPipeline},
+    render_queue::RenderQueue,
+    resources::{LightData, MeshPushConstants, PreviewTarget, UniformBufferObject},
+    target::FrameTarget,
+    uniform_ring::UniformRing,
 };
+use crate::scene::RenderLayers;
 use anyhow::{Context, Result};
-use glam::{Mat4, Vec3};
-use std::{sync::Arc, time::Instant};
-use tracing::error;
+use egui_winit_vulkano::Gui;
+use glam::{Mat4, Quat, Vec3};
+use std::{sync::Arc, time::Duration};
+use tracing::{error, info_span};
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
 use vulkano::command_buffer::{
-    CommandBufferUsage, RenderingAttachmentInfo, RenderingAttachmentResolveInfo, RenderingInfo,
+    CommandBufferUsage, CopyImageToBufferInfo, RenderingAttachmentInfo,
+    RenderingAttachmentResolveInfo, RenderingInfo,
 };
 use vulkano::device::Queue;
-use vulkano::format::ClearValue;
+use vulkano::format::{ClearValue, Format};
 use vulkano::image::ImageLayout;
 use vulkano::image::ImageLayout::DepthAttachmentOptimal;
 use vulkano::image::view::ImageView;
 use vulkano::pipeline::PipelineBindPoint;
+use vulkano::query::{QueryPool, QueryResultFlags};
 use vulkano::render_pass::{AttachmentLoadOp, AttachmentStoreOp, ResolveMode};
 use vulkano::swapchain::SwapchainPresentInfo;
+use vulkano::sync::PipelineStage;
 use vulkano::{
     Validated, VulkanError,
     buffer::Subbuffer,
     command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
-    descriptor_set::DescriptorSet,
+    descriptor_set::{DescriptorSet, DescriptorSetWithOffsets},
     pipeline::graphics::viewport::Viewport,
     sync::{GpuFuture, future::FenceSignalFuture},
 };
 
 pub struct RenderContext {
-    pub swapchain: VulkanSwapchain,
+    pub target: FrameTarget,
     pub pipeline: VulkanPipeline,
     pub viewport: Viewport,
     pub recreate_swapchain: bool,
     pub frames: Vec<FrameState>,
     pub current_frame: usize,
-    pub start_time: Instant,
+    /// Draw meshes with the wireframe pipeline, if the device supports it.
+    pub wireframe: bool,
+    /// Nanoseconds per timestamp tick.
+    pub timestamp_period: f32,
+    /// Draw order of the current frame, kept to reuse its allocations.
+    pub render_queue: RenderQueue,
 }
 
 pub struct FrameState {
-    pub in_flight_future: Option<FenceSignalFuture<Box<dyn GpuFuture>>>,
-    pub descriptor_sets: Vec<Arc<DescriptorSet>>,
+    /// Signalled once the frame's commands finished on the graphics queue.
+    pub in_flight_future: Option<Arc<FenceSignalFuture<Box<dyn GpuFuture>>>>,
+    /// The present, when it went to a separate present queue. Kept until it finished,
+    /// since dropping it earlier blocks.
+    pub present_future: Option<FenceSignalFuture<Box<dyn GpuFuture>>>,
+    /// Set 0 of the mesh pipeline, bound with the frame's offset into the uniform ring.
+    pub descriptor_set: Arc<DescriptorSet>,
+    /// Start and end timestamps of this frame's scene pass. `None` if the graphics queue
+    /// doesn't support timestamps.
+    pub timestamps: Option<Arc<QueryPool>>,
+    /// Set once `timestamps` were written by a submitted frame, so they can be read back.
+    pub timestamps_written: bool,
+    /// Copy of the presented image, while frame capture is recording.
+    pub readback: Option<FrameReadback>,
+}
+
+/// Host buffer a presented swapchain image is copied into.
+pub struct FrameReadback {
+    pub buffer: Subbuffer<[u8]>,
+    pub extent: [u32; 2],
+    pub bgra: bool,
 }
 
 impl RenderContext {
-    pub fn update_uniform_buffer(
-        &mut self,
-        ubo_buffer: Subbuffer<UniformBufferObject>,
-    ) -> Result<()> {
-        let current_time = Instant::now();
-        let elapsed = current_time.duration_since(self.start_time);
-
-        let mut ubo = UniformBufferObject {
-            model: Mat4::from_rotation_z(elapsed.as_secs_f32() * 90.0f32.to_radians()),
-            view: Mat4::look_at_rh(Vec3::new(5.0, 5.0, 5.0), Vec3::ZERO, Vec3::Z),
-            proj: Mat4::perspective_rh(
-                45.0f32.to_radians(),
-                self.viewport.extent[0] / self.viewport.extent[1],
-                0.5,
-                20.0,
-            ),
+    /// Builds the context from per-frame descriptor sets and optional timestamp pools,
+    /// one of each per frame in flight.
+    pub fn new(
+        target: FrameTarget,
+        pipeline: VulkanPipeline,
+        viewport: Viewport,
+        frames: Vec<(Arc<DescriptorSet>, Option<Arc<QueryPool>>)>,
+        timestamp_period: f32,
+    ) -> Self {
+        let frames = frames
+            .into_iter()
+            .map(|(descriptor_set, timestamps)| FrameState {
+                in_flight_future: None,
+                present_future: None,
+                descriptor_set,
+                timestamps,
+                timestamps_written: false,
+                readback: None,
+            })
+            .collect();
+        RenderContext {
+            target,
+            pipeline,
+            viewport,
+            recreate_swapchain: false,
+            frames,
+            current_frame: 0,
+            wireframe: false,
+            timestamp_period,
+            render_queue: RenderQueue::new(),
+        }
+    }
+
+    /// Waits for the current frame slot's submission, then moves on to the next slot.
+    /// Called once the frame's locks are released, so other threads aren't held up.
+    /// Returns whether the fence signalled.
+    pub fn finish_frame(&mut self) -> bool {
+        let frame = &mut self.frames[self.current_frame];
+        if let Some(present_future) = frame.present_future.as_mut() {
+            match present_future.wait(None) {
+                Ok(()) => present_future.cleanup_finished(),
+                Err(e) => error!("Failed to wait for present future: {:?}", e),
+            }
+        }
+        let Some(fence_future) = frame.in_flight_future.as_mut() else {
+            return false;
         };
-        ubo.proj.y_axis.y *= -1.0; // Invert Y coordinate for Vulkan
+        match fence_future.wait(None) {
+            Ok(()) => {
+                // Fence successfully waited: we can safely clean up and advance to the next frame.
+                fence_future.cleanup_finished();
+                self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+                true
+            }
+            Err(e) => {
+                error!("Failed to wait for fence future: {:?}", e);
+                false
+            }
+        }
+    }
 
-        *ubo_buffer.write()? = ubo;
-        Ok(())
+    /// GPU time of the scene pass last submitted in the current frame slot. Its fence
+    /// was already waited on, so the results don't stall.
+    pub fn read_gpu_time(&mut self) -> Option<Duration> {
+        let frame = &mut self.frames[self.current_frame];
+        let pool = frame
+            .timestamps
+            .as_ref()
+            .filter(|_| frame.timestamps_written)?;
+        let mut ticks = [0u64; 2];
+        match pool.get_results(0..2, &mut ticks, QueryResultFlags::empty()) {
+            Ok(true) => {
+                frame.timestamps_written = false;
+                let nanos = ticks[1].saturating_sub(ticks[0]) as f64 * self.timestamp_period as f64;
+                Some(Duration::from_nanos(nanos as u64))
+            }
+            Ok(false) => None,
+            Err(e) => {
+                error!("Failed to read timestamp queries: {:?}", e);
+                frame.timestamps_written = false;
+                None
+            }
+        }
+    }
+
+    /// Whether the target's images can be copied out for frame capture, and if so
+    /// whether their channels are in BGRA order.
+    pub fn capture_layout(&self) -> Option<bool> {
+        if !self.target.copyable() {
+            return None;
+        }
+        match self.target.format() {
+            Format::R8G8B8A8_SRGB | Format::R8G8B8A8_UNORM => Some(false),
+            Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Records a copy of target image `image_index` into `buffer`, to run after the
+    /// overlay was drawn onto it.
+    pub fn build_capture_command_buffer(
+        &self,
+        command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+        graphics_queue: &Arc<Queue>,
+        image_index: u32,
+        buffer: Subbuffer<[u8]>,
+    ) -> Result<Arc<PrimaryAutoCommandBuffer>> {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            command_buffer_allocator,
+            graphics_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        let image = self.target.image_views()[image_index as usize]
+            .image()
+            .clone();
+        builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer))?;
+        Ok(builder.build()?)
+    }
+
+    /// The frame captured the last time the current frame slot was submitted. Its fence
+    /// was already waited on.
+    pub fn take_capture(&mut self) -> Result<Option<CapturedFrame>> {
+        let Some(readback) = self.frames[self.current_frame].readback.take() else {
+            return Ok(None);
+        };
+        let pixels = readback.buffer.read()?.to_vec();
+        Ok(Some(CapturedFrame {
+            width: readback.extent[0],
+            height: readback.extent[1],
+            pixels,
+            bgra: readback.bgra,
+        }))
+    }
+
+    /// Writes the first `lights` that fit the frame's light buffer. Returns how many did.
+    pub fn write_lights(lights: &[LightData], light_buffer: Subbuffer<[LightData]>) -> Result<u32> {
+        let light_count = lights.len().min(light_buffer.len() as usize);
+        light_buffer.write()?[..light_count].copy_from_slice(&lights[..light_count]);
+        Ok(light_count as u32)
+    }
+
+    /// Pushes a camera and the ambient light into `ring`. Called once per view drawn this
+    /// frame; they share the lights of [`RenderContext::write_lights`]. Returns the matrix
+    /// from mesh instance space to view space, for sorting draws by depth, the projection,
+    /// for culling them, and the uniforms' dynamic offset.
+    pub fn push_camera(
+        &self,
+        elapsed: Duration,
+        camera: &Camera,
+        aspect_ratio: f32,
+        ambient: Vec3,
+        light_count: u32,
+        ring: &mut UniformRing,
+    ) -> Result<(Mat4, Mat4, u32)> {
+        let ubo = UniformBufferObject {
+            model: Transform::from_rotation(Quat::from_rotation_z(
+                elapsed.as_secs_f32() * 90.0f32.to_radians(),
+            ))
+            .compute_matrix(),
+            view: camera.view_matrix(),
+            proj: camera.projection_matrix(aspect_ratio),
+            ambient: ambient.extend(1.0),
+            light_count,
+            _padding: [0; 3],
+        };
+
+        let offset = ring.push(ubo)?;
+        Ok((ubo.view * ubo.model, ubo.proj, offset))
     }
 
     pub fn build_command_buffer(
         &mut self,
         command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
         graphics_queue: Arc<Queue>,
-        color_image_view: Arc<ImageView>,
+        color_image_view: Option<Arc<ImageView>>,
         depth_image_view: Arc<ImageView>,
         image_index: u32,
+        ubo_offset: u32,
     ) -> Result<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>> {
         let mut builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> =
             AutoCommandBufferBuilder::primary(
@@ -82,22 +266,26 @@ impl RenderContext {
                 CommandBufferUsage::OneTimeSubmit,
             )?;
 
-        let clear_color = ClearValue::Float([0.0, 0.0, 0.0, 1.0]);
+        if let Some(pool) = self.frames[self.current_frame].timestamps.clone() {
+            // SAFETY: the queries were reset just before, and the pool is only used by
+            // this frame slot, whose previous submission has finished.
+            unsafe {
+                builder
+                    .reset_query_pool(pool.clone(), 0..2)?
+                    .write_timestamp(pool, 0, PipelineStage::TopOfPipe)?;
+            }
+        }
+
+        // Transparent windows show the desktop wherever nothing is drawn.
+        let clear_alpha = if self.target.transparent() { 0.0 } else { 1.0 };
+        let clear_color = ClearValue::Float([0.0, 0.0, 0.0, clear_alpha]);
         let clear_depth = ClearValue::DepthStencil((1.0, 0));
 
-        let color_attachments = vec![Some(RenderingAttachmentInfo {
-            load_op: AttachmentLoadOp::Clear,
-            store_op: AttachmentStoreOp::DontCare,
-            clear_value: Some(clear_color),
-            image_layout: ImageLayout::ColorAttachmentOptimal,
-            resolve_info: Some(RenderingAttachmentResolveInfo {
-                mode: ResolveMode::Average,
-                ..RenderingAttachmentResolveInfo::image_view(
-                    self.swapchain.image_views[image_index as usize].clone(),
-                )
-            }),
-            ..RenderingAttachmentInfo::image_view(color_image_view.clone())
-        })];
+        let color_attachments = vec![Some(color_attachment(
+            color_image_view,
+            self.target.image_views()[image_index as usize].clone(),
+            clear_color,
+        ))];
 
         let depth_attachment = Some(RenderingAttachmentInfo {
             load_op: AttachmentLoadOp::Clear,
@@ -109,20 +297,25 @@ impl RenderContext {
 
         builder
             .begin_rendering(RenderingInfo {
-                render_area_extent: self.swapchain.extent,
+                render_area_extent: self.target.extent(),
                 layer_count: 1,
                 color_attachments,
                 depth_attachment,
                 ..Default::default()
             })
             .with_context(|| "Begin rendering")?
-            .bind_pipeline_graphics(self.pipeline.pipeline())?
+            .bind_pipeline_graphics(self.pipeline.pipeline_for(
+                PipelineKey::default().with(PipelineKey::WIREFRAME, self.wireframe),
+            )?)?
             .set_viewport(0, [self.viewport.clone()].into_iter().collect())?
             .bind_descriptor_sets(
                 PipelineBindPoint::Graphics,
                 self.pipeline.layout(),
                 0,
-                self.frames[self.current_frame].descriptor_sets.clone(),
+                DescriptorSetWithOffsets::new(
+                    self.frames[self.current_frame].descriptor_set.clone(),
+                    [ubo_offset],
+                ),
             )
             .with_context(|| "Bind descriptor sets")?;
 
@@ -130,6 +323,34 @@ impl RenderContext {
     }
 }
 
+/// A cleared color attachment ending up in `target`: drawn into `msaa` and resolved
+/// when multisampling, into `target` directly otherwise.
+fn color_attachment(
+    msaa: Option<Arc<ImageView>>,
+    target: Arc<ImageView>,
+    clear_value: ClearValue,
+) -> RenderingAttachmentInfo {
+    let (image_view, store_op, resolve_info) = match msaa {
+        Some(msaa) => (
+            msaa,
+            AttachmentStoreOp::DontCare,
+            Some(RenderingAttachmentResolveInfo {
+                mode: ResolveMode::Average,
+                ..RenderingAttachmentResolveInfo::image_view(target)
+            }),
+        ),
+        None => (target, AttachmentStoreOp::Store, None),
+    };
+    RenderingAttachmentInfo {
+        load_op: AttachmentLoadOp::Clear,
+        store_op,
+        clear_value: Some(clear_value),
+        image_layout: ImageLayout::ColorAttachmentOptimal,
+        resolve_info,
+        ..RenderingAttachmentInfo::image_view(image_view)
+    }
+}
+
 pub struct ActiveFrame<'a> {
     pub rcx: &'a mut RenderContext,
     pub resources: &'a VulkanResources,
@@ -139,80 +360,236 @@ pub struct ActiveFrame<'a> {
 }
 
 impl<'a> ActiveFrame<'a> {
-    pub fn draw(&mut self) -> Result<()> {
-        for mesh in &self.resources.meshes {
+    /// Records a draw for every mesh instance in [`RenderQueue`] order, switching pipeline
+    /// variant, material set and mesh buffers only when they change between instances. `view` is the
+    /// matrix from instance space to view space; `layers` are the camera's. Returns
+    /// `(draw_calls, triangles)`.
+    pub fn draw(
+        &mut self,
+        view: Mat4,
+        projection: Mat4,
+        layers: RenderLayers,
+    ) -> Result<(u32, u64)> {
+        let mut draw_calls = 0;
+        let mut triangles = 0;
+        let rcx = &mut *self.rcx;
+        rcx.render_queue
+            .build(self.resources, view, projection, layers);
+        let layout = rcx.pipeline.layout();
+        let mut bound_material = None;
+        let mut bound_mesh = None;
+        // Matches the pipeline bound by `build_command_buffer` and `render_preview`.
+        let mut bound_key = PipelineKey::default().with(PipelineKey::WIREFRAME, rcx.wireframe);
+        for index in rcx.render_queue.draw_order() {
+            let instance = &self.resources.instances[index];
+            let mesh = self
+                .resources
+                .mesh_or_fallback(instance.mesh)
+                .ok_or_else(|| anyhow::anyhow!("Mesh {} not found", instance.mesh))?;
+            let material = self
+                .resources
+                .material_or_error(instance.material)
+                .ok_or_else(|| anyhow::anyhow!("Material {:?} not found", instance.material))?;
             if let Some(ref mut builder) = self.builder {
-                builder
-                    .bind_vertex_buffers(0, mesh.vertex_buffer.clone())?
-                    .bind_index_buffer(mesh.index_buffer.clone())?;
+                if bound_material != Some(instance.material) {
+                    let key = PipelineKey::for_material(&material.desc, rcx.wireframe);
+                    if key != bound_key {
+                        bound_key = key;
+                        builder.bind_pipeline_graphics(rcx.pipeline.pipeline_for(key)?)?;
+                    }
+                    builder.bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        layout.clone(),
+                        1,
+                        material.descriptor_set.clone(),
+                    )?;
+                    bound_material = Some(instance.material);
+                }
+                builder.push_constants(
+                    layout.clone(),
+                    0,
+                    MeshPushConstants {
+                        node: instance.transform,
+                    },
+                )?;
+                if bound_mesh != Some(instance.mesh) {
+                    builder
+                        .bind_vertex_buffers(0, mesh.vertex_buffer.clone())?
+                        .bind_index_buffer(mesh.index_buffer.clone())?;
+                    bound_mesh = Some(instance.mesh);
+                }
                 // We add a draw command.
                 unsafe {
                     builder.draw_indexed(mesh.index_count, 1, 0, 0, 0)?;
                 };
+                draw_calls += 1;
+                triangles += mesh.index_count as u64 / 3;
             } else {
                 return Err(anyhow::anyhow!("Command buffer builder not initialized"));
             }
         }
 
-        Ok(())
+        Ok((draw_calls, triangles))
+    }
+
+    /// Ends the main pass and draws the scene again into `target`, seen through the
+    /// camera pushed at `ubo_offset`. The pass is left open for
+    /// [`ActiveFrame::execute_command_buffer`] to end. Returns what [`ActiveFrame::draw`]
+    /// does.
+    pub fn render_preview(
+        &mut self,
+        target: &PreviewTarget,
+        ubo_offset: u32,
+        view: Mat4,
+        projection: Mat4,
+        layers: RenderLayers,
+    ) -> Result<(u32, u64)> {
+        let rcx = &*self.rcx;
+        let builder = self
+            .builder
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Command buffer builder not initialized"))?;
+        builder.end_rendering()?;
+        let color_attachments = vec![Some(color_attachment(
+            target.color.clone(),
+            target.resolve.clone(),
+            ClearValue::Float([0.0, 0.0, 0.0, 1.0]),
+        ))];
+        let depth_attachment = Some(RenderingAttachmentInfo {
+            load_op: AttachmentLoadOp::Clear,
+            store_op: AttachmentStoreOp::DontCare,
+            clear_value: Some(ClearValue::DepthStencil((1.0, 0))),
+            image_layout: DepthAttachmentOptimal,
+            ..RenderingAttachmentInfo::image_view(target.depth.clone())
+        });
+        let viewport = Viewport {
+            offset: [0.0, 0.0],
+            extent: [target.extent[0] as f32, target.extent[1] as f32],
+            depth_range: 0.0..=1.0,
+        };
+        builder
+            .begin_rendering(RenderingInfo {
+                render_area_extent: target.extent,
+                layer_count: 1,
+                color_attachments,
+                depth_attachment,
+                ..Default::default()
+            })
+            .with_context(|| "Begin preview rendering")?
+            .bind_pipeline_graphics(rcx.pipeline.pipeline_for(
+                PipelineKey::default().with(PipelineKey::WIREFRAME, rcx.wireframe),
+            )?)?
+            .set_viewport(0, [viewport].into_iter().collect())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                rcx.pipeline.layout(),
+                0,
+                DescriptorSetWithOffsets::new(
+                    rcx.frames[rcx.current_frame].descriptor_set.clone(),
+                    [ubo_offset],
+                ),
+            )
+            .with_context(|| "Bind preview descriptor sets")?;
+        self.draw(view, projection, layers)
     }
 
-    pub fn execute_command_buffer(&mut self, graphics_queue: &Arc<Queue>) -> Result<()> {
+    // The fence futures never leave the render thread; the `Arc` only lets the present
+    // chain on to a fence the frame keeps too.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn execute_command_buffer(
+        &mut self,
+        graphics_queue: &Arc<Queue>,
+        present_queue: &Arc<Queue>,
+        gui: Option<&mut Gui>,
+        capture: Option<(Arc<PrimaryAutoCommandBuffer>, FrameReadback)>,
+    ) -> Result<()> {
         let mut builder = self
             .builder
             .take()
             .ok_or_else(|| anyhow::anyhow!("Command buffer builder not initialized"))?;
         builder.end_rendering()?;
+        let frame = &mut self.rcx.frames[self.rcx.current_frame];
+        if let Some(pool) = frame.timestamps.clone() {
+            // SAFETY: query 1 was reset with query 0 when recording started.
+            unsafe {
+                builder.write_timestamp(pool, 1, PipelineStage::BottomOfPipe)?;
+            }
+            frame.timestamps_written = true;
+        }
 
         let command_buffer = builder.build()?;
 
-        // Build the future chain and obtain a fence future we can wait on next use of this slot.
-        let execution_future = self
+        let span_submit = info_span!("submit", subsystem = "renderer").entered();
+        let rendered = self
             .acquire_future
             .take()
             .ok_or_else(|| anyhow::anyhow!("Acquire future not complete"))?
-            .then_execute(graphics_queue.clone(), command_buffer)?
-            .then_swapchain_present(
-                graphics_queue.clone(),
-                SwapchainPresentInfo::swapchain_image_index(
-                    self.rcx.swapchain.swapchain.clone(),
-                    self.image_index,
-                ),
-            )
-            .boxed() // erase concrete type so we have a uniform storage type
-            .then_signal_fence_and_flush();
+            .then_execute(graphics_queue.clone(), command_buffer)?;
 
-        match execution_future.map_err(Validated::unwrap) {
-            Ok(future) => {
-                self.rcx.frames[self.rcx.current_frame].in_flight_future = Some(future);
-            }
-            Err(VulkanError::OutOfDate) => {
-                self.rcx.recreate_swapchain = true;
-                self.rcx.frames[self.rcx.current_frame].in_flight_future = None;
-            }
+        // The overlay is drawn straight onto the target image after the scene.
+        let rendered = match gui {
+            Some(gui) => gui.draw_on_image(
+                rendered,
+                self.rcx.target.image_views()[self.image_index as usize].clone(),
+            ),
+            None => rendered.boxed(),
+        };
+        let (rendered, readback) = match capture {
+            Some((copy, readback)) => (
+                rendered.then_execute(graphics_queue.clone(), copy)?.boxed(),
+                Some(readback),
+            ),
+            None => (rendered, None),
+        };
+        drop(span_submit);
+
+        // Build the future chain and obtain a fence future we can wait on next use of this slot.
+        let _span_present = info_span!("present", subsystem = "renderer").entered();
+        let frame = &mut self.rcx.frames[self.rcx.current_frame];
+        let Some(swapchain) = self.rcx.target.swapchain() else {
+            // Offscreen images aren't presented; the fence covers the rendering.
+            let rendered = rendered
+                .then_signal_fence_and_flush()
+                .map_err(Validated::unwrap)?;
+            frame.in_flight_future = Some(Arc::new(rendered));
+            frame.readback = readback;
+            return Ok(());
+        };
+        let present_info = SwapchainPresentInfo::swapchain_image_index(
+            swapchain.swapchain.clone(),
+            self.image_index,
+        );
+        let presented = if Arc::ptr_eq(graphics_queue, present_queue) {
+            frame.in_flight_future = None;
+            rendered
+                .then_swapchain_present(present_queue.clone(), present_info)
+                .boxed() // erase concrete type so we have a uniform storage type
+                .then_signal_fence_and_flush()
+                .map(|future| frame.in_flight_future = Some(Arc::new(future)))
+        } else {
+            // A fence after a present on another queue wouldn't cover the rendering, so
+            // the frame's fence is signalled on the graphics queue and the present waits
+            // for a semaphore signalled after it.
+            let rendered = Arc::new(
+                rendered
+                    .then_signal_fence_and_flush()
+                    .map_err(Validated::unwrap)?,
+            );
+            frame.in_flight_future = Some(rendered.clone());
+            rendered
+                .then_signal_semaphore()
+                .then_swapchain_present(present_queue.clone(), present_info)
+                .boxed()
+                .then_signal_fence_and_flush()
+                .map(|future| frame.present_future = Some(future))
+        };
+
+        match presented.map_err(Validated::unwrap) {
+            Ok(()) => frame.readback = readback,
+            Err(VulkanError::OutOfDate) => self.rcx.recreate_swapchain = true,
             Err(e) => return Err(e.into()),
         }
 
         Ok(())
     }
 }
-
-impl Drop for ActiveFrame<'_> {
-    fn drop(&mut self) {
-        if let Some(fence_future) = self.rcx.frames[self.rcx.current_frame]
-            .in_flight_future
-            .as_mut()
-        {
-            match fence_future.wait(None) {
-                Ok(()) => {
-                    // Fence successfully waited: we can safely clean up and advance to the next frame.
-                    fence_future.cleanup_finished();
-                    self.rcx.current_frame = (self.rcx.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
-                }
-                Err(e) => {
-                    error!("Failed to wait for fence future: {:?}", e);
-                }
-            }
-        }
-    }
-}
@@ -0,0 +1,91 @@
+use crate::core::bounds::Frustum;
+use crate::renderer::renderer_vulkan::pipeline::PipelineKey;
+use crate::renderer::renderer_vulkan::resources::VulkanResources;
+use crate::scene::RenderLayers;
+use glam::{Mat4, Vec3};
+
+/// Mesh instances split into opaque and transparent draws, rebuilt every frame. Opaque
+/// instances are grouped by pipeline variant, then material, then mesh, so consecutive
+/// draws rebind as little as possible; transparent ones are sorted back to front by the
+/// view depth of their bounds' center, so blending composites them correctly.
+///
+/// Hidden instances, those on none of the camera's [`RenderLayers`] and those whose
+/// bounds are outside the view frustum are left out. This is a linear
+/// pass on the render thread; moving it to a compute shader needs indirect draws first,
+/// since every instance is still drawn with its own push constants.
+#[derive(Default)]
+pub struct RenderQueue {
+    /// Pipeline variant, material, mesh and instance index.
+    opaque: Vec<(PipelineKey, usize, usize, usize)>,
+    /// View depth and instance index.
+    transparent: Vec<(f32, usize)>,
+    /// Instances left out by the last build.
+    culled: usize,
+}
+
+impl RenderQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Culls and sorts the instances of `resources` drawn by a camera on `layers`. `view`
+    /// takes world space into view space.
+    pub fn build(
+        &mut self,
+        resources: &VulkanResources,
+        view: Mat4,
+        projection: Mat4,
+        layers: RenderLayers,
+    ) {
+        self.opaque.clear();
+        self.transparent.clear();
+        self.culled = 0;
+        let frustum = Frustum::from_view_projection(&(projection * view));
+        for (index, instance) in resources.instances.iter().enumerate() {
+            if !instance.visible || !instance.layers.intersects(layers) {
+                continue;
+            }
+            let visible = resources
+                .mesh_or_fallback(instance.mesh)
+                .is_none_or(|mesh| {
+                    frustum.intersects_aabb(&mesh.bounds.transformed(&instance.transform))
+                });
+            if !visible {
+                self.culled += 1;
+                continue;
+            }
+            let desc = resources
+                .material_or_error(instance.material)
+                .map(|material| material.desc);
+            if !desc.is_some_and(|desc| desc.blend) {
+                let key = desc.map_or(PipelineKey::default(), |desc| {
+                    PipelineKey::for_material(&desc, false)
+                });
+                self.opaque
+                    .push((key, instance.material.0, instance.mesh, index));
+                continue;
+            }
+            let center = resources
+                .mesh_or_fallback(instance.mesh)
+                .map_or(Vec3::ZERO, |mesh| mesh.bounds.center());
+            let depth = (view * instance.transform).transform_point3(center).z;
+            self.transparent.push((depth, index));
+        }
+        self.opaque.sort_unstable();
+        // View space looks down -Z, so the farthest instances have the lowest depth.
+        self.transparent.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    /// Instances outside the frustum in the last build.
+    pub fn culled(&self) -> usize {
+        self.culled
+    }
+
+    /// Instance indices in draw order: opaque first, then transparent back to front.
+    pub fn draw_order(&self) -> impl Iterator<Item = usize> + '_ {
+        self.opaque
+            .iter()
+            .map(|&(_, _, _, index)| index)
+            .chain(self.transparent.iter().map(|&(_, index)| index))
+    }
+}
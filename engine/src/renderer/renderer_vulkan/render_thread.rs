@@ -0,0 +1,510 @@
+use crate::capture::CapturedFrame;
+use crate::renderer::SwapchainChanged;
+use crate::renderer::render_world::RenderWorld;
+use crate::renderer::renderer_vulkan::{
+    pipeline::VulkanPipeline,
+    render_context::{ActiveFrame, FrameReadback, RenderContext},
+    resources::VulkanResources,
+};
+use anyhow::{Context, Result, anyhow, ensure};
+use egui_winit_vulkano::Gui;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{Level, debug, error, info, info_span, span, warn};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::descriptor_set::DescriptorSet;
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::format::Format;
+use vulkano::image::SampleCount;
+use vulkano::swapchain::Surface;
+use vulkano::{Validated, VulkanError};
+
+/// Locks state shared with the render thread. A panic on the other side already
+/// surfaces through [`RenderThread::finish_frame`], so poisoning is ignored.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Work for the render thread, handled in the order it was sent.
+pub enum RenderCommand {
+    /// Record, submit and present a frame, then send back its [`FrameReport`].
+    Frame(FrameRequest),
+    SetWireframe(bool),
+    /// Swap in a rebuilt mesh pipeline and its per-frame descriptor sets.
+    SetPipeline(VulkanPipeline, Vec<Arc<DescriptorSet>>),
+    /// Rebuild the render targets and mesh pipeline for a new MSAA sample count.
+    SetMsaa(SampleCount),
+}
+
+/// One frame's input besides the GPU resources shared with the main thread.
+pub struct FrameRequest {
+    pub world: RenderWorld,
+    /// Inner size of the window, read on the main thread, or the offscreen images' size
+    /// when rendering headless.
+    pub window_size: [u32; 2],
+    pub minimized: bool,
+    /// Copy the presented image out for frame capture.
+    pub capture: bool,
+    /// Draw and close the overlay frame opened by `begin_ui`.
+    pub draw_ui: bool,
+}
+
+/// What a frame produced on the render thread, applied to the engine's resources when
+/// the next frame is handed over.
+#[derive(Default)]
+pub struct FrameReport {
+    pub gpu_time: Option<Duration>,
+    /// Bytes of device memory used by the renderer and available on the device.
+    pub gpu_memory: Option<(u64, u64)>,
+    /// Draw calls and triangles of every view drawn, and instances culled from the main
+    /// one, if the frame was recorded.
+    pub draws: Option<(u32, u64, u32)>,
+    pub render_time: Option<Duration>,
+    pub captured: Option<CapturedFrame>,
+    /// Capture was requested but the swapchain images can't be copied.
+    pub capture_unsupported: bool,
+    /// The overlay frame was drawn, which also closed it.
+    pub ui_drawn: bool,
+    /// The swapchain was renegotiated into a different format, color space or present
+    /// mode before this frame.
+    pub swapchain_changed: Option<SwapchainChanged>,
+}
+
+/// Everything the render thread starts from. Its [`RenderContext`] is built on the thread
+/// itself, since the futures of frames in flight can't move between threads.
+pub struct RenderSetup {
+    pub graphics_queue: Arc<Queue>,
+    pub present_queue: Arc<Queue>,
+    pub command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    pub resources: Arc<Mutex<VulkanResources>>,
+    pub gui: Arc<Mutex<Option<Gui>>>,
+    pub context: Box<dyn FnOnce() -> RenderContext + Send>,
+}
+
+/// Records, submits and presents frames on a dedicated thread, so the main thread can
+/// simulate frame N+1 while frame N renders. At most one frame is queued: the main thread
+/// collects a frame's report before handing over the next one.
+pub struct RenderThread {
+    commands: Sender<RenderCommand>,
+    reports: Receiver<Result<FrameReport>>,
+    handle: JoinHandle<()>,
+    /// A frame was handed over and its report not collected yet.
+    pending: bool,
+    /// Surface and format of the swapchain owned by the thread. No surface when
+    /// rendering headless.
+    pub surface: Option<Arc<Surface>>,
+    pub format: Format,
+    pub supports_wireframe: bool,
+    /// Last value sent with [`RenderCommand::SetWireframe`].
+    pub wireframe: bool,
+}
+
+impl RenderThread {
+    pub fn spawn(
+        setup: RenderSetup,
+        surface: Option<Arc<Surface>>,
+        format: Format,
+        supports_wireframe: bool,
+    ) -> Result<Self> {
+        let (commands, command_receiver) = mpsc::channel();
+        let (report_sender, reports) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name("render".to_owned())
+            .spawn(move || {
+                let mut worker = RenderWorker {
+                    rcx: (setup.context)(),
+                    graphics_queue: setup.graphics_queue,
+                    present_queue: setup.present_queue,
+                    command_buffer_allocator: setup.command_buffer_allocator,
+                    resources: setup.resources,
+                    gui: setup.gui,
+                    swapchain_failures: 0,
+                };
+                for command in command_receiver {
+                    if let Some(report) = worker.handle(command)
+                        && report_sender.send(report).is_err()
+                    {
+                        break;
+                    }
+                }
+                info!("Render thread stopped");
+            })
+            .with_context(|| "Failed to spawn render thread")?;
+        Ok(RenderThread {
+            commands,
+            reports,
+            handle,
+            pending: false,
+            surface,
+            format,
+            supports_wireframe,
+            wireframe: false,
+        })
+    }
+
+    /// Queues a command behind the frame being rendered, if any.
+    pub fn send(&self, command: RenderCommand) -> Result<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| anyhow!("Render thread has stopped"))
+    }
+
+    /// Hands a frame over. Collect the previous one with [`RenderThread::finish_frame`]
+    /// first.
+    pub fn submit_frame(&mut self, request: FrameRequest) -> Result<()> {
+        ensure!(!self.pending, "The previous frame has not been finished");
+        self.send(RenderCommand::Frame(request))?;
+        self.pending = true;
+        Ok(())
+    }
+
+    /// Waits for the frame handed over last and returns its report, or `None` if there
+    /// is no such frame. The thread is idle afterwards until the next command.
+    pub fn finish_frame(&mut self) -> Option<Result<FrameReport>> {
+        if !std::mem::take(&mut self.pending) {
+            return None;
+        }
+        Some(
+            self.reports
+                .recv()
+                .unwrap_or_else(|_| Err(anyhow!("Render thread has stopped"))),
+        )
+    }
+
+    /// Stops the thread after its queued commands and drops the render context there.
+    pub fn join(self) -> Result<()> {
+        let RenderThread {
+            commands, handle, ..
+        } = self;
+        drop(commands);
+        handle.join().map_err(|_| anyhow!("Render thread panicked"))
+    }
+}
+
+/// The render thread's side: owns the render context and borrows the shared resources
+/// while recording a frame.
+struct RenderWorker {
+    rcx: RenderContext,
+    graphics_queue: Arc<Queue>,
+    present_queue: Arc<Queue>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    resources: Arc<Mutex<VulkanResources>>,
+    gui: Arc<Mutex<Option<Gui>>>,
+    /// Failed swapchain recreations in a row, so a lost surface isn't logged every frame.
+    swapchain_failures: u32,
+}
+
+impl RenderWorker {
+    fn handle(&mut self, command: RenderCommand) -> Option<Result<FrameReport>> {
+        match command {
+            RenderCommand::Frame(request) => {
+                let mut report = FrameReport::default();
+                let result = self.render_frame(request, &mut report);
+                Some(result.map(|submitted| {
+                    // Wait outside the locks, so the main thread can keep changing the
+                    // scene while the GPU finishes.
+                    if let Some(frame) = submitted
+                        && self.rcx.finish_frame()
+                    {
+                        lock(&self.resources)
+                            .deletion_queue_mut()
+                            .frame_completed(frame);
+                    }
+                    report
+                }))
+            }
+            RenderCommand::SetWireframe(enabled) => {
+                self.rcx.wireframe = enabled;
+                None
+            }
+            RenderCommand::SetPipeline(pipeline, descriptor_sets) => {
+                let old_pipeline = std::mem::replace(&mut self.rcx.pipeline, pipeline);
+                let old_sets = self
+                    .rcx
+                    .frames
+                    .iter_mut()
+                    .zip(descriptor_sets)
+                    .map(|(frame, set)| std::mem::replace(&mut frame.descriptor_set, set))
+                    .collect::<Vec<_>>();
+                lock(&self.resources)
+                    .deletion_queue_mut()
+                    .push((old_pipeline, old_sets));
+                None
+            }
+            RenderCommand::SetMsaa(samples) => {
+                // Logged rather than returned: only frames send reports back.
+                if let Err(e) = self.set_msaa(samples) {
+                    error!("Failed to change MSAA to {samples:?}: {e:?}");
+                }
+                None
+            }
+        }
+    }
+
+    fn set_msaa(&mut self, samples: SampleCount) -> Result<()> {
+        let rcx = &mut self.rcx;
+        let mut resources = lock(&self.resources);
+        let depth_format = resources.find_depth_format()?;
+        let pipeline = rcx.pipeline.with_format(
+            self.graphics_queue.device().clone(),
+            resources.descriptor_cache_mut(),
+            rcx.target.format(),
+            samples,
+            depth_format,
+        )?;
+        resources.set_msaa_samples(samples);
+        resources.create_color_resources(rcx.target.extent(), rcx.target.format())?;
+        resources.create_depth_resources(rcx.target.extent())?;
+        let old_pipeline = std::mem::replace(&mut rcx.pipeline, pipeline);
+        resources.deletion_queue_mut().push(old_pipeline);
+        info!(?samples, "MSAA changed");
+        Ok(())
+    }
+
+    /// Draws `request` into `report`. Returns the number of the submitted frame, if one
+    /// was: not while minimized or when the swapchain has to be recreated first.
+    fn render_frame(
+        &mut self,
+        request: FrameRequest,
+        report: &mut FrameReport,
+    ) -> Result<Option<u64>> {
+        let rcx = &mut self.rcx;
+        let _span_draw_frame = span!(
+            Level::INFO,
+            "VulkanRenderer::draw_frame",
+            FrameIndex = rcx.current_frame
+        )
+        .entered();
+
+        let window_size = request.window_size;
+        if request.minimized || window_size[0] == 0 || window_size[1] == 0 {
+            // If the window is minimized, we skip rendering this frame.
+            thread::sleep(Duration::from_millis(50));
+            rcx.recreate_swapchain = true;
+            return Ok(None);
+        }
+
+        let mut resources = lock(&self.resources);
+
+        // Whenever the window resizes we need to recreate everything dependent on the
+        // window size. In this example that includes the swapchain, the framebuffers and
+        // the dynamic state viewport.
+        // Only set for swapchains; offscreen images keep their size.
+        if rcx.recreate_swapchain
+            && let Some(swapchain) = rcx.target.swapchain_mut()
+        {
+            info!(
+                "Recreating swapchain for new window size: {:?}",
+                window_size
+            );
+            let previous = swapchain.settings();
+            let old_swapchain = match swapchain.recreate(window_size) {
+                Ok(old_swapchain) => old_swapchain,
+                Err(e) => {
+                    // Surfaces can be briefly unusable during a monitor hot-plug or driver
+                    // reset; keep the old swapchain and try again next frame.
+                    if self.swapchain_failures == 0 {
+                        warn!("Failed to recreate swapchain, retrying: {e:?}");
+                    } else {
+                        debug!(
+                            attempt = self.swapchain_failures + 1,
+                            "Swapchain still unusable: {e:?}"
+                        );
+                    }
+                    self.swapchain_failures += 1;
+                    drop(resources);
+                    thread::sleep(Duration::from_millis(50));
+                    return Ok(None);
+                }
+            };
+            if self.swapchain_failures > 0 {
+                info!(
+                    "Swapchain recovered after {} failed attempts",
+                    self.swapchain_failures
+                );
+                self.swapchain_failures = 0;
+            }
+            resources.deletion_queue_mut().push(old_swapchain);
+            let extent = swapchain.extent;
+            let (format, color_space, present_mode) = swapchain.settings();
+            resources.create_color_resources(extent, format)?;
+            resources.create_depth_resources(extent)?;
+            rcx.viewport.extent = [extent[0] as f32, extent[1] as f32];
+            rcx.recreate_swapchain = false;
+
+            if (format, color_space, present_mode) != previous {
+                info!(
+                    ?format,
+                    ?color_space,
+                    ?present_mode,
+                    "Swapchain renegotiated"
+                );
+                let format_changed = format != previous.0;
+                if format_changed {
+                    let msaa_samples = resources.msaa_samples();
+                    let depth_format = resources.find_depth_format()?;
+                    let pipeline = rcx.pipeline.with_format(
+                        self.graphics_queue.device().clone(),
+                        resources.descriptor_cache_mut(),
+                        format,
+                        msaa_samples,
+                        depth_format,
+                    )?;
+                    let old_pipeline = std::mem::replace(&mut rcx.pipeline, pipeline);
+                    resources.deletion_queue_mut().push(old_pipeline);
+                }
+                report.swapchain_changed = Some(SwapchainChanged {
+                    format,
+                    color_space,
+                    present_mode,
+                    extent,
+                    format_changed,
+                });
+            }
+        }
+
+        let (image_index, suboptimal, acquire_future) = match rcx
+            .target
+            .acquire_next_image(self.graphics_queue.device())
+            .map_err(Validated::unwrap)
+        {
+            Ok(r) => r,
+            Err(VulkanError::OutOfDate) => {
+                rcx.recreate_swapchain = true;
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+
+        if suboptimal {
+            info!("Swapchain is suboptimal; recreating");
+            rcx.recreate_swapchain = true;
+            return Ok(None);
+        }
+
+        let record_start = Instant::now();
+        report.gpu_time = rcx.read_gpu_time();
+        report.gpu_memory = Some((resources.memory_used(), resources.memory_total()));
+        report.captured = rcx
+            .take_capture()
+            .with_context(|| "Failed to read back captured frame")?;
+        let capture = match rcx.capture_layout().filter(|_| request.capture) {
+            Some(bgra) => {
+                let extent = rcx.target.extent();
+                let buffer =
+                    resources.create_readback_buffer(extent[0] as u64 * extent[1] as u64 * 4)?;
+                let copy = rcx.build_capture_command_buffer(
+                    self.command_buffer_allocator.clone(),
+                    &self.graphics_queue,
+                    image_index,
+                    buffer.clone(),
+                )?;
+                Some((
+                    copy,
+                    FrameReadback {
+                        buffer,
+                        extent,
+                        bgra,
+                    },
+                ))
+            }
+            None => {
+                report.capture_unsupported = request.capture;
+                None
+            }
+        };
+
+        let world = &request.world;
+        let light_buffer = resources
+            .get_light_buffer(rcx.current_frame)
+            .with_context(|| "Light buffer not found")?;
+        // A target left in an old format or sample count after renegotiation or a quality
+        // change waits for the main thread to recreate it.
+        let msaa_samples = resources.msaa_samples();
+        let preview_extent = resources
+            .preview_target()
+            .filter(|target| target.format == rcx.target.format())
+            .filter(|target| target.samples == msaa_samples)
+            .map(|target| target.extent);
+        let light_count = RenderContext::write_lights(&world.lights, light_buffer)
+            .with_context(|| "Failed to write lights")?;
+        let ring = resources
+            .uniform_ring_mut()
+            .with_context(|| "Uniform ring not created")?;
+        ring.begin_frame(rcx.current_frame);
+        let (view, projection, ubo_offset) = rcx
+            .push_camera(
+                world.elapsed,
+                &world.camera,
+                rcx.viewport.extent[0] / rcx.viewport.extent[1],
+                world.ambient,
+                light_count,
+                ring,
+            )
+            .with_context(|| "Failed to update uniform buffer")?;
+        let preview = match (&world.preview, preview_extent) {
+            (Some(camera), Some(extent)) => Some(
+                rcx.push_camera(
+                    world.elapsed,
+                    camera,
+                    extent[0] as f32 / extent[1] as f32,
+                    world.ambient,
+                    light_count,
+                    ring,
+                )
+                .with_context(|| "Failed to update preview uniform buffer")?,
+            ),
+            _ => None,
+        };
+
+        let span_record = info_span!("record", subsystem = "renderer").entered();
+        let builder = rcx
+            .build_command_buffer(
+                self.command_buffer_allocator.clone(),
+                self.graphics_queue.clone(),
+                resources.get_color_resources(),
+                resources.get_depth_resources()?,
+                image_index,
+                ubo_offset,
+            )
+            .map_err(|err| anyhow!("Failed to build command buffer: {:?}", err))?;
+        let mut active_frame = ActiveFrame {
+            rcx,
+            resources: &resources,
+            builder: Some(builder),
+            image_index,
+            acquire_future: Some(acquire_future),
+        };
+        let (mut draw_calls, mut triangles) = active_frame
+            .draw(view, projection, world.camera.layers)
+            .with_context(|| "Failed to draw mesh")?;
+        let culled = active_frame.rcx.render_queue.culled() as u32;
+        if let (Some((view, projection, ubo_offset)), Some(camera), Some(target)) =
+            (preview, &world.preview, resources.preview_target())
+        {
+            let (preview_calls, preview_triangles) = active_frame
+                .render_preview(target, ubo_offset, view, projection, camera.layers)
+                .with_context(|| "Failed to draw editor preview")?;
+            draw_calls += preview_calls;
+            triangles += preview_triangles;
+        }
+        report.draws = Some((draw_calls, triangles, culled));
+        drop(span_record);
+        let mut gui = lock(&self.gui);
+        // The overlay's pipeline targets the old format until the main thread rebuilds it.
+        let ui_usable = !report
+            .swapchain_changed
+            .is_some_and(|changed| changed.format_changed);
+        let gui = gui.as_mut().filter(|_| request.draw_ui && ui_usable);
+        report.ui_drawn = gui.is_some();
+        active_frame
+            .execute_command_buffer(&self.graphics_queue, &self.present_queue, gui, capture)
+            .with_context(|| "Failed to execute command buffer")?;
+        report.render_time = Some(record_start.elapsed());
+        Ok(Some(resources.deletion_queue_mut().frame_submitted()))
+    }
+}
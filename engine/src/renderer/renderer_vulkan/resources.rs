@@ -1,11 +1,30 @@
-pub(crate) use crate::core::ubo::UniformBufferObject;
+use crate::asset_loader::baked::BlockFormat;
+use crate::asset_loader::texture_settings::ColorSpace;
+use crate::core::bounds::Aabb;
+use crate::core::mesh::MeshData;
+pub(crate) use crate::core::ubo::{
+    LightData, MaterialParams, MeshPushConstants, UniformBufferObject,
+};
 pub(crate) use crate::core::vertex::ElmVertex;
-use anyhow::{Result, anyhow};
+use crate::lighting::MAX_LIGHTS;
+use crate::renderer::renderer_vulkan::deletion_queue::DeferredDeletionQueue;
+use crate::renderer::renderer_vulkan::descriptor_cache::DescriptorCache;
+use crate::renderer::renderer_vulkan::pipeline;
+use crate::renderer::renderer_vulkan::uniform_ring::UniformRing;
+use crate::renderer::{MaterialDesc, MaterialHandle};
+use crate::scene::RenderLayers;
+use crate::task_pool::TaskPool;
+use anyhow::{Result, anyhow, ensure};
+use glam::{Mat4, Vec3};
 use std::cmp::max;
 use std::sync::Arc;
+use tracing::warn;
 use vulkano::command_buffer::{
-    BlitImageInfo, CopyBufferToImageInfo, ImageBlit, PrimaryAutoCommandBuffer,
+    BlitImageInfo, BufferImageCopy, CopyBufferToImageInfo, ImageBlit, PrimaryAutoCommandBuffer,
 };
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::descriptor_set::{DescriptorSet, WriteDescriptorSet};
 use vulkano::format::{Format, FormatFeatures};
 use vulkano::image::sampler::BorderColor::IntOpaqueBlack;
 use vulkano::image::sampler::SamplerMipmapMode::Linear;
@@ -24,6 +43,7 @@ use vulkano::{
         allocator::StandardCommandBufferAllocator,
     },
     device::{Device, Queue},
+    memory::MemoryHeapFlags,
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
     sync::GpuFuture,
 };
@@ -33,6 +53,21 @@ pub struct GPUMesh {
     pub index_buffer: Subbuffer<[u32]>,
     pub _vertex_count: u32,
     pub index_count: u32,
+    /// Bounds of the vertex positions in mesh space, for culling and picking.
+    pub bounds: Aabb,
+    /// Buffers are host-visible so [`VulkanResources::update_mesh`] can write them in place.
+    pub dynamic: bool,
+}
+
+/// An uploaded mesh placed in the world; each instance is one draw call.
+pub struct MeshInstance {
+    pub mesh: usize,
+    pub material: MaterialHandle,
+    pub transform: Mat4,
+    /// Left out of every draw while `false`.
+    pub visible: bool,
+    /// Drawn by cameras sharing one of these layers.
+    pub layers: RenderLayers,
 }
 
 pub struct GPUTexture {
@@ -40,63 +75,255 @@ pub struct GPUTexture {
     pub sampler: Arc<Sampler>,
 }
 
+/// A material's parameter buffer and textures, bound as descriptor set 1 of the mesh
+/// pipeline.
+pub struct GPUMaterial {
+    pub desc: MaterialDesc,
+    pub params: Subbuffer<MaterialParams>,
+    pub descriptor_set: Arc<DescriptorSet>,
+}
+
+/// Offscreen images a second camera is drawn into: multisampled color and depth like
+/// the swapchain pass, resolved into an image the overlay samples.
+pub struct PreviewTarget {
+    /// `None` without multisampling, which draws into `resolve` directly.
+    pub color: Option<Arc<ImageView>>,
+    pub depth: Arc<ImageView>,
+    pub resolve: Arc<ImageView>,
+    pub extent: [u32; 2],
+    pub format: Format,
+    pub samples: SampleCount,
+}
+
+/// Texture data with sampler parameters already translated to Vulkan.
+pub struct TextureDesc<'a> {
+    pub pixels: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub mips: &'a [Vec<u8>],
+    pub block_format: Option<BlockFormat>,
+    pub color_space: ColorSpace,
+    pub generate_mips: bool,
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub address_mode: [SamplerAddressMode; 3],
+}
+
+/// Format of an 8-bit RGBA texture encoded in `color_space`. sRGB formats decode to
+/// linear when sampled, so filtering and lighting happen on linear values.
+pub fn texture_format(color_space: ColorSpace) -> Format {
+    match color_space {
+        ColorSpace::Srgb => Format::R8G8B8A8_SRGB,
+        ColorSpace::Linear => Format::R8G8B8A8_UNORM,
+    }
+}
+
+/// Like [`texture_format`], for textures compressed to `block_format`.
+pub fn block_texture_format(block_format: BlockFormat, color_space: ColorSpace) -> Format {
+    match (block_format, color_space) {
+        (BlockFormat::Bc1, ColorSpace::Srgb) => Format::BC1_RGB_SRGB_BLOCK,
+        (BlockFormat::Bc1, ColorSpace::Linear) => Format::BC1_RGB_UNORM_BLOCK,
+        (BlockFormat::Bc3, ColorSpace::Srgb) => Format::BC3_SRGB_BLOCK,
+        (BlockFormat::Bc3, ColorSpace::Linear) => Format::BC3_UNORM_BLOCK,
+    }
+}
+
 pub struct VulkanResources {
     device: Arc<Device>,
     graphics_queue: Arc<Queue>,
     memory_allocator: Arc<StandardMemoryAllocator>,
     command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    descriptor_cache: DescriptorCache,
+    /// Replaced and removed objects, kept until the frames in flight finish.
+    deletion_queue: DeferredDeletionQueue,
     pub meshes: Vec<GPUMesh>,
     pub textures: Vec<GPUTexture>,
+    pub instances: Vec<MeshInstance>,
+    /// Indexed by [`MaterialHandle`]; the first ones are [`MaterialHandle::DEFAULT`] and
+    /// [`MaterialHandle::ERROR`].
+    pub materials: Vec<GPUMaterial>,
+    material_layout: Arc<DescriptorSetLayout>,
+    /// 1x1 white texture bound by materials without a base color texture.
+    white_texture: Option<GPUTexture>,
+    /// Magenta checkerboard bound by [`MaterialHandle::ERROR`] and by materials whose
+    /// texture is missing.
+    fallback_texture: Option<GPUTexture>,
+    /// Unit cube drawn in place of instances whose mesh is missing.
+    fallback_mesh: Option<GPUMesh>,
     msaa_samples: SampleCount,
+    /// Sampler settings of uploaded textures, from [`VulkanResources::set_sampling`].
+    anisotropy: Option<f32>,
+    mip_bias: f32,
     color_resource: Option<Arc<ImageView>>,
     depth_resource: Option<Arc<ImageView>>,
-    uniform_buffers: Vec<Subbuffer<UniformBufferObject>>,
+    preview_target: Option<PreviewTarget>,
+    uniform_ring: Option<UniformRing>,
+    /// One [`MAX_LIGHTS`]-sized storage buffer per uniform ring region.
+    light_buffers: Vec<Subbuffer<[LightData]>>,
 }
 
 impl VulkanResources {
+    /// Also uploads the fallback textures and cube and creates [`MaterialHandle::DEFAULT`]
+    /// and [`MaterialHandle::ERROR`].
     pub fn new(
         device: Arc<Device>,
         graphics_queue: Arc<Queue>,
         command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
-    ) -> Self {
+        descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+    ) -> Result<Self> {
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
         let properties = device.physical_device().properties();
         let msaa_samples = properties
             .framebuffer_color_sample_counts
             .intersection(properties.framebuffer_depth_sample_counts)
             .max_count();
-        Self {
+        let max_anisotropy = properties.max_sampler_anisotropy;
+        let mut descriptor_cache = DescriptorCache::new(device.clone(), descriptor_set_allocator);
+        let material_layout = pipeline::default_set_layouts(device.clone(), &mut descriptor_cache)?
+            .get(pipeline::MATERIAL_SET)
+            .cloned()
+            .ok_or_else(|| anyhow!("Mesh shaders don't use a material set"))?;
+        let mut resources = Self {
             device,
             graphics_queue,
             memory_allocator,
             command_buffer_allocator,
+            descriptor_cache,
+            deletion_queue: DeferredDeletionQueue::new(),
             meshes: Vec::new(),
             textures: Vec::new(),
+            instances: Vec::new(),
+            materials: Vec::new(),
+            material_layout,
+            white_texture: None,
+            fallback_texture: None,
+            fallback_mesh: None,
             msaa_samples,
+            anisotropy: Some(max_anisotropy),
+            mip_bias: 0.0,
             color_resource: None,
             depth_resource: None,
-            uniform_buffers: Vec::new(),
-        }
+            preview_target: None,
+            uniform_ring: None,
+            light_buffers: Vec::new(),
+        };
+        resources.white_texture = Some(resources.create_texture(&TextureDesc {
+            pixels: &[u8::MAX; 4],
+            width: 1,
+            height: 1,
+            mips: &[],
+            block_format: None,
+            color_space: ColorSpace::Srgb,
+            generate_mips: false,
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            address_mode: [SamplerAddressMode::Repeat; 3],
+        })?);
+        resources.fallback_texture = Some(resources.create_texture(&TextureDesc {
+            pixels: &checkerboard(8),
+            width: 8,
+            height: 8,
+            mips: &[],
+            block_format: None,
+            color_space: ColorSpace::Srgb,
+            generate_mips: false,
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            address_mode: [SamplerAddressMode::Repeat; 3],
+        })?);
+        let cube = MeshData::cube(Vec3::ONE);
+        resources.fallback_mesh =
+            Some(resources.create_mesh(&cube.vertices, &cube.indices, false)?);
+        resources.create_material(&MaterialDesc::new())?;
+        resources.create_material(&MaterialDesc::new())?;
+        Ok(resources)
     }
 
-    pub fn upload_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<()> {
-        let vertex_buffer = self.create_vertex_buffer(vertices)?;
-        let index_buffer = self.create_index_buffer(indices)?;
+    /// Uploads a mesh into device-local memory and returns its index in
+    /// [`VulkanResources::meshes`].
+    pub fn upload_mesh(&mut self, vertices: &[ElmVertex], indices: &[u32]) -> Result<usize> {
+        let mesh = self.create_mesh(vertices, indices, false)?;
+        self.meshes.push(mesh);
+        Ok(self.meshes.len() - 1)
+    }
 
-        let mesh = GPUMesh {
+    /// Uploads a mesh into host-visible memory, for geometry that changes often.
+    pub fn upload_dynamic_mesh(
+        &mut self,
+        vertices: &[ElmVertex],
+        indices: &[u32],
+    ) -> Result<usize> {
+        let mesh = self.create_mesh(vertices, indices, true)?;
+        self.meshes.push(mesh);
+        Ok(self.meshes.len() - 1)
+    }
+
+    /// Replaces a mesh's geometry. Dynamic meshes are written in place if their buffers
+    /// are large enough and no frame in flight still reads them. Otherwise new buffers are
+    /// uploaded and the old ones deferred until the frames in flight finish.
+    pub fn update_mesh(
+        &mut self,
+        mesh_id: usize,
+        vertices: &[ElmVertex],
+        indices: &[u32],
+    ) -> Result<()> {
+        ensure!(
+            !vertices.is_empty() && !indices.is_empty(),
+            "Mesh {mesh_id} can't be updated to empty geometry"
+        );
+        let mesh = self
+            .meshes
+            .get_mut(mesh_id)
+            .ok_or_else(|| anyhow!("Mesh {mesh_id} has not been uploaded"))?;
+        if mesh.dynamic && write_in_place(mesh, vertices, indices) {
+            mesh._vertex_count = vertices.len() as u32;
+            mesh.index_count = indices.len() as u32;
+            mesh.bounds = mesh_bounds(vertices);
+            return Ok(());
+        }
+        let dynamic = mesh.dynamic;
+        let new = self.create_mesh(vertices, indices, dynamic)?;
+        let old = std::mem::replace(&mut self.meshes[mesh_id], new);
+        self.deletion_queue.push(old);
+        Ok(())
+    }
+
+    fn create_mesh(
+        &self,
+        vertices: &[ElmVertex],
+        indices: &[u32],
+        dynamic: bool,
+    ) -> Result<GPUMesh> {
+        let (vertex_buffer, index_buffer) = if dynamic {
+            (
+                self.create_host_buffer(BufferUsage::VERTEX_BUFFER, vertices)?,
+                self.create_host_buffer(BufferUsage::INDEX_BUFFER, indices)?,
+            )
+        } else {
+            (
+                self.create_vertex_buffer(vertices)?,
+                self.create_index_buffer(indices)?,
+            )
+        };
+        Ok(GPUMesh {
             _vertex_count: vertices.len() as u32,
             index_count: indices.len() as u32,
             vertex_buffer,
             index_buffer,
-        };
-        self.meshes.push(mesh);
-        Ok(())
+            bounds: mesh_bounds(vertices),
+            dynamic,
+        })
     }
 
     pub fn get_mesh(&self, mesh_id: usize) -> Option<&GPUMesh> {
         self.meshes.get(mesh_id)
     }
 
+    /// The mesh, or the fallback cube if it has not been uploaded.
+    pub fn mesh_or_fallback(&self, mesh_id: usize) -> Option<&GPUMesh> {
+        self.meshes.get(mesh_id).or(self.fallback_mesh.as_ref())
+    }
+
     pub fn upload_texture(
         &mut self,
         image_data: &[u8],
@@ -105,38 +332,204 @@ impl VulkanResources {
         mag_filter: Filter,
         min_filter: Filter,
         address_mode: [SamplerAddressMode; 3],
-    ) -> Result<()> {
-        let mip_levels = max(width, height).ilog2() + 1;
-        let image = self.create_texture_image(image_data, width, height, mip_levels)?;
-        let image_view = ImageView::new_default(image.clone())?;
-        let sampler = self.create_texture_sampler(image, mag_filter, min_filter, address_mode)?;
+    ) -> Result<usize> {
+        let texture = self.create_texture(&TextureDesc {
+            pixels: image_data,
+            width,
+            height,
+            mips: &[],
+            block_format: None,
+            color_space: ColorSpace::Srgb,
+            generate_mips: true,
+            mag_filter,
+            min_filter,
+            address_mode,
+        })?;
+        self.textures.push(texture);
+        Ok(self.textures.len() - 1)
+    }
+
+    /// Uploads several textures at once. Each texture records and submits its own transfer
+    /// command buffer on a pool worker. Returns their indices in the order of `descs`.
+    pub fn upload_textures(
+        &mut self,
+        task_pool: &TaskPool,
+        descs: &[TextureDesc],
+    ) -> Result<Vec<usize>> {
+        let textures = task_pool
+            .par_map(descs, |desc| self.create_texture(desc))
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        let first = self.textures.len();
+        self.textures.extend(textures);
+        Ok((first..self.textures.len()).collect())
+    }
+
+    fn create_texture(&self, desc: &TextureDesc) -> Result<GPUTexture> {
+        let image = self.create_texture_image(desc)?;
+        let image_view = ImageView::new_default(image)?;
+        let sampler =
+            self.create_texture_sampler(desc.mag_filter, desc.min_filter, desc.address_mode)?;
 
-        let texture = GPUTexture {
+        Ok(GPUTexture {
             image_view,
             sampler,
-        };
-        self.textures.push(texture);
-        Ok(())
+        })
     }
 
     pub fn get_texture(&self, texture_id: usize) -> Option<&GPUTexture> {
         self.textures.get(texture_id)
     }
 
-    fn create_texture_image(
-        &self,
-        image_data: &[u8],
-        width: u32,
-        height: u32,
-        mip_levels: u32,
-    ) -> Result<Arc<Image>> {
-        let staging_buffer = self.create_staging_buffer(image_data)?;
+    /// Creates a material's parameter buffer and descriptor set and returns its handle.
+    pub fn create_material(&mut self, desc: &MaterialDesc) -> Result<MaterialHandle> {
+        let material = self.build_material(MaterialHandle(self.materials.len()), desc)?;
+        self.materials.push(material);
+        Ok(MaterialHandle(self.materials.len() - 1))
+    }
+
+    /// Replaces `material`'s parameters and texture. Uses new buffers rather than
+    /// writing the old ones, which frames in flight may still read.
+    pub fn update_material(&mut self, material: MaterialHandle, desc: &MaterialDesc) -> Result<()> {
+        ensure!(
+            material.0 < self.materials.len(),
+            "Material {material:?} has not been created"
+        );
+        let new = self.build_material(material, desc)?;
+        let old = std::mem::replace(&mut self.materials[material.0], new);
+        self.descriptor_cache.forget(&old.descriptor_set);
+        self.deletion_queue.push(old);
+        Ok(())
+    }
+
+    /// Builds the GPU side of `material`. Missing textures sample the fallback checkerboard,
+    /// which [`MaterialHandle::ERROR`] always does.
+    fn build_material(
+        &mut self,
+        material: MaterialHandle,
+        desc: &MaterialDesc,
+    ) -> Result<GPUMaterial> {
+        let texture = match desc.base_color_texture {
+            _ if material == MaterialHandle::ERROR => self.fallback_texture.as_ref(),
+            Some(id) => self.get_texture(id).or_else(|| {
+                warn!("Texture {id} has not been uploaded, {material:?} uses the fallback");
+                self.fallback_texture.as_ref()
+            }),
+            None => self.white_texture.as_ref(),
+        }
+        .ok_or_else(|| anyhow!("Resources have been cleared"))?;
+        let params = Buffer::from_data(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::UNIFORM_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            MaterialParams {
+                base_color_factor: desc.base_color_factor,
+                alpha_cutoff: desc.alpha_cutoff.unwrap_or_default(),
+                _padding: [0; 3],
+            },
+        )?;
+        let descriptor_set = self.descriptor_cache.set(
+            &self.material_layout,
+            [
+                WriteDescriptorSet::buffer(0, params.clone()),
+                WriteDescriptorSet::image_view_sampler(
+                    1,
+                    texture.image_view.clone(),
+                    texture.sampler.clone(),
+                ),
+            ],
+        )?;
+        Ok(GPUMaterial {
+            desc: *desc,
+            params,
+            descriptor_set,
+        })
+    }
+
+    pub fn get_material(&self, material: MaterialHandle) -> Option<&GPUMaterial> {
+        self.materials.get(material.0)
+    }
+
+    /// The material, or [`MaterialHandle::ERROR`] if it has not been created.
+    pub fn material_or_error(&self, material: MaterialHandle) -> Option<&GPUMaterial> {
+        self.materials
+            .get(material.0)
+            .or_else(|| self.materials.get(MaterialHandle::ERROR.0))
+    }
+
+    pub fn descriptor_cache_mut(&mut self) -> &mut DescriptorCache {
+        &mut self.descriptor_cache
+    }
+
+    pub fn deletion_queue_mut(&mut self) -> &mut DeferredDeletionQueue {
+        &mut self.deletion_queue
+    }
+
+    /// Removes meshes, instances, textures and every material but the default and error
+    /// ones. Their GPU objects are deferred until the frames in flight finish.
+    pub fn clear_scene(&mut self) {
+        let built_in = self.materials.len().min(MaterialHandle::ERROR.0 + 1);
+        self.deletion_queue.push((
+            std::mem::take(&mut self.meshes),
+            std::mem::take(&mut self.textures),
+            self.materials.split_off(built_in),
+        ));
+        self.instances.clear();
+        self.descriptor_cache.clear_sets();
+    }
+
+    fn create_texture_image(&self, desc: &TextureDesc) -> Result<Arc<Image>> {
+        let (width, height) = (desc.width, desc.height);
+        if let Some(block_format) = desc.block_format
+            && !self.device.enabled_features().texture_compression_bc
+        {
+            let pixels = block_format.decompress(desc.pixels, width, height);
+            let mips = desc
+                .mips
+                .iter()
+                .enumerate()
+                .map(|(level, blocks)| {
+                    let shift = level as u32 + 1;
+                    block_format.decompress(blocks, max(width >> shift, 1), max(height >> shift, 1))
+                })
+                .collect::<Vec<_>>();
+            return self.create_texture_image(&TextureDesc {
+                pixels: &pixels,
+                mips: &mips,
+                block_format: None,
+                ..*desc
+            });
+        }
+
+        let mip_levels = if !desc.mips.is_empty() {
+            desc.mips.len() as u32 + 1
+        } else if desc.generate_mips && desc.block_format.is_none() {
+            max(width, height).ilog2() + 1
+        } else {
+            1
+        };
+        let staging_buffer = if desc.mips.is_empty() {
+            self.create_staging_buffer(desc.pixels)?
+        } else {
+            let levels = std::iter::once(desc.pixels).chain(desc.mips.iter().map(Vec::as_slice));
+            self.create_staging_buffer(&levels.flatten().copied().collect::<Vec<_>>())?
+        };
 
         let texture_image = Image::new(
             self.memory_allocator.clone(),
             ImageCreateInfo {
                 image_type: ImageType::Dim2d,
-                format: Format::R8G8B8A8_SRGB,
+                format: match desc.block_format {
+                    Some(block_format) => block_texture_format(block_format, desc.color_space),
+                    None => texture_format(desc.color_space),
+                },
                 extent: [width, height, 1],
                 mip_levels,
                 array_layers: 1,
@@ -149,9 +542,14 @@ impl VulkanResources {
             },
         )?;
 
-        self.copy_buffer_to_image(staging_buffer, texture_image.clone())?;
-
-        self.generate_mipmaps(texture_image.clone())?;
+        if desc.mips.is_empty() {
+            self.copy_buffer_to_image(staging_buffer, texture_image.clone())?;
+            if mip_levels > 1 {
+                self.generate_mipmaps(texture_image.clone())?;
+            }
+        } else {
+            self.copy_mips_to_image(staging_buffer, texture_image.clone(), desc)?;
+        }
 
         Ok(texture_image)
     }
@@ -223,7 +621,6 @@ impl VulkanResources {
 
     fn create_texture_sampler(
         &self,
-        _image: Arc<Image>,
         mag_filter: Filter,
         min_filter: Filter,
         address_mode: [SamplerAddressMode; 3],
@@ -234,14 +631,10 @@ impl VulkanResources {
                 mag_filter,
                 min_filter,
                 address_mode,
-                anisotropy: Some(
-                    self.device
-                        .physical_device()
-                        .properties()
-                        .max_sampler_anisotropy,
-                ),
+                anisotropy: self.anisotropy,
                 border_color: IntOpaqueBlack,
                 mipmap_mode: Linear,
+                mip_lod_bias: self.mip_bias,
                 lod: 0.0..=LOD_CLAMP_NONE,
                 ..Default::default()
             },
@@ -249,11 +642,73 @@ impl VulkanResources {
         Ok(sampler)
     }
 
+    /// Changes the anisotropy and mip bias of every texture, clamped to the device's
+    /// limits, and rebuilds the materials sampling them. `anisotropy` of 1 or less turns
+    /// anisotropic filtering off.
+    pub fn set_sampling(&mut self, anisotropy: f32, mip_bias: f32) -> Result<()> {
+        let properties = self.device.physical_device().properties();
+        self.anisotropy =
+            (anisotropy > 1.0).then(|| anisotropy.min(properties.max_sampler_anisotropy));
+        self.mip_bias = mip_bias.clamp(
+            -properties.max_sampler_lod_bias,
+            properties.max_sampler_lod_bias,
+        );
+        for index in 0..self.textures.len() {
+            let sampler = self.resample(&self.textures[index].sampler)?;
+            let old = std::mem::replace(&mut self.textures[index].sampler, sampler);
+            self.deletion_queue.push(old);
+        }
+        for index in 0..self.materials.len() {
+            let desc = self.materials[index].desc;
+            self.update_material(MaterialHandle(index), &desc)?;
+        }
+        Ok(())
+    }
+
+    /// A sampler like `sampler` with the current anisotropy and mip bias.
+    fn resample(&self, sampler: &Sampler) -> Result<Arc<Sampler>> {
+        self.create_texture_sampler(
+            sampler.mag_filter(),
+            sampler.min_filter(),
+            sampler.address_mode(),
+        )
+    }
+
     pub fn msaa_samples(&self) -> SampleCount {
         self.msaa_samples
     }
 
+    /// The largest sample count up to `requested` that the device renders color and depth
+    /// with.
+    pub fn supported_msaa(&self, requested: u32) -> SampleCount {
+        let properties = self.device.physical_device().properties();
+        let supported = properties
+            .framebuffer_color_sample_counts
+            .intersection(properties.framebuffer_depth_sample_counts);
+        [64, 32, 16, 8, 4, 2]
+            .into_iter()
+            .filter(|&samples| samples <= requested)
+            .filter_map(|samples| SampleCount::try_from(samples).ok())
+            .find(|&samples| supported.contains_enum(samples))
+            .unwrap_or(SampleCount::Sample1)
+    }
+
+    /// Takes effect with the next [`VulkanResources::create_color_resources`] and
+    /// [`VulkanResources::create_depth_resources`]; the mesh pipeline has to be rebuilt
+    /// for it too.
+    pub fn set_msaa_samples(&mut self, samples: SampleCount) {
+        self.msaa_samples = samples;
+    }
+
+    /// Creates the multisampled color target the scene pass resolves into the swapchain.
+    /// Without multisampling there is none; the pass draws into the swapchain directly.
     pub fn create_color_resources(&mut self, extent: [u32; 2], format: Format) -> Result<()> {
+        if self.msaa_samples == SampleCount::Sample1 {
+            if let Some(old) = self.color_resource.take() {
+                self.deletion_queue.push(old);
+            }
+            return Ok(());
+        }
         let color_image = Image::new(
             self.memory_allocator.clone(),
             ImageCreateInfo {
@@ -273,15 +728,15 @@ impl VulkanResources {
         )?;
 
         let color_image_view = ImageView::new_default(color_image.clone())?;
-        self.color_resource = Some(color_image_view);
+        if let Some(old) = self.color_resource.replace(color_image_view) {
+            self.deletion_queue.push(old);
+        }
         Ok(())
     }
 
-    pub fn get_color_resources(&self) -> Result<Arc<ImageView>> {
-        self.color_resource
-            .as_ref()
-            .cloned()
-            .ok_or(anyhow!("Color resources not created"))
+    /// `None` without multisampling.
+    pub fn get_color_resources(&self) -> Option<Arc<ImageView>> {
+        self.color_resource.clone()
     }
 
     pub fn create_depth_resources(&mut self, extent: [u32; 2]) -> Result<()> {
@@ -306,7 +761,9 @@ impl VulkanResources {
         )?;
 
         let depth_image_view = ImageView::new_default(depth_image.clone())?;
-        self.depth_resource = Some(depth_image_view);
+        if let Some(old) = self.depth_resource.replace(depth_image_view) {
+            self.deletion_queue.push(old);
+        }
         Ok(())
     }
 
@@ -317,6 +774,107 @@ impl VulkanResources {
             .ok_or(anyhow!("Depth resources not created"))
     }
 
+    /// Creates the offscreen target of [`PreviewTarget`] in the swapchain `format`, so
+    /// the mesh pipeline can draw into it. Replaces the previous one; returns the view
+    /// the overlay samples.
+    pub fn create_preview_target(
+        &mut self,
+        extent: [u32; 2],
+        format: Format,
+    ) -> Result<Arc<ImageView>> {
+        let depth_format = self.find_depth_format()?;
+        let image = |format, usage, samples| -> Result<Arc<ImageView>> {
+            let image = Image::new(
+                self.memory_allocator.clone(),
+                ImageCreateInfo {
+                    image_type: ImageType::Dim2d,
+                    format,
+                    extent: [extent[0], extent[1], 1],
+                    mip_levels: 1,
+                    array_layers: 1,
+                    usage,
+                    samples,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                    ..Default::default()
+                },
+            )?;
+            Ok(ImageView::new_default(image)?)
+        };
+        let color = if self.msaa_samples == SampleCount::Sample1 {
+            None
+        } else {
+            Some(image(
+                format,
+                ImageUsage::TRANSIENT_ATTACHMENT | ImageUsage::COLOR_ATTACHMENT,
+                self.msaa_samples,
+            )?)
+        };
+        let target = PreviewTarget {
+            color,
+            depth: image(
+                depth_format,
+                ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+                self.msaa_samples,
+            )?,
+            resolve: image(
+                format,
+                ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+                SampleCount::Sample1,
+            )?,
+            extent,
+            format,
+            samples: self.msaa_samples,
+        };
+        let resolve = target.resolve.clone();
+        self.drop_preview_target();
+        self.preview_target = Some(target);
+        Ok(resolve)
+    }
+
+    /// Creates `count` color images to render into instead of a swapchain's when there
+    /// is no window. They can be copied out for frame capture.
+    pub fn create_offscreen_images(
+        &self,
+        extent: [u32; 2],
+        format: Format,
+        count: usize,
+    ) -> Result<Vec<Arc<ImageView>>> {
+        (0..count)
+            .map(|_| {
+                let image = Image::new(
+                    self.memory_allocator.clone(),
+                    ImageCreateInfo {
+                        image_type: ImageType::Dim2d,
+                        format,
+                        extent: [extent[0], extent[1], 1],
+                        usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                        ..Default::default()
+                    },
+                )?;
+                Ok(ImageView::new_default(image)?)
+            })
+            .collect()
+    }
+
+    pub fn preview_target(&self) -> Option<&PreviewTarget> {
+        self.preview_target.as_ref()
+    }
+
+    /// Releases the preview target once the frames drawing into it finish.
+    pub fn drop_preview_target(&mut self) {
+        if let Some(old) = self.preview_target.take() {
+            self.deletion_queue
+                .push((old.color, old.depth, old.resolve));
+        }
+    }
+
     fn find_supported_format(
         &self,
         candidates: &[Format],
@@ -367,6 +925,52 @@ impl VulkanResources {
         Ok(())
     }
 
+    /// Copies every level of a pre-mipped texture, packed back to back in `src_buffer`.
+    fn copy_mips_to_image(
+        &self,
+        src_buffer: Subbuffer<[u8]>,
+        dst_image: Arc<Image>,
+        desc: &TextureDesc,
+    ) -> Result<()> {
+        let mut regions = Vec::new();
+        let mut offset = 0;
+        let (mut width, mut height) = (desc.width, desc.height);
+        for (level, pixels) in std::iter::once(desc.pixels)
+            .chain(desc.mips.iter().map(Vec::as_slice))
+            .enumerate()
+        {
+            let expected = match desc.block_format {
+                Some(block_format) => block_format.size(width, height),
+                None => (width * height * 4) as usize,
+            };
+            ensure!(
+                pixels.len() == expected,
+                "Mip level {level} has {} bytes, expected {expected} for {width}x{height}",
+                pixels.len()
+            );
+            regions.push(BufferImageCopy {
+                buffer_offset: offset,
+                image_subresource: ImageSubresourceLayers {
+                    aspects: ImageAspect::Color.into(),
+                    mip_level: level as u32,
+                    array_layers: 0..1,
+                },
+                image_extent: [width, height, 1],
+                ..BufferImageCopy::default()
+            });
+            offset += pixels.len() as DeviceSize;
+            width = max(width / 2, 1);
+            height = max(height / 2, 1);
+        }
+        let mut cbb = self.begin_single_time_commands()?;
+        cbb.copy_buffer_to_image(CopyBufferToImageInfo {
+            regions: regions.into(),
+            ..CopyBufferToImageInfo::buffer_image(src_buffer, dst_image)
+        })?;
+        self.end_single_time_commands(cbb)?;
+        Ok(())
+    }
+
     fn create_vertex_buffer(&self, vertices: &[ElmVertex]) -> Result<Subbuffer<[ElmVertex]>> {
         let staging_buffer = self.create_staging_buffer(vertices)?;
 
@@ -411,6 +1015,28 @@ impl VulkanResources {
         Ok(index_buffer)
     }
 
+    /// Buffer the CPU writes directly and the GPU reads in place.
+    fn create_host_buffer<T: BufferContents + Clone>(
+        &self,
+        usage: BufferUsage,
+        data: &[T],
+    ) -> Result<Subbuffer<[T]>> {
+        let buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            data.iter().cloned(),
+        )?;
+        Ok(buffer)
+    }
+
     fn create_staging_buffer<T: BufferContents + Clone>(
         &self,
         data: &[T],
@@ -431,6 +1057,24 @@ impl VulkanResources {
         Ok(staging_buffer)
     }
 
+    /// Host-visible buffer that the GPU copies `size` bytes into, e.g. a captured frame.
+    pub fn create_readback_buffer(&self, size: DeviceSize) -> Result<Subbuffer<[u8]>> {
+        let buffer = Buffer::new_slice(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            size,
+        )?;
+        Ok(buffer)
+    }
+
     fn copy_buffer<T: BufferContents + Clone>(
         &self,
         src: Subbuffer<[T]>,
@@ -464,16 +1108,24 @@ impl VulkanResources {
         Ok(())
     }
 
-    pub fn create_uniform_buffers(&mut self, count: usize) -> Result<()> {
-        if self.uniform_buffers.len() == count {
+    /// Creates a uniform ring with `count` regions of `frame_size` bytes, and a light
+    /// storage buffer for each region.
+    pub fn create_uniform_buffers(&mut self, count: usize, frame_size: DeviceSize) -> Result<()> {
+        if self.light_buffers.len() == count && self.uniform_ring.is_some() {
             return Ok(()); // Already sized correctly.
         }
-        self.uniform_buffers.clear();
+        self.uniform_ring = Some(UniformRing::new(
+            &self.device,
+            self.memory_allocator.clone(),
+            count,
+            frame_size,
+        )?);
+        self.light_buffers.clear();
         for _ in 0..count {
-            let uniform_buffer = Buffer::new_sized::<UniformBufferObject>(
+            let light_buffer = Buffer::new_slice::<LightData>(
                 self.memory_allocator.clone(),
                 BufferCreateInfo {
-                    usage: BufferUsage::UNIFORM_BUFFER,
+                    usage: BufferUsage::STORAGE_BUFFER,
                     ..Default::default()
                 },
                 AllocationCreateInfo {
@@ -481,13 +1133,132 @@ impl VulkanResources {
                         | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                     ..Default::default()
                 },
+                MAX_LIGHTS as DeviceSize,
             )?;
-            self.uniform_buffers.push(uniform_buffer);
+            self.light_buffers.push(light_buffer);
         }
         Ok(())
     }
 
-    pub fn get_uniform_buffer(&self, index: usize) -> Option<Subbuffer<UniformBufferObject>> {
-        self.uniform_buffers.get(index).cloned()
+    pub fn uniform_ring(&self) -> Option<&UniformRing> {
+        self.uniform_ring.as_ref()
+    }
+
+    pub fn uniform_ring_mut(&mut self) -> Option<&mut UniformRing> {
+        self.uniform_ring.as_mut()
+    }
+
+    pub fn get_light_buffer(&self, index: usize) -> Option<Subbuffer<[LightData]>> {
+        self.light_buffers.get(index).cloned()
+    }
+
+    /// Bytes of device memory held by meshes, textures, render targets, uniform buffers and
+    /// material parameters.
+    pub fn memory_used(&self) -> u64 {
+        let image_size = |view: &Arc<ImageView>| -> u64 {
+            view.image()
+                .memory_requirements()
+                .iter()
+                .map(|requirements| requirements.layout.size())
+                .sum()
+        };
+        let meshes: u64 = self
+            .meshes
+            .iter()
+            .chain(&self.fallback_mesh)
+            .map(|mesh| mesh.vertex_buffer.size() + mesh.index_buffer.size())
+            .sum();
+        let textures: u64 = self
+            .textures
+            .iter()
+            .chain(&self.white_texture)
+            .chain(&self.fallback_texture)
+            .map(|texture| image_size(&texture.image_view))
+            .sum();
+        let targets: u64 = self
+            .color_resource
+            .iter()
+            .chain(&self.depth_resource)
+            .map(image_size)
+            .sum();
+        let uniforms: u64 = self
+            .uniform_ring
+            .iter()
+            .map(UniformRing::size)
+            .chain(self.light_buffers.iter().map(|buffer| buffer.size()))
+            .chain(self.materials.iter().map(|material| material.params.size()))
+            .sum();
+        meshes + textures + targets + uniforms
+    }
+
+    /// Total size of the device-local memory heaps.
+    pub fn memory_total(&self) -> u64 {
+        self.device
+            .physical_device()
+            .memory_properties()
+            .memory_heaps
+            .iter()
+            .filter(|heap| heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    /// Drops every GPU buffer and image owned by this struct, deferred ones included. Only
+    /// once the device is idle.
+    pub fn clear(&mut self) {
+        self.clear_scene();
+        self.materials.clear();
+        self.white_texture = None;
+        self.fallback_texture = None;
+        self.fallback_mesh = None;
+        self.color_resource = None;
+        self.depth_resource = None;
+        self.preview_target = None;
+        self.uniform_ring = None;
+        self.light_buffers.clear();
+        self.deletion_queue.flush();
+    }
+}
+
+/// RGBA pixels of a `size` by `size` magenta and black checkerboard.
+fn checkerboard(size: u32) -> Vec<u8> {
+    (0..size * size)
+        .flat_map(|i| {
+            if (i % size + i / size).is_multiple_of(2) {
+                [u8::MAX, 0, u8::MAX, u8::MAX]
+            } else {
+                [0, 0, 0, u8::MAX]
+            }
+        })
+        .collect()
+}
+
+fn mesh_bounds(vertices: &[ElmVertex]) -> Aabb {
+    Aabb::from_points(vertices.iter().map(|vertex| *vertex.position))
+        .unwrap_or(Aabb::new(Vec3::ZERO, Vec3::ZERO))
+}
+
+/// Overwrites the start of a dynamic mesh's buffers. Returns `false`, leaving both
+/// untouched, if the data doesn't fit or the GPU may still be reading them.
+fn write_in_place(mesh: &GPUMesh, vertices: &[ElmVertex], indices: &[u32]) -> bool {
+    if vertices.len() as DeviceSize > mesh.vertex_buffer.len()
+        || indices.len() as DeviceSize > mesh.index_buffer.len()
+    {
+        return false;
     }
+    let vertex_buffer = mesh
+        .vertex_buffer
+        .clone()
+        .slice(0..vertices.len() as DeviceSize);
+    let index_buffer = mesh
+        .index_buffer
+        .clone()
+        .slice(0..indices.len() as DeviceSize);
+    let (Ok(mut vertex_guard), Ok(mut index_guard)) = (vertex_buffer.write(), index_buffer.write())
+    else {
+        return false;
+    };
+    vertex_guard.copy_from_slice(vertices);
+    index_guard.copy_from_slice(indices);
+    true
 }
@@ -4,23 +4,36 @@ pub mod vs {
         src: r"
             #version 450
 
-            layout(binding = 0) uniform UniformBufferObject {
+            layout(set = 0, binding = 0) uniform UniformBufferObject {
                 mat4 model;
                 mat4 view;
                 mat4 proj;
+                vec4 ambient;
+                uint lightCount;
             } ubo;
+
+            layout(push_constant) uniform PushConstants {
+                mat4 node;
+            } pc;
             
             layout(location = 0) in vec3 inPosition;
             layout(location = 1) in vec3 inColor;
             layout(location = 2) in vec2 inTexCoord;
+            layout(location = 3) in vec3 inNormal;
             
             layout(location = 0) out vec3 fragColor;
             layout(location = 1) out vec2 fragTexCoord;
+            layout(location = 2) out vec3 fragNormal;
+            layout(location = 3) out vec3 fragWorldPos;
             
             void main() {
-                gl_Position = ubo.proj * ubo.view * ubo.model * vec4(inPosition, 1.0);
+                mat4 world = ubo.model * pc.node;
+                vec4 worldPos = world * vec4(inPosition, 1.0);
+                gl_Position = ubo.proj * ubo.view * worldPos;
+                fragWorldPos = worldPos.xyz;
                 fragColor = inColor;
                 fragTexCoord = inTexCoord;
+                fragNormal = normalize(transpose(inverse(mat3(world))) * inNormal);
             }
         ",
     }
@@ -34,12 +47,84 @@ pub mod fs {
 
             layout(location = 0) in vec3 fragColor;
             layout(location = 1) in vec2 fragTexCoord;
+            layout(location = 2) in vec3 fragNormal;
+            layout(location = 3) in vec3 fragWorldPos;
             
             layout(location = 0) out vec4 outColor;
-            layout(binding = 1) uniform sampler2D texSampler;
+
+            // Set per pipeline variant, see `PipelineKey`.
+            layout(constant_id = 0) const bool ALPHA_TEST = false;
+            layout(constant_id = 1) const bool UNLIT = false;
+
+            layout(set = 0, binding = 0) uniform UniformBufferObject {
+                mat4 model;
+                mat4 view;
+                mat4 proj;
+                vec4 ambient;
+                uint lightCount;
+            } ubo;
+
+            const uint DIRECTIONAL = 0;
+            const uint POINT = 1;
+            const uint SPOT = 2;
+
+            struct Light {
+                vec4 positionRange;
+                vec4 directionKind;
+                vec4 color;
+                vec4 spotCos;
+            };
+            layout(std430, set = 0, binding = 1) readonly buffer Lights {
+                Light lights[];
+            };
+
+            layout(set = 1, binding = 0) uniform MaterialParams {
+                vec4 baseColorFactor;
+                float alphaCutoff;
+            } material;
+            layout(set = 1, binding = 1) uniform sampler2D baseColorTexture;
+            
+            // Diffuse light arriving from one light. Point and spot lights fall off with
+            // the inverse square of the distance, windowed to reach zero at their range.
+            vec3 lightContribution(Light light, vec3 normal) {
+                uint kind = uint(light.directionKind.w);
+                vec3 toLight = -light.directionKind.xyz;
+                float attenuation = 1.0;
+                if (kind != DIRECTIONAL) {
+                    vec3 offset = light.positionRange.xyz - fragWorldPos;
+                    float distanceSquared = max(dot(offset, offset), 0.0001);
+                    toLight = offset * inversesqrt(distanceSquared);
+                    float ratio = distanceSquared / (light.positionRange.w * light.positionRange.w);
+                    float window = clamp(1.0 - ratio * ratio, 0.0, 1.0);
+                    attenuation = window * window / distanceSquared;
+                }
+                if (kind == SPOT) {
+                    float cosAngle = dot(-toLight, light.directionKind.xyz);
+                    attenuation *= smoothstep(light.spotCos.y, light.spotCos.x, cosAngle);
+                }
+                return light.color.rgb * attenuation * max(dot(normal, toLight), 0.0);
+            }
             
             void main() {
-                outColor = texture(texSampler, fragTexCoord);
+                vec4 baseColor = texture(baseColorTexture, fragTexCoord)
+                    * material.baseColorFactor
+                    * vec4(fragColor, 1.0);
+                if (ALPHA_TEST) {
+                    if (baseColor.a < material.alphaCutoff) {
+                        discard;
+                    }
+                    baseColor.a = 1.0;
+                }
+                if (UNLIT) {
+                    outColor = baseColor;
+                    return;
+                }
+                vec3 normal = normalize(fragNormal);
+                vec3 light = ubo.ambient.rgb;
+                for (uint i = 0; i < ubo.lightCount; i++) {
+                    light += lightContribution(lights[i], normal);
+                }
+                outColor = vec4(baseColor.rgb * light, baseColor.a);
             }
         ",
     }
@@ -2,16 +2,18 @@ use std::sync::Arc;
 
 use crate::renderer::renderer_vulkan::MAX_FRAMES_IN_FLIGHT;
 use anyhow::{Result, anyhow};
+use tracing::warn;
 use vulkano::image::view::ImageView;
 use vulkano::{
     Validated, VulkanError,
-    device::Device,
+    device::{Device, DeviceOwned},
     format::Format,
     image::{Image, ImageUsage},
     swapchain::{
-        ColorSpace, Surface, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo,
-        acquire_next_image,
+        ColorSpace, CompositeAlpha, CompositeAlphas, PresentMode, Surface, Swapchain,
+        SwapchainAcquireFuture, SwapchainCreateInfo, acquire_next_image,
     },
+    sync::Sharing,
 };
 
 pub struct VulkanSwapchain {
@@ -19,82 +21,187 @@ pub struct VulkanSwapchain {
     pub image_views: Vec<Arc<ImageView>>,
     pub format: Format,
     pub extent: [u32; 2],
+    /// The compositor blends the images with what's behind the window.
+    pub transparent: bool,
 }
 
-impl VulkanSwapchain {
-    pub fn new(device: Arc<Device>, surface: Arc<Surface>, window_size: [u32; 2]) -> Result<Self> {
-        let (swapchain, images) = {
-            // Querying the capabilities of the surface. When we create the swapchain we can only
-            // pass values that are allowed by the capabilities.
-            let surface_capabilities = device
-                .physical_device()
-                .surface_capabilities(&surface, Default::default())?;
-
-            // Choosing the internal format that the images will have.
-            let (image_format, _) = {
-                let formats = device
-                    .physical_device()
-                    .surface_formats(&surface, Default::default())?;
-                // Prefer sRGB non-linear formats
-                formats
-                    .iter()
-                    .find(|(f, c)| {
-                        f.ycbcr_chroma_sampling().is_none()
-                            && *f == Format::R8G8B8A8_SRGB
-                            && *c == ColorSpace::SrgbNonLinear
-                    })
-                    .cloned()
-                    .unwrap_or_else(|| formats[0])
-            };
-
-            Swapchain::new(
-                device.clone(),
-                surface.clone(),
-                SwapchainCreateInfo {
-                    min_image_count: surface_capabilities
-                        .min_image_count
-                        .max(MAX_FRAMES_IN_FLIGHT as u32),
-                    image_format,
-                    image_extent: window_size,
-                    image_usage: ImageUsage::COLOR_ATTACHMENT,
-                    present_mode: surface_capabilities
-                        .compatible_present_modes
-                        .iter()
-                        .find(|m| **m == vulkano::swapchain::PresentMode::Mailbox)
-                        .copied()
-                        .unwrap_or(vulkano::swapchain::PresentMode::Fifo),
-                    composite_alpha: surface_capabilities
-                        .supported_composite_alpha
-                        .into_iter()
-                        .next()
-                        .ok_or(anyhow!("No supported composite alpha"))?,
-                    ..Default::default()
-                },
-            )?
-        };
+/// Composite alpha for a window that wants to be `transparent`, if `supported` allows it.
+fn choose_composite_alpha(supported: CompositeAlphas, transparent: bool) -> Result<CompositeAlpha> {
+    let preferred: &[CompositeAlpha] = if transparent {
+        &[
+            CompositeAlpha::PreMultiplied,
+            CompositeAlpha::PostMultiplied,
+            CompositeAlpha::Inherit,
+        ]
+    } else {
+        &[CompositeAlpha::Opaque]
+    };
+    let chosen = preferred
+        .iter()
+        .copied()
+        .find(|alpha| supported.contains_enum(*alpha))
+        .or_else(|| supported.into_iter().next())
+        .ok_or(anyhow!("No supported composite alpha"))?;
+    if transparent && chosen == CompositeAlpha::Opaque {
+        warn!("The surface doesn't support transparency; the window will be opaque");
+    }
+    Ok(chosen)
+}
 
-        let image_views = VulkanSwapchain::create_image_views(&images)?;
+/// Surface format to use out of the `supported` ones: the current one while it's still
+/// there, so a renegotiation doesn't needlessly rebuild pipelines, otherwise sRGB.
+fn choose_format(
+    supported: &[(Format, ColorSpace)],
+    current: Option<(Format, ColorSpace)>,
+) -> Result<(Format, ColorSpace)> {
+    let usable = |(format, _): &&(Format, ColorSpace)| format.ycbcr_chroma_sampling().is_none();
+    current
+        .filter(|current| supported.contains(current))
+        .or_else(|| {
+            supported
+                .iter()
+                .filter(usable)
+                .find(|(format, color_space)| {
+                    *format == Format::R8G8B8A8_SRGB && *color_space == ColorSpace::SrgbNonLinear
+                })
+                .copied()
+        })
+        .or_else(|| supported.iter().find(usable).copied())
+        .ok_or(anyhow!("The surface supports no usable formats"))
+}
 
-        let format = swapchain.image_format();
-        let extent = swapchain.image_extent();
+/// Mailbox if available, otherwise FIFO, which every surface supports.
+fn choose_present_mode(supported: impl IntoIterator<Item = PresentMode>) -> PresentMode {
+    supported
+        .into_iter()
+        .find(|mode| *mode == PresentMode::Mailbox)
+        .unwrap_or(PresentMode::Fifo)
+}
 
-        Ok(VulkanSwapchain {
-            swapchain,
-            image_views,
-            format,
-            extent,
+/// Swapchain settings for `surface` as it is now. Capabilities are queried every time,
+/// since they change at runtime, e.g. when the window moves to another monitor or HDR is
+/// toggled. `current` is the swapchain being replaced, whose format is kept if possible.
+/// `queue_families` are the families that use the images: rendering and presenting.
+fn negotiate(
+    device: &Device,
+    surface: &Surface,
+    window_size: [u32; 2],
+    transparent: bool,
+    queue_families: &[u32],
+    current: Option<&Swapchain>,
+) -> Result<SwapchainCreateInfo> {
+    let physical_device = device.physical_device();
+    let capabilities = physical_device.surface_capabilities(surface, Default::default())?;
+    let formats = physical_device.surface_formats(surface, Default::default())?;
+    let present_modes = physical_device.surface_present_modes(surface, Default::default())?;
+    let (image_format, image_color_space) = choose_format(
+        &formats,
+        current.map(|swapchain| (swapchain.image_format(), swapchain.image_color_space())),
+    )?;
+
+    let mut min_image_count = capabilities
+        .min_image_count
+        .max(MAX_FRAMES_IN_FLIGHT as u32);
+    if let Some(max_image_count) = capabilities.max_image_count {
+        min_image_count = min_image_count.min(max_image_count);
+    }
+    // Some platforms dictate the extent; otherwise the window size, within the limits.
+    let image_extent = capabilities.current_extent.unwrap_or_else(|| {
+        [0, 1].map(|axis| {
+            window_size[axis].clamp(
+                capabilities.min_image_extent[axis],
+                capabilities.max_image_extent[axis],
+            )
         })
+    });
+
+    Ok(SwapchainCreateInfo {
+        min_image_count,
+        image_format,
+        image_color_space,
+        image_extent,
+        // Copying presented images out is only needed for frame capture.
+        image_usage: ImageUsage::COLOR_ATTACHMENT
+            | (capabilities.supported_usage_flags & ImageUsage::TRANSFER_SRC),
+        // Shared concurrently between the graphics and present families when they
+        // differ, which saves transferring ownership of every image back and forth.
+        image_sharing: if queue_families.len() > 1 {
+            Sharing::Concurrent(queue_families.iter().copied().collect())
+        } else {
+            Sharing::Exclusive
+        },
+        present_mode: choose_present_mode(present_modes),
+        composite_alpha: choose_composite_alpha(
+            capabilities.supported_composite_alpha,
+            transparent,
+        )?,
+        ..Default::default()
+    })
+}
+
+impl VulkanSwapchain {
+    /// Creates a swapchain for `surface`, blended with the desktop if `transparent`.
+    /// `queue_families` are the distinct graphics and present queue families.
+    pub fn new(
+        device: Arc<Device>,
+        surface: Arc<Surface>,
+        window_size: [u32; 2],
+        transparent: bool,
+        queue_families: &[u32],
+    ) -> Result<Self> {
+        let create_info = negotiate(
+            &device,
+            &surface,
+            window_size,
+            transparent,
+            queue_families,
+            None,
+        )?;
+        let (swapchain, images) = Swapchain::new(device, surface, create_info)?;
+        let image_views = VulkanSwapchain::create_image_views(&images)?;
+        Ok(VulkanSwapchain::from_parts(swapchain, image_views))
+    }
+
+    /// Replaces the swapchain and its image views, returning the old ones so they can be
+    /// kept until the frames in flight finish. Formats, present modes and limits are
+    /// negotiated with the surface again, so the new swapchain may differ in more than
+    /// its size; compare [`VulkanSwapchain::settings`] before and after.
+    pub fn recreate(
+        &mut self,
+        window_size: [u32; 2],
+    ) -> Result<(Arc<Swapchain>, Vec<Arc<ImageView>>)> {
+        let create_info = negotiate(
+            self.swapchain.device(),
+            self.swapchain.surface(),
+            window_size,
+            self.transparent,
+            &queue_families(&self.swapchain),
+            Some(&self.swapchain),
+        )?;
+        let (new_swapchain, new_images) = self.swapchain.recreate(create_info)?;
+        let image_views = VulkanSwapchain::create_image_views(new_images.as_slice())?;
+        let new = VulkanSwapchain::from_parts(new_swapchain, image_views);
+        let old = std::mem::replace(self, new);
+        Ok((old.swapchain, old.image_views))
+    }
+
+    /// What passes rendering to the swapchain images depend on, besides the size.
+    pub fn settings(&self) -> (Format, ColorSpace, PresentMode) {
+        (
+            self.format,
+            self.swapchain.image_color_space(),
+            self.swapchain.present_mode(),
+        )
     }
 
-    pub fn recreate(&mut self, window_size: [u32; 2]) -> Result<()> {
-        let (new_swapchain, new_images) = self.swapchain.recreate(SwapchainCreateInfo {
-            image_extent: window_size,
-            ..self.swapchain.create_info()
-        })?;
-        self.swapchain = new_swapchain;
-        self.image_views = VulkanSwapchain::create_image_views(new_images.as_slice())?;
-        self.extent = window_size;
-        Ok(())
+    fn from_parts(swapchain: Arc<Swapchain>, image_views: Vec<Arc<ImageView>>) -> Self {
+        VulkanSwapchain {
+            format: swapchain.image_format(),
+            extent: swapchain.image_extent(),
+            transparent: swapchain.composite_alpha() != CompositeAlpha::Opaque,
+            swapchain,
+            image_views,
+        }
     }
 
     pub fn acquire_next_image(
@@ -117,3 +224,11 @@ impl VulkanSwapchain {
     // TODO: Implement present function
     // pub fn present(&self) {}
 }
+
+/// Queue families an existing swapchain's images are shared between.
+fn queue_families(swapchain: &Swapchain) -> Vec<u32> {
+    match swapchain.image_sharing() {
+        Sharing::Exclusive => Vec::new(),
+        Sharing::Concurrent(families) => families.to_vec(),
+    }
+}
@@ -0,0 +1,111 @@
+use crate::renderer::renderer_vulkan::swapchain::VulkanSwapchain;
+use std::sync::Arc;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::ImageUsage;
+use vulkano::image::view::ImageView;
+use vulkano::sync::{self, GpuFuture};
+use vulkano::{Validated, VulkanError};
+
+/// Where frames are drawn: the window's swapchain, or offscreen images when rendering
+/// headless.
+pub enum FrameTarget {
+    Swapchain(VulkanSwapchain),
+    Offscreen(OffscreenImages),
+}
+
+/// Color images drawn into in turn instead of a swapchain's, for rendering without a
+/// window. They're never presented; frame capture copies them out like swapchain images.
+pub struct OffscreenImages {
+    pub image_views: Vec<Arc<ImageView>>,
+    pub format: Format,
+    pub extent: [u32; 2],
+    /// Image the next frame draws into.
+    next: u32,
+}
+
+impl OffscreenImages {
+    pub fn new(image_views: Vec<Arc<ImageView>>, format: Format, extent: [u32; 2]) -> Self {
+        OffscreenImages {
+            image_views,
+            format,
+            extent,
+            next: 0,
+        }
+    }
+}
+
+impl FrameTarget {
+    pub fn image_views(&self) -> &[Arc<ImageView>] {
+        match self {
+            FrameTarget::Swapchain(swapchain) => &swapchain.image_views,
+            FrameTarget::Offscreen(offscreen) => &offscreen.image_views,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        match self {
+            FrameTarget::Swapchain(swapchain) => swapchain.format,
+            FrameTarget::Offscreen(offscreen) => offscreen.format,
+        }
+    }
+
+    pub fn extent(&self) -> [u32; 2] {
+        match self {
+            FrameTarget::Swapchain(swapchain) => swapchain.extent,
+            FrameTarget::Offscreen(offscreen) => offscreen.extent,
+        }
+    }
+
+    /// The compositor blends the images with what's behind the window.
+    pub fn transparent(&self) -> bool {
+        match self {
+            FrameTarget::Swapchain(swapchain) => swapchain.transparent,
+            FrameTarget::Offscreen(_) => false,
+        }
+    }
+
+    /// Whether the images can be copied out for frame capture.
+    pub fn copyable(&self) -> bool {
+        match self {
+            FrameTarget::Swapchain(swapchain) => swapchain
+                .swapchain
+                .image_usage()
+                .intersects(ImageUsage::TRANSFER_SRC),
+            FrameTarget::Offscreen(_) => true,
+        }
+    }
+
+    pub fn swapchain(&self) -> Option<&VulkanSwapchain> {
+        match self {
+            FrameTarget::Swapchain(swapchain) => Some(swapchain),
+            FrameTarget::Offscreen(_) => None,
+        }
+    }
+
+    pub fn swapchain_mut(&mut self) -> Option<&mut VulkanSwapchain> {
+        match self {
+            FrameTarget::Swapchain(swapchain) => Some(swapchain),
+            FrameTarget::Offscreen(_) => None,
+        }
+    }
+
+    /// The image to draw the next frame into, whether the swapchain is suboptimal, and
+    /// what to wait on before drawing. Offscreen images are used round robin; the frame
+    /// slot's fence already covers the previous frame that drew into one.
+    pub fn acquire_next_image(
+        &mut self,
+        device: &Arc<Device>,
+    ) -> Result<(u32, bool, Box<dyn GpuFuture>), Validated<VulkanError>> {
+        match self {
+            FrameTarget::Swapchain(swapchain) => swapchain
+                .acquire_next_image()
+                .map(|(index, suboptimal, future)| (index, suboptimal, future.boxed())),
+            FrameTarget::Offscreen(offscreen) => {
+                let index = offscreen.next;
+                offscreen.next = (index + 1) % offscreen.image_views.len() as u32;
+                Ok((index, false, sync::now(device.clone()).boxed()))
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{Result, ensure};
+use std::mem::size_of;
+use std::sync::Arc;
+use vulkano::DeviceSize;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::descriptor_set::DescriptorBufferInfo;
+use vulkano::device::Device;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter};
+
+/// Uniform data suballocated from one host-visible buffer split into a region per frame
+/// in flight. Values are pushed into the current frame's region and bound with the
+/// returned dynamic offset, against a descriptor written with [`UniformRing::binding`].
+pub struct UniformRing {
+    buffer: Subbuffer<[u8]>,
+    frame_size: DeviceSize,
+    alignment: DeviceSize,
+    frame_start: DeviceSize,
+    cursor: DeviceSize,
+}
+
+impl UniformRing {
+    /// Allocates `frames` regions of at least `frame_size` bytes each.
+    pub fn new(
+        device: &Arc<Device>,
+        memory_allocator: Arc<dyn MemoryAllocator>,
+        frames: usize,
+        frame_size: DeviceSize,
+    ) -> Result<Self> {
+        let alignment = device
+            .physical_device()
+            .properties()
+            .min_uniform_buffer_offset_alignment
+            .as_devicesize()
+            .max(16);
+        let frame_size = frame_size.next_multiple_of(alignment);
+        let buffer = Buffer::new_slice::<u8>(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::UNIFORM_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            frame_size * frames.max(1) as DeviceSize,
+        )?;
+        Ok(UniformRing {
+            buffer,
+            frame_size,
+            alignment,
+            frame_start: 0,
+            cursor: 0,
+        })
+    }
+
+    /// Starts filling `frame`'s region, reusing the space of what it pushed last time.
+    /// Only call once that frame's previous submission has finished.
+    pub fn begin_frame(&mut self, frame: usize) {
+        self.frame_start = frame as DeviceSize * self.frame_size;
+        self.cursor = 0;
+    }
+
+    /// Copies `value` into the current frame's region and returns its dynamic offset.
+    pub fn push<T: BufferContents + Copy>(&mut self, value: T) -> Result<u32> {
+        let size = size_of::<T>() as DeviceSize;
+        ensure!(
+            self.cursor + size <= self.frame_size,
+            "Uniform ring is full: {} of {} bytes used this frame",
+            self.cursor,
+            self.frame_size
+        );
+        let offset = self.frame_start + self.cursor;
+        *self
+            .buffer
+            .clone()
+            .slice(offset..offset + size)
+            .reinterpret::<T>()
+            .write()? = value;
+        self.cursor = (self.cursor + size).next_multiple_of(self.alignment);
+        Ok(offset as u32)
+    }
+
+    /// Descriptor range for a dynamic uniform buffer of `T`, at offset zero.
+    pub fn binding<T: BufferContents>(&self) -> DescriptorBufferInfo {
+        DescriptorBufferInfo {
+            buffer: self.buffer.clone(),
+            range: 0..size_of::<T>() as DeviceSize,
+        }
+    }
+
+    pub fn size(&self) -> DeviceSize {
+        self.buffer.size()
+    }
+}
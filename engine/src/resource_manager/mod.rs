@@ -1,51 +1,330 @@
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
 
+use anyhow::{Context, Result, anyhow};
 use tracing::{error, info};
 
+mod snapshot;
+
+use snapshot::SnapshotRegistry;
+
+/// Resources that know how to build themselves from other resources.
+///
+/// Implementations pull their dependencies through [`ResourceManager::init`] (or
+/// [`ResourceManager::try_get`] for optional ones), so construction order follows the
+/// dependency graph instead of the order of `add` calls.
+pub trait FromResources: Sized + 'static {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self>;
+}
+
+#[doc(hidden)]
+pub struct ResourceEntry {
+    type_name: &'static str,
+    value: Box<dyn Any>,
+}
+
+/// Handle to a child scope created with [`ResourceManager::create_scope`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeId(u32);
+
 /// A generic container for storing and retrieving shared data of any type.
+///
+/// Besides the global resources, the manager can hold child scopes (e.g. one per scene or
+/// editor tab). While a scope is active, new resources go into it and lookups check it
+/// before falling back to the globals, so dropping the scope discards all of its state at
+/// once while the renderer, asset cache and friends stay in place.
 pub struct ResourceManager {
-    resources: HashMap<TypeId, Box<dyn Any>>,
+    resources: HashMap<TypeId, ResourceEntry>,
+    scopes: HashMap<ScopeId, HashMap<TypeId, ResourceEntry>>,
+    active_scope: Option<ScopeId>,
+    next_scope: u32,
+    /// Types whose `from_resources` is currently running, outermost first.
+    initializing: Vec<(TypeId, &'static str)>,
+    snapshots: SnapshotRegistry,
 }
 
 impl ResourceManager {
     pub fn new() -> Self {
         ResourceManager {
             resources: HashMap::new(),
+            scopes: HashMap::new(),
+            active_scope: None,
+            next_scope: 0,
+            initializing: Vec::new(),
+            snapshots: SnapshotRegistry::default(),
         }
     }
 
+    /// Adds `resource` to the active scope, or to the globals if no scope is active.
     pub fn add<T: 'static>(&mut self, resource: T) {
         let type_id = TypeId::of::<T>();
         let type_name = std::any::type_name::<T>();
 
         let boxed_resource = Box::new(resource);
 
-        self.resources.insert(type_id, boxed_resource);
+        let entry = ResourceEntry {
+            type_name,
+            value: boxed_resource,
+        };
+        match self.active_scope_mut() {
+            Some(scope) => {
+                scope.insert(type_id, entry);
+                info!("Added scoped resource {type_name}");
+            }
+            None => {
+                self.resources.insert(type_id, entry);
+                info!("Added resource {type_name}");
+            }
+        }
+    }
 
-        info!("Added resource {type_name}");
+    /// Adds `resource` to the globals regardless of the active scope.
+    pub fn add_global<T: 'static>(&mut self, resource: T) {
+        let active_scope = self.active_scope.take();
+        self.add(resource);
+        self.active_scope = active_scope;
     }
 
-    pub fn get<T: 'static>(&self) -> &T {
+    /// Creates an empty child scope. It has no effect until made active with
+    /// [`ResourceManager::set_active_scope`].
+    pub fn create_scope(&mut self) -> ScopeId {
+        let id = ScopeId(self.next_scope);
+        self.next_scope += 1;
+        self.scopes.insert(id, HashMap::new());
+        id
+    }
+
+    /// Switches which child scope receives new resources and is searched first.
+    /// `None` goes back to the globals only.
+    pub fn set_active_scope(&mut self, scope: Option<ScopeId>) {
+        if let Some(id) = scope {
+            assert!(
+                self.scopes.contains_key(&id),
+                "Unknown resource scope {id:?}"
+            );
+        }
+        self.active_scope = scope;
+    }
+
+    pub fn active_scope(&self) -> Option<ScopeId> {
+        self.active_scope
+    }
+
+    /// Drops a child scope and every resource in it. Deactivates it first if it was active.
+    pub fn drop_scope(&mut self, scope: ScopeId) {
+        if self.active_scope == Some(scope) {
+            self.active_scope = None;
+        }
+        if let Some(resources) = self.scopes.remove(&scope) {
+            info!(
+                "Dropped resource scope {:?} with {} resources",
+                scope,
+                resources.len()
+            );
+        }
+    }
+
+    fn active_scope_ref(&self) -> Option<&HashMap<TypeId, ResourceEntry>> {
+        self.active_scope.and_then(|id| self.scopes.get(&id))
+    }
+
+    fn active_scope_mut(&mut self) -> Option<&mut HashMap<TypeId, ResourceEntry>> {
+        self.active_scope.and_then(|id| self.scopes.get_mut(&id))
+    }
+
+    /// Builds `T` with [`FromResources`] unless it is already registered. Dependencies
+    /// initialized from inside `from_resources` are built first; a dependency cycle is
+    /// reported as an error naming every type in it.
+    pub fn init<T: FromResources>(&mut self) -> Result<()> {
+        if self.contains::<T>() {
+            return Ok(());
+        }
         let type_id = TypeId::of::<T>();
-        self.resources
-            .get(&type_id)
-            .and_then(|boxed| boxed.downcast_ref::<T>())
-            .unwrap_or_else(|| panic!("Resource of type {:?} not found", type_id))
+        let type_name = std::any::type_name::<T>();
+        if let Some(start) = self.initializing.iter().position(|(id, _)| *id == type_id) {
+            let cycle = self.initializing[start..]
+                .iter()
+                .map(|(_, name)| *name)
+                .chain([type_name])
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(anyhow!("Resource dependency cycle: {cycle}"));
+        }
+
+        self.initializing.push((type_id, type_name));
+        let resource = T::from_resources(self);
+        self.initializing.pop();
+        let resource = resource.with_context(|| format!("Failed to initialize {type_name}"))?;
+        self.add(resource);
+        Ok(())
     }
 
-    pub fn get_mut<T: 'static>(&mut self) -> &mut T {
+    /// Like [`ResourceManager::get`], but returns an error instead of panicking. Meant for
+    /// required dependencies inside [`FromResources`] impls that cannot be built lazily.
+    pub fn require<T: 'static>(&self) -> Result<&T> {
+        self.try_get::<T>().ok_or_else(|| {
+            anyhow!(
+                "Required resource {} is not registered",
+                std::any::type_name::<T>()
+            )
+        })
+    }
+
+    /// Swaps in a new value for `T`, returning the previous one if there was any. A global
+    /// resource stays global even while a scope is active.
+    pub fn replace<T: 'static>(&mut self, resource: T) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        let is_global = !self
+            .active_scope_ref()
+            .is_some_and(|scope| scope.contains_key(&type_id))
+            && self.resources.contains_key(&type_id);
+        let previous = self.remove::<T>();
+        if is_global {
+            self.add_global(resource);
+        } else {
+            self.add(resource);
+        }
+        previous
+    }
+
+    /// Takes `T` out of the manager so it can be torn down or moved elsewhere. The active
+    /// scope is checked before the globals.
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        let entry = self
+            .active_scope_mut()
+            .and_then(|scope| scope.remove(&type_id))
+            .or_else(|| self.resources.remove(&type_id))?;
+        info!("Removed resource {}", entry.type_name);
+        entry.value.downcast::<T>().ok().map(|boxed| *boxed)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.active_scope_ref()
+            .is_some_and(|scope| scope.contains_key(&type_id))
+            || self.resources.contains_key(&type_id)
+    }
+
+    /// Type names of every resource visible from the active scope, in no particular order.
+    pub fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.active_scope_ref()
+            .into_iter()
+            .flat_map(|scope| scope.values())
+            .chain(self.resources.values())
+            .map(|entry| entry.type_name)
+    }
+
+    /// Number of global resources plus those in the active scope.
+    pub fn len(&self) -> usize {
+        self.resources.len() + self.active_scope_ref().map_or(0, HashMap::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn try_get<T: 'static>(&self) -> Option<&T> {
+        let type_id = TypeId::of::<T>();
+        self.active_scope_ref()
+            .and_then(|scope| scope.get(&type_id))
+            .or_else(|| self.resources.get(&type_id))
+            .and_then(|entry| entry.value.downcast_ref::<T>())
+    }
+
+    pub fn try_get_mut<T: 'static>(&mut self) -> Option<&mut T> {
         let type_id = TypeId::of::<T>();
-        self.resources
-            .get_mut(&type_id)
-            .and_then(|boxed| boxed.downcast_mut::<T>())
-            .unwrap_or_else(|| {
-                error!("Resource of type {:?} not found", type_id);
-                panic!("Resource of type {:?} not found", type_id)
-            })
+        let scope = self.active_scope.and_then(|id| self.scopes.get_mut(&id));
+        match scope.and_then(|scope| scope.get_mut(&type_id)) {
+            Some(entry) => entry.value.downcast_mut::<T>(),
+            None => self
+                .resources
+                .get_mut(&type_id)
+                .and_then(|entry| entry.value.downcast_mut::<T>()),
+        }
+    }
+
+    pub fn get<T: 'static>(&self) -> &T {
+        self.try_get::<T>()
+            .unwrap_or_else(|| panic!("Resource of type {} not found", std::any::type_name::<T>()))
+    }
+
+    /// Borrows several distinct resources mutably at once, e.g.
+    /// `let (window, input) = resources.get_many_mut::<(Window, Input)>();`.
+    ///
+    /// Panics if a type is missing or appears more than once in the tuple.
+    pub fn get_many_mut<'a, S: ResourceSet<'a>>(&'a mut self) -> S::Output {
+        let scope = self.active_scope.and_then(|id| self.scopes.get_mut(&id));
+        S::fetch(&mut self.resources, scope)
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> &mut T {
+        self.try_get_mut::<T>().unwrap_or_else(|| {
+            let type_name = std::any::type_name::<T>();
+            error!("Resource of type {type_name} not found");
+            panic!("Resource of type {type_name} not found")
+        })
     }
 }
 
+/// A tuple of resource types that can be borrowed together through
+/// [`ResourceManager::get_many_mut`].
+pub trait ResourceSet<'a> {
+    type Output;
+
+    #[doc(hidden)]
+    fn fetch(
+        resources: &'a mut HashMap<TypeId, ResourceEntry>,
+        scope: Option<&'a mut HashMap<TypeId, ResourceEntry>>,
+    ) -> Self::Output;
+}
+
+macro_rules! impl_resource_set {
+    ($($t:ident),+) => {
+        impl<'a, $($t: 'static),+> ResourceSet<'a> for ($($t,)+) {
+            type Output = ($(&'a mut $t,)+);
+
+            #[allow(non_snake_case)]
+            fn fetch(
+                resources: &'a mut HashMap<TypeId, ResourceEntry>,
+                scope: Option<&'a mut HashMap<TypeId, ResourceEntry>>,
+            ) -> Self::Output {
+                let ids = [$(TypeId::of::<$t>()),+];
+                let names = [$(std::any::type_name::<$t>()),+];
+                for (i, id) in ids.iter().enumerate() {
+                    if let Some(j) = ids[i + 1..].iter().position(|other| other == id) {
+                        panic!(
+                            "Resource {} requested more than once (positions {} and {})",
+                            names[i],
+                            i,
+                            i + 1 + j
+                        );
+                    }
+                }
+                let keys = [$(&TypeId::of::<$t>()),+];
+                let mut scoped = scope
+                    .map(|scope| scope.get_disjoint_mut(keys))
+                    .into_iter()
+                    .flatten();
+                let [$($t),+] = resources
+                    .get_disjoint_mut(keys)
+                    .map(|global| scoped.next().flatten().or(global));
+                ($(
+                    $t.and_then(|entry| entry.value.downcast_mut::<$t>())
+                        .unwrap_or_else(|| {
+                            panic!("Resource of type {} not found", std::any::type_name::<$t>())
+                        }),
+                )+)
+            }
+        }
+    };
+}
+
+impl_resource_set!(A, B);
+impl_resource_set!(A, B, C);
+impl_resource_set!(A, B, C, D);
+impl_resource_set!(A, B, C, D, E);
+
 impl Default for ResourceManager {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use ron::value::RawValue;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tracing::{info, warn};
+
+use super::ResourceManager;
+
+type SaveFn = fn(&ResourceManager) -> Option<Result<Box<RawValue>>>;
+type LoadFn = fn(&mut ResourceManager, &RawValue) -> Result<()>;
+
+#[derive(Clone, Copy)]
+struct SnapshotEntry {
+    key: &'static str,
+    save: SaveFn,
+    load: LoadFn,
+}
+
+/// Resources that opted into snapshots, keyed by a stable name so saves survive
+/// refactors that move or rename the Rust type.
+#[derive(Default)]
+pub(super) struct SnapshotRegistry {
+    entries: Vec<SnapshotEntry>,
+}
+
+impl SnapshotRegistry {
+    pub(super) fn register<T: Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        key: &'static str,
+    ) {
+        let entry = SnapshotEntry {
+            key,
+            save: |resources| {
+                resources.try_get::<T>().map(|resource| {
+                    RawValue::from_rust(resource).with_context(|| {
+                        format!("Failed to serialize {}", std::any::type_name::<T>())
+                    })
+                })
+            },
+            load: |resources, value| {
+                let resource = value.into_rust::<T>().with_context(|| {
+                    format!("Failed to deserialize {}", std::any::type_name::<T>())
+                })?;
+                resources.replace(resource);
+                Ok(())
+            },
+        };
+        match self.entries.iter_mut().find(|e| e.key == key) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+}
+
+impl ResourceManager {
+    /// Marks `T` as part of snapshots under `key`. Register before saving or loading;
+    /// resources that aren't registered are left out of snapshots entirely.
+    pub fn register_snapshot<T: Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        key: &'static str,
+    ) {
+        self.snapshots.register::<T>(key);
+    }
+
+    /// Serializes every registered resource that is currently present into a RON map.
+    pub fn save_snapshot(&self) -> Result<String> {
+        let values = self.snapshot_values()?;
+        let ron = ron::ser::to_string_pretty(&values, ron::ser::PrettyConfig::default())
+            .context("Failed to write snapshot")?;
+        info!("Saved snapshot of {} resources", values.len());
+        Ok(ron)
+    }
+
+    /// Restores resources from a string produced by [`ResourceManager::save_snapshot`],
+    /// replacing the current values. Registered resources missing from the snapshot are
+    /// left alone; unknown keys are skipped with a warning.
+    pub fn load_snapshot(&mut self, ron: &str) -> Result<()> {
+        let values: BTreeMap<String, Box<RawValue>> =
+            ron::from_str(ron).context("Failed to parse snapshot")?;
+        self.restore_snapshot_values(&values)?;
+        info!("Loaded snapshot of {} resources", values.len());
+        Ok(())
+    }
+
+    /// Every registered resource that is currently present, by key, for embedding in a
+    /// larger file such as a [`SaveGame`](crate::save_game::SaveGame).
+    pub fn snapshot_values(&self) -> Result<BTreeMap<String, Box<RawValue>>> {
+        let mut values = BTreeMap::new();
+        for entry in &self.snapshots.entries {
+            if let Some(value) = (entry.save)(self) {
+                values.insert(entry.key.to_owned(), value?);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Restores resources from [`ResourceManager::snapshot_values`], like
+    /// [`ResourceManager::load_snapshot`].
+    pub fn restore_snapshot_values(
+        &mut self,
+        values: &BTreeMap<String, Box<RawValue>>,
+    ) -> Result<()> {
+        let entries = self.snapshots.entries.clone();
+        for (key, value) in values {
+            match entries.iter().find(|entry| entry.key == key) {
+                Some(entry) => (entry.load)(self, value)
+                    .with_context(|| format!("Failed to restore snapshot entry {key}"))?,
+                None => warn!("Snapshot entry {key} has no registered resource, skipping"),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_snapshot_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let ron = self.save_snapshot()?;
+        std::fs::write(path, ron)
+            .with_context(|| format!("Failed to write snapshot to {}", path.display()))
+    }
+
+    pub fn load_snapshot_from_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let ron = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read snapshot from {}", path.display()))?;
+        self.load_snapshot(&ron)
+    }
+}
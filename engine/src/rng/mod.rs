@@ -0,0 +1,104 @@
+use crate::engine::EngineConfig;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Central source of randomness for the engine and game code.
+///
+/// Every system that needs random numbers should pull them from this resource (or from a
+/// stream [`fork`](Rng::fork)ed off it) so that a single seed reproduces a whole run.
+/// The generator is SplitMix64: tiny, fast and with a stable output sequence across
+/// platforms and releases, which is what replays care about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { seed, state: seed }
+    }
+
+    /// Seeds the generator from the system clock. Not reproducible; use [`Rng::new`]
+    /// when determinism matters.
+    pub fn from_entropy() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    /// The seed this generator was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the sequence from the original seed.
+    pub fn reset(&mut self) {
+        self.state = self.seed;
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+
+    /// Uniform float in `[min, max)`.
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Uniform integer in `[min, max)`. Returns `min` if the range is empty.
+    pub fn range_u32(&mut self, min: u32, max: u32) -> u32 {
+        if max <= min {
+            return min;
+        }
+        min + (self.next_u64() % (max - min) as u64) as u32
+    }
+
+    /// Derives an independent generator, e.g. for a subsystem that should not perturb
+    /// the main sequence when it draws a different number of values.
+    pub fn fork(&mut self) -> Rng {
+        Rng::new(self.next_u64())
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+impl FromResources for Rng {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        let rng = match resources
+            .try_get::<EngineConfig>()
+            .and_then(|config| config.rng_seed)
+        {
+            Some(seed) => Rng::new(seed),
+            None => Rng::from_entropy(),
+        };
+        info!("Rng seed: {}", rng.seed());
+        Ok(rng)
+    }
+}
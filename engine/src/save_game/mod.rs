@@ -0,0 +1,171 @@
+use crate::asset_loader::AssetLoader;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::{SceneAsset, World};
+use anyhow::{Context, Result, bail};
+use ron::value::RawValue;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::info;
+
+/// Upgrades save data from one version to the next.
+type Migration = Box<dyn Fn(&mut SaveData) -> Result<()>>;
+
+/// Everything a save file holds: the resources registered with
+/// [`ResourceManager::register_snapshot`] and the entities of the [`World`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveData {
+    /// [`SaveGame::version`] of the game that wrote it.
+    pub version: u32,
+    #[serde(default)]
+    pub resources: BTreeMap<String, Box<RawValue>>,
+    /// Entities with their registered components, as in a level file.
+    #[serde(default)]
+    pub world: SceneAsset,
+}
+
+/// Writes and loads save files, and upgrades the ones written by older versions of the
+/// game. Entity ids aren't kept, so components that store an [`Entity`](crate::scene::Entity)
+/// need fixing up after a load.
+///
+/// Bump the version whenever a saved type changes in a way serde can't absorb, and
+/// register a migration from the previous version:
+///
+/// ```ignore
+/// let save_game = resources.get_mut::<SaveGame>();
+/// save_game.set_version(2);
+/// save_game.add_migration(1, |data| {
+///     if let Some(value) = data.resources.remove("inventory") {
+///         data.resources.insert("backpack".to_owned(), value);
+///     }
+///     Ok(())
+/// });
+/// ```
+pub struct SaveGame {
+    version: u32,
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl SaveGame {
+    pub fn new() -> Self {
+        SaveGame {
+            version: 0,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Version stamped on new saves. Starts at 0.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    /// Registers how to upgrade data saved at version `from` to `from + 1`. Loading an
+    /// older save runs every migration from its version up to the current one, in order.
+    pub fn add_migration(
+        &mut self,
+        from: u32,
+        migration: impl Fn(&mut SaveData) -> Result<()> + 'static,
+    ) {
+        self.migrations.insert(from, Box::new(migration));
+    }
+
+    /// Brings `data` up to the current version.
+    pub fn migrate(&self, data: &mut SaveData) -> Result<()> {
+        if data.version > self.version {
+            bail!(
+                "Save version {} is newer than this game's {}",
+                data.version,
+                self.version
+            );
+        }
+        while data.version < self.version {
+            let migration = self
+                .migrations
+                .get(&data.version)
+                .with_context(|| format!("No migration from save version {}", data.version))?;
+            migration(data)
+                .with_context(|| format!("Failed to migrate save version {}", data.version))?;
+            data.version += 1;
+        }
+        Ok(())
+    }
+
+    /// Snapshots the registered resources and the world.
+    pub fn capture(resources: &ResourceManager) -> Result<SaveData> {
+        Ok(SaveData {
+            version: resources.try_get::<SaveGame>().map_or(0, SaveGame::version),
+            resources: resources.snapshot_values()?,
+            world: SceneAsset::from_world(resources.get::<World>())?,
+        })
+    }
+
+    /// Migrates `data`, then replaces the registered resources it has and every entity
+    /// of the world with its contents.
+    pub fn restore(resources: &mut ResourceManager, mut data: SaveData) -> Result<()> {
+        match resources.try_get::<SaveGame>() {
+            Some(save_game) => save_game.migrate(&mut data)?,
+            None => SaveGame::new().migrate(&mut data)?,
+        }
+        resources.restore_snapshot_values(&data.resources)?;
+        let (world, assets) = resources.get_many_mut::<(World, AssetLoader)>();
+        world.clear();
+        data.world.spawn(world, assets, None)?;
+        Ok(())
+    }
+
+    /// Writes a save file. The old file is only replaced once the new one is complete.
+    pub fn write(resources: &ResourceManager, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let data = Self::capture(resources)?;
+        let ron = ron::ser::to_string_pretty(&data, ron::ser::PrettyConfig::default())
+            .context("Failed to serialize save")?;
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, ron)
+            .with_context(|| format!("Failed to write save to {}", partial.display()))?;
+        std::fs::rename(&partial, path)
+            .with_context(|| format!("Failed to move save to {}", path.display()))?;
+        info!(
+            "Saved {} resources and {} entities to {}",
+            data.resources.len(),
+            resources.get::<World>().entities().count(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Loads a save file written by [`SaveGame::write`], see [`SaveGame::restore`].
+    pub fn load(resources: &mut ResourceManager, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let ron = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read save from {}", path.display()))?;
+        let data: SaveData = ron::from_str(&ron)
+            .with_context(|| format!("Failed to parse save {}", path.display()))?;
+        let version = data.version;
+        Self::restore(resources, data)?;
+        info!("Loaded save version {version} from {}", path.display());
+        Ok(())
+    }
+}
+
+impl Default for SaveGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for SaveGame {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(SaveGame::new())
+    }
+}
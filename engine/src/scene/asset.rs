@@ -0,0 +1,188 @@
+use crate::asset_loader::gltf_model::GltfModel;
+use crate::asset_loader::{AssetLoader, Handle};
+use crate::core::transform::Transform;
+use crate::resource_manager::ResourceManager;
+use crate::scene::{Entity, World};
+use anyhow::{Context, Result, ensure};
+use assets_manager::{BoxedError, FileAsset};
+use glam::{Quat, Vec3};
+use ron::value::RawValue;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Prefabs may include prefabs; this bounds the nesting so a prefab that includes
+/// itself fails instead of recursing forever.
+const MAX_PREFAB_DEPTH: usize = 16;
+
+/// Component attached to entities whose scene entry names a `model`.
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub handle: Handle<GltfModel>,
+}
+
+/// Level or prefab authored as RON, e.g. `assets/levels/garage.ron`:
+///
+/// ```ron
+/// (
+///     entities: [
+///         (
+///             name: Some("car"),
+///             translation: (0.0, 0.0, -2.0),
+///             model: Some("super_car.scene"),
+///             components: { "spin": (speed: 1.0) },
+///             children: [(name: Some("driver"), prefab: Some("prefabs.driver"))],
+///         ),
+///     ],
+/// )
+/// ```
+///
+/// Component keys must be registered with [`World::register_component`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SceneAsset {
+    #[serde(default)]
+    pub entities: Vec<EntityDesc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntityDesc {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub translation: Vec3,
+    #[serde(default = "identity_rotation")]
+    pub rotation: Quat,
+    #[serde(default = "unit_scale")]
+    pub scale: Vec3,
+    /// Asset id or `guid:` reference of a glTF model, attached as a [`Model`] component
+    /// and loaded async.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Asset id or `guid:` reference of another [`SceneAsset`] whose entities are spawned
+    /// as children.
+    #[serde(default)]
+    pub prefab: Option<String>,
+    #[serde(default)]
+    pub components: BTreeMap<String, Box<RawValue>>,
+    #[serde(default)]
+    pub children: Vec<EntityDesc>,
+}
+
+fn identity_rotation() -> Quat {
+    Quat::IDENTITY
+}
+
+fn unit_scale() -> Vec3 {
+    Vec3::ONE
+}
+
+impl FileAsset for SceneAsset {
+    const EXTENSION: &'static str = "ron";
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+}
+
+impl SceneAsset {
+    /// Describes every entity of `world` with its name, transform, model and registered
+    /// components, so spawning the result recreates it. Prefabs are written out as the
+    /// children they spawned, and entity ids aren't kept.
+    pub fn from_world(world: &World) -> Result<Self> {
+        let entities = world
+            .roots()
+            .map(|root| describe_entity(world, root))
+            .collect::<Result<_>>()?;
+        Ok(SceneAsset { entities })
+    }
+
+    /// Spawns every entity under `parent`, or as roots with `None`. Returns the top-level
+    /// entities. On error, entities spawned so far stay in the world.
+    pub fn spawn(
+        &self,
+        world: &mut World,
+        assets: &AssetLoader,
+        parent: Option<Entity>,
+    ) -> Result<Vec<Entity>> {
+        self.spawn_nested(world, assets, parent, 0)
+    }
+
+    /// Loads the scene `id` and spawns it as roots of the [`World`] resource.
+    pub fn spawn_into(resources: &mut ResourceManager, id: &str) -> Result<Vec<Entity>> {
+        let (world, assets) = resources.get_many_mut::<(World, AssetLoader)>();
+        let scene = assets
+            .load::<SceneAsset>(&assets.canonical_id(id))
+            .with_context(|| format!("Failed to load scene {id}"))?;
+        scene.read().spawn(world, assets, None)
+    }
+
+    fn spawn_nested(
+        &self,
+        world: &mut World,
+        assets: &AssetLoader,
+        parent: Option<Entity>,
+        depth: usize,
+    ) -> Result<Vec<Entity>> {
+        self.entities
+            .iter()
+            .map(|desc| spawn_entity(desc, world, assets, parent, depth))
+            .collect()
+    }
+}
+
+fn describe_entity(world: &World, entity: Entity) -> Result<EntityDesc> {
+    let node = world.node(entity).context("Entity was despawned")?;
+    Ok(EntityDesc {
+        name: node.name.clone(),
+        translation: node.transform.translation,
+        rotation: node.transform.rotation,
+        scale: node.transform.scale,
+        model: world
+            .get::<Model>(entity)
+            .map(|model| model.handle.id().to_string()),
+        prefab: None,
+        components: world.serialize_components(entity)?,
+        children: node
+            .children()
+            .iter()
+            .map(|&child| describe_entity(world, child))
+            .collect::<Result<_>>()?,
+    })
+}
+
+fn spawn_entity(
+    desc: &EntityDesc,
+    world: &mut World,
+    assets: &AssetLoader,
+    parent: Option<Entity>,
+    depth: usize,
+) -> Result<Entity> {
+    let entity = world.spawn(desc.name.clone());
+    world.set_parent(entity, parent)?;
+    if let Some(node) = world.node_mut(entity) {
+        node.transform = Transform::new(desc.translation, desc.rotation, desc.scale);
+    }
+    if let Some(model) = &desc.model {
+        let handle = assets.load_async::<GltfModel>(model);
+        world.insert(entity, Model { handle });
+    }
+    for (key, value) in &desc.components {
+        world.insert_serialized(entity, key, value)?;
+    }
+    if let Some(prefab) = &desc.prefab {
+        ensure!(
+            depth < MAX_PREFAB_DEPTH,
+            "Prefab {prefab} nested more than {MAX_PREFAB_DEPTH} deep; does it include itself?"
+        );
+        let scene = assets
+            .load::<SceneAsset>(&assets.canonical_id(prefab))
+            .with_context(|| format!("Failed to load prefab {prefab}"))?;
+        scene
+            .read()
+            .spawn_nested(world, assets, Some(entity), depth + 1)?;
+    }
+    for child in &desc.children {
+        spawn_entity(child, world, assets, Some(entity), depth)?;
+    }
+    Ok(entity)
+}
@@ -0,0 +1,364 @@
+use crate::core::transform::Transform;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::{Context, Result, anyhow};
+use glam::Mat4;
+use ron::value::RawValue;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use tracing::warn;
+
+pub mod asset;
+mod visibility;
+
+pub use asset::{EntityDesc, Model, SceneAsset};
+pub use visibility::{RenderLayers, Visibility};
+
+/// Id of an entity in a [`World`]. Stale ids of despawned entities never alias new ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// Name, place in the hierarchy and local transform of an entity.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: Option<String>,
+    pub transform: Transform,
+    parent: Option<Entity>,
+    children: Vec<Entity>,
+}
+
+impl Node {
+    /// Transform relative to the parent entity.
+    pub fn local_transform(&self) -> Mat4 {
+        self.transform.compute_matrix()
+    }
+
+    pub fn parent(&self) -> Option<Entity> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[Entity] {
+        &self.children
+    }
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+type ComponentLoader = fn(&mut World, Entity, &RawValue) -> Result<()>;
+type ComponentSaver = fn(&World, Entity) -> Option<Result<Box<RawValue>>>;
+
+/// Entities arranged in a transform hierarchy, each carrying any number of typed
+/// components. Components are stored per type in entity order, so iteration is
+/// deterministic.
+#[derive(Default)]
+pub struct World {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    components: HashMap<TypeId, Box<dyn ErasedStorage>>,
+    loaders: HashMap<&'static str, ComponentLoader>,
+    savers: Vec<(&'static str, ComponentSaver)>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a root entity with an identity transform.
+    pub fn spawn(&mut self, name: Option<String>) -> Entity {
+        let node = Node {
+            name,
+            transform: Transform::IDENTITY,
+            parent: None,
+            children: Vec::new(),
+        };
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                Entity {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                Entity {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes `entity`, its components and all of its descendants.
+    pub fn despawn(&mut self, entity: Entity) {
+        let Some(node) = self.node(entity) else {
+            return;
+        };
+        if let Some(parent) = node.parent
+            && let Some(parent) = self.node_mut(parent)
+        {
+            parent.children.retain(|&child| child != entity);
+        }
+        let mut pending = vec![entity];
+        while let Some(entity) = pending.pop() {
+            let slot = &mut self.slots[entity.index as usize];
+            if let Some(node) = slot.node.take() {
+                pending.extend(node.children);
+            }
+            slot.generation += 1;
+            self.free.push(entity.index);
+            for storage in self.components.values_mut() {
+                storage.remove(entity);
+            }
+        }
+    }
+
+    /// Despawns every entity. Registered components stay registered.
+    pub fn clear(&mut self) {
+        let roots = self.roots().collect::<Vec<_>>();
+        for root in roots {
+            self.despawn(root);
+        }
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.node(entity).is_some()
+    }
+
+    pub fn node(&self, entity: Entity) -> Option<&Node> {
+        self.slots
+            .get(entity.index as usize)
+            .filter(|slot| slot.generation == entity.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    pub fn node_mut(&mut self, entity: Entity) -> Option<&mut Node> {
+        self.slots
+            .get_mut(entity.index as usize)
+            .filter(|slot| slot.generation == entity.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    /// Every live entity, in id order.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.node.as_ref().map(|_| Entity {
+                index: index as u32,
+                generation: slot.generation,
+            })
+        })
+    }
+
+    /// The first entity, in id order, named `name`.
+    pub fn find(&self, name: &str) -> Option<Entity> {
+        self.entities().find(|&entity| {
+            self.node(entity)
+                .is_some_and(|node| node.name.as_deref() == Some(name))
+        })
+    }
+
+    /// Entities without a parent.
+    pub fn roots(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities()
+            .filter(|&entity| self.node(entity).is_some_and(|node| node.parent.is_none()))
+    }
+
+    /// Moves `child` under `parent`, or to the root with `None`. Fails if either entity
+    /// is gone or the move would create a cycle.
+    pub fn set_parent(&mut self, child: Entity, parent: Option<Entity>) -> Result<()> {
+        let old_parent = self.node(child).context("Entity was despawned")?.parent;
+        if let Some(parent) = parent {
+            let mut ancestor = Some(parent);
+            while let Some(current) = ancestor {
+                if current == child {
+                    return Err(anyhow!("Parenting would create a cycle"));
+                }
+                ancestor = self.node(current).context("Parent was despawned")?.parent;
+            }
+        }
+        if let Some(old_parent) = old_parent
+            && let Some(old_parent) = self.node_mut(old_parent)
+        {
+            old_parent.children.retain(|&entity| entity != child);
+        }
+        if let Some(parent) = parent
+            && let Some(parent) = self.node_mut(parent)
+        {
+            parent.children.push(child);
+        }
+        if let Some(child) = self.node_mut(child) {
+            child.parent = parent;
+        }
+        Ok(())
+    }
+
+    /// Transform from the entity's space to world space.
+    pub fn world_transform(&self, entity: Entity) -> Option<Mat4> {
+        let node = self.node(entity)?;
+        let local = node.local_transform();
+        Some(match node.parent {
+            Some(parent) => self.world_transform(parent).unwrap_or(Mat4::IDENTITY) * local,
+            None => local,
+        })
+    }
+
+    /// Whether `entity` is drawn: the nearest [`Visibility`] other than `Inherit` on it or
+    /// its ancestors decides, and everything is visible by default. `false` for despawned
+    /// entities.
+    pub fn is_visible(&self, entity: Entity) -> bool {
+        let Some(node) = self.node(entity) else {
+            return false;
+        };
+        match self.get::<Visibility>(entity).copied().unwrap_or_default() {
+            Visibility::Visible => true,
+            Visibility::Hidden => false,
+            Visibility::Inherit => node.parent.is_none_or(|parent| self.is_visible(parent)),
+        }
+    }
+
+    /// Adds or replaces the `C` component of `entity`.
+    pub fn insert<C: 'static>(&mut self, entity: Entity, component: C) {
+        if !self.is_alive(entity) {
+            warn!(
+                "Ignoring {} for despawned entity",
+                std::any::type_name::<C>()
+            );
+            return;
+        }
+        self.storage_mut::<C>().insert(entity, component);
+    }
+
+    pub fn remove<C: 'static>(&mut self, entity: Entity) -> Option<C> {
+        self.storage_mut::<C>().remove(&entity)
+    }
+
+    pub fn get<C: 'static>(&self, entity: Entity) -> Option<&C> {
+        self.storage::<C>()?.get(&entity)
+    }
+
+    pub fn get_mut<C: 'static>(&mut self, entity: Entity) -> Option<&mut C> {
+        self.storage_mut::<C>().get_mut(&entity)
+    }
+
+    /// Every entity with a `C` component, in entity order.
+    pub fn query<C: 'static>(&self) -> impl Iterator<Item = (Entity, &C)> {
+        self.storage::<C>().into_iter().flat_map(|storage| {
+            storage
+                .iter()
+                .map(|(&entity, component)| (entity, component))
+        })
+    }
+
+    pub fn query_mut<C: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut C)> {
+        self.storage_mut::<C>()
+            .iter_mut()
+            .map(|(&entity, component)| (entity, component))
+    }
+
+    /// Lets scene files attach `C` under `key` in an entity's `components` map, and
+    /// [`SceneAsset::from_world`] write it back.
+    pub fn register_component<C: Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        key: &'static str,
+    ) {
+        self.loaders.insert(key, |world, entity, value| {
+            let component = value
+                .into_rust::<C>()
+                .with_context(|| format!("Failed to deserialize {}", std::any::type_name::<C>()))?;
+            world.insert(entity, component);
+            Ok(())
+        });
+        let saver: ComponentSaver = |world, entity| {
+            world.get::<C>(entity).map(|component| {
+                RawValue::from_rust(component)
+                    .with_context(|| format!("Failed to serialize {}", std::any::type_name::<C>()))
+            })
+        };
+        match self
+            .savers
+            .iter_mut()
+            .find(|(existing, _)| *existing == key)
+        {
+            Some(entry) => entry.1 = saver,
+            None => self.savers.push((key, saver)),
+        }
+    }
+
+    /// Serializes the registered components of `entity`, by key. Unregistered ones are
+    /// left out.
+    pub fn serialize_components(&self, entity: Entity) -> Result<BTreeMap<String, Box<RawValue>>> {
+        let mut components = BTreeMap::new();
+        for (key, saver) in &self.savers {
+            if let Some(value) = saver(self, entity) {
+                components.insert(key.to_string(), value?);
+            }
+        }
+        Ok(components)
+    }
+
+    /// Deserializes a component registered under `key` and attaches it to `entity`.
+    pub fn insert_serialized(&mut self, entity: Entity, key: &str, value: &RawValue) -> Result<()> {
+        let loader = self
+            .loaders
+            .get(key)
+            .with_context(|| format!("No component registered as {key}"))?;
+        loader(self, entity, value)
+    }
+
+    fn storage<C: 'static>(&self) -> Option<&BTreeMap<Entity, C>> {
+        self.components
+            .get(&TypeId::of::<C>())
+            .and_then(|storage| storage.as_any().downcast_ref::<BTreeMap<Entity, C>>())
+    }
+
+    fn storage_mut<C: 'static>(&mut self) -> &mut BTreeMap<Entity, C> {
+        self.components
+            .entry(TypeId::of::<C>())
+            .or_insert_with(|| Box::new(BTreeMap::<Entity, C>::new()))
+            .as_any_mut()
+            .downcast_mut::<BTreeMap<Entity, C>>()
+            .expect("Component storage stored under the wrong type")
+    }
+}
+
+/// Type-erased access to a component storage, so despawning can clear every type.
+trait ErasedStorage {
+    fn remove(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<C: 'static> ErasedStorage for BTreeMap<Entity, C> {
+    fn remove(&mut self, entity: Entity) {
+        BTreeMap::remove(self, &entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl FromResources for World {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        let mut world = World::new();
+        world.register_component::<Visibility>("visibility");
+        world.register_component::<RenderLayers>("render_layers");
+        Ok(world)
+    }
+}
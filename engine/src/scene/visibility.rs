@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+/// Component hiding an entity and, unless they say otherwise, its children. Registered as
+/// `visibility` for scene files, e.g. `"visibility": Hidden`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    /// Visible if the parent is; roots are visible.
+    #[default]
+    Inherit,
+    Visible,
+    Hidden,
+}
+
+/// Component picking which cameras draw an entity: it shows up in a camera whose
+/// [`Camera::layers`](crate::camera::Camera::layers) share at least one layer with it.
+/// Entities without one are on layer 0 only. Registered as `render_layers`, a bit mask,
+/// e.g. `"render_layers": 4` for layer 2.
+///
+/// Put a first-person weapon on a layer only the main camera draws, or minimap icons on
+/// one only the minimap camera draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RenderLayers(pub u32);
+
+impl RenderLayers {
+    pub const NONE: RenderLayers = RenderLayers(0);
+    pub const ALL: RenderLayers = RenderLayers(u32::MAX);
+
+    /// Just layer `layer`, from 0 to 31.
+    pub const fn layer(layer: u32) -> Self {
+        RenderLayers(1 << layer)
+    }
+
+    /// These layers plus `layer`.
+    pub const fn with(self, layer: u32) -> Self {
+        RenderLayers(self.0 | 1 << layer)
+    }
+
+    /// These layers without `layer`.
+    pub const fn without(self, layer: u32) -> Self {
+        RenderLayers(self.0 & !(1 << layer))
+    }
+
+    pub const fn intersects(self, other: RenderLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        RenderLayers::layer(0)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::core::transform::Transform;
+use crate::scene::Visibility;
+use crate::tween::{Ease, Tween};
+use assets_manager::{BoxedError, FileAsset};
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// A cutscene: tracks of keys along one timeline, played by the
+/// [`Sequencer`](crate::sequencer::Sequencer). Authored as RON, e.g.
+/// `assets/cutscenes/intro.sequence`:
+///
+/// ```ron
+/// (
+///     tracks: [
+///         Camera([
+///             (time: 0.0, translation: (0.0, 2.0, 8.0)),
+///             (time: 4.0, translation: (4.0, 1.5, 3.0), rotation: (0.0, 0.38, 0.0, 0.92), ease: SineInOut),
+///         ]),
+///         Transform(entity: "garage_door", keys: [
+///             (time: 1.0, translation: (0.0, 0.0, 0.0)),
+///             (time: 2.5, translation: (0.0, 3.0, 0.0), ease: QuadOut),
+///         ]),
+///         Visibility(entity: "car", keys: [(time: 0.0, visibility: Hidden), (time: 2.0, visibility: Visible)]),
+///         Audio([(time: 1.0, clip: "sfx.door_open")]),
+///         Event([(time: 4.0, name: "intro_done")]),
+///     ],
+/// )
+/// ```
+///
+/// Entities are looked up by name when the track applies, so they may spawn after the
+/// sequence starts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequence {
+    pub tracks: Vec<Track>,
+    /// Seconds. Defaults to the time of the last key.
+    #[serde(default)]
+    pub duration: Option<f32>,
+    /// Starts over at the end instead of finishing.
+    #[serde(default)]
+    pub looping: bool,
+    /// The transform tracks as tweens, parallel to `tracks`.
+    #[serde(skip)]
+    tweens: Vec<Option<Tween<Transform>>>,
+}
+
+/// Keys of one kind along a [`Sequence`]'s timeline, sorted by time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Track {
+    /// Moves the [`Camera`](crate::camera::Camera). Key scales are ignored.
+    Camera(Vec<TransformKey>),
+    /// Moves the local transform of the entity named `entity`.
+    Transform {
+        entity: String,
+        keys: Vec<TransformKey>,
+    },
+    /// Sets the [`Visibility`] of the entity named `entity`; each key holds until the next.
+    Visibility {
+        entity: String,
+        keys: Vec<VisibilityKey>,
+    },
+    /// Sends an [`AudioCue`](crate::sequencer::AudioCue) when the playhead passes a key.
+    Audio(Vec<AudioKey>),
+    /// Sends a [`SequenceEvent`](crate::sequencer::SequenceEvent) when the playhead passes
+    /// a key.
+    Event(Vec<EventKey>),
+}
+
+/// Transform reached at `time`, eased from the previous key. Before the first key, the
+/// first key's transform holds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TransformKey {
+    pub time: f32,
+    #[serde(default)]
+    pub translation: Vec3,
+    #[serde(default = "identity_rotation")]
+    pub rotation: Quat,
+    #[serde(default = "unit_scale")]
+    pub scale: Vec3,
+    #[serde(default)]
+    pub ease: Ease,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VisibilityKey {
+    pub time: f32,
+    pub visibility: Visibility,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioKey {
+    pub time: f32,
+    /// Asset id of an [`AudioClip`](crate::asset_loader::audio::AudioClip).
+    pub clip: String,
+    #[serde(default = "full_volume")]
+    pub volume: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventKey {
+    pub time: f32,
+    pub name: String,
+}
+
+fn identity_rotation() -> Quat {
+    Quat::IDENTITY
+}
+
+fn unit_scale() -> Vec3 {
+    Vec3::ONE
+}
+
+fn full_volume() -> f32 {
+    1.0
+}
+
+impl Track {
+    /// Times of every key, in order.
+    fn key_times(&self) -> Vec<f32> {
+        match self {
+            Track::Camera(keys) | Track::Transform { keys, .. } => {
+                keys.iter().map(|key| key.time).collect()
+            }
+            Track::Visibility { keys, .. } => keys.iter().map(|key| key.time).collect(),
+            Track::Audio(keys) => keys.iter().map(|key| key.time).collect(),
+            Track::Event(keys) => keys.iter().map(|key| key.time).collect(),
+        }
+    }
+}
+
+impl Sequence {
+    /// Seconds from the start to the end.
+    pub fn duration(&self) -> f32 {
+        self.duration.unwrap_or_else(|| {
+            self.tracks
+                .iter()
+                .flat_map(Track::key_times)
+                .fold(0.0, f32::max)
+        })
+    }
+
+    /// Transform of transform or camera track `track` at `time`.
+    pub(crate) fn transform_at(&self, track: usize, time: f32) -> Option<Transform> {
+        let tween = self.tweens.get(track)?.as_ref()?;
+        let start = match &self.tracks[track] {
+            Track::Camera(keys) | Track::Transform { keys, .. } => keys.first()?.time,
+            _ => return None,
+        };
+        Some(tween.sample(time - start))
+    }
+
+    /// Builds the tween of every transform and camera track.
+    fn compile(&mut self) {
+        self.tweens = self
+            .tracks
+            .iter()
+            .map(|track| match track {
+                Track::Camera(keys) | Track::Transform { keys, .. } => {
+                    let (first, rest) = keys.split_first()?;
+                    let mut previous = first.time;
+                    let tween = rest
+                        .iter()
+                        .fold(Tween::new(first.transform()), |tween, key| {
+                            let duration = key.time - previous;
+                            previous = key.time;
+                            tween.to(key.transform(), duration, key.ease)
+                        });
+                    Some(tween)
+                }
+                _ => None,
+            })
+            .collect();
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (index, track) in self.tracks.iter().enumerate() {
+            let times = track.key_times();
+            if times.iter().any(|time| time.is_nan() || *time < 0.0) {
+                return Err(format!("Track {index} has a key before the start"));
+            }
+            if times.windows(2).any(|pair| pair[1] < pair[0]) {
+                return Err(format!("Track {index} has keys out of order"));
+            }
+        }
+        if self
+            .duration
+            .is_some_and(|duration| duration.is_nan() || duration < 0.0)
+        {
+            return Err("Sequence duration can't be negative".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl TransformKey {
+    fn transform(&self) -> Transform {
+        Transform::new(self.translation, self.rotation, self.scale)
+    }
+}
+
+impl VisibilityKey {
+    /// Visibility `keys` set at `time`, if a key was passed yet.
+    pub(crate) fn at(keys: &[VisibilityKey], time: f32) -> Option<Visibility> {
+        keys.iter()
+            .take_while(|key| key.time <= time)
+            .last()
+            .map(|key| key.visibility)
+    }
+}
+
+impl FileAsset for Sequence {
+    const EXTENSION: &'static str = "sequence";
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Result<Self, BoxedError> {
+        let mut sequence: Sequence = ron::de::from_bytes(&bytes)?;
+        sequence.validate()?;
+        sequence.compile();
+        Ok(sequence)
+    }
+}
@@ -0,0 +1,269 @@
+use crate::asset_loader::audio::AudioClip;
+use crate::asset_loader::{AssetLoader, Handle, LoadState};
+use crate::camera::Camera;
+use crate::events::Events;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::World;
+use anyhow::Result;
+use tracing::warn;
+
+mod asset;
+
+pub use asset::{AudioKey, EventKey, Sequence, Track, TransformKey, VisibilityKey};
+
+/// Id of a sequence started with [`Sequencer::play`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SequenceId(u64);
+
+/// Sent on the event bus when a sequence's [`Track::Event`] key is passed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceEvent {
+    pub sequence: SequenceId,
+    pub name: String,
+}
+
+/// Sent on the event bus when a sequence's [`Track::Audio`] key is passed, for the game's
+/// audio output to play. The clip was loaded when the sequence started.
+#[derive(Debug, Clone)]
+pub struct AudioCue {
+    pub clip: Handle<AudioClip>,
+    pub volume: f32,
+}
+
+/// Sent on the event bus when a sequence that doesn't loop reaches its end, or when one
+/// failed to load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceFinished(pub SequenceId);
+
+struct Playback {
+    id: SequenceId,
+    handle: Handle<Sequence>,
+    time: f32,
+    /// Cues up to this time were sent.
+    cued: f32,
+    paused: bool,
+    speed: f32,
+    /// Audio clips of the sequence, kept loaded while it plays. `None` until the sequence
+    /// itself loaded.
+    clips: Option<Vec<Handle<AudioClip>>>,
+}
+
+/// Plays [`Sequence`] assets. The engine advances it every tick, right after
+/// [`Tweens`](crate::tween::Tweens), so a playing camera track overrides the camera
+/// controller:
+///
+/// ```ignore
+/// let (sequencer, assets) = resources.get_many_mut::<(Sequencer, AssetLoader)>();
+/// let intro = sequencer.play(assets, "cutscenes.intro");
+/// // Later, on `SequenceFinished(intro)`, hand control back to the player.
+/// ```
+#[derive(Default)]
+pub struct Sequencer {
+    playing: Vec<Playback>,
+    next_id: u64,
+}
+
+impl Sequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the sequence `id` and plays it from the start once it has loaded.
+    pub fn play(&mut self, assets: &AssetLoader, id: &str) -> SequenceId {
+        let sequence = SequenceId(self.next_id);
+        self.next_id += 1;
+        self.playing.push(Playback {
+            id: sequence,
+            handle: assets.load_async::<Sequence>(id),
+            time: 0.0,
+            cued: f32::NEG_INFINITY,
+            paused: false,
+            speed: 1.0,
+            clips: None,
+        });
+        sequence
+    }
+
+    /// Stops a sequence where it is, without a [`SequenceFinished`] event.
+    pub fn stop(&mut self, id: SequenceId) {
+        self.playing.retain(|playback| playback.id != id);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.playing.clear();
+    }
+
+    pub fn is_playing(&self, id: SequenceId) -> bool {
+        self.playback(id).is_some()
+    }
+
+    /// Paused sequences keep applying their tracks at the current time.
+    pub fn set_paused(&mut self, id: SequenceId, paused: bool) {
+        if let Some(playback) = self.playback_mut(id) {
+            playback.paused = paused;
+        }
+    }
+
+    /// Playback rate, 1 by default.
+    pub fn set_speed(&mut self, id: SequenceId, speed: f32) {
+        if let Some(playback) = self.playback_mut(id) {
+            playback.speed = speed.max(0.0);
+        }
+    }
+
+    /// Jumps to `time` seconds in without sending the cues in between.
+    pub fn seek(&mut self, id: SequenceId, time: f32) {
+        if let Some(playback) = self.playback_mut(id) {
+            playback.time = time.max(0.0);
+            playback.cued = playback.time;
+        }
+    }
+
+    /// Seconds into the sequence.
+    pub fn time(&self, id: SequenceId) -> Option<f32> {
+        self.playback(id).map(|playback| playback.time)
+    }
+
+    /// Advances every loaded sequence by `delta` seconds, applies its tracks to `world`
+    /// and `camera` and sends the cues it passed.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        camera: &mut Camera,
+        assets: &AssetLoader,
+        delta: f32,
+        events: &mut Events,
+    ) {
+        self.playing.retain_mut(|playback| {
+            let Some(sequence) = assets.asset(&playback.handle) else {
+                if assets.load_state(&playback.handle) == LoadState::Failed {
+                    warn!("Sequence {} failed to load", playback.handle.id());
+                    events.send(SequenceFinished(playback.id));
+                    return false;
+                }
+                return true;
+            };
+            let sequence = sequence.read();
+            let clips = playback
+                .clips
+                .take()
+                .unwrap_or_else(|| load_clips(&sequence, assets));
+
+            let duration = sequence.duration();
+            if !playback.paused {
+                playback.time += delta * playback.speed;
+            }
+            let mut finished = false;
+            if playback.time >= duration {
+                if sequence.looping && duration > 0.0 {
+                    send_cues(&sequence, playback, &clips, duration, events);
+                    playback.time %= duration;
+                    playback.cued = f32::NEG_INFINITY;
+                } else {
+                    playback.time = duration;
+                    finished = true;
+                }
+            }
+            send_cues(&sequence, playback, &clips, playback.time, events);
+            apply_tracks(&sequence, playback.time, world, camera);
+            playback.clips = Some(clips);
+            if finished {
+                events.send(SequenceFinished(playback.id));
+            }
+            !finished
+        });
+    }
+
+    fn playback(&self, id: SequenceId) -> Option<&Playback> {
+        self.playing.iter().find(|playback| playback.id == id)
+    }
+
+    fn playback_mut(&mut self, id: SequenceId) -> Option<&mut Playback> {
+        self.playing.iter_mut().find(|playback| playback.id == id)
+    }
+}
+
+/// Starts loading every clip of `sequence`'s audio tracks, in track and key order.
+fn load_clips(sequence: &Sequence, assets: &AssetLoader) -> Vec<Handle<AudioClip>> {
+    sequence
+        .tracks
+        .iter()
+        .filter_map(|track| match track {
+            Track::Audio(keys) => Some(keys),
+            _ => None,
+        })
+        .flatten()
+        .map(|key| assets.load_async::<AudioClip>(&key.clip))
+        .collect()
+}
+
+/// Sends the audio and event cues after `playback.cued` up to `until`.
+fn send_cues(
+    sequence: &Sequence,
+    playback: &mut Playback,
+    clips: &[Handle<AudioClip>],
+    until: f32,
+    events: &mut Events,
+) {
+    let passed = |time: f32| time > playback.cued && time <= until;
+    let mut clip_index = 0;
+    for track in &sequence.tracks {
+        match track {
+            Track::Audio(keys) => {
+                for key in keys {
+                    if passed(key.time) {
+                        events.send(AudioCue {
+                            clip: clips[clip_index].clone(),
+                            volume: key.volume,
+                        });
+                    }
+                    clip_index += 1;
+                }
+            }
+            Track::Event(keys) => {
+                for key in keys.iter().filter(|key| passed(key.time)) {
+                    events.send(SequenceEvent {
+                        sequence: playback.id,
+                        name: key.name.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    playback.cued = until;
+}
+
+fn apply_tracks(sequence: &Sequence, time: f32, world: &mut World, camera: &mut Camera) {
+    for (index, track) in sequence.tracks.iter().enumerate() {
+        match track {
+            Track::Camera(_) => {
+                if let Some(transform) = sequence.transform_at(index, time) {
+                    camera.transform.translation = transform.translation;
+                    camera.transform.rotation = transform.rotation;
+                }
+            }
+            Track::Transform { entity, .. } => {
+                if let Some(transform) = sequence.transform_at(index, time)
+                    && let Some(node) = world.find(entity).and_then(|e| world.node_mut(e))
+                {
+                    node.transform = transform;
+                }
+            }
+            Track::Visibility { entity, keys } => {
+                if let Some(visibility) = VisibilityKey::at(keys, time)
+                    && let Some(entity) = world.find(entity)
+                {
+                    world.insert(entity, visibility);
+                }
+            }
+            Track::Audio(_) | Track::Event(_) => {}
+        }
+    }
+}
+
+impl FromResources for Sequencer {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Sequencer::new())
+    }
+}
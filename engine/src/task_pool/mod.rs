@@ -0,0 +1,116 @@
+use crate::engine::EngineConfig;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use rayon::{Scope, ThreadPool, ThreadPoolBuilder};
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Work-stealing thread pool shared by the engine and game code.
+///
+/// Cloning is cheap; every clone refers to the same worker threads. Code running inside
+/// [`TaskPool::install`] (and inside any task spawned on the pool) uses this pool for
+/// rayon parallel iterators as well.
+#[derive(Clone)]
+pub struct TaskPool {
+    pool: Arc<ThreadPool>,
+}
+
+impl TaskPool {
+    /// Creates a pool with `num_threads` workers. Zero picks one worker per logical core,
+    /// minus one for the main thread.
+    pub fn new(num_threads: usize) -> Result<Self> {
+        let num_threads = if num_threads == 0 {
+            std::thread::available_parallelism()
+                .map(|n| n.get().saturating_sub(1))
+                .unwrap_or(1)
+                .max(1)
+        } else {
+            num_threads
+        };
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|i| format!("elements-worker-{i}"))
+            .panic_handler(|panic| {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                error!("Task panicked: {message}");
+            })
+            .build()
+            .with_context(|| "Failed to build task pool")?;
+        Ok(TaskPool {
+            pool: Arc::new(pool),
+        })
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Fire-and-forget: runs `task` on a worker at some point in the future.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.pool.spawn(task);
+    }
+
+    /// Runs `f` with a scope that can spawn tasks borrowing from the caller's stack.
+    /// Returns once every task spawned in the scope has finished.
+    pub fn scope<'scope, F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Scope<'scope>) -> R + Send,
+        R: Send,
+    {
+        self.pool.scope(f)
+    }
+
+    /// Runs `f` inside the pool, so rayon parallel iterators it uses run on these workers.
+    pub fn install<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R + Send,
+        R: Send,
+    {
+        self.pool.install(f)
+    }
+
+    /// Calls `f` on every element in parallel and waits for all of them.
+    pub fn par_for_each<T, F>(&self, items: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Send + Sync,
+    {
+        self.pool.install(|| items.par_iter_mut().for_each(f));
+    }
+
+    /// Maps every element in parallel, preserving order.
+    pub fn par_map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Send + Sync,
+    {
+        self.pool.install(|| items.par_iter().map(f).collect())
+    }
+}
+
+impl Default for TaskPool {
+    fn default() -> Self {
+        Self::new(0).unwrap_or_else(|err| panic!("Failed to create default task pool: {err}"))
+    }
+}
+
+impl FromResources for TaskPool {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        let worker_threads = resources
+            .try_get::<EngineConfig>()
+            .map(|config| config.worker_threads)
+            .unwrap_or(0);
+        let task_pool = TaskPool::new(worker_threads)?;
+        info!("Task pool running {} workers", task_pool.thread_count());
+        Ok(task_pool)
+    }
+}
@@ -0,0 +1,320 @@
+use crate::asset_loader::{AssetLoader, AssetStats};
+use crate::engine::EngineConfig;
+use crate::frame_stats::FrameStats;
+use crate::logger::LogBuffer;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+mod prometheus;
+
+use prometheus::MetricsServer;
+
+/// How often the Prometheus endpoint's text is refreshed. Scrapers poll far less often.
+const PROMETHEUS_REFRESH: Duration = Duration::from_millis(250);
+
+/// Where [`Telemetry`] publishes metrics. Off unless set in
+/// [`EngineConfig::metrics`](crate::engine::EngineConfig::metrics).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricsExport {
+    /// Serves the latest metrics as Prometheus text on `http://<addr>/metrics`.
+    Prometheus { addr: SocketAddr },
+    /// Appends one JSON object per line to `path` every `interval`.
+    JsonFile { path: PathBuf, interval: Duration },
+}
+
+/// One sample of the engine's health. Times are in milliseconds and sizes in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Metrics {
+    pub frame: u64,
+    pub uptime_secs: f64,
+    pub fps: f64,
+    pub one_percent_low_fps: f64,
+    pub frame_time_ms: f64,
+    pub update_time_ms: f64,
+    pub render_time_ms: f64,
+    pub gpu_time_ms: Option<f64>,
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub culled: u32,
+    pub gpu_memory_used: u64,
+    pub gpu_memory_total: u64,
+    pub assets_loaded: usize,
+    pub assets_loading: usize,
+    pub assets_failed: usize,
+    pub asset_bytes: u64,
+    /// Error and warning log events since startup.
+    pub log_errors: u64,
+    pub log_warnings: u64,
+}
+
+impl Metrics {
+    pub fn collect(
+        uptime: Duration,
+        stats: &FrameStats,
+        assets: &AssetStats,
+        logs: &LogBuffer,
+    ) -> Self {
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        let (log_errors, log_warnings) = logs.problem_counts();
+        Metrics {
+            frame: stats.frame_count(),
+            uptime_secs: uptime.as_secs_f64(),
+            fps: stats.fps(),
+            one_percent_low_fps: stats.one_percent_low_fps(),
+            frame_time_ms: stats.frame_time_ms(),
+            update_time_ms: ms(stats.update_time()),
+            render_time_ms: ms(stats.render_time()),
+            gpu_time_ms: stats.gpu_time().map(ms),
+            draw_calls: stats.draw_calls(),
+            triangles: stats.triangles(),
+            culled: stats.culled(),
+            gpu_memory_used: stats.gpu_memory_used(),
+            gpu_memory_total: stats.gpu_memory_total(),
+            assets_loaded: assets.loaded,
+            assets_loading: assets.loading,
+            assets_failed: assets.failed,
+            asset_bytes: assets.bytes,
+            log_errors,
+            log_warnings,
+        }
+    }
+
+    /// Prometheus text exposition format, with every name prefixed by `elements_`.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = writeln!(text, "# HELP elements_{name} {help}");
+            let _ = writeln!(text, "# TYPE elements_{name} {kind}");
+            let _ = writeln!(text, "elements_{name} {value}");
+        };
+        metric("frames_total", "counter", "Frames run.", self.frame as f64);
+        metric(
+            "uptime_seconds",
+            "gauge",
+            "Seconds since startup.",
+            self.uptime_secs,
+        );
+        metric("fps", "gauge", "Average frames per second.", self.fps);
+        metric(
+            "fps_one_percent_low",
+            "gauge",
+            "Frames per second of the slowest 1% of recent frames.",
+            self.one_percent_low_fps,
+        );
+        metric(
+            "frame_time_ms",
+            "gauge",
+            "Last frame time.",
+            self.frame_time_ms,
+        );
+        metric(
+            "update_time_ms",
+            "gauge",
+            "CPU time of the last update.",
+            self.update_time_ms,
+        );
+        metric(
+            "render_time_ms",
+            "gauge",
+            "CPU time of the last render.",
+            self.render_time_ms,
+        );
+        if let Some(gpu_time) = self.gpu_time_ms {
+            metric(
+                "gpu_time_ms",
+                "gauge",
+                "GPU time of the scene pass.",
+                gpu_time,
+            );
+        }
+        metric(
+            "draw_calls",
+            "gauge",
+            "Draw calls in the last frame.",
+            self.draw_calls as f64,
+        );
+        metric(
+            "triangles",
+            "gauge",
+            "Triangles in the last frame.",
+            self.triangles as f64,
+        );
+        metric(
+            "culled",
+            "gauge",
+            "Instances culled in the last frame.",
+            self.culled as f64,
+        );
+        metric(
+            "gpu_memory_used_bytes",
+            "gauge",
+            "GPU memory held by the renderer.",
+            self.gpu_memory_used as f64,
+        );
+        metric(
+            "gpu_memory_total_bytes",
+            "gauge",
+            "Size of the device-local heaps.",
+            self.gpu_memory_total as f64,
+        );
+        metric(
+            "assets_loaded",
+            "gauge",
+            "Loaded assets.",
+            self.assets_loaded as f64,
+        );
+        metric(
+            "assets_loading",
+            "gauge",
+            "Assets still loading.",
+            self.assets_loading as f64,
+        );
+        metric(
+            "assets_failed",
+            "gauge",
+            "Assets that failed to load.",
+            self.assets_failed as f64,
+        );
+        metric(
+            "asset_bytes",
+            "gauge",
+            "Bytes read for loaded assets.",
+            self.asset_bytes as f64,
+        );
+        metric(
+            "log_errors_total",
+            "counter",
+            "Error log events.",
+            self.log_errors as f64,
+        );
+        metric(
+            "log_warnings_total",
+            "counter",
+            "Warning log events.",
+            self.log_warnings as f64,
+        );
+        text
+    }
+}
+
+enum Exporter {
+    Prometheus(MetricsServer),
+    JsonFile(File),
+}
+
+/// Publishes [`Metrics`] from [`FrameStats`], the [`AssetLoader`] and the log, for soak
+/// tests and dedicated servers. The engine samples it after every update; it does
+/// nothing unless an export was configured.
+pub struct Telemetry {
+    exporter: Option<Exporter>,
+    interval: Duration,
+    last_sample: Option<Instant>,
+    started: Instant,
+}
+
+impl Telemetry {
+    /// A telemetry resource that exports nowhere.
+    pub fn new() -> Self {
+        Telemetry {
+            exporter: None,
+            interval: Duration::ZERO,
+            last_sample: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Starts exporting to `export`: binds the server or opens the file for appending.
+    pub fn with_export(export: &MetricsExport) -> Result<Self> {
+        let (exporter, interval) = match export {
+            MetricsExport::Prometheus { addr } => {
+                let server = MetricsServer::bind(*addr)?;
+                info!("Serving metrics on http://{}/metrics", server.local_addr());
+                (Exporter::Prometheus(server), PROMETHEUS_REFRESH)
+            }
+            MetricsExport::JsonFile { path, interval } => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                info!("Writing metrics to {}", path.display());
+                (Exporter::JsonFile(file), *interval)
+            }
+        };
+        Ok(Telemetry {
+            exporter: Some(exporter),
+            interval,
+            last_sample: None,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn is_exporting(&self) -> bool {
+        self.exporter.is_some()
+    }
+
+    /// Address the Prometheus endpoint listens on, e.g. after binding port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.exporter {
+            Some(Exporter::Prometheus(server)) => Some(server.local_addr()),
+            _ => None,
+        }
+    }
+
+    /// Samples the stats and publishes the metrics if a sample is due.
+    pub fn update(&mut self, stats: &FrameStats, assets: &AssetLoader, logs: &LogBuffer) {
+        let due = self
+            .last_sample
+            .is_none_or(|last| last.elapsed() >= self.interval);
+        if self.exporter.is_none() || !due {
+            return;
+        }
+        self.last_sample = Some(Instant::now());
+        let metrics = Metrics::collect(self.started.elapsed(), stats, &assets.stats(), logs);
+        match &mut self.exporter {
+            Some(Exporter::Prometheus(server)) => server.publish(metrics.to_prometheus()),
+            Some(Exporter::JsonFile(file)) => {
+                let written = serde_json::to_string(&metrics)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|line| Ok(writeln!(file, "{line}")?));
+                if let Err(e) = written {
+                    warn!("Failed to write metrics, stopping export: {e:?}");
+                    self.exporter = None;
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromResources for Telemetry {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        resources.init::<FrameStats>()?;
+        resources.init::<AssetLoader>()?;
+        let Some(export) = resources
+            .try_get::<EngineConfig>()
+            .and_then(|config| config.metrics.clone())
+        else {
+            return Ok(Telemetry::new());
+        };
+        // Metrics are a diagnostic; a taken port shouldn't stop the game.
+        Ok(Telemetry::with_export(&export).unwrap_or_else(|e| {
+            warn!("Metrics export disabled: {e:?}");
+            Telemetry::new()
+        }))
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+use tracing::debug;
+
+/// A minimal HTTP server answering every request with the last published metrics text.
+/// One request per connection, handled on a background thread.
+pub struct MetricsServer {
+    body: Arc<Mutex<String>>,
+    stop: Arc<AtomicBool>,
+    local_addr: SocketAddr,
+}
+
+impl MetricsServer {
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("Failed to bind metrics to {addr}"))?;
+        let local_addr = listener.local_addr()?;
+        let body = Arc::new(Mutex::new(String::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (served, stopped) = (body.clone(), stop.clone());
+        thread::Builder::new()
+            .name("metrics".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let body = served
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .clone();
+                    if let Err(e) = respond(stream, &body) {
+                        debug!("Metrics request failed: {e}");
+                    }
+                }
+            })
+            .context("Failed to spawn metrics thread")?;
+        Ok(MetricsServer {
+            body,
+            stop,
+            local_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Replaces what the next requests are answered with.
+    pub fn publish(&self, body: String) {
+        *self.body.lock().unwrap_or_else(PoisonError::into_inner) = body;
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wakes the blocking accept so the thread sees the flag.
+        let mut addr = self.local_addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&addr, Duration::from_millis(100));
+    }
+}
+
+fn respond(mut stream: TcpStream, body: &str) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    // Skip the request head; every path gets the metrics.
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
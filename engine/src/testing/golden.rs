@@ -0,0 +1,240 @@
+use anyhow::{Context, Result, bail};
+use image::{Rgba, RgbaImage};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Set to write the rendered images as the new goldens instead of comparing.
+pub const UPDATE_GOLDEN_VAR: &str = "ELEMENTS_UPDATE_GOLDEN";
+
+/// Largest possible [`color_delta`], between black and white.
+const MAX_DELTA: f32 = 35215.0;
+
+/// How two images differ.
+#[derive(Debug, Clone)]
+pub struct ImageComparison {
+    /// Pixels whose perceptual difference exceeds the threshold.
+    pub differing_pixels: u64,
+    pub total_pixels: u64,
+    /// Largest perceptual difference of any pixel, from 0 to 1.
+    pub max_difference: f32,
+    /// The actual image with differing pixels in red, or `None` if the sizes differ.
+    pub diff: Option<RgbaImage>,
+}
+
+impl ImageComparison {
+    pub fn differing_ratio(&self) -> f64 {
+        if self.total_pixels == 0 {
+            return 0.0;
+        }
+        self.differing_pixels as f64 / self.total_pixels as f64
+    }
+
+    /// Whether at most `max_ratio` of the pixels differ and the sizes match.
+    pub fn passes(&self, max_ratio: f64) -> bool {
+        self.diff.is_some() && self.differing_ratio() <= max_ratio
+    }
+}
+
+/// Brightness and chroma difference in YIQ space, weighted by how much each axis is
+/// noticed, so dithering and small hue shifts count less than edges or missing geometry.
+fn color_delta(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let yiq = |Rgba([r, g, b, _]): Rgba<u8>| {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        (
+            r * 0.299 + g * 0.587 + b * 0.114,
+            r * 0.596 - g * 0.274 - b * 0.322,
+            r * 0.211 - g * 0.523 + b * 0.312,
+        )
+    };
+    let (y1, i1, q1) = yiq(a);
+    let (y2, i2, q2) = yiq(b);
+    let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+/// Compares `actual` against `expected` pixel by pixel. A pixel differs when its
+/// perceptual difference is above `threshold`, from 0 (exact) to 1 (anything goes);
+/// around 0.1 tolerates driver-to-driver rasterization noise.
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, threshold: f32) -> ImageComparison {
+    let total_pixels = actual.width() as u64 * actual.height() as u64;
+    if actual.dimensions() != expected.dimensions() {
+        return ImageComparison {
+            differing_pixels: total_pixels,
+            total_pixels,
+            max_difference: 1.0,
+            diff: None,
+        };
+    }
+    let max_delta = MAX_DELTA * threshold * threshold;
+    let mut diff = actual.clone();
+    let mut differing_pixels = 0;
+    let mut max_difference = 0.0f32;
+    for ((a, b), out) in actual
+        .pixels()
+        .zip(expected.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let delta = color_delta(*a, *b);
+        max_difference = max_difference.max((delta / MAX_DELTA).sqrt());
+        if delta > max_delta {
+            differing_pixels += 1;
+            *out = Rgba([255, 0, 0, 255]);
+        } else {
+            // Fade matching pixels so the differing ones stand out.
+            let Rgba([r, g, b, _]) = *out;
+            let gray = ((r as u32 + g as u32 + b as u32) / 3 / 4 + 191) as u8;
+            *out = Rgba([gray, gray, gray, 255]);
+        }
+    }
+    ImageComparison {
+        differing_pixels,
+        total_pixels,
+        max_difference,
+        diff: Some(diff),
+    }
+}
+
+/// A reference image on disk, with the actual and diff images written next to it when
+/// a comparison fails.
+pub struct Golden {
+    path: PathBuf,
+}
+
+impl Golden {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Golden { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.path.with_file_name(format!("{stem}.{suffix}.png"))
+    }
+
+    /// Compares `actual` against the golden. With [`UPDATE_GOLDEN_VAR`] set, writes
+    /// `actual` as the new golden instead and returns `None`. A missing golden is an
+    /// error otherwise, so a test can't pass by writing its own reference.
+    pub fn check(&self, actual: &RgbaImage, threshold: f32) -> Result<Option<ImageComparison>> {
+        if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            actual
+                .save(&self.path)
+                .with_context(|| format!("Failed to write {}", self.path.display()))?;
+            info!("Wrote golden image {}", self.path.display());
+            return Ok(None);
+        }
+        if !self.path.exists() {
+            bail!(
+                "Golden image {} is missing; set {UPDATE_GOLDEN_VAR}=1 to write it",
+                self.path.display()
+            );
+        }
+        let expected = image::open(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?
+            .into_rgba8();
+        Ok(Some(compare_images(actual, &expected, threshold)))
+    }
+
+    /// Writes `actual` and the comparison's diff next to the golden, for inspection.
+    pub fn write_failure(&self, actual: &RgbaImage, comparison: &ImageComparison) -> Result<()> {
+        let actual_path = self.sibling("actual");
+        actual
+            .save(&actual_path)
+            .with_context(|| format!("Failed to write {}", actual_path.display()))?;
+        if let Some(diff) = comparison.diff.as_ref() {
+            let diff_path = self.sibling("diff");
+            diff.save(&diff_path)
+                .with_context(|| format!("Failed to write {}", diff_path.display()))?;
+        }
+        warn!(
+            "{} differs: {} of {} pixels, see {}",
+            self.path.display(),
+            comparison.differing_pixels,
+            comparison.total_pixels,
+            actual_path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    #[test]
+    fn color_delta_is_zero_for_equal_colors_and_near_max_for_black_and_white() {
+        assert_eq!(color_delta(WHITE, WHITE), 0.0);
+        assert_eq!(
+            color_delta(Rgba([12, 200, 99, 255]), Rgba([12, 200, 99, 0])),
+            0.0
+        );
+        let black_white = color_delta(BLACK, WHITE);
+        assert!(black_white > 0.9 * MAX_DELTA && black_white <= MAX_DELTA);
+        assert_eq!(black_white, color_delta(WHITE, BLACK));
+    }
+
+    #[test]
+    fn color_delta_weighs_brightness_over_chroma() {
+        let brighter = color_delta(Rgba([100, 100, 100, 255]), Rgba([110, 110, 110, 255]));
+        let bluer = color_delta(Rgba([100, 100, 100, 255]), Rgba([100, 100, 110, 255]));
+        assert!(brighter > bluer);
+    }
+
+    #[test]
+    fn compare_images_passes_identical_images() {
+        let image = RgbaImage::from_pixel(4, 3, Rgba([40, 80, 120, 255]));
+        let comparison = compare_images(&image, &image, 0.0);
+        assert_eq!(comparison.differing_pixels, 0);
+        assert_eq!(comparison.total_pixels, 12);
+        assert_eq!(comparison.max_difference, 0.0);
+        assert!(comparison.passes(0.0));
+    }
+
+    #[test]
+    fn compare_images_marks_differing_pixels_red() {
+        let expected = RgbaImage::from_pixel(4, 4, BLACK);
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 2, WHITE);
+        let comparison = compare_images(&actual, &expected, 0.1);
+        assert_eq!(comparison.differing_pixels, 1);
+        assert!(comparison.max_difference > 0.9);
+        let diff = comparison.diff.as_ref().unwrap();
+        assert_eq!(*diff.get_pixel(1, 2), Rgba([255, 0, 0, 255]));
+        assert_ne!(*diff.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert!(!comparison.passes(0.0));
+        assert!(comparison.passes(1.0 / 16.0));
+    }
+
+    #[test]
+    fn compare_images_tolerates_differences_below_the_threshold() {
+        let expected = RgbaImage::from_pixel(2, 2, Rgba([100, 100, 100, 255]));
+        let actual = RgbaImage::from_pixel(2, 2, Rgba([102, 101, 100, 255]));
+        assert_eq!(compare_images(&actual, &expected, 0.1).differing_pixels, 0);
+        assert_eq!(compare_images(&actual, &expected, 0.0).differing_pixels, 4);
+    }
+
+    #[test]
+    fn compare_images_fails_on_size_mismatch() {
+        let comparison = compare_images(
+            &RgbaImage::from_pixel(4, 4, BLACK),
+            &RgbaImage::from_pixel(4, 3, BLACK),
+            1.0,
+        );
+        assert!(comparison.diff.is_none());
+        assert_eq!(comparison.differing_pixels, 16);
+        assert!(!comparison.passes(1.0));
+    }
+}
@@ -0,0 +1,161 @@
+//! Screenshot regression tests: render a scene headless for a fixed number of frames and
+//! compare the result against a golden image. Needs a Vulkan device but no display; check
+//! [`vulkan_available`] to skip where there is none. Run them from a test target with
+//! `harness = false`, as `engine/tests/screenshot.rs` does:
+//!
+//! ```ignore
+//! ScreenshotTest::new("car", "super_car.scene").assert_matches()?;
+//! ```
+//!
+//! Set [`UPDATE_GOLDEN_VAR`] to write the current output as the new goldens; without it a
+//! missing golden fails the test.
+
+use crate::capture::{FrameCapture, to_rgba};
+use crate::engine::{Engine, EngineConfig};
+use crate::power::PowerSettings;
+use anyhow::{Result, bail};
+use image::RgbaImage;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::info;
+use vulkano::VulkanLibrary;
+use vulkano::instance::{Instance, InstanceCreateFlags, InstanceCreateInfo};
+
+mod golden;
+
+pub use golden::{Golden, ImageComparison, UPDATE_GOLDEN_VAR, compare_images};
+
+/// How long startup assets may take to load.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+/// Frames to wait for a requested screenshot to be read back.
+const READBACK_FRAMES: u32 = 8;
+
+/// Whether there is a Vulkan device to render on.
+pub fn vulkan_available() -> bool {
+    let Ok(library) = VulkanLibrary::new() else {
+        return false;
+    };
+    Instance::new(
+        library,
+        InstanceCreateInfo {
+            flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+            ..Default::default()
+        },
+    )
+    .ok()
+    .and_then(|instance| instance.enumerate_physical_devices().ok())
+    .is_some_and(|mut devices| devices.next().is_some())
+}
+
+/// One scene rendered and compared against `golden_dir/<name>.png`.
+#[derive(Debug, Clone)]
+pub struct ScreenshotTest {
+    pub name: String,
+    /// Engine setup. Defaults to a deterministic 60 Hz tick without power throttling;
+    /// `window.size` is the size of the image.
+    pub config: EngineConfig,
+    /// Frames simulated after the startup assets load, before the screenshot.
+    pub frames: u32,
+    /// Per-pixel perceptual threshold, see [`compare_images`].
+    pub threshold: f32,
+    /// Share of pixels allowed to differ.
+    pub max_differing_ratio: f64,
+    pub golden_dir: PathBuf,
+}
+
+impl ScreenshotTest {
+    /// Renders `scene`, an asset id, at 640x360.
+    pub fn new(name: impl Into<String>, scene: impl Into<String>) -> Self {
+        let mut config = EngineConfig::deterministic(Duration::from_secs_f64(1.0 / 60.0), 0);
+        config.scene = scene.into();
+        config.window.size = Some([640, 360]);
+        config.power = PowerSettings::disabled();
+        ScreenshotTest {
+            name: name.into(),
+            config,
+            frames: 10,
+            threshold: 0.1,
+            max_differing_ratio: 0.001,
+            golden_dir: PathBuf::from("tests/golden"),
+        }
+    }
+
+    pub fn golden(&self) -> Golden {
+        Golden::new(self.golden_dir.join(format!("{}.png", self.name)))
+    }
+
+    /// Renders the scene in a fresh headless engine and returns the last frame.
+    pub fn render(&self) -> Result<RgbaImage> {
+        let mut engine = Engine::with_config(self.config.clone());
+        engine.set_headless();
+        engine.run();
+        let result = self.render_with(&mut engine);
+        engine.shutdown();
+        result
+    }
+
+    fn render_with(&self, engine: &mut Engine) -> Result<RgbaImage> {
+        let step = |engine: &mut Engine| {
+            engine.on_update();
+            engine.render();
+        };
+        let deadline = Instant::now() + LOAD_TIMEOUT;
+        while engine.is_loading() {
+            if Instant::now() > deadline {
+                bail!("{}: startup assets didn't load in time", self.name);
+            }
+            step(engine);
+        }
+        for _ in 0..self.frames {
+            step(engine);
+        }
+        engine
+            .resources_mut()
+            .get_mut::<FrameCapture>()
+            .request_screenshot();
+        for _ in 0..READBACK_FRAMES {
+            step(engine);
+            let frame = engine
+                .resources_mut()
+                .get_mut::<FrameCapture>()
+                .take_screenshot();
+            if let Some(frame) = frame {
+                return to_rgba(frame);
+            }
+        }
+        bail!("{}: no frame was read back", self.name)
+    }
+
+    /// Renders the scene and compares it against its golden. Returns `None` if the
+    /// golden was written instead, see [`UPDATE_GOLDEN_VAR`].
+    pub fn check(&self) -> Result<Option<ImageComparison>> {
+        let actual = self.render()?;
+        let golden = self.golden();
+        let comparison = golden.check(&actual, self.threshold)?;
+        if let Some(comparison) = comparison.as_ref()
+            && !comparison.passes(self.max_differing_ratio)
+        {
+            golden.write_failure(&actual, comparison)?;
+        }
+        Ok(comparison)
+    }
+
+    /// Like [`ScreenshotTest::check`], but fails if the images differ too much.
+    pub fn assert_matches(&self) -> Result<()> {
+        let Some(comparison) = self.check()? else {
+            return Ok(());
+        };
+        if !comparison.passes(self.max_differing_ratio) {
+            bail!(
+                "{}: {} of {} pixels differ from {} (max difference {:.3})",
+                self.name,
+                comparison.differing_pixels,
+                comparison.total_pixels,
+                self.golden().path().display(),
+                comparison.max_difference
+            );
+        }
+        info!("{}: matches its golden image", self.name);
+        Ok(())
+    }
+}
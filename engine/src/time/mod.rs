@@ -0,0 +1,116 @@
+use crate::engine::EngineConfig;
+use crate::resource_manager::{FromResources, ResourceManager};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// How the engine advances time from one frame to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TickMode {
+    /// Delta is the measured wall-clock time since the previous frame.
+    #[default]
+    Variable,
+    /// Every frame advances by exactly `delta`, regardless of how long it actually took.
+    /// Combined with a seeded [`Rng`](crate::rng::Rng) this makes simulation
+    /// deterministic, which is required for lockstep networking and replays.
+    Fixed { delta: Duration },
+}
+
+impl TickMode {
+    /// Fixed tick mode running at `hz` updates per simulated second.
+    pub fn fixed_hz(hz: u32) -> Self {
+        TickMode::Fixed {
+            delta: Duration::from_secs_f64(1.0 / hz.max(1) as f64),
+        }
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        matches!(self, TickMode::Fixed { .. })
+    }
+}
+
+/// Simulation clock resource. Advanced exactly once at the start of every engine update.
+pub struct Time {
+    mode: TickMode,
+    delta: Duration,
+    elapsed: Duration,
+    frame: u64,
+    last_tick: Option<Instant>,
+}
+
+impl Time {
+    pub fn new(mode: TickMode) -> Self {
+        Time {
+            mode,
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            frame: 0,
+            last_tick: None,
+        }
+    }
+
+    /// Advances the clock by one frame according to the current [`TickMode`].
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.delta = match self.mode {
+            TickMode::Variable => self
+                .last_tick
+                .map(|last| now.duration_since(last))
+                .unwrap_or(Duration::ZERO),
+            TickMode::Fixed { delta } => delta,
+        };
+        self.last_tick = Some(now);
+        self.elapsed += self.delta;
+        self.frame += 1;
+    }
+
+    pub fn mode(&self) -> TickMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: TickMode) {
+        self.mode = mode;
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.mode.is_deterministic()
+    }
+
+    /// Time the current frame advanced the simulation by.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_secs(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Total simulated time since the engine started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn elapsed_secs(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    /// Number of frames ticked so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new(TickMode::default())
+    }
+}
+
+impl FromResources for Time {
+    fn from_resources(resources: &mut ResourceManager) -> Result<Self> {
+        let mode = resources
+            .try_get::<EngineConfig>()
+            .map(|config| config.tick_mode)
+            .unwrap_or_default();
+        Ok(Time::new(mode))
+    }
+}
@@ -0,0 +1,314 @@
+use crate::core::transform::Transform;
+use crate::events::Events;
+use crate::resource_manager::{FromResources, ResourceManager};
+use crate::scene::{Entity, World};
+use anyhow::Result;
+use glam::{Quat, Vec2, Vec3, Vec4};
+
+mod ease;
+
+pub use ease::Ease;
+
+/// A value a [`Tween`] can animate.
+pub trait Tweenable: Clone {
+    /// Value `t` of the way from `self` to `to`. `t` may leave `[0, 1]` for easings that
+    /// overshoot.
+    fn interpolate(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Tweenable for Vec2 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+impl Tweenable for Vec3 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+/// Linear RGBA colors, as used by [`Billboard`](crate::billboard::Billboard).
+impl Tweenable for Vec4 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+impl Tweenable for Quat {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.slerp(*to, t)
+    }
+}
+
+impl Tweenable for Transform {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(to, t)
+    }
+}
+
+/// What a [`Tween`] does after its last step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Repeat {
+    /// Holds the last value.
+    #[default]
+    Once,
+    /// Jumps back to the start value and plays again, forever.
+    Loop,
+    /// Plays backwards to the start, then forwards again, forever.
+    PingPong,
+}
+
+#[derive(Debug, Clone)]
+struct Step<T> {
+    to: T,
+    /// Seconds.
+    duration: f32,
+    ease: Ease,
+}
+
+/// A value moving through a sequence of eased steps, e.g. fading a panel in, holding it,
+/// then fading it out:
+///
+/// ```ignore
+/// let mut alpha = Tween::new(0.0)
+///     .to(1.0, 0.25, Ease::QuadOut)
+///     .wait(2.0)
+///     .to(0.0, 0.5, Ease::QuadIn);
+/// let value = alpha.advance(time.delta_secs());
+/// ```
+///
+/// Keep one in UI state and advance it directly, or hand it to [`Tweens`] to have it
+/// applied to the world every tick.
+#[derive(Debug, Clone)]
+pub struct Tween<T> {
+    start: T,
+    steps: Vec<Step<T>>,
+    repeat: Repeat,
+    time: f32,
+}
+
+impl<T: Tweenable> Tween<T> {
+    /// A tween resting at `start` until steps are added.
+    pub fn new(start: T) -> Self {
+        Tween {
+            start,
+            steps: Vec::new(),
+            repeat: Repeat::Once,
+            time: 0.0,
+        }
+    }
+
+    /// Appends a step moving to `value` over `duration` seconds.
+    pub fn to(mut self, value: T, duration: f32, ease: Ease) -> Self {
+        self.steps.push(Step {
+            to: value,
+            duration: duration.max(0.0),
+            ease,
+        });
+        self
+    }
+
+    /// Appends a step holding the current end value for `duration` seconds.
+    pub fn wait(self, duration: f32) -> Self {
+        let value = self.end().clone();
+        self.to(value, duration, Ease::Linear)
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Seconds for one pass through every step.
+    pub fn duration(&self) -> f32 {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+
+    /// Seconds played so far, including earlier loops.
+    pub fn elapsed(&self) -> f32 {
+        self.time
+    }
+
+    /// Only tweens that play [`Repeat::Once`] ever finish.
+    pub fn is_finished(&self) -> bool {
+        self.repeat == Repeat::Once && self.time >= self.duration()
+    }
+
+    /// Moves on by `delta` seconds and returns the new value.
+    pub fn advance(&mut self, delta: f32) -> T {
+        self.time += delta.max(0.0);
+        if self.repeat == Repeat::Once {
+            self.time = self.time.min(self.duration());
+        }
+        self.value()
+    }
+
+    /// Starts over from the start value.
+    pub fn reset(&mut self) {
+        self.time = 0.0;
+    }
+
+    /// Value at the current time.
+    pub fn value(&self) -> T {
+        self.sample(self.time)
+    }
+
+    /// Value `time` seconds in, following the repeat mode.
+    pub fn sample(&self, time: f32) -> T {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return self.end().clone();
+        }
+        let time = time.max(0.0);
+        let mut local = match self.repeat {
+            Repeat::Once => time.min(duration),
+            Repeat::Loop => time % duration,
+            Repeat::PingPong => {
+                let time = time % (2.0 * duration);
+                if time > duration {
+                    2.0 * duration - time
+                } else {
+                    time
+                }
+            }
+        };
+        let mut from = &self.start;
+        for step in &self.steps {
+            if local < step.duration {
+                return from.interpolate(&step.to, step.ease.apply(local / step.duration));
+            }
+            local -= step.duration;
+            from = &step.to;
+        }
+        from.clone()
+    }
+
+    fn end(&self) -> &T {
+        self.steps.last().map_or(&self.start, |step| &step.to)
+    }
+}
+
+/// Id of a tween added to [`Tweens`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TweenId(u64);
+
+/// Sent on the event bus when a tween added to [`Tweens`] finishes, to chain transitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TweenFinished(pub TweenId);
+
+/// A tween with somewhere to put its value.
+trait Driver {
+    /// Advances and applies the tween. Returns `false` once it's done or its target is gone.
+    fn update(&mut self, world: &mut World, delta: f32) -> bool;
+}
+
+struct Apply<T, F> {
+    tween: Tween<T>,
+    apply: F,
+}
+
+impl<T: Tweenable, F: FnMut(&mut World, T)> Driver for Apply<T, F> {
+    fn update(&mut self, world: &mut World, delta: f32) -> bool {
+        let value = self.tween.advance(delta);
+        (self.apply)(world, value);
+        !self.tween.is_finished()
+    }
+}
+
+struct EntityTransform {
+    entity: Entity,
+    tween: Tween<Transform>,
+}
+
+impl Driver for EntityTransform {
+    fn update(&mut self, world: &mut World, delta: f32) -> bool {
+        let Some(node) = world.node_mut(self.entity) else {
+            return false;
+        };
+        node.transform = self.tween.advance(delta);
+        !self.tween.is_finished()
+    }
+}
+
+/// Tweens the engine advances with [`Time`](crate::time::Time) every tick, right after
+/// animations, writing their values into the [`World`]:
+///
+/// ```ignore
+/// let tweens = resources.get_mut::<Tweens>();
+/// tweens.add_transform(door, Tween::new(closed).to(open, 1.5, Ease::CubicInOut));
+/// tweens.add(Tween::new(1.0).to(0.0, 0.3, Ease::Linear), move |world, alpha| {
+///     if let Some(tag) = world.get_mut::<Billboard>(enemy) {
+///         tag.color.w = alpha;
+///     }
+/// });
+/// ```
+#[derive(Default)]
+pub struct Tweens {
+    active: Vec<(TweenId, Box<dyn Driver>)>,
+    next_id: u64,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plays `tween`, passing every new value to `apply`, e.g. to set a component field.
+    pub fn add<T: Tweenable + 'static>(
+        &mut self,
+        tween: Tween<T>,
+        apply: impl FnMut(&mut World, T) + 'static,
+    ) -> TweenId {
+        self.push(Box::new(Apply { tween, apply }))
+    }
+
+    /// Plays `tween` on the local transform of `entity`. Stops if the entity despawns.
+    pub fn add_transform(&mut self, entity: Entity, tween: Tween<Transform>) -> TweenId {
+        self.push(Box::new(EntityTransform { entity, tween }))
+    }
+
+    /// Stops a tween where it is, without a [`TweenFinished`] event.
+    pub fn cancel(&mut self, id: TweenId) {
+        self.active.retain(|(active, _)| *active != id);
+    }
+
+    pub fn is_active(&self, id: TweenId) -> bool {
+        self.active.iter().any(|(active, _)| *active == id)
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    /// Advances every tween by `delta` seconds and applies its value. Sends a
+    /// [`TweenFinished`] for each one that completed or whose entity despawned.
+    pub fn update(&mut self, world: &mut World, delta: f32, events: &mut Events) {
+        self.active.retain_mut(|(id, driver)| {
+            let running = driver.update(world, delta);
+            if !running {
+                events.send(TweenFinished(*id));
+            }
+            running
+        });
+    }
+
+    fn push(&mut self, driver: Box<dyn Driver>) -> TweenId {
+        let id = TweenId(self.next_id);
+        self.next_id += 1;
+        self.active.push((id, driver));
+        id
+    }
+}
+
+impl FromResources for Tweens {
+    fn from_resources(_resources: &mut ResourceManager) -> Result<Self> {
+        Ok(Tweens::new())
+    }
+}
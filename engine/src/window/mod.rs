@@ -1,19 +1,90 @@
 use std::sync::Arc;
-use winit::window::Window as WinitWindow;
+use winit::dpi::PhysicalSize;
+use winit::window::{Window as WinitWindow, WindowAttributes, WindowLevel};
+
+/// How the main window is created. Set through
+/// [`EngineConfig::window`](crate::engine::EngineConfig).
+#[derive(Debug, Clone)]
+pub struct WindowDescriptor {
+    pub title: String,
+    /// Inner size in physical pixels. `None` lets the platform pick.
+    pub size: Option<[u32; 2]>,
+    pub resizable: bool,
+    /// Show the title bar and borders.
+    pub decorations: bool,
+    /// Let the desktop show through wherever the frame's alpha is below one. The
+    /// swapchain picks a composite alpha mode to match, if the surface supports one.
+    pub transparent: bool,
+    /// Keep the window above other windows.
+    pub always_on_top: bool,
+    /// Show the window once created. Hidden windows still render.
+    pub visible: bool,
+}
+
+impl Default for WindowDescriptor {
+    fn default() -> Self {
+        WindowDescriptor {
+            title: "Elements".to_owned(),
+            size: None,
+            resizable: true,
+            decorations: true,
+            transparent: false,
+            always_on_top: false,
+            visible: true,
+        }
+    }
+}
+
+impl WindowDescriptor {
+    /// A borderless, transparent window that stays on top, for overlay-style tools.
+    pub fn overlay() -> Self {
+        WindowDescriptor {
+            decorations: false,
+            transparent: true,
+            always_on_top: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn attributes(&self) -> WindowAttributes {
+        let mut attributes = WinitWindow::default_attributes()
+            .with_title(self.title.clone())
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_transparent(self.transparent)
+            .with_window_level(window_level(self.always_on_top))
+            .with_visible(self.visible);
+        if let Some([width, height]) = self.size {
+            attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+        attributes
+    }
+}
+
+fn window_level(always_on_top: bool) -> WindowLevel {
+    if always_on_top {
+        WindowLevel::AlwaysOnTop
+    } else {
+        WindowLevel::Normal
+    }
+}
 
 pub struct Window {
     winit_window: Arc<WinitWindow>,
     is_focused: bool,
+    /// Created with [`WindowDescriptor::transparent`].
+    transparent: bool,
     width: u32,
     height: u32,
 }
 
 impl Window {
-    pub fn new(winit_window: Arc<WinitWindow>) -> Self {
+    pub fn new(winit_window: Arc<WinitWindow>, descriptor: &WindowDescriptor) -> Self {
         let size = winit_window.inner_size();
         Window {
             winit_window,
             is_focused: false,
+            transparent: descriptor.transparent,
             width: size.width,
             height: size.height,
         }
@@ -52,7 +123,26 @@ impl Window {
         (self.width, self.height)
     }
 
+    /// Physical pixels per logical point, e.g. 2 on a Retina display.
+    pub fn scale_factor(&self) -> f64 {
+        self.winit_window.scale_factor()
+    }
+
     pub fn set_title(&self, title: &str) {
         self.winit_window.set_title(title);
     }
+
+    /// Whether the window was created transparent. Fixed for the window's lifetime.
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    pub fn set_always_on_top(&self, always_on_top: bool) {
+        self.winit_window
+            .set_window_level(window_level(always_on_top));
+    }
+
+    pub fn set_decorations(&self, decorations: bool) {
+        self.winit_window.set_decorations(decorations);
+    }
 }
@@ -0,0 +1,24 @@
+//! Renders the scenes in `tests/assets` headless and compares them against the goldens
+//! in `tests/golden`. Skips without a Vulkan device. Goldens are only ever written from a
+//! real render: run with `ELEMENTS_UPDATE_GOLDEN=1` on a device to record a missing one.
+
+use elements_engine::asset_loader::AssetMount;
+use elements_engine::testing::{ScreenshotTest, vulkan_available};
+use std::path::PathBuf;
+
+fn main() -> anyhow::Result<()> {
+    if !vulkan_available() {
+        eprintln!("No Vulkan device; skipping screenshot tests");
+        return Ok(());
+    }
+    // Two unlit disks around the Z axis, a green one in front of a red one, seen from the
+    // default camera. They look the same however far the renderer's demo spin around Z
+    // has turned, so the golden doesn't depend on how many frames loading took. Edge
+    // pixels depend on the rasterizer and MSAA, hence the looser ratio.
+    let mut disks = ScreenshotTest::new("disks", "disks.scene");
+    disks.config.asset_mounts = vec![AssetMount::Directory(PathBuf::from("tests/assets"))];
+    disks.max_differing_ratio = 0.01;
+    disks.assert_matches()?;
+    println!("screenshot tests passed");
+    Ok(())
+}
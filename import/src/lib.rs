@@ -0,0 +1,368 @@
+//! Offline conversion of source assets into the engine's baked formats, so the runtime
+//! skips glTF parsing, PNG decoding, mip generation and tangent computation.
+
+use anyhow::{Context, Result, anyhow};
+use assets_manager::AssetCache;
+use assets_manager::asset::Gltf;
+use elements_engine::asset_loader::baked::{
+    self, AssetManifest, BakedModel, BakedSampler, BakedTexture, BakedTextureRef, BlockFormat,
+    TextureFormat,
+};
+use elements_engine::asset_loader::database::{
+    self, AssetDatabase, AssetGuid, AssetRecord, StableHasher,
+};
+use elements_engine::asset_loader::gltf_model::GltfModel;
+use elements_engine::asset_loader::texture_settings::TextureSettings;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageBuffer, Pixel, RgbaImage};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+/// Directory under the assets root that baked files and the manifest are written to.
+pub const BAKED_DIR: &str = "baked";
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub models: usize,
+    pub textures: usize,
+    pub failed: usize,
+    /// Assets found under a new id since the last import, and redirected.
+    pub moved: usize,
+}
+
+/// Bakes every glTF, PNG and HDR file under `assets_dir` into `assets_dir/baked` and
+/// writes the manifest the runtime [`AssetLoader`](elements_engine::asset_loader::AssetLoader)
+/// reads. Files that fail are logged and counted; the rest are still baked. Also brings
+/// the [`AssetDatabase`] up to date; see [`update_database`].
+pub fn import_dir(assets_dir: &Path) -> Result<ImportReport> {
+    let cache = AssetCache::new(assets_dir)
+        .with_context(|| format!("Failed to open assets at {}", assets_dir.display()))?;
+    let mut manifest = AssetManifest::default();
+    let mut report = ImportReport {
+        moved: update_database(assets_dir)?,
+        ..Default::default()
+    };
+
+    for path in source_files(assets_dir)? {
+        let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+            continue;
+        };
+        let extension = extension.to_ascii_lowercase();
+        let is_model = match extension.as_str() {
+            "gltf" | "glb" => true,
+            "png" | "hdr" => false,
+            _ => continue,
+        };
+        let Some(id) = asset_id(assets_dir, &path) else {
+            warn!("Skipping {}: not a valid asset id", path.display());
+            continue;
+        };
+        let baked_id = format!("{BAKED_DIR}.{id}");
+        let result = if is_model {
+            bake_model(&cache, &id)
+                .and_then(|model| model.encode())
+                .and_then(|bytes| write_baked(assets_dir, &baked_id, baked::MODEL_EXT, &bytes))
+        } else {
+            bake_texture(&path, TextureSettings::for_image(&cache, &id))
+                .and_then(|texture| texture.encode())
+                .and_then(|bytes| write_baked(assets_dir, &baked_id, baked::TEXTURE_EXT, &bytes))
+        };
+        match result {
+            Ok(()) if is_model => {
+                info!("Baked model {id}");
+                manifest.models.insert(id, baked_id);
+                report.models += 1;
+            }
+            Ok(()) => {
+                info!("Baked texture {id}");
+                manifest.textures.insert(id, baked_id);
+                report.textures += 1;
+            }
+            Err(e) => {
+                error!("Failed to import {}: {:?}", path.display(), e);
+                report.failed += 1;
+            }
+        }
+    }
+
+    let manifest = ron::ser::to_string_pretty(&manifest, ron::ser::PrettyConfig::default())?;
+    let manifest_path = assets_dir.join(BAKED_DIR).join("manifest.ron");
+    fs::create_dir_all(assets_dir.join(BAKED_DIR))?;
+    fs::write(&manifest_path, manifest)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    Ok(report)
+}
+
+/// Gives every asset under `assets_dir` a GUID in `assets_dir/asset_db.ron`. An asset
+/// whose id disappeared since the last import and whose files reappeared unchanged under
+/// another id is taken to have moved: it keeps its GUID and the old id redirects to it.
+/// Assets that are gone are dropped along with their redirects. Returns how many moved.
+pub fn update_database(assets_dir: &Path) -> Result<usize> {
+    let path = assets_dir.join(format!("{}.ron", database::DATABASE_ID));
+    let mut database = if path.exists() {
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        ron::from_str::<AssetDatabase>(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?
+    } else {
+        AssetDatabase::default()
+    };
+
+    // Every file of an asset shares its id, e.g. a glTF file and its buffers.
+    let mut files = BTreeMap::<String, Vec<PathBuf>>::new();
+    for path in source_files(assets_dir)? {
+        if let Some(id) = asset_id(assets_dir, &path)
+            && id != database::DATABASE_ID
+        {
+            files.entry(id).or_default().push(path);
+        }
+    }
+    let mut hashes = BTreeMap::new();
+    for (id, paths) in &files {
+        hashes.insert(id.clone(), content_hash(paths)?);
+    }
+
+    let (mut known, mut missing): (BTreeMap<_, _>, BTreeMap<_, _>) =
+        std::mem::take(&mut database.assets)
+            .into_iter()
+            .partition(|(_, record)| hashes.contains_key(&record.id));
+    let known_ids: BTreeSet<String> = known.values().map(|record| record.id.clone()).collect();
+    let mut moved = 0;
+    for (id, &hash) in &hashes {
+        if known_ids.contains(id) {
+            continue;
+        }
+        let previous = missing
+            .iter()
+            .find(|(_, record)| record.hash == hash)
+            .map(|(&guid, _)| guid);
+        let guid = match previous.and_then(|guid| missing.remove_entry(&guid)) {
+            Some((guid, record)) => {
+                info!("Asset {} moved to {id}", record.id);
+                database.redirects.insert(record.id, guid);
+                moved += 1;
+                guid
+            }
+            None => {
+                let mut guid = AssetGuid::from_id(id);
+                while known.contains_key(&guid)
+                    || missing.contains_key(&guid)
+                    || database.redirects.values().any(|&used| used == guid)
+                {
+                    guid = AssetGuid(guid.0.wrapping_add(1));
+                }
+                guid
+            }
+        };
+        known.insert(
+            guid,
+            AssetRecord {
+                id: id.clone(),
+                hash,
+            },
+        );
+    }
+    for record in known.values_mut() {
+        record.hash = hashes[&record.id];
+    }
+    for record in missing.values() {
+        warn!("Asset {} was removed", record.id);
+    }
+    database
+        .redirects
+        .retain(|id, guid| known.contains_key(guid) && !hashes.contains_key(id));
+    database.assets = known;
+
+    let text = ron::ser::to_string_pretty(&database, ron::ser::PrettyConfig::default())?;
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(moved)
+}
+
+/// Hash of the names' extensions and contents of `paths`, which share an asset id.
+fn content_hash(paths: &[PathBuf]) -> Result<u64> {
+    let mut hasher = StableHasher::new();
+    for path in paths {
+        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+            hasher.write(extension.as_bytes());
+        }
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        hasher.write(&bytes);
+    }
+    Ok(hasher.finish())
+}
+
+/// Loads a glTF model and converts it to [`BakedModel`], adding tangents where the source
+/// has none and a full mip chain for every image whose settings want mips.
+pub fn bake_model(cache: &AssetCache, id: &str) -> Result<BakedModel> {
+    let gltf = cache.load::<Gltf>(id)?.read();
+    let mut model = GltfModel::from_gltf(&gltf)?;
+    model.apply_texture_settings(cache, id, &gltf);
+    for primitive in model
+        .meshes
+        .iter_mut()
+        .flat_map(|mesh| &mut mesh.primitives)
+    {
+        primitive.generate_tangents();
+    }
+
+    let images = model
+        .images
+        .into_iter()
+        .map(|image| {
+            let (width, height) = (image.width, image.height);
+            let pixels = RgbaImage::from_raw(width, height, image.pixels)
+                .ok_or_else(|| anyhow!("Image data doesn't match {width}x{height} RGBA8"))?;
+            let (format, mips) = rgba8_levels(pixels, image.settings);
+            Ok(BakedTexture {
+                width,
+                height,
+                format,
+                mips,
+                settings: image.settings,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let textures = model
+        .textures
+        .iter()
+        .map(|texture| BakedTextureRef {
+            image: texture.image,
+            sampler: BakedSampler::from(&texture.sampler),
+        })
+        .collect();
+
+    Ok(BakedModel {
+        scenes: model.scenes,
+        nodes: model.nodes,
+        meshes: model.meshes,
+        images,
+        textures,
+        materials: model.materials,
+        animations: model.animations,
+    })
+}
+
+/// Decodes a PNG or HDR file and builds its mip chain if `settings` ask for one. HDR
+/// images stay 32-bit float; others are block-compressed if `settings` ask for that.
+pub fn bake_texture(path: &Path, settings: TextureSettings) -> Result<BakedTexture> {
+    let image = image::open(path)?;
+    let (width, height) = (image.width(), image.height());
+    let (format, mips) = match image {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => (
+            TextureFormat::Rgba32Float,
+            levels(image.to_rgba32f(), settings.generate_mips)
+                .into_iter()
+                .map(|mip| {
+                    mip.into_raw()
+                        .iter()
+                        .flat_map(|f| f.to_le_bytes())
+                        .collect()
+                })
+                .collect(),
+        ),
+        _ => rgba8_levels(image.to_rgba8(), settings),
+    };
+    Ok(BakedTexture {
+        width,
+        height,
+        format,
+        mips,
+        settings,
+    })
+}
+
+/// Mip levels of an 8-bit image, compressed to BC1 or BC3 if `settings` ask for block
+/// compression and the `block-compression` feature is on.
+fn rgba8_levels(level0: RgbaImage, settings: TextureSettings) -> (TextureFormat, Vec<Vec<u8>>) {
+    let mips = levels(level0, settings.generate_mips);
+    let block_compression = settings.block_compression && cfg!(feature = "block-compression");
+    if settings.block_compression && !block_compression {
+        warn!("Baking as RGBA8: block_compression needs the block-compression feature");
+    }
+    if !block_compression {
+        let mips = mips.into_iter().map(|mip| mip.into_raw()).collect();
+        return (TextureFormat::Rgba8Srgb, mips);
+    }
+    let block_format = BlockFormat::for_rgba8(mips[0].as_raw());
+    let mips = mips
+        .iter()
+        .map(|mip| block_format.compress(mip.as_raw(), mip.width(), mip.height()))
+        .collect();
+    (TextureFormat::Block(block_format), mips)
+}
+
+fn levels<P>(
+    level0: ImageBuffer<P, Vec<P::Subpixel>>,
+    mips: bool,
+) -> Vec<ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P: Pixel + 'static,
+{
+    if mips {
+        mip_chain(level0)
+    } else {
+        vec![level0]
+    }
+}
+
+/// Every mip level down to 1x1, largest first.
+fn mip_chain<P>(level0: ImageBuffer<P, Vec<P::Subpixel>>) -> Vec<ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P: Pixel + 'static,
+{
+    let mut mips = vec![level0];
+    while let Some(last) = mips.last()
+        && (last.width() > 1 || last.height() > 1)
+    {
+        let (width, height) = ((last.width() / 2).max(1), (last.height() / 2).max(1));
+        let next = imageops::resize(last, width, height, FilterType::Triangle);
+        mips.push(next);
+    }
+    mips
+}
+
+/// Source files under `assets_dir`, skipping previously baked output.
+fn source_files(assets_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![assets_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path != assets_dir.join(BAKED_DIR) {
+                    dirs.push(path);
+                }
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The asset id `assets_manager` uses for `path`, e.g. `super_car.scene`.
+fn asset_id(assets_dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(assets_dir).ok()?.with_extension("");
+    let segments = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    if segments.iter().any(|segment| segment.contains('.')) {
+        return None;
+    }
+    Some(segments.join("."))
+}
+
+fn write_baked(assets_dir: &Path, baked_id: &str, extension: &str, bytes: &[u8]) -> Result<()> {
+    let mut path = assets_dir.to_path_buf();
+    path.extend(baked_id.split('.'));
+    path.set_extension(extension);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, bytes).with_context(|| format!("Failed to write {}", path.display()))
+}
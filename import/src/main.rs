@@ -0,0 +1,30 @@
+use anyhow::{Result, bail};
+use std::path::PathBuf;
+use tracing::info;
+
+/// Usage: `elements-import [ASSETS_DIR]` (defaults to `assets`).
+fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let assets_dir = std::env::args_os()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("assets"));
+
+    let report = elements_import::import_dir(&assets_dir)?;
+    info!(
+        "Baked {} models and {} textures into {}",
+        report.models,
+        report.textures,
+        assets_dir.join(elements_import::BAKED_DIR).display()
+    );
+    if report.moved > 0 {
+        info!(
+            "{} assets moved; their old ids redirect to the new ones",
+            report.moved
+        );
+    }
+    if report.failed > 0 {
+        bail!("{} assets failed to import", report.failed);
+    }
+    Ok(())
+}
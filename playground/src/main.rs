@@ -1,5 +1,9 @@
 use ::elements_engine::application::Application;
+use ::elements_engine::engine::EngineConfig;
 
 fn main() {
-    Application::new().run();
+    let config = EngineConfig::default()
+        .with_args(std::env::args().skip(1))
+        .expect("Invalid command line");
+    Application::with_config(config).run();
 }